@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::ops::Not;
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::{INTERVALS_TABLE, QA_TABLE, REMARKS_TABLE, VIOLATIONS_TABLE};
+use crate::sort;
+
+/// A group-by summary of the exported rows, written alongside them so the
+/// totals don't have to be pivoted out of the export by hand.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Aggregation {
+	/// Names the sheet, table or file the summary is written to.
+	pub name: String,
+	/// Columns whose values make up a group, every distinct combination of
+	/// them gets a row. Empty sums up every row into a single one.
+	#[serde(default)]
+	pub group_by: Vec<String>,
+	pub measures: Vec<Measure>,
+}
+
+/// A value computed for every group, named like `sum_meters`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Measure {
+	pub column: String,
+	pub function: AggregateFn,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFn {
+	Sum,
+	/// Number of non-empty values.
+	Count,
+	Min,
+	Max,
+	Mean,
+	/// First and last value in order, dates by day and numbers by value,
+	/// like the first and last date a hole was drilled on.
+	Earliest,
+	Latest,
+}
+
+impl AggregateFn {
+	pub fn name(&self) -> &'static str {
+		match self {
+			AggregateFn::Sum => "sum",
+			AggregateFn::Count => "count",
+			AggregateFn::Min => "min",
+			AggregateFn::Max => "max",
+			AggregateFn::Mean => "mean",
+			AggregateFn::Earliest => "earliest",
+			AggregateFn::Latest => "latest",
+		}
+	}
+}
+
+impl Aggregation {
+	/// Name of the summary [`Aggregation::per_hole`] writes.
+	pub const HOLES: &str = "holes";
+
+	/// One row per hole for the drilling progress report, with the meters
+	/// drilled, the number of shifts (rows with a date) and the first and
+	/// last date it was drilled on.
+	pub fn per_hole(hole: &str, meters: &str, date: &str) -> Aggregation {
+		let measure = |column: &str, function| Measure {
+			column: column.to_string(),
+			function,
+		};
+
+		Aggregation {
+			name: Aggregation::HOLES.to_string(),
+			group_by: vec![hole.to_string()],
+			measures: vec![
+				measure(meters, AggregateFn::Sum),
+				measure(date, AggregateFn::Count),
+				measure(date, AggregateFn::Earliest),
+				measure(date, AggregateFn::Latest),
+			],
+		}
+	}
+
+	/// Checks the summary can be written, its name ends up in a file or
+	/// sheet name.
+	pub fn validate(&self) -> Result<(), String> {
+		if self.name.is_empty() {
+			return Err("summary name can't be empty".to_string());
+		}
+
+		if let Some(c) = self.name.chars().find(|c| "/\\:*?\"<>|[]".contains(*c)) {
+			return Err(format!("summary name {} can't contain {}", self.name, c));
+		}
+
+		if self.name == VIOLATIONS_TABLE {
+			return Err(format!(
+				"summary name {} is taken by the violations report",
+				self.name
+			));
+		}
+
+		if self.name == QA_TABLE {
+			return Err(format!(
+				"summary name {} is taken by the qa report",
+				self.name
+			));
+		}
+
+		if self.name == REMARKS_TABLE {
+			return Err(format!(
+				"summary name {} is taken by the remarks",
+				self.name
+			));
+		}
+
+		if self.name == INTERVALS_TABLE {
+			return Err(format!(
+				"summary name {} is taken by the intervals report",
+				self.name
+			));
+		}
+
+		if self.measures.is_empty() {
+			return Err(format!("summary {} has no measures", self.name));
+		}
+
+		Ok(())
+	}
+
+	/// Column names of the summary table.
+	pub fn columns(&self) -> Vec<String> {
+		let measures = self
+			.measures
+			.iter()
+			.map(|m| format!("{}_{}", m.function.name(), m.column));
+		self.group_by.iter().cloned().chain(measures).collect()
+	}
+}
+
+/// Where rows totalling up columns of the export are written, tagged in a
+/// `row_type` column so they can be told apart from the data.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Totals {
+	#[default]
+	None,
+	/// A single row after all the others.
+	End,
+	/// A row after each file's rows.
+	PerFile,
+}
+
+impl Totals {
+	pub const NAMES: &[&str] = &["none", "end", "per_file"];
+
+	pub fn from_name(name: &str) -> Option<Totals> {
+		Some(match name {
+			"none" => Totals::None,
+			"end" => Totals::End,
+			"per_file" => Totals::PerFile,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Totals::None => "none",
+			Totals::End => "end",
+			Totals::PerFile => "per_file",
+		}
+	}
+
+	/// Value of the `row_type` column for the totals rows.
+	pub(crate) fn row_type(&self) -> &'static str {
+		match self {
+			Totals::PerFile => "file_total",
+			_ => "total",
+		}
+	}
+}
+
+/// Sums columns of the rows added since the last totals row.
+pub(crate) struct TotalRow {
+	cols: Vec<usize>,
+	accumulators: Vec<Accumulator>,
+	rows: usize,
+}
+
+impl TotalRow {
+	/// Columns that aren't in `header` are left out, and returned so they
+	/// can be warned about.
+	pub fn new<'c>(columns: &'c [String], header: &[String]) -> (TotalRow, Vec<&'c String>) {
+		let mut cols = Vec::new();
+		let mut missing = Vec::new();
+		for column in columns {
+			match header.iter().position(|h| h == column) {
+				Some(col) => cols.push(col),
+				None => missing.push(column),
+			}
+		}
+
+		let total = TotalRow {
+			accumulators: vec![Accumulator::default(); cols.len()],
+			cols,
+			rows: 0,
+		};
+		(total, missing)
+	}
+
+	pub fn add(&mut self, row: &[&str]) {
+		self.rows += 1;
+		for (accumulator, col) in self.accumulators.iter_mut().zip(&self.cols) {
+			accumulator.add(row.get(*col).copied().unwrap_or_default());
+		}
+	}
+
+	/// The sums as a row `width` columns wide with the other columns left
+	/// empty, none if no rows were added. Starts over for the next one.
+	pub fn take(&mut self, width: usize) -> Option<Vec<String>> {
+		if self.rows == 0 {
+			return None;
+		}
+
+		let mut row = vec![String::new(); width];
+		for (accumulator, col) in self.accumulators.iter_mut().zip(&self.cols) {
+			row[*col] = accumulator.value(AggregateFn::Sum);
+			*accumulator = Accumulator::default();
+		}
+
+		self.rows = 0;
+		Some(row)
+	}
+}
+
+// what's needed of a column's values to compute any of the functions
+#[derive(Clone, Default)]
+struct Accumulator {
+	values: usize,
+	numbers: usize,
+	sum: f64,
+	min: Option<f64>,
+	max: Option<f64>,
+	earliest: Option<String>,
+	latest: Option<String>,
+}
+
+impl Accumulator {
+	fn add(&mut self, value: &str) {
+		if value.is_empty() {
+			return;
+		}
+
+		self.values += 1;
+		if self
+			.earliest
+			.as_ref()
+			.is_none_or(|earliest| sort::compare_values(value.trim(), earliest).is_lt())
+		{
+			self.earliest = Some(value.trim().to_string());
+		}
+
+		if self
+			.latest
+			.as_ref()
+			.is_none_or(|latest| sort::compare_values(value.trim(), latest).is_gt())
+		{
+			self.latest = Some(value.trim().to_string());
+		}
+
+		// text is only counted, it doesn't add up to anything
+		let Ok(number) = value.trim().parse::<f64>() else {
+			return;
+		};
+
+		if number.is_finite().not() {
+			return;
+		}
+
+		self.numbers += 1;
+		self.sum += number;
+		self.min = Some(self.min.map_or(number, |min| min.min(number)));
+		self.max = Some(self.max.map_or(number, |max| max.max(number)));
+	}
+
+	fn value(&self, function: AggregateFn) -> String {
+		let number = match function {
+			AggregateFn::Count => return self.values.to_string(),
+			AggregateFn::Sum => Some(self.sum),
+			AggregateFn::Min => self.min,
+			AggregateFn::Max => self.max,
+			AggregateFn::Mean => (self.numbers > 0).then(|| self.sum / self.numbers as f64),
+			AggregateFn::Earliest => return self.earliest.clone().unwrap_or_default(),
+			AggregateFn::Latest => return self.latest.clone().unwrap_or_default(),
+		};
+
+		number.map(format_number).unwrap_or_default()
+	}
+}
+
+/// Computes an [`Aggregation`] over rows as they're written.
+pub(crate) struct Aggregator<'a> {
+	aggregation: &'a Aggregation,
+	group_cols: Vec<usize>,
+	measure_cols: Vec<usize>,
+	// groups in the order they were first seen, which follows the files
+	groups: Vec<(Vec<String>, Vec<Accumulator>)>,
+	group_index: HashMap<Vec<String>, usize>,
+}
+
+impl<'a> Aggregator<'a> {
+	/// Fails when a column the aggregation needs isn't in `header`.
+	pub fn new(aggregation: &'a Aggregation, header: &[String]) -> Result<Aggregator<'a>, String> {
+		let position = |column: &String| {
+			header
+				.iter()
+				.position(|h| h == column)
+				.ok_or_else(|| format!("summary {}: there's no {} column", aggregation.name, column))
+		};
+
+		Ok(Aggregator {
+			aggregation,
+			group_cols: aggregation
+				.group_by
+				.iter()
+				.map(position)
+				.collect::<Result<_, _>>()?,
+			measure_cols: aggregation
+				.measures
+				.iter()
+				.map(|m| position(&m.column))
+				.collect::<Result<_, _>>()?,
+			groups: Vec::new(),
+			group_index: HashMap::new(),
+		})
+	}
+
+	pub fn aggregation(&self) -> &Aggregation {
+		self.aggregation
+	}
+
+	pub fn add(&mut self, row: &[&str]) {
+		let value = |col: usize| row.get(col).copied().unwrap_or_default();
+		let key: Vec<String> = self
+			.group_cols
+			.iter()
+			.map(|c| value(*c).to_string())
+			.collect();
+		let idx = match self.group_index.get(&key) {
+			Some(idx) => *idx,
+			None => {
+				let accumulators = vec![Accumulator::default(); self.measure_cols.len()];
+				self.groups.push((key.clone(), accumulators));
+				self.group_index.insert(key, self.groups.len() - 1);
+				self.groups.len() - 1
+			}
+		};
+
+		let accumulators = &mut self.groups[idx].1;
+		for (accumulator, col) in accumulators.iter_mut().zip(&self.measure_cols) {
+			accumulator.add(value(*col));
+		}
+	}
+
+	/// One row per group, in the order the groups were first seen.
+	pub fn rows(&self) -> impl Iterator<Item = Vec<String>> + '_ {
+		self.groups.iter().map(|(key, accumulators)| {
+			let measures = accumulators
+				.iter()
+				.zip(&self.aggregation.measures)
+				.map(|(accumulator, measure)| accumulator.value(measure.function));
+			key.iter().cloned().chain(measures).collect()
+		})
+	}
+}
+
+// sums of decimals pick up float noise like 0.30000000000000004, which is
+// rounded off rather than written out
+pub(crate) fn format_number(number: f64) -> String {
+	let formatted = format!("{:.9}", number);
+	let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+	match formatted {
+		"-0" => "0".to_string(),
+		_ => formatted.to_string(),
+	}
+}
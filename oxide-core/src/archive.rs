@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+
+use crate::source::SpreadsheetSource;
+use crate::summary::FileTiming;
+use crate::workbook::{WorkbookFile, worksheet_name};
+
+// lists the workbooks renamed where they are, in the folder they're in
+const RENAMED_FILE: &str = ".oxide-renamed";
+
+/// Where workbooks go once they're exported, so the folder they're read
+/// from only holds the ones that haven't been yet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Archive {
+	/// Folder the workbooks are put in, in a subfolder for the day they're
+	/// exported like `done/2024-05-18`. Relative to the folder they're read
+	/// from unless it's absolute, empty to leave them in that folder.
+	pub folder: PathBuf,
+	/// Copies them rather than moving them, for an inbox that's cleaned up
+	/// by hand.
+	pub copy: bool,
+	/// What they're renamed to, like `DONE_{name}` or `{name}_{date}_{time}`,
+	/// empty to keep their names. See [`Archive::RENAME_FIELDS`].
+	pub rename: String,
+}
+
+impl Default for Archive {
+	fn default() -> Self {
+		Archive {
+			folder: "done".into(),
+			copy: false,
+			rename: String::new(),
+		}
+	}
+}
+
+impl Archive {
+	/// What a rename can hold: the workbook's name without its extension,
+	/// and the day and time of the export, like `2024-05-18` and `063000`.
+	pub const RENAME_FIELDS: [&str; 3] = ["{name}", "{date}", "{time}"];
+
+	/// Fails with why `rename` isn't a name every workbook can be renamed
+	/// to, as without `{name}` they'd all end up with the same one, and with
+	/// only `{name}` they'd keep theirs.
+	pub fn check_rename(rename: &str) -> Result<(), String> {
+		if rename.contains("{name}").not() {
+			return Err(format!("rename {} doesn't have {{name}} in it", rename));
+		}
+		if rename == "{name}" {
+			return Err("rename {name} keeps the names as they are".to_string());
+		}
+		if rename.contains(['/', '\\']) {
+			return Err(format!("rename {} can't have folders in it", rename));
+		}
+
+		Ok(())
+	}
+
+	fn renames_in_place(&self) -> bool {
+		self.rename.is_empty().not() && self.folder.as_os_str().is_empty()
+	}
+
+	// leaves out the workbooks that were renamed in the folder they're read
+	// from, as they've already been exported. they're told apart by the list
+	// of them kept in that folder rather than by their names, which new
+	// workbooks can look like too
+	pub(crate) fn drop_renamed(
+		&self,
+		source: &dyn SpreadsheetSource,
+		files: Vec<WorkbookFile>,
+	) -> Vec<WorkbookFile> {
+		if self.renames_in_place().not() {
+			return files;
+		}
+
+		let mut renamed: HashMap<PathBuf, BTreeSet<String>> = HashMap::new();
+		files
+			.into_iter()
+			.filter(|file| {
+				let Some(path) = source.local_path(&file.path) else {
+					return true;
+				};
+				let folder = path.parent().unwrap_or(Path::new(""));
+				let renamed = renamed
+					.entry(folder.to_path_buf())
+					.or_insert_with_key(|folder| read_renamed(folder));
+				let file_name = path.file_name().and_then(|name| name.to_str());
+				file_name.is_none_or(|name| renamed.contains(name).not())
+			})
+			.collect()
+	}
+
+	// moves the workbooks whose rows were written into the folder for the
+	// day of `now`, returning a warning for each one that couldn't be.
+	// workbooks that aren't files of their own, like a zip's, stay where
+	// they are
+	pub(crate) fn run(
+		&self,
+		source: &dyn SpreadsheetSource,
+		files: &[FileTiming],
+		now: NaiveDateTime,
+	) -> Vec<String> {
+		let day = now.format("%Y-%m-%d").to_string();
+		let mut warnings = Vec::new();
+		// names given to the workbooks renamed where they are, by folder
+		let mut renamed: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+		for file in files {
+			let Some(path) = source.local_path(&file.path) else {
+				continue;
+			};
+			let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+				continue;
+			};
+			let file_name = match self.rename.is_empty() {
+				true => file_name.to_string(),
+				false => {
+					let name = self
+						.rename
+						.replace("{name}", worksheet_name(&path))
+						.replace("{date}", &day)
+						.replace("{time}", &now.format("%H%M%S").to_string());
+					format!("{}.xlsx", name)
+				}
+			};
+
+			let folder = path.parent().unwrap_or(Path::new(""));
+			let folder = match self.folder.as_os_str().is_empty() {
+				true => folder.to_path_buf(),
+				false => folder.join(&self.folder).join(&day),
+			};
+			let target = folder.join(&file_name);
+			let result = fs::create_dir_all(&folder).and_then(|_| match self.copy {
+				true => fs::copy(&path, &target).map(|_| ()),
+				false => move_file(&path, &target),
+			});
+			match result {
+				Ok(()) if self.renames_in_place() => {
+					renamed.entry(folder).or_default().push(file_name);
+				}
+				Ok(()) => {}
+				Err(e) => warnings.push(format!(
+					"{}: failed to archive it to {}: {}",
+					file.file_name,
+					folder.display(),
+					e
+				)),
+			}
+		}
+
+		for (folder, names) in renamed {
+			if let Err(e) = write_renamed(&folder, names) {
+				warnings.push(format!(
+					"failed to list the renamed workbooks in {}: {}",
+					folder.join(RENAMED_FILE).display(),
+					e
+				));
+			}
+		}
+
+		warnings
+	}
+}
+
+fn read_renamed(folder: &Path) -> BTreeSet<String> {
+	let renamed = fs::read_to_string(folder.join(RENAMED_FILE)).unwrap_or_default();
+	renamed.lines().map(String::from).collect()
+}
+
+// adds `names` to the folder's list, dropping the workbooks that have since
+// been taken out of it so it doesn't grow forever
+fn write_renamed(folder: &Path, names: Vec<String>) -> io::Result<()> {
+	let mut renamed = read_renamed(folder);
+	renamed.retain(|name| folder.join(name).exists());
+	renamed.extend(names);
+	let contents: String = renamed.into_iter().map(|name| name + "\n").collect();
+	fs::write(folder.join(RENAMED_FILE), contents)
+}
+
+// renames the file, copying it and removing the original when it's moved
+// to another drive, which can't be renamed across
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+	if fs::rename(from, to).is_ok() {
+		return Ok(());
+	}
+
+	fs::copy(from, to)?;
+	fs::remove_file(from)
+}
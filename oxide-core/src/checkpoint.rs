@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::export::ParsedFile;
+use crate::remarks::Remark;
+use crate::section::SectionRows;
+use crate::staging::{decode_row, encode_row};
+use crate::workbook::WorkbookFile;
+
+// source path and modified time, mapped to the journal offset of its record
+type CompletedIndex = HashMap<(String, u64), u64>;
+
+/// Journal of every file whose rows have been fully parsed, written next to
+/// the output so an interrupted export can pick up where it left off.
+pub struct Checkpoint {
+	path: PathBuf,
+	writer: BufWriter<File>,
+	completed: CompletedIndex,
+}
+
+impl Checkpoint {
+	/// Where the checkpoint for `output_file` is kept.
+	pub fn path_for(output_file: &str) -> PathBuf {
+		PathBuf::from(format!("{}.checkpoint", output_file))
+	}
+
+	/// Whether an export to `output_file` was interrupted and can be resumed.
+	pub fn exists(output_file: &str) -> bool {
+		Checkpoint::path_for(output_file).exists()
+	}
+
+	// starts a fresh journal, or continues the existing one when resuming
+	pub(crate) fn open(output_file: &str, resume: bool) -> io::Result<Checkpoint> {
+		let path = Checkpoint::path_for(output_file);
+		let mut completed = HashMap::new();
+		let mut valid_len = 0;
+		if resume && path.exists() {
+			(completed, valid_len) = read_index(&path)?;
+		}
+
+		let mut file = OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.read(true)
+			.write(true)
+			.open(&path)?;
+		// drop whatever was left of a record that was being written when we died
+		file.set_len(valid_len)?;
+		file.seek(SeekFrom::End(0))?;
+
+		Ok(Checkpoint {
+			path,
+			writer: BufWriter::new(file),
+			completed,
+		})
+	}
+
+	pub(crate) fn is_completed(&self, source: &WorkbookFile) -> bool {
+		// without a modified time there's no telling whether the file changed
+		let (_, modified) = source_key(source);
+		modified != 0 && self.completed.contains_key(&source_key(source))
+	}
+
+	pub(crate) fn restore(&self, source: &WorkbookFile) -> io::Result<ParsedFile> {
+		let offset = self.completed.get(&source_key(source)).copied();
+		let Some(offset) = offset else {
+			return Err(io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("{} is not in the checkpoint", source.path.display()),
+			));
+		};
+
+		let mut reader = BufReader::new(File::open(&self.path)?);
+		reader.seek(SeekFrom::Start(offset))?;
+
+		let mut parsed = ParsedFile::default();
+
+		for line in reader.lines() {
+			let mut record = decode_row(&line?);
+			let kind = record.remove(0);
+			match kind.as_str() {
+				"file" => parsed.file_name = record.remove(0),
+				"headers" => parsed.headers = record,
+				"row" => parsed.rows.push(record),
+				"section" if record.is_empty().not() => parsed.sections.push(SectionRows {
+					name: record.remove(0),
+					headers: record,
+					rows: Vec::new(),
+				}),
+				"section_row" => {
+					if let Some(section) = parsed.sections.last_mut() {
+						section.rows.push(record);
+					}
+				}
+				"sheet" if record.len() == 2 => {
+					let rows = record[1].parse().unwrap_or(0);
+					parsed.sheets.push((record.remove(0), rows));
+				}
+				"remark" if record.len() == 3 => parsed.remarks.push(Remark {
+					date: record[0].clone(),
+					hole: record[1].clone(),
+					text: record[2].clone(),
+				}),
+				"done" => break,
+				_ => {}
+			}
+		}
+
+		Ok(parsed)
+	}
+
+	pub(crate) fn record(&mut self, source: &WorkbookFile, parsed: &ParsedFile) -> io::Result<()> {
+		let (source_path, modified) = source_key(source);
+		let modified = modified.to_string();
+		let file_record = ["file", &parsed.file_name, &source_path, &modified];
+		writeln!(
+			self.writer,
+			"{}",
+			encode_row(&file_record.map(String::from))
+		)?;
+
+		let mut headers = vec!["headers".to_string()];
+		headers.extend(parsed.headers.iter().cloned());
+		writeln!(self.writer, "{}", encode_row(&headers))?;
+		for row in &parsed.rows {
+			let mut record = Vec::with_capacity(row.len() + 1);
+			record.push("row".to_string());
+			record.extend(row.iter().cloned());
+			writeln!(self.writer, "{}", encode_row(&record))?;
+		}
+
+		for (sheet, rows) in &parsed.sheets {
+			let record = ["sheet".to_string(), sheet.clone(), rows.to_string()];
+			writeln!(self.writer, "{}", encode_row(&record))?;
+		}
+
+		for section in &parsed.sections {
+			let mut record = vec!["section".to_string(), section.name.clone()];
+			record.extend(section.headers.iter().cloned());
+			writeln!(self.writer, "{}", encode_row(&record))?;
+			for row in &section.rows {
+				let mut record = Vec::with_capacity(row.len() + 1);
+				record.push("section_row".to_string());
+				record.extend(row.iter().cloned());
+				writeln!(self.writer, "{}", encode_row(&record))?;
+			}
+		}
+
+		for remark in &parsed.remarks {
+			let record = ["remark", &remark.date, &remark.hole, &remark.text];
+			writeln!(self.writer, "{}", encode_row(&record.map(String::from)))?;
+		}
+
+		writeln!(self.writer, "done")?;
+		self.writer.flush()
+	}
+
+	// the export finished, nothing left to resume
+	pub(crate) fn finish(self) -> io::Result<()> {
+		drop(self.writer);
+		fs::remove_file(&self.path)
+	}
+}
+
+fn source_key(source: &WorkbookFile) -> (String, u64) {
+	let modified = source
+		.modified
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default();
+	(source.path.display().to_string(), modified)
+}
+
+// returns the completed records and the length of the journal up to the
+// end of the last complete record
+fn read_index(path: &Path) -> io::Result<(CompletedIndex, u64)> {
+	let mut completed = HashMap::new();
+	let mut reader = BufReader::new(File::open(path)?);
+	let mut offset = 0;
+	let mut valid_len = 0;
+	let mut current = None;
+	let mut line = String::new();
+	loop {
+		line.clear();
+		let read = reader.read_line(&mut line)?;
+		if read == 0 || line.ends_with('\n').not() {
+			break;
+		}
+
+		let record = decode_row(line.trim_end_matches('\n'));
+		match record.first().map(|s| s.as_str()) {
+			Some("file") if record.len() == 4 => {
+				let modified = record[3].parse().unwrap_or_default();
+				current = Some(((record[2].clone(), modified), offset));
+			}
+			Some("done") => {
+				if let Some((key, start)) = current.take() {
+					completed.insert(key, start);
+				}
+
+				valid_len = offset + read as u64;
+			}
+			_ => {}
+		}
+
+		offset += read as u64;
+	}
+
+	Ok((completed, valid_len))
+}
@@ -0,0 +1,64 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::merge::ExistingOutput;
+use crate::sink::CsvDialect;
+
+/// Most rows a table can have to be copied, anything bigger is better opened
+/// from the file than pasted.
+pub const CLIPBOARD_ROWS: usize = 5000;
+
+/// An exported csv table as it's put on the clipboard: the csv itself as
+/// text, and an html table that mail clients and spreadsheets paste as one.
+pub struct ClipboardTable {
+	pub text: String,
+	pub html: String,
+	pub rows: usize,
+}
+
+/// Reads the csv output at `path`, laid out like `dialect`, to put on the
+/// clipboard, failing when it has more than [`CLIPBOARD_ROWS`] rows.
+pub fn clipboard_table(path: &Path, dialect: &CsvDialect) -> Result<ClipboardTable, String> {
+	let table = ExistingOutput::read(path, dialect)
+		.map_err(|e| e.to_string())?
+		.ok_or_else(|| format!("{} doesn't exist", path.display()))?;
+	if table.rows.len() > CLIPBOARD_ROWS {
+		return Err(format!(
+			"{} rows are too many to copy, at most {} can be",
+			table.rows.len(),
+			CLIPBOARD_ROWS
+		));
+	}
+
+	let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+	let mut html = String::from("<table>\n<thead>\n");
+	html_row(&mut html, &table.header, "th");
+	html.push_str("</thead>\n<tbody>\n");
+	for row in &table.rows {
+		html_row(&mut html, row, "td");
+	}
+	html.push_str("</tbody>\n</table>\n");
+
+	Ok(ClipboardTable {
+		text,
+		html,
+		rows: table.rows.len(),
+	})
+}
+
+fn html_row(html: &mut String, cells: &[String], tag: &str) {
+	html.push_str("<tr>");
+	for cell in cells {
+		let _ = write!(html, "<{}>{}</{}>", tag, escape(cell), tag);
+	}
+	html.push_str("</tr>\n");
+}
+
+fn escape(value: &str) -> String {
+	value
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
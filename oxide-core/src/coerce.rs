@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::ops::Not;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinate;
+
+// days from the start of excel's calendar, 1899-12-30, to 1970-01-01
+const EXCEL_EPOCH_DAYS: i64 = 25569;
+
+/// Type a template expects a column's values to have.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueType {
+	Int,
+	Float,
+	/// Written as `YYYY-MM-DD`, read from that, `DD/MM/YYYY` or an excel
+	/// date.
+	Date,
+	String,
+	/// Decimal degrees, read from those or degrees, minutes and seconds with
+	/// an `N` or `S`, like `23°26'21"S`.
+	Latitude,
+	/// Like a latitude, with an `E` or `W`.
+	Longitude,
+	/// An easting or northing in meters, read with thousands separators and
+	/// units like `mE` dropped.
+	Grid,
+}
+
+impl ValueType {
+	pub fn name(&self) -> &'static str {
+		match self {
+			ValueType::Int => "int",
+			ValueType::Float => "float",
+			ValueType::Date => "date",
+			ValueType::String => "string",
+			ValueType::Latitude => "latitude",
+			ValueType::Longitude => "longitude",
+			ValueType::Grid => "grid",
+		}
+	}
+
+	/// The value written the way this type is, none if it isn't one.
+	pub fn coerce(&self, value: &str) -> Option<String> {
+		let value = value.trim();
+		match self {
+			ValueType::Int => {
+				let number = value.parse::<f64>().ok()?;
+				let fits = number.fract() == 0.0 && number.abs() < i64::MAX as f64;
+				fits.then(|| (number as i64).to_string())
+			}
+			ValueType::Float => {
+				let number = value.parse::<f64>().ok()?;
+				number.is_finite().then(|| number.to_string())
+			}
+			ValueType::Date => parse_date(value).map(|(y, m, d)| format!("{:04}-{:02}-{:02}", y, m, d)),
+			ValueType::String => Some(value.to_string()),
+			ValueType::Latitude => {
+				coordinate::parse_degrees(value, ['N', 'S'], 90.0).map(|v| v.to_string())
+			}
+			ValueType::Longitude => {
+				coordinate::parse_degrees(value, ['E', 'W'], 180.0).map(|v| v.to_string())
+			}
+			ValueType::Grid => coordinate::parse_grid(value).map(|v| v.to_string()),
+		}
+	}
+}
+
+/// Coerces every value in a column with an expected type, keeping the ones
+/// that don't parse as they are. Returns a warning for each column that had
+/// any of those.
+pub(crate) fn coerce_rows(
+	headers: &[String],
+	rows: &mut [Vec<String>],
+	types: &BTreeMap<String, ValueType>,
+) -> Vec<String> {
+	let mut warnings = Vec::new();
+	for (col, header) in headers.iter().enumerate() {
+		let Some(value_type) = types.get(header) else {
+			continue;
+		};
+
+		// rows are numbered counting from the first data row
+		let mut invalid = Vec::new();
+		for (row_idx, row) in rows.iter_mut().enumerate() {
+			let Some(value) = row.get_mut(col).filter(|v| v.is_empty().not()) else {
+				continue;
+			};
+
+			match value_type.coerce(value) {
+				Some(coerced) => *value = coerced,
+				None => invalid.push((row_idx + 1, value.clone())),
+			}
+		}
+
+		if let Some((row, value)) = invalid.first() {
+			warnings.push(format!(
+				"{} values in {} aren't {}, the first in row {}: {}",
+				invalid.len(),
+				header,
+				value_type.name(),
+				row,
+				value
+			));
+		}
+	}
+
+	warnings
+}
+
+// year, month and day of a date written as YYYY-MM-DD or DD/MM/YYYY, or of
+// an excel date's serial number
+pub(crate) fn parse_date(value: &str) -> Option<(i64, u32, u32)> {
+	let parts = |separator| -> Option<Vec<u32>> {
+		let parts: Vec<_> = value.split(separator).collect();
+		if parts.len() != 3 || parts.iter().any(|p| p.is_empty()) {
+			return None;
+		}
+
+		parts.iter().map(|p| p.parse().ok()).collect()
+	};
+
+	let (year, month, day) = if let Some(parts) = parts('-') {
+		(parts[0] as i64, parts[1], parts[2])
+	} else if let Some(parts) = parts('/') {
+		(parts[2] as i64, parts[1], parts[0])
+	} else {
+		let serial = value
+			.parse::<f64>()
+			.ok()
+			.filter(|s| *s >= 1.0 && *s < 2958466.0)?;
+		return Some(civil_from_days(serial.floor() as i64 - EXCEL_EPOCH_DAYS));
+	};
+
+	let valid = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+	valid.then_some((year, month, day))
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+	match month {
+		2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
+	}
+}
+
+// the date `days` after 1970-01-01, from howard hinnant's date algorithms
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
@@ -0,0 +1,77 @@
+use std::ops::Not;
+
+// degrees are written with 6 decimals, around 10cm on the ground
+const DEGREE_DECIMALS: f64 = 1e6;
+
+/// Decimal degrees of a latitude or longitude written in decimal degrees or
+/// degrees, minutes and seconds, like `-23.4392`, `23°26'21"S` or
+/// `S 23 26.35`. `hemispheres` are the letters of the positive and negative
+/// hemisphere, and `max` how far from 0 it can be.
+pub(crate) fn parse_degrees(value: &str, hemispheres: [char; 2], max: f64) -> Option<f64> {
+	let mut value = value.trim().to_uppercase();
+	let mut sign = 1.0;
+	let hemisphere = value
+		.chars()
+		.next()
+		.filter(|c| hemispheres.contains(c))
+		.or_else(|| value.chars().last().filter(|c| hemispheres.contains(c)));
+	if let Some(hemisphere) = hemisphere {
+		value = value.replacen(hemisphere, "", 1);
+		if hemisphere == hemispheres[1] {
+			sign = -1.0;
+		}
+	}
+
+	let value = value.replace(['°', 'º', '\'', '′', '’', '"', '″', '”', ':'], " ");
+	let mut parts = value.split_whitespace();
+	let degrees = parts.next()?;
+	if degrees.starts_with('-') {
+		// a hemisphere already says which side it's on
+		if hemisphere.is_some() {
+			return None;
+		}
+		sign = -1.0;
+	}
+
+	let mut last = degrees.trim_start_matches(['-', '+']).parse::<f64>().ok()?;
+	let mut total = last;
+	for divisor in [60.0, 3600.0] {
+		let Some(part) = parts.next() else {
+			break;
+		};
+
+		// minutes and seconds only follow whole degrees and minutes
+		let part = part
+			.parse::<f64>()
+			.ok()
+			.filter(|p| *p >= 0.0 && *p < 60.0)?;
+		if last.fract() != 0.0 {
+			return None;
+		}
+		last = part;
+		total += part / divisor;
+	}
+
+	if parts.next().is_some() || total.is_finite().not() || total > max {
+		return None;
+	}
+
+	Some((sign * total * DEGREE_DECIMALS).round() / DEGREE_DECIMALS)
+}
+
+/// An easting or northing in meters, written with thousands separators or
+/// a unit like `mE` or `mN` or not.
+pub(crate) fn parse_grid(value: &str) -> Option<f64> {
+	let value = value.trim();
+	let value = value
+		.strip_suffix(['E', 'N', 'e', 'n'])
+		.unwrap_or(value)
+		.trim_end();
+	let value: String = value
+		.strip_suffix(['m', 'M'])
+		.unwrap_or(value)
+		.chars()
+		.filter(|c| *c != ',' && c.is_whitespace().not())
+		.collect();
+	value.parse::<f64>().ok().filter(|v| v.is_finite())
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::ops::Not;
+
+use crate::aggregate;
+use crate::sort;
+
+/// Columns a running total of the meters drilled per hole is computed
+/// from, in date order, and added to every row as `cumulative_meters`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CumulativeMeters {
+	pub hole: String,
+	pub meters: String,
+	pub date: String,
+}
+
+impl Default for CumulativeMeters {
+	fn default() -> Self {
+		CumulativeMeters {
+			hole: "hole_number".to_string(),
+			meters: "meters".to_string(),
+			date: "date".to_string(),
+		}
+	}
+}
+
+// a row's share of the running total, by the file and row it was staged
+// with
+struct Entry {
+	hole: String,
+	date: String,
+	meters: f64,
+	file_idx: usize,
+	file_row: usize,
+}
+
+/// Gathers every row's meters as files are staged, since the rows are only
+/// in date order once all of them are read.
+pub(crate) struct RunningMeters<'a> {
+	columns: &'a CumulativeMeters,
+	entries: Vec<Entry>,
+	totals: HashMap<(usize, usize), String>,
+}
+
+impl<'a> RunningMeters<'a> {
+	pub fn new(columns: &'a CumulativeMeters) -> RunningMeters<'a> {
+		RunningMeters {
+			columns,
+			entries: Vec::new(),
+			totals: HashMap::new(),
+		}
+	}
+
+	// rows are numbered from 1 within their file, like they're staged
+	pub fn add(&mut self, file_idx: usize, headers: &[String], rows: &[Vec<String>]) {
+		let position = |column: &String| headers.iter().position(|h| h == column);
+		let Some(hole) = position(&self.columns.hole) else {
+			return;
+		};
+
+		let (meters, date) = (position(&self.columns.meters), position(&self.columns.date));
+		for (row_idx, row) in rows.iter().enumerate() {
+			let value = |col: Option<usize>| col.and_then(|col| row.get(col)).map_or("", |v| v.trim());
+			if value(Some(hole)).is_empty() {
+				continue;
+			}
+
+			self.entries.push(Entry {
+				hole: value(Some(hole)).to_string(),
+				date: value(date).to_string(),
+				meters: value(meters).parse().unwrap_or(0.0),
+				file_idx,
+				file_row: row_idx + 1,
+			});
+		}
+	}
+
+	/// Sums up every hole's meters by date, rows of the same date in the
+	/// order they were read and rows without one after the rest. Files
+	/// `skipped` says aren't exported don't add to it.
+	pub fn finish(&mut self, skipped: impl Fn(usize) -> bool) {
+		let mut entries = std::mem::take(&mut self.entries);
+		entries.retain(|entry| skipped(entry.file_idx).not());
+		entries.sort_by(|a, b| {
+			a.hole
+				.cmp(&b.hole)
+				.then(a.date.is_empty().cmp(&b.date.is_empty()))
+				.then_with(|| sort::compare_values(&a.date, &b.date))
+				.then((a.file_idx, a.file_row).cmp(&(b.file_idx, b.file_row)))
+		});
+
+		let mut total = 0.0;
+		let mut hole: Option<&str> = None;
+		for entry in &entries {
+			if hole != Some(entry.hole.as_str()) {
+				hole = Some(&entry.hole);
+				total = 0.0;
+			}
+
+			total += entry.meters;
+			self.totals.insert(
+				(entry.file_idx, entry.file_row),
+				aggregate::format_number(total),
+			);
+		}
+	}
+
+	/// The running total at a row, empty for rows without a hole.
+	pub fn get(&self, file_idx: usize, file_row: usize) -> &str {
+		self
+			.totals
+			.get(&(file_idx, file_row))
+			.map_or("", |total| total.as_str())
+	}
+}
@@ -0,0 +1,149 @@
+use std::fmt::Write;
+use std::ops::Not;
+
+use calamine::{Data, ExcelDateTime};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{FixedOffset, TimeZone};
+
+/// How cells holding a date, a time, both or a duration are written,
+/// rather than as the serial number excel keeps them as, like `45429` for
+/// 2024-05-17 or `0.25` for six in the morning.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DateTimes {
+	date_format: String,
+	time_format: String,
+	datetime_format: String,
+	// offsets the workbooks' times are in and the one they're moved to,
+	// none to write them as they are
+	offsets: Option<(FixedOffset, FixedOffset)>,
+}
+
+impl Default for DateTimes {
+	fn default() -> Self {
+		DateTimes {
+			date_format: DateTimes::DATE_FORMAT.to_string(),
+			time_format: DateTimes::TIME_FORMAT.to_string(),
+			datetime_format: DateTimes::DATETIME_FORMAT.to_string(),
+			offsets: None,
+		}
+	}
+}
+
+impl DateTimes {
+	/// Formats dates and times are written in unless they're given others.
+	pub const DATE_FORMAT: &str = "%Y-%m-%d";
+	pub const TIME_FORMAT: &str = "%H:%M";
+	pub const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+	/// Dates are written in `date_format`, times of day in `time_format` and
+	/// dates with a time in `datetime_format`, all strftime formats like
+	/// `%H:%M`. Dates with a time are moved from the `timezone` the
+	/// workbooks were filled in to the `output_timezone`, utc offsets like
+	/// `+08:00` or `Z`, when both are set.
+	pub fn new(
+		date_format: &str,
+		time_format: &str,
+		datetime_format: &str,
+		timezone: &str,
+		output_timezone: &str,
+	) -> Result<DateTimes, String> {
+		for format in [date_format, time_format, datetime_format] {
+			if StrftimeItems::new(format).any(|item| item == Item::Error) {
+				return Err(format!("invalid time format {}", format));
+			}
+		}
+
+		let offsets = match (timezone.is_empty(), output_timezone.is_empty()) {
+			(true, true) => None,
+			(false, false) => Some((parse_offset(timezone)?, parse_offset(output_timezone)?)),
+			_ => {
+				return Err(
+					"times can only be moved to another timezone when both timezones are set".to_string(),
+				);
+			}
+		};
+
+		Ok(DateTimes {
+			date_format: date_format.to_string(),
+			time_format: time_format.to_string(),
+			datetime_format: datetime_format.to_string(),
+			offsets,
+		})
+	}
+
+	/// The cell's value as it's written, cells that aren't a time as they
+	/// are.
+	pub(crate) fn cell(&self, cell: &Data) -> String {
+		match cell {
+			Data::DateTime(datetime) => self.datetime(datetime),
+			_ => cell.to_string(),
+		}
+	}
+
+	fn datetime(&self, datetime: &ExcelDateTime) -> String {
+		let serial = datetime.as_f64();
+		if datetime.is_duration() {
+			let minutes = (serial * 24.0 * 60.0).round() as i64;
+			let sign = if minutes < 0 { "-" } else { "" };
+			return format!("{}{}:{:02}", sign, minutes.abs() / 60, minutes.abs() % 60);
+		}
+
+		let Some(naive) = datetime.as_datetime() else {
+			return datetime.to_string();
+		};
+		let utc = FixedOffset::east_opt(0).unwrap();
+		let (from, to) = self.offsets.unwrap_or((utc, utc));
+		let Some(local) = from.from_local_datetime(&naive).single() else {
+			return datetime.to_string();
+		};
+
+		// whole days are dates, which aren't moved as they have no time, and
+		// neither is a time without a day, as there'd be no day to move it to
+		let (time, format) = match serial {
+			_ if serial.fract() == 0.0 => (local, &self.date_format),
+			_ if (0.0..1.0).contains(&serial) => (local, &self.time_format),
+			_ => (local.with_timezone(&to), &self.datetime_format),
+		};
+		let mut formatted = String::new();
+		match write!(formatted, "{}", time.format(format)) {
+			Ok(_) => formatted,
+			Err(_) => datetime.to_string(),
+		}
+	}
+}
+
+// a utc offset like +08:00, -0530, +8 or Z
+fn parse_offset(value: &str) -> Result<FixedOffset, String> {
+	let invalid = || {
+		format!(
+			"invalid timezone {}, expected a utc offset like +08:00 or Z",
+			value
+		)
+	};
+
+	let trimmed = value.trim();
+	if trimmed.eq_ignore_ascii_case("z") || trimmed.eq_ignore_ascii_case("utc") {
+		return Ok(FixedOffset::east_opt(0).unwrap());
+	}
+
+	let (sign, rest) = match trimmed.split_at_checked(1) {
+		Some(("+", rest)) => (1, rest),
+		Some(("-", rest)) => (-1, rest),
+		_ => return Err(invalid()),
+	};
+	let (hours, minutes) = match rest.split_once(':') {
+		Some(parts) => parts,
+		None if rest.len() == 4 => rest.split_at(2),
+		None => (rest, "0"),
+	};
+	if [hours, minutes]
+		.iter()
+		.any(|part| part.is_empty() || part.chars().all(|c| c.is_ascii_digit()).not())
+	{
+		return Err(invalid());
+	}
+
+	let hours: i32 = hours.parse().map_err(|_| invalid())?;
+	let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+	FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::coerce;
+use crate::manifest::sha256;
+use crate::source::SpreadsheetSource;
+use crate::workbook::WorkbookFile;
+
+/// What happens when more than one workbook is a report for the same date,
+/// like a corrected copy sent again under a new name.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DuplicateReports {
+	/// Every workbook is exported, with a warning naming them.
+	#[default]
+	Warn,
+	/// Only the most recently modified workbook is exported.
+	KeepNewest,
+	/// Every workbook is exported, numbered in a `revision` column from 1 for
+	/// the oldest.
+	Revision,
+}
+
+impl DuplicateReports {
+	pub const NAMES: &[&str] = &["warn", "keep_newest", "revision"];
+
+	pub fn from_name(name: &str) -> Option<DuplicateReports> {
+		Some(match name {
+			"warn" => DuplicateReports::Warn,
+			"keep_newest" => DuplicateReports::KeepNewest,
+			"revision" => DuplicateReports::Revision,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			DuplicateReports::Warn => "warn",
+			DuplicateReports::KeepNewest => "keep_newest",
+			DuplicateReports::Revision => "revision",
+		}
+	}
+}
+
+/// Copies of the same workbook, byte for byte, like a report dropped in
+/// both the daily and the weekly folder under another name.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IdenticalFile {
+	/// The copy that was left out.
+	pub path: PathBuf,
+	/// The one it's identical to, which was read.
+	pub same_as: PathBuf,
+}
+
+// the files that aren't a copy of one before them, and the ones that are.
+// files that can't be read to hash them are kept, to fail when they're
+// parsed like they would otherwise
+pub(crate) fn drop_identical(
+	source: &dyn SpreadsheetSource,
+	files: Vec<WorkbookFile>,
+) -> (Vec<WorkbookFile>, Vec<IdenticalFile>) {
+	let mut hashes: HashMap<String, PathBuf> = HashMap::new();
+	let mut kept = Vec::with_capacity(files.len());
+	let mut identical = Vec::new();
+	for file in files {
+		let hash = source
+			.reader(&file.path)
+			.ok()
+			.and_then(|mut reader| sha256(&mut reader).ok());
+		let Some(hash) = hash else {
+			kept.push(file);
+			continue;
+		};
+
+		match hashes.get(&hash) {
+			Some(same_as) => identical.push(IdenticalFile {
+				path: file.path,
+				same_as: same_as.clone(),
+			}),
+			None => {
+				hashes.insert(hash, file.path.clone());
+				kept.push(file);
+			}
+		}
+	}
+
+	(kept, identical)
+}
+
+/// What a workbook is a report for, the ones for the same one are
+/// duplicates.
+#[derive(Clone)]
+pub(crate) struct ReportDay {
+	pub date: String,
+	// day and night reports for the same date aren't duplicates, and neither
+	// are ones read with different template rules, like two contractors'
+	pub shift: String,
+	pub rule: Option<usize>,
+}
+
+/// Workbooks numbered by when they were modified among the ones reporting
+/// the same date.
+pub(crate) struct Revisions {
+	// revision of every file, by the index rows are staged with
+	numbers: Vec<usize>,
+	latest: Vec<bool>,
+	// report dates more than one file covers, with the files oldest first
+	duplicates: Vec<(String, Vec<usize>)>,
+}
+
+impl Revisions {
+	/// Takes what every file in `files` is a report for, none for ones
+	/// without a date or rows.
+	pub fn new(files: &[WorkbookFile], days: &[Option<ReportDay>]) -> Revisions {
+		// dates written differently can still be the same day
+		let mut by_date: BTreeMap<_, (String, Vec<usize>)> = BTreeMap::new();
+		for (idx, day) in days.iter().enumerate() {
+			let Some(ReportDay { date, shift, rule }) = day else {
+				continue;
+			};
+
+			let day = coerce::parse_date(date).map_or_else(
+				|| date.clone(),
+				|(y, m, d)| format!("{:04}-{:02}-{:02}", y, m, d),
+			);
+			by_date
+				.entry((*rule, day, shift.as_str()))
+				.or_insert_with(|| (date.clone(), Vec::new()))
+				.1
+				.push(idx);
+		}
+
+		let mut revisions = Revisions {
+			numbers: vec![1; files.len()],
+			latest: vec![true; files.len()],
+			duplicates: Vec::new(),
+		};
+		for (date, mut idxs) in by_date.into_values() {
+			if idxs.len() < 2 {
+				continue;
+			}
+
+			// files modified at the same time keep the order they were listed in
+			idxs.sort_by_key(|idx| files[*idx].modified);
+			for (revision, idx) in idxs.iter().enumerate() {
+				revisions.numbers[*idx] = revision + 1;
+				revisions.latest[*idx] = revision + 1 == idxs.len();
+			}
+
+			revisions.duplicates.push((date, idxs));
+		}
+
+		revisions
+	}
+
+	pub fn number(&self, file_idx: usize) -> usize {
+		self.numbers.get(file_idx).copied().unwrap_or(1)
+	}
+
+	pub fn is_latest(&self, file_idx: usize) -> bool {
+		self.latest.get(file_idx).copied().unwrap_or(true)
+	}
+
+	/// A warning for every report date more than one file covers.
+	pub fn warnings(&self, files: &[WorkbookFile], handling: DuplicateReports) -> Vec<String> {
+		let name = |idx: &usize| {
+			files[*idx]
+				.path
+				.file_name()
+				.unwrap_or_default()
+				.to_string_lossy()
+				.to_string()
+		};
+
+		self
+			.duplicates
+			.iter()
+			.filter_map(|(date, idxs)| {
+				let names: Vec<_> = idxs.iter().map(name).collect();
+				let (newest, older) = names.split_last()?;
+				let mut warning = format!(
+					"{} and {} are reports for {}",
+					older.join(", "),
+					newest,
+					date
+				);
+				match handling {
+					DuplicateReports::Warn => {}
+					DuplicateReports::KeepNewest => {
+						warning.push_str(&format!(", only {} was exported", newest))
+					}
+					DuplicateReports::Revision => warning.push_str(", told apart by their revision"),
+				}
+
+				Some(warning)
+			})
+			.collect()
+	}
+}
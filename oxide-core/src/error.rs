@@ -0,0 +1,98 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use calamine::XlsxError;
+
+/// Everything that can go wrong while reading workbooks or writing the
+/// aggregated output. Each variant names the file it happened in, so the
+/// message can be shown to the user as is.
+#[derive(Debug, thiserror::Error)]
+pub enum OxideError {
+	#[error("{}: {source}", path.display())]
+	Io {
+		path: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+	#[error("failed to open workbook {}: {source}", file.display())]
+	WorkbookOpen {
+		file: PathBuf,
+		#[source]
+		source: XlsxError,
+	},
+	#[error("{} is in use by another program, like excel", file.display())]
+	FileInUse { file: PathBuf },
+	#[error("worksheet \"{sheet}\" not found in {}", file.display())]
+	SheetMissing { file: PathBuf, sheet: String },
+	#[error("failed to find \"{marker}\" in worksheet \"{sheet}\" of {}", file.display())]
+	MarkerNotFound {
+		file: PathBuf,
+		sheet: String,
+		marker: String,
+	},
+	#[error(
+		"{}, row {row}: expected {expected} columns but found {found}",
+		file.display()
+	)]
+	SchemaMismatch {
+		file: PathBuf,
+		// 1-based, as shown in excel
+		row: usize,
+		expected: usize,
+		found: usize,
+	},
+	#[error("failed to transform rows of {}: {message}", file.display())]
+	Transform { file: PathBuf, message: String },
+	#[error("failed to write output {}: {source}", file.display())]
+	Sink {
+		file: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+}
+
+impl OxideError {
+	pub(crate) fn io(path: &Path, source: io::Error) -> OxideError {
+		OxideError::Io {
+			path: path.to_path_buf(),
+			source,
+		}
+	}
+
+	pub(crate) fn sink(file: &Path, source: io::Error) -> OxideError {
+		OxideError::Sink {
+			file: file.to_path_buf(),
+			source,
+		}
+	}
+
+	// whether the file couldn't be read as another program has it open or
+	// locked, which windows reports as a sharing or lock violation
+	pub(crate) fn in_use(&self) -> bool {
+		let source = match self {
+			OxideError::Io { source, .. }
+			| OxideError::WorkbookOpen {
+				source: XlsxError::Io(source),
+				..
+			} => source,
+			_ => return false,
+		};
+
+		let locked = cfg!(windows) && matches!(source.raw_os_error(), Some(32 | 33));
+		locked || source.kind() == io::ErrorKind::ResourceBusy
+	}
+
+	/// The file the error happened in.
+	pub fn file(&self) -> &Path {
+		match self {
+			OxideError::Io { path, .. } => path,
+			OxideError::WorkbookOpen { file, .. }
+			| OxideError::FileInUse { file }
+			| OxideError::SheetMissing { file, .. }
+			| OxideError::MarkerNotFound { file, .. }
+			| OxideError::SchemaMismatch { file, .. }
+			| OxideError::Sink { file, .. } => file,
+			OxideError::Transform { file, .. } => file,
+		}
+	}
+}
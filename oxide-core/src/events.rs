@@ -0,0 +1,28 @@
+use crate::summary::RunSummary;
+
+/// Progress reported while an export runs, sent to the channel set in
+/// [`ExportOptions::events`](crate::ExportOptions::events).
+pub enum ExportEvent {
+	/// A workbook is being read, `index` counts up from 0 to `total`.
+	FileStarted {
+		file_name: String,
+		index: usize,
+		total: usize,
+	},
+	/// A workbook's rows were added to the output.
+	RowsWritten {
+		file_name: String,
+		rows: usize,
+	},
+	Warning {
+		file_name: String,
+		message: String,
+	},
+	/// A workbook couldn't be read and was left out of the output.
+	FileSkipped {
+		file_name: String,
+		error: String,
+	},
+	/// The output was written, always the last event of a successful run.
+	Finished(RunSummary),
+}
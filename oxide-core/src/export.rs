@@ -0,0 +1,2270 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use calamine::{Data, DataType, Range, Reader, ToCellDeserializer, XlsxError};
+
+use crate::aggregate::{self, Aggregation, Aggregator, TotalRow, Totals};
+use crate::archive::Archive;
+use crate::checkpoint::Checkpoint;
+use crate::coerce::{self, ValueType};
+use crate::cumulative::{CumulativeMeters, RunningMeters};
+use crate::datetime::DateTimes;
+use crate::duplicates::{DuplicateReports, ReportDay, Revisions};
+use crate::error::OxideError;
+use crate::events::ExportEvent;
+use crate::fill::{self, VoidedRows};
+use crate::filter::{DateRange, FilterCheck, RowFilter};
+use crate::header::HeaderStyle;
+use crate::header_match;
+use crate::hole_id::HoleIds;
+use crate::hyperlink::{Hyperlinks, Links};
+use crate::intervals::{DepthIntervals, IntervalCheck};
+use crate::lookup::{Joiner, Lookup};
+use crate::manifest::{file_sha256, keep_edited_output, write_checksum, write_manifest};
+use crate::merge::{ExistingOutput, MergeSink};
+use crate::number_format::{NumberFormat, NumberFormats};
+use crate::output_schema::{OutputSchema, SchemaRecorder, SchemaSidecar};
+use crate::plugin::Plugins;
+use crate::preset::{ExportPreset, PresetSink};
+use crate::provenance::{self, Provenance, ProvenanceSink};
+use crate::qa::{QaCheck, QaChecker, QaOutput, QaStats};
+use crate::quarantine::quarantine;
+use crate::recovery;
+use crate::region::Region;
+use crate::remarks::{self, Remark};
+use crate::report::{self, PdfReport, ReportTotals};
+use crate::retry::OpenRetries;
+use crate::schema_cache::{self, CacheLookup, CachedSchema, SchemaCache};
+use crate::section::{Section, SectionRows, SectionTable};
+use crate::sink::{CsvDialect, CsvSink, NullValues, OutputFormat, OutputSink};
+use crate::sort::SortKey;
+use crate::source::{SpreadsheetSource, Workbook};
+use crate::split;
+use crate::staging::{self, RowStage};
+use crate::summary::{FileTiming, RunSummary};
+use crate::template::{FileDate, Remarks, ReportTemplate, TemplateRule};
+use crate::validation::{InvalidRows, Validation, Validator};
+use crate::workbook::{WorkbookFile, worksheet_name};
+
+/// First-column text of the row holding the table's main headers, in the
+/// default [`ReportTemplate`].
+pub const DATA_START_ID: &str = "Hole Number";
+/// First-column text of the row that ends the table.
+pub const DATA_END_ID: &str = "Sub-Totals";
+/// First-column text of the row that starts the remarks block.
+pub const REMARKS_START_ID: &str = "Remarks";
+/// Name of the table or file the validation violations are written to.
+pub const VIOLATIONS_TABLE: &str = "violations";
+/// Name of the table or file the QA report is written to.
+pub const QA_TABLE: &str = "qa";
+/// Name of the table or file the remarks blocks are written to.
+pub const REMARKS_TABLE: &str = "remarks";
+/// Name of the table or file the gaps and overlaps between depth intervals
+/// are written to.
+pub const INTERVALS_TABLE: &str = "intervals";
+
+/// Settings for a single [`generate_output`] run.
+#[derive(Clone)]
+pub struct ExportOptions {
+	/// Workbooks usually live on a smb share, where too many concurrent readers
+	/// end up slower than reading them one after another.
+	pub max_concurrent_files: usize,
+	/// Roughly how many bytes of rows are held in memory before they're spilled
+	/// to temporary files, site laptops often have little ram to spare.
+	pub memory_limit: usize,
+	/// Reuse files already recorded in the output's checkpoint instead of
+	/// starting the export over.
+	pub resume: bool,
+	/// Only the first rows of every workbook are exported, to try out what a
+	/// large folder's export looks like before running all of it.
+	pub row_limit: Option<usize>,
+	/// How often workbooks open in excel are tried again before they're
+	/// skipped.
+	pub open_retries: OpenRetries,
+	/// Workbooks that are empty or were modified less than this long ago are
+	/// still being written or synced, and are left for the next export.
+	pub settle_time: Duration,
+	/// Receives progress while the export runs, a failing file is reported here
+	/// and skipped instead of failing the whole export.
+	pub events: Option<mpsc::Sender<ExportEvent>>,
+	/// How the headers read from the workbooks are written.
+	pub header_style: HeaderStyle,
+	/// Layout of the report table in every workbook.
+	pub template: ReportTemplate,
+	/// Layouts of the workbooks matching a rule's pattern, the first matching
+	/// rule wins and the rest are read with `template`.
+	pub template_rules: Vec<TemplateRule>,
+	/// Sinks and row transforms added on top of the built in ones.
+	pub plugins: Plugins,
+	/// Where header layouts detected in earlier runs are kept, none detects
+	/// them anew every run.
+	pub schema_cache: Option<PathBuf>,
+	/// Journal parsed files next to the output, so an interrupted export can be
+	/// resumed.
+	pub checkpoint: bool,
+	/// Group-by summaries written alongside the rows.
+	pub aggregations: Vec<Aggregation>,
+	/// Rows summing up `total_columns`, tagged in a `row_type` column.
+	pub totals: Totals,
+	/// Columns summed up by the total rows.
+	pub total_columns: Vec<String>,
+	/// Rules rows are checked against, breaking ones end up in a violations
+	/// report written alongside the rows.
+	pub validation: Option<Validation>,
+	/// Schema of the exported table inferred from its values, written next to
+	/// the output for loading it into a database.
+	pub schema_sidecar: SchemaSidecar,
+	/// Where the export says what wrote it, with `profile` naming the settings
+	/// or template it ran with.
+	pub provenance: Provenance,
+	/// Name of the settings or template the export ran with, for the
+	/// provenance.
+	pub profile: String,
+	/// Written in place of empty values in every table and format, empty leaves
+	/// them empty.
+	pub null_value: String,
+	/// How csv outputs are laid out, like excel in a locale with decimal commas
+	/// expects them.
+	pub csv_dialect: CsvDialect,
+	/// Tables joined onto every row by a key column.
+	pub lookups: Vec<Lookup>,
+	/// Add the workbook and sheet every row was read from.
+	pub source_columns: bool,
+	/// Number rows in a leading `row_id` column.
+	pub row_ids: RowIds,
+	/// Only rows whose date falls within it are written.
+	pub date_range: DateRange,
+	/// Only rows matching it are written, it sees the lookups' columns too.
+	pub filter: Option<RowFilter>,
+	/// Columns rows are sorted by once every file is read, in file order when
+	/// empty.
+	pub sort_by: Vec<SortKey>,
+	/// Columns written first, in this order, whatever order the workbooks have
+	/// them in. The rest follow as they're merged.
+	pub column_order: Vec<String>,
+	/// What happens to workbooks reporting the same date as another one.
+	pub duplicate_reports: DuplicateReports,
+	/// Only the first of byte for byte identical workbooks is read, at the cost
+	/// of reading every workbook once more to hash it.
+	pub skip_identical: bool,
+	/// Columns identifying a row, like date and hole_number. When set, rows are
+	/// merged into an existing csv output instead of replacing it, new ones
+	/// updating the rows with the same key and added after the rest.
+	pub merge_key: Vec<String>,
+	/// Checks flagging numeric values that look off, in a `qa_flags` column
+	/// or a QA report as `qa_output` says.
+	pub qa_checks: Vec<QaCheck>,
+	/// Where the QA checks' flags are written.
+	pub qa_output: QaOutput,
+	/// Write every report's remarks block to a remarks table, keyed by date and
+	/// hole.
+	pub remarks_output: bool,
+	/// Columns of the depth intervals checked for gaps and overlaps per hole,
+	/// written to an intervals report.
+	pub depth_intervals: Option<DepthIntervals>,
+	/// Columns a running total of every hole's meters is computed from, in a
+	/// `cumulative_meters` column.
+	pub cumulative_meters: Option<CumulativeMeters>,
+	/// Layout of the table written, renaming and picking columns for another
+	/// system to import.
+	pub preset: ExportPreset,
+	/// Rewrites a column's hole ids to a single spelling as files are read,
+	/// before anything groups or matches rows by them.
+	pub hole_ids: Option<HoleIds>,
+	/// Hash the output into `out.csv.sha256`, and list the hash of every
+	/// workbook read in `out.manifest.json`.
+	pub manifest: bool,
+	/// Write a short pdf of the files read, the totals per hole and per day and
+	/// the warnings next to the output.
+	pub pdf_report: Option<PdfReport>,
+	/// Move the workbooks read out of the folder once the output's written.
+	pub archive: Option<Archive>,
+	/// Copy workbooks that fail to parse into this folder with a text file of
+	/// the error, relative to the one they're read from unless absolute.
+	pub quarantine: Option<PathBuf>,
+	/// Formats numbers of the columns named like they're written, after a
+	/// preset renamed them, in csv as text and in excel as a cell format.
+	pub number_formats: BTreeMap<String, NumberFormat>,
+	/// How cells holding times, dates with a time and durations are written.
+	pub date_times: DateTimes,
+}
+
+impl Default for ExportOptions {
+	fn default() -> Self {
+		ExportOptions {
+			max_concurrent_files: 4,
+			memory_limit: staging::DEFAULT_MEMORY_LIMIT,
+			resume: false,
+			row_limit: None,
+			open_retries: OpenRetries::default(),
+			settle_time: Duration::from_secs(5),
+			events: None,
+			header_style: HeaderStyle::default(),
+			template: ReportTemplate::default(),
+			template_rules: Vec::new(),
+			plugins: Plugins::default(),
+			schema_cache: SchemaCache::default_path(),
+			checkpoint: true,
+			aggregations: Vec::new(),
+			totals: Totals::None,
+			total_columns: Vec::new(),
+			validation: None,
+			schema_sidecar: SchemaSidecar::None,
+			provenance: Provenance::None,
+			profile: String::new(),
+			null_value: String::new(),
+			csv_dialect: CsvDialect::default(),
+			lookups: Vec::new(),
+			source_columns: false,
+			row_ids: RowIds::None,
+			date_range: DateRange::default(),
+			filter: None,
+			sort_by: Vec::new(),
+			column_order: Vec::new(),
+			duplicate_reports: DuplicateReports::Warn,
+			skip_identical: true,
+			merge_key: Vec::new(),
+			qa_checks: Vec::new(),
+			qa_output: QaOutput::Column,
+			remarks_output: false,
+			depth_intervals: None,
+			cumulative_meters: None,
+			preset: ExportPreset::None,
+			hole_ids: None,
+			manifest: false,
+			pdf_report: None,
+			archive: None,
+			quarantine: None,
+			number_formats: BTreeMap::new(),
+			date_times: DateTimes::default(),
+		}
+	}
+}
+
+/// How rows are numbered in a leading `row_id` column, totals rows are left
+/// without one.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RowIds {
+	#[default]
+	None,
+	/// Counting up from 1 across the whole export.
+	Sequential,
+	/// The row's position in its file, counting from the first data row, like
+	/// in the violations report.
+	PerFile,
+}
+
+impl RowIds {
+	pub const NAMES: &[&str] = &["none", "sequential", "per_file"];
+
+	pub fn from_name(name: &str) -> Option<RowIds> {
+		Some(match name {
+			"none" => RowIds::None,
+			"sequential" => RowIds::Sequential,
+			"per_file" => RowIds::PerFile,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			RowIds::None => "none",
+			RowIds::Sequential => "sequential",
+			RowIds::PerFile => "per_file",
+		}
+	}
+}
+
+impl ExportOptions {
+	/// The template the workbook at `path` is read with.
+	pub fn template_for(&self, path: &Path) -> &ReportTemplate {
+		self
+			.template_rules
+			.iter()
+			.find(|rule| rule.matches(path))
+			.map_or(&self.template, |rule| &rule.template)
+	}
+
+	fn emit(&self, event: ExportEvent) {
+		// nobody listening anymore is no reason to stop the export
+		if let Some(events) = &self.events {
+			let _ = events.send(event);
+		}
+	}
+}
+
+/// Time spent in each stage of parsing a single workbook.
+#[derive(Default, Clone, Copy)]
+pub struct StageTimings {
+	pub open: Duration,
+	pub decode: Duration,
+	pub scan: Duration,
+	pub serialize: Duration,
+}
+
+impl StageTimings {
+	pub fn total(&self) -> Duration {
+		self.open + self.decode + self.scan + self.serialize
+	}
+}
+
+/// The table read from a single workbook.
+#[derive(Default)]
+pub struct ParsedFile {
+	pub file_name: String,
+	// empty when the workbook has no sheet named after the file
+	pub headers: Vec<String>,
+	pub rows: Vec<Vec<String>>,
+	// sheets the rows were read from and how many came from each, in order
+	pub sheets: Vec<(String, usize)>,
+	// lines of the remarks block below the table
+	pub remarks: Vec<Remark>,
+	// blocks besides the table the template reads, like the consumables
+	pub sections: Vec<SectionRows>,
+	pub parse: Duration,
+	pub stages: StageTimings,
+	// set when the header layout had to be detected, so it can be cached
+	pub schema: Option<CachedSchema>,
+	pub warnings: Vec<String>,
+}
+
+impl ParsedFile {
+	// keeps the first `rows` rows, and the sheets they came from
+	fn truncate(&mut self, rows: usize) {
+		self.rows.truncate(rows);
+		let mut left = rows;
+		for (_, sheet_rows) in &mut self.sheets {
+			*sheet_rows = (*sheet_rows).min(left);
+			left -= *sheet_rows;
+		}
+		self.sheets.retain(|(_, sheet_rows)| *sheet_rows > 0);
+	}
+}
+
+/// Parses every workbook in `source` and writes the aggregated table to
+/// `output_file`, in the format matching its extension and replacing
+/// anything already there, unless it's merged into it. Sink plugins take
+/// precedence over the built in formats.
+pub fn generate_output(
+	source: &dyn SpreadsheetSource,
+	output_file: String,
+	options: &ExportOptions,
+) -> Result<RunSummary, OxideError> {
+	let path = Path::new(&output_file);
+	let mut summary = write_to_file(source, path, &output_file, options)?;
+	if options.manifest {
+		summary.output_sha256 = Some(write_manifest(source, path, &summary)?);
+	}
+	if let Some(archive) = &options.archive {
+		let now = chrono::Local::now().naive_local();
+		let warnings = archive.run(source, &summary.files, now);
+		summary.warnings.extend(warnings);
+	}
+
+	Ok(summary)
+}
+
+fn write_to_file(
+	source: &dyn SpreadsheetSource,
+	path: &Path,
+	output_file: &str,
+	options: &ExportOptions,
+) -> Result<RunSummary, OxideError> {
+	if options.merge_key.is_empty() {
+		return write_complete(path, |partial| {
+			let mut sink = create_sink(partial, options)?;
+			write_output(source, sink.as_mut(), output_file, options)
+		});
+	}
+
+	if OutputFormat::from_path(path) != OutputFormat::Csv {
+		let error = io::Error::new(
+			io::ErrorKind::Unsupported,
+			"only csv outputs can be merged into",
+		);
+		return Err(OxideError::sink(path, error));
+	}
+
+	// read before the new output replaces it
+	let edited = keep_edited_output(path)?;
+	let existing = ExistingOutput::read(path, &options.csv_dialect)?;
+	let mut summary = write_complete(path, |partial| {
+		let mut sink = create_sink(partial, options)?;
+		match existing {
+			Some(existing) => {
+				let mut sink = MergeSink::new(sink.as_mut(), existing, &options.merge_key);
+				let mut summary = write_output(source, &mut sink, output_file, options)?;
+				summary.merged = Some(sink.counts());
+				Ok(summary)
+			}
+			None => write_output(source, sink.as_mut(), output_file, options),
+		}
+	})?;
+	summary.warnings.extend(edited);
+
+	// for the next merge to tell whether it was edited since
+	let hash = file_sha256(path).map_err(|e| OxideError::io(path, e))?;
+	write_checksum(path, &hash)?;
+	Ok(summary)
+}
+
+// writes the output to its partial path with `write`, only renaming it over
+// the output once it's complete, so an export that fails or is killed
+// halfway leaves the last output as it was
+fn write_complete<T>(
+	path: &Path,
+	write: impl FnOnce(&Path) -> Result<T, OxideError>,
+) -> Result<T, OxideError> {
+	let partial = recovery::partial_path(path);
+	let result = write(&partial);
+	match &result {
+		Ok(_) => fs::rename(&partial, path).map_err(|e| OxideError::sink(path, e))?,
+		Err(_) => {
+			let _ = fs::remove_file(&partial);
+		}
+	}
+
+	result
+}
+
+fn create_sink(path: &Path, options: &ExportOptions) -> Result<Box<dyn OutputSink>, OxideError> {
+	options
+		.plugins
+		.create_sink(path)
+		.unwrap_or_else(|| {
+			let format = OutputFormat::from_path(path);
+			if format != OutputFormat::Csv {
+				return format.create_sink(path);
+			}
+			let sink = CsvSink::create_with_dialect(path, options.csv_dialect)?;
+			Ok(Box::new(sink) as Box<dyn OutputSink>)
+		})
+		.map_err(|e| OxideError::sink(path, e))
+}
+
+/// Same as [`generate_output`], but writes into an already created `sink`.
+/// The files `options` ask for besides the table are still written next
+/// to `output_file`: the checkpoint, side tables the sink can't hold, the
+/// schema and provenance sidecars and the PDF report. The schema cache is
+/// updated and workbooks that fail to parse are copied to the quarantine
+/// folder as well, so only `source` and `sink` are touched with all of
+/// those turned off.
+pub fn write_output(
+	source: &dyn SpreadsheetSource,
+	sink: &mut dyn OutputSink,
+	output_file: &str,
+	options: &ExportOptions,
+) -> Result<RunSummary, OxideError> {
+	let run_start = Instant::now();
+	let mut summary = RunSummary::default();
+	let output_path = output_file;
+	let output_file_path = PathBuf::from(output_path);
+	let sink_error = |e| OxideError::sink(&output_file_path, e);
+
+	// reading a workbook a sync client is still writing would find it empty
+	// or cut short
+	let now = SystemTime::now();
+	let mut files = source.list()?;
+	if let Some(archive) = &options.archive {
+		files = archive.drop_renamed(source, files);
+	}
+	let (files, unsettled): (Vec<_>, Vec<_>) = files
+		.into_iter()
+		.partition(|file| file.settled(now, options.settle_time));
+	let total = files.len() + unsettled.len();
+	let files = match options.skip_identical {
+		true => {
+			let (files, identical) = crate::duplicates::drop_identical(source, files);
+			summary.identical_files = identical;
+			files
+		}
+		false => files,
+	};
+	for file in unsettled {
+		let file_name = worksheet_name(&file.path).to_string();
+		let error = "it's still being written or synced, it's left for the next export";
+		summary
+			.warnings
+			.push(format!("{}: skipped, {}", file_name, error));
+		options.emit(ExportEvent::FileSkipped {
+			file_name,
+			error: error.to_string(),
+		});
+	}
+
+	let checkpoint_path = Checkpoint::path_for(output_path);
+	let checkpoint_error = |e| OxideError::io(&checkpoint_path, e);
+	let mut checkpoint = match options.checkpoint {
+		true => Some(Checkpoint::open(output_path, options.resume).map_err(checkpoint_error)?),
+		false => None,
+	};
+	let mut pending = BTreeMap::new();
+	if let Some(checkpoint) = &checkpoint {
+		for (idx, file) in files.iter().enumerate() {
+			if checkpoint.is_completed(file) {
+				options.emit(ExportEvent::FileStarted {
+					file_name: worksheet_name(&file.path).to_string(),
+					index: idx,
+					total,
+				});
+				pending.insert(idx, Ok(checkpoint.restore(file).map_err(checkpoint_error)?));
+			}
+		}
+	}
+
+	let remaining: Vec<_> = files
+		.iter()
+		.enumerate()
+		.filter(|(idx, _)| pending.contains_key(idx).not())
+		.collect();
+
+	// each distinct header layout seen so far, staged rows are prefixed with
+	// the index of the layout they were read with, of the file they're from,
+	// their number within it and the sheet they're from
+	let mut schemas: Vec<Vec<String>> = Vec::new();
+	let mut stage = RowStage::new(options.memory_limit);
+	let cache = options
+		.schema_cache
+		.as_deref()
+		.map(SchemaCache::load)
+		.unwrap_or_default();
+	let mut detected_schemas = Vec::new();
+	// report date of every file, to find the ones reporting the same day
+	let mut report_days = vec![None; files.len()];
+	let mut stats = RowStats {
+		qa: QaStats::new(&options.qa_checks),
+		cumulative: options.cumulative_meters.as_ref().map(RunningMeters::new),
+	};
+	let mut remark_rows = Vec::new();
+	let mut section_tables = Vec::new();
+	let mut stage_file =
+		|idx: usize, result: Result<ParsedFile, OxideError>| -> Result<(), OxideError> {
+			let mut parsed = match result {
+				Ok(parsed) => parsed,
+				Err(e) => {
+					let file_name = worksheet_name(&files[idx].path).to_string();
+					summary
+						.warnings
+						.push(format!("{}: skipped, {}", file_name, e));
+					// a workbook that's only open elsewhere isn't broken
+					if let Some(folder) = &options.quarantine
+						&& matches!(e, OxideError::FileInUse { .. }).not()
+					{
+						let now = chrono::Local::now().naive_local();
+						let copied = quarantine(source, folder, &files[idx].path, &e, now);
+						if let Err(copy_error) = copied {
+							summary.warnings.push(format!(
+								"{}: failed to quarantine it to {}: {}",
+								file_name,
+								folder.display(),
+								copy_error
+							));
+						}
+					}
+					options.emit(ExportEvent::FileSkipped {
+						file_name,
+						error: e.to_string(),
+					});
+					return Ok(());
+				}
+			};
+
+			let stage_start = Instant::now();
+			if let Some(checkpoint) = &mut checkpoint
+				&& checkpoint.is_completed(&files[idx]).not()
+			{
+				checkpoint
+					.record(&files[idx], &parsed)
+					.map_err(checkpoint_error)?;
+			}
+
+			// the checkpoint keeps every row, for a full export resumed later
+			if let Some(limit) = options.row_limit {
+				parsed.truncate(limit);
+			}
+
+			detected_schemas.extend(parsed.schema.take());
+			if let Some(col) = parsed.headers.iter().rposition(|h| h == "date")
+				&& let Some(date) = parsed.rows.first().and_then(|row| row.get(col))
+				&& date.is_empty().not()
+			{
+				let shift = parsed.headers.iter().position(|h| h == "shift");
+				report_days[idx] = Some(ReportDay {
+					date: date.clone(),
+					shift: shift
+						.and_then(|col| parsed.rows[0].get(col))
+						.cloned()
+						.unwrap_or_default(),
+					rule: options
+						.template_rules
+						.iter()
+						.position(|rule| rule.matches(&files[idx].path)),
+				});
+			}
+
+			for warning in parsed.warnings.drain(..) {
+				summary
+					.warnings
+					.push(format!("{}: {}", parsed.file_name, warning));
+				options.emit(ExportEvent::Warning {
+					file_name: parsed.file_name.clone(),
+					message: warning,
+				});
+			}
+
+			if let Some(hole_ids) = &options.hole_ids {
+				hole_ids.apply(&parsed.headers, &mut parsed.rows);
+				for remark in &mut parsed.remarks {
+					remark.hole = hole_ids.normalize(&remark.hole);
+				}
+			}
+
+			for section in parsed.sections.drain(..) {
+				SectionTable::add(&mut section_tables, section, &parsed.file_name);
+			}
+
+			if options.remarks_output {
+				for remark in parsed.remarks.drain(..) {
+					remark_rows.push(vec![
+						remark.date,
+						remark.hole,
+						remark.text,
+						parsed.file_name.clone(),
+					]);
+				}
+			}
+
+			let rows = parsed.rows.len();
+			stats.qa.add(&parsed.headers, &parsed.rows);
+			if let Some(cumulative) = &mut stats.cumulative {
+				cumulative.add(idx, &parsed.headers, &parsed.rows);
+			}
+
+			if parsed.headers.is_empty().not() {
+				let schema_idx = match schemas.iter().position(|s| *s == parsed.headers) {
+					Some(idx) => idx,
+					None => {
+						schemas.push(parsed.headers);
+						schemas.len() - 1
+					}
+				};
+
+				// files restored from an older checkpoint don't say which
+				// sheet they were read from
+				let sheets = match parsed.sheets.is_empty() {
+					true => vec![(parsed.file_name.clone(), rows)],
+					false => parsed.sheets,
+				};
+				let row_sheets = sheets
+					.iter()
+					.flat_map(|(sheet, rows)| std::iter::repeat_n(sheet, *rows));
+				for (row_idx, (row, sheet)) in parsed.rows.into_iter().zip(row_sheets).enumerate() {
+					let mut row_data = Vec::with_capacity(row.len() + 4);
+					row_data.push(schema_idx.to_string());
+					row_data.push(idx.to_string());
+					row_data.push((row_idx + 1).to_string());
+					row_data.push(sheet.clone());
+					row_data.extend(row);
+					stage.push(row_data).map_err(sink_error)?;
+				}
+			}
+
+			options.emit(ExportEvent::RowsWritten {
+				file_name: parsed.file_name.clone(),
+				rows,
+			});
+			summary.files.push(FileTiming {
+				file_name: parsed.file_name,
+				path: files[idx].path.clone(),
+				rows,
+				parse: parsed.parse,
+				write: stage_start.elapsed(),
+			});
+
+			Ok(())
+		};
+
+	let workers = options
+		.max_concurrent_files
+		.clamp(1, remaining.len().max(1));
+	let next_path = AtomicUsize::new(0);
+	let cancelled = AtomicBool::new(false);
+	let (result_tx, result_rx) = mpsc::channel();
+	thread::scope(|scope| -> Result<(), OxideError> {
+		for _ in 0..workers {
+			let result_tx = result_tx.clone();
+			let (remaining, next_path, cancelled, cache) = (&remaining, &next_path, &cancelled, &cache);
+			scope.spawn(move || {
+				while cancelled.load(Ordering::Relaxed).not() {
+					let next = next_path.fetch_add(1, Ordering::Relaxed);
+					let Some(&(idx, file)) = remaining.get(next) else {
+						break;
+					};
+
+					options.emit(ExportEvent::FileStarted {
+						file_name: worksheet_name(&file.path).to_string(),
+						index: idx,
+						total,
+					});
+
+					let result = options.open_retries.run(&file.path, || {
+						parse_file(
+							source,
+							&file.path,
+							cache,
+							options.template_for(&file.path),
+							&options.header_style,
+							&options.date_times,
+						)
+					});
+					let result = result.and_then(|mut parsed| {
+						options.plugins.transform(&file.path, &mut parsed)?;
+						Ok(parsed)
+					});
+
+					if result_tx.send((idx, result)).is_err() {
+						break;
+					}
+				}
+			});
+		}
+
+		drop(result_tx);
+
+		// results arrive in whatever order the workers finish, but rows are
+		// staged in file order so the output stays deterministic
+		let mut next_idx = 0;
+		let mut stage_ready =
+			|pending: &mut BTreeMap<usize, Result<ParsedFile, OxideError>>| -> Result<(), OxideError> {
+				while let Some(result) = pending.remove(&next_idx) {
+					let idx = next_idx;
+					next_idx += 1;
+					if let Err(e) = stage_file(idx, result) {
+						cancelled.store(true, Ordering::Relaxed);
+						return Err(e);
+					}
+				}
+
+				Ok(())
+			};
+
+		stage_ready(&mut pending)?;
+		for (idx, result) in result_rx {
+			pending.insert(idx, result);
+			stage_ready(&mut pending)?;
+		}
+
+		Ok(())
+	})?;
+
+	if let Some(cache_path) = &options.schema_cache
+		&& detected_schemas.is_empty().not()
+	{
+		let mut cache = cache;
+		for schema in detected_schemas {
+			cache.insert(schema);
+		}
+
+		// a stale cache only costs a slower next run, so don't fail the export
+		let _ = cache.save(cache_path);
+	}
+
+	summary.header_matches = header_match::propose(&schemas);
+	if let Some(hole_ids) = &options.hole_ids
+		&& schemas
+			.iter()
+			.all(|schema| schema.contains(&hole_ids.column).not())
+	{
+		summary
+			.warnings
+			.push(format!("hole ids: there's no {} column", hole_ids.column));
+	}
+
+	let revisions = Revisions::new(&files, &report_days);
+	summary
+		.warnings
+		.extend(revisions.warnings(&files, options.duplicate_reports));
+
+	let output_start = Instant::now();
+	let mut sink = NumberFormats::new(sink, &options.number_formats);
+	let mut sink = NullValues::new(&mut sink, &options.null_value);
+	// the schema is recorded before nulls are replaced, so they still count
+	let mut recorder = SchemaRecorder::new(&mut sink, options.schema_sidecar != SchemaSidecar::None);
+	let exported_at = chrono::Local::now()
+		.format("%Y-%m-%dT%H:%M:%S%:z")
+		.to_string();
+	let fields = provenance::fields(&options.profile, &exported_at, files.len());
+	// after the preset, which would leave its columns out
+	let mut provenance = ProvenanceSink::new(&mut recorder, options.provenance, fields.clone());
+	let mut sink = PresetSink::new(&mut provenance, options.preset);
+	if let Some(cumulative) = &mut stats.cumulative {
+		let keep_newest = options.duplicate_reports == DuplicateReports::KeepNewest;
+		cumulative.finish(|idx| keep_newest && revisions.is_latest(idx).not());
+	}
+
+	let reports = write_union(
+		&mut sink, &schemas, stage, options, &files, &revisions, &stats,
+	)
+	.map_err(sink_error)?;
+	summary.warnings.extend(reports.warnings);
+	for aggregator in &reports.aggregators {
+		let aggregation = aggregator.aggregation();
+		let table = Table {
+			name: &aggregation.name,
+			header: aggregation.columns(),
+			rows: aggregator.rows(),
+		};
+		write_table(&mut sink, table, &output_file_path, options)?;
+	}
+
+	if options.validation.is_some() {
+		let violations = reports.violations;
+		if violations.is_empty().not() {
+			summary.warnings.push(format!(
+				"{} values break validation rules, see the violations report",
+				violations.len()
+			));
+		}
+
+		let table = Table {
+			name: VIOLATIONS_TABLE,
+			header: ["file", "row", "column", "rule", "value"]
+				.map(String::from)
+				.to_vec(),
+			rows: violations.into_iter(),
+		};
+		write_table(&mut sink, table, &output_file_path, options)?;
+	}
+
+	if options.qa_checks.is_empty().not() {
+		if reports.qa_flagged > 0 {
+			let place = match options.qa_output {
+				QaOutput::Column => "the qa_flags column",
+				QaOutput::Report => "the qa report",
+			};
+			summary.warnings.push(format!(
+				"{} values were flagged by qa checks, see {}",
+				reports.qa_flagged, place
+			));
+		}
+
+		if options.qa_output == QaOutput::Report {
+			let table = Table {
+				name: QA_TABLE,
+				header: ["file", "row", "column", "flag", "value"]
+					.map(String::from)
+					.to_vec(),
+				rows: reports.qa.into_iter(),
+			};
+			write_table(&mut sink, table, &output_file_path, options)?;
+		}
+	}
+
+	if options.depth_intervals.is_some() {
+		let intervals = reports.intervals;
+		if intervals.is_empty().not() {
+			summary.warnings.push(format!(
+				"{} gaps and overlaps between depth intervals, see the intervals report",
+				intervals.len()
+			));
+		}
+
+		let table = Table {
+			name: INTERVALS_TABLE,
+			header: ["hole", "issue", "from", "to", "file", "row"]
+				.map(String::from)
+				.to_vec(),
+			rows: intervals.into_iter(),
+		};
+		write_table(&mut sink, table, &output_file_path, options)?;
+	}
+
+	if options.remarks_output {
+		let table = Table {
+			name: REMARKS_TABLE,
+			header: ["date", "hole", "remark", "file"]
+				.map(String::from)
+				.to_vec(),
+			rows: remark_rows.into_iter(),
+		};
+		write_table(&mut sink, table, &output_file_path, options)?;
+	}
+
+	for section in section_tables {
+		let name = section.name.clone();
+		let (header, rows) = section.into_rows();
+		let table = Table {
+			name: &name,
+			header,
+			rows,
+		};
+		write_table(&mut sink, table, &output_file_path, options)?;
+	}
+
+	sink.finish().map_err(sink_error)?;
+	summary.warnings.extend(
+		sink
+			.missing()
+			.iter()
+			.map(|column| format!("preset: there's no {} column", column)),
+	);
+	let no_preamble = provenance.no_preamble();
+	if options.provenance == Provenance::Sidecar || no_preamble {
+		let path = provenance::write_sidecar(&fields, &output_file_path)?;
+		if no_preamble {
+			summary.warnings.push(format!(
+				"provenance: the output can't hold a preamble, it's in {} instead",
+				path.display()
+			));
+		}
+	}
+	if let Some(mut schema) = recorder.into_schema() {
+		schema.null_value = options.null_value.clone();
+		write_schema(&schema, &output_file_path, options.schema_sidecar)?;
+	}
+
+	if let Some(checkpoint) = checkpoint {
+		checkpoint.finish().map_err(checkpoint_error)?;
+	}
+	summary.output = output_start.elapsed();
+	summary.elapsed = run_start.elapsed();
+	if options.pdf_report.is_some() {
+		let path = report::report_path(&output_file_path);
+		let pdf = report::render(&output_file_path, &summary, reports.report.as_ref());
+		recovery::write_file(&path, pdf).map_err(|e| OxideError::io(&path, e))?;
+		summary.report = Some(path);
+	}
+
+	options.emit(ExportEvent::Finished(summary.clone()));
+	Ok(summary)
+}
+
+/// Reads the report table laid out as `template` describes out of a single
+/// workbook in `source`, using `cache` to skip header detection for known
+/// layouts. When the template reads several sheets, their rows follow each
+/// other in the order the sheets are in.
+pub fn parse_file(
+	source: &dyn SpreadsheetSource,
+	path: &Path,
+	cache: &SchemaCache,
+	template: &ReportTemplate,
+	header_style: &HeaderStyle,
+	date_times: &DateTimes,
+) -> Result<ParsedFile, OxideError> {
+	let worksheet_name = worksheet_name(path);
+	let mut parsed = ParsedFile {
+		file_name: worksheet_name.to_string(),
+		..Default::default()
+	};
+
+	let stage_start = Instant::now();
+	let mut workbook = source.open(path)?;
+	parsed.stages.open = stage_start.elapsed();
+
+	let sheets = template.select_sheets(worksheet_name, &workbook.sheet_names());
+	if template.data_table.is_empty().not() {
+		workbook
+			.load_tables()
+			.map_err(|source| OxideError::WorkbookOpen {
+				file: path.to_path_buf(),
+				source,
+			})?;
+	}
+	if sheets.is_empty() {
+		parsed.warnings.push(template.no_sheets().to_string());
+	}
+
+	for sheet in sheets {
+		let stage_start = Instant::now();
+		let range = match workbook.worksheet_range(&sheet) {
+			Ok(r) => r,
+			// files without a matching sheet are skipped rather than failing the run
+			Err(XlsxError::WorksheetNotFound(_)) => {
+				parsed.warnings.push(
+					OxideError::SheetMissing {
+						file: path.to_path_buf(),
+						sheet: sheet.clone(),
+					}
+					.to_string(),
+				);
+				continue;
+			}
+			Err(source) => {
+				return Err(OxideError::WorkbookOpen {
+					file: path.to_path_buf(),
+					source,
+				});
+			}
+		};
+		let links = match template.hyperlinks {
+			Hyperlinks::Text => Links::default(),
+			hyperlinks => {
+				let mut reader = source.reader(path)?;
+				Links::read(reader.as_mut(), &sheet, hyperlinks).map_err(|e| OxideError::io(path, e))?
+			}
+		};
+		let voided = match fill::parse_color(&template.voided_fill) {
+			Some(color) => {
+				let mut reader = source.reader(path)?;
+				fill::filled_cells(reader.as_mut(), &sheet, &color).map_err(|e| OxideError::io(path, e))?
+			}
+			None => HashSet::new(),
+		};
+		let region = match template.data_table.is_empty() {
+			true => None,
+			false => table_region(&mut workbook, path, &sheet, &template.data_table)?,
+		};
+		let region = match (region, template.data_name.is_empty()) {
+			(None, false) => Region::named(workbook.defined_names(), &template.data_name, &sheet),
+			(region, _) => region,
+		};
+		let region = region.or_else(|| Region::parse(&template.data_range));
+		parsed.stages.decode += stage_start.elapsed();
+
+		let read = read_sheet(
+			&Sheet {
+				name: &sheet,
+				range: &range,
+				date_times,
+				links,
+				voided,
+				region,
+			},
+			path,
+			cache,
+			template,
+			header_style,
+		)?;
+		parsed.stages.scan += read.stages.scan;
+		parsed.stages.serialize += read.stages.serialize;
+		// several sheets are only told apart in the warnings
+		let named = |warning: String| match template.several_sheets() {
+			true => format!("{}: {}", sheet, warning),
+			false => warning,
+		};
+
+		if parsed.headers.is_empty() {
+			parsed.headers = read.headers;
+			parsed.schema = read.schema;
+		} else if parsed.headers != read.headers {
+			parsed.warnings.push(named(
+				"skipped, its columns aren't the same as the first sheet's".to_string(),
+			));
+			continue;
+		}
+
+		parsed.warnings.extend(read.warnings.into_iter().map(named));
+		parsed.sheets.push((sheet, read.rows.len()));
+		parsed.rows.extend(read.rows);
+		parsed.remarks.extend(read.remarks);
+		parsed.sections.extend(read.sections);
+	}
+
+	parsed.parse = parsed.stages.total();
+	Ok(parsed)
+}
+
+// where the excel table called `name` on the sheet is, or the first one
+// there for `*`, none when there isn't one
+fn table_region(
+	workbook: &mut Workbook,
+	path: &Path,
+	sheet: &str,
+	name: &str,
+) -> Result<Option<Region>, OxideError> {
+	let Some(table_name) = workbook
+		.table_names_in_sheet(sheet)
+		.into_iter()
+		.find(|table| name == "*" || table.eq_ignore_ascii_case(name))
+		.cloned()
+	else {
+		return Ok(None);
+	};
+
+	let table = workbook
+		.table_by_name(&table_name)
+		.map_err(|source| OxideError::WorkbookOpen {
+			file: path.to_path_buf(),
+			source,
+		})?;
+	Ok(Region::table(&table))
+}
+
+// a sheet being read, with what's read out of the workbook about it
+// besides its cells' values
+struct Sheet<'a> {
+	name: &'a str,
+	range: &'a Range<Data>,
+	date_times: &'a DateTimes,
+	links: Links,
+	// cells filled in the template's voided colour
+	voided: HashSet<(usize, usize)>,
+	region: Option<Region>,
+}
+
+impl Sheet<'_> {
+	// the value written for the cell at the zero-based row and column
+	fn cell(&self, position: (usize, usize), cell: &Data) -> String {
+		self.links.value(position, self.date_times.cell(cell))
+	}
+
+	// the sheet's cells the table is read from, which start on its first
+	// row like the sheet's do, so rows are counted the same in both. that's
+	// all of them unless the workbook says where the table is, and none when
+	// that's outside of the sheet
+	fn table(&self) -> Option<Cow<'_, Range<Data>>> {
+		let Some(region) = &self.region else {
+			return Some(Cow::Borrowed(self.range));
+		};
+
+		let (start, end) = self.range.start().zip(self.range.end())?;
+		let (top, left) = (region.top as u32, region.left as u32);
+		let (bottom, right) = (region.bottom as u32, region.right as u32);
+		if top < start.0 || top > end.0 || left > end.1 || right < start.1 {
+			return None;
+		}
+
+		let table = self
+			.range
+			.range((start.0, left.max(start.1)), (bottom, right.min(end.1)));
+		Some(Cow::Owned(table))
+	}
+}
+
+// the table of a single sheet, with the time spent scanning and reading it
+fn read_sheet(
+	sheet: &Sheet,
+	path: &Path,
+	cache: &SchemaCache,
+	template: &ReportTemplate,
+	header_style: &HeaderStyle,
+) -> Result<ParsedFile, OxideError> {
+	let r = sheet.range;
+	let mut parsed = ParsedFile::default();
+	let stage_start = Instant::now();
+	let table = sheet.table();
+	let (markers, cached_headers) = match (&sheet.region, &table) {
+		// the workbook says where the header is, so there's nothing to detect
+		// or cache. an excel table has a single header row, which names its
+		// columns
+		(Some(region), Some(_)) => {
+			let header_row = region.top - r.start().unwrap_or_default().0 as usize;
+			let mut markers = scan_markers_from(r, header_row, template);
+			if region.headers.is_empty().not() {
+				markers.header_rows = 1;
+			}
+			let headers = Some(region.headers.clone()).filter(|headers| headers.is_empty().not());
+			(markers, headers)
+		}
+		(Some(_), None) => (Markers::default(), None),
+		(None, _) => match cache.lookup(r, template) {
+			CacheLookup::Hit(entry) => (
+				scan_markers_from(r, entry.header_row, template),
+				Some(entry.headers),
+			),
+			CacheLookup::Drift(row) => {
+				parsed.warnings.push(format!(
+					"header layout at row {} no longer matches the cached template",
+					row + 1
+				));
+				(scan_markers(r, template), None)
+			}
+			CacheLookup::Miss => (scan_markers(r, template), None),
+		},
+	};
+	parsed.stages.scan = stage_start.elapsed();
+
+	let (Some(header_row), Some(table)) = (markers.header_row, table) else {
+		let marker = match &sheet.region {
+			Some(region) if region.headers.is_empty().not() => &template.data_table,
+			Some(_) if template.data_range.is_empty().not() => &template.data_range,
+			Some(_) => &template.data_name,
+			None => &template.data_start,
+		};
+		return Err(OxideError::MarkerNotFound {
+			file: path.to_path_buf(),
+			sheet: sheet.name.to_string(),
+			marker: marker.clone(),
+		});
+	};
+
+	let stage_start = Instant::now();
+	let headers = match cached_headers {
+		Some(headers) => headers,
+		None => {
+			let headers = read_headers(&table, header_row, template.header_rows);
+			if sheet.region.is_none() {
+				parsed.schema = Some(CachedSchema {
+					header_row,
+					header_rows: template.header_rows,
+					fingerprint: schema_cache::fingerprint(r, header_row, template.header_rows),
+					headers: headers.clone(),
+				});
+			}
+			headers
+		}
+	};
+
+	// a cached layout is only matched on its header cells, so make sure
+	// the sheet still has the columns it describes
+	let expected = headers.len();
+	if table.width() != expected {
+		return Err(OxideError::SchemaMismatch {
+			file: path.to_path_buf(),
+			row: header_row + 1,
+			expected,
+			found: table.width(),
+		});
+	}
+
+	parsed.headers = format_headers(&headers, template, header_style);
+	// links and the voided flag get their columns after the table's, before
+	// the date
+	let (first_row, first_col) = table.start().unwrap_or_default();
+	let (first_row, first_col) = (first_row as usize, first_col as usize);
+	let url_columns = sheet.links.url_columns(
+		first_row + header_row + markers.header_rows,
+		first_col..first_col + table.width(),
+	);
+	let mut extra_headers: Vec<_> = url_columns
+		.iter()
+		.map(|col| format!("{}_url", parsed.headers[col - first_col]))
+		.collect();
+	if template.flags_voided() {
+		extra_headers.push("voided".to_string());
+	}
+	let date_col = parsed.headers.len() - 1;
+	parsed.headers.splice(date_col..date_col, extra_headers);
+	let date = report_date(r, path, template, &mut parsed.warnings);
+	parsed.rows = read_rows(&table, &markers, template, &date, sheet, &url_columns);
+	if template.check_subtotals
+		&& let Some(end_row) = markers.end_row
+	{
+		let warnings = check_subtotals(&table, end_row, &parsed.headers, &parsed.rows, template);
+		parsed.warnings.extend(warnings);
+	}
+
+	if let Some(position) = template.shift_position() {
+		let shift = shift_name(&cell_text(r, Some(position)));
+		parsed.headers.push("shift".to_string());
+		for row in &mut parsed.rows {
+			row.push(shift.clone());
+		}
+	}
+
+	for (column, position) in template.metadata_positions() {
+		let value = cell_text(r, Some(position));
+		parsed.headers.push(column.clone());
+		for row in &mut parsed.rows {
+			row.push(value.clone());
+		}
+	}
+
+	// the blocks below the table aren't coerced with the rest, so their date
+	// is written like the table's will be
+	let date = match template.types.get("date") {
+		Some(value_type) => value_type.coerce(&date).unwrap_or(date),
+		None => date,
+	};
+	if let Some(remarks_row) = markers.remarks_row {
+		// the block runs to the end of the sheet, or to the table's end or
+		// the next section when they come after it
+		let end_row = markers
+			.end_row
+			.into_iter()
+			.chain(markers.section_rows.iter().flatten().copied())
+			.filter(|end_row| *end_row > remarks_row)
+			.min()
+			.unwrap_or(usize::MAX);
+		let holes: Vec<_> = parsed
+			.rows
+			.iter()
+			.filter_map(|row| row.first())
+			.map(|hole| hole.as_str())
+			.collect();
+		let rows = r
+			.rows()
+			.enumerate()
+			.skip(remarks_row + 1)
+			.take_while(|(row_idx, _)| *row_idx < end_row)
+			.map(|(_, row)| row);
+		parsed.remarks = remarks::read_remarks(rows, &date, &holes);
+	}
+
+	for (section, start) in template.sections.iter().zip(&markers.section_rows) {
+		let Some(start) = *start else {
+			continue;
+		};
+		let mut rows = read_section(r, section, start, header_style, &date, sheet.date_times);
+		let warnings = coerce::coerce_rows(&rows.headers, &mut rows.rows, &section.types);
+		parsed.warnings.extend(
+			warnings
+				.into_iter()
+				.map(|warning| format!("{}: {}", section.name, warning)),
+		);
+		parsed.sections.push(rows);
+	}
+
+	let warnings = split::split_columns(&mut parsed.headers, &mut parsed.rows, &template.splits);
+	parsed.warnings.extend(warnings);
+	if let Some(unpivot) = &template.unpivot {
+		parsed
+			.warnings
+			.extend(unpivot.apply(&mut parsed.headers, &mut parsed.rows));
+	}
+
+	let warnings = coerce::coerce_rows(&parsed.headers, &mut parsed.rows, &template.types);
+	parsed.warnings.extend(warnings);
+	parsed.stages.serialize = stage_start.elapsed();
+	Ok(parsed)
+}
+
+#[derive(Default)]
+struct Markers {
+	// row holding the data start marker, further header rows follow it
+	header_row: Option<usize>,
+	// the template's, or the one of an excel table
+	header_rows: usize,
+	end_row: Option<usize>,
+	remarks_row: Option<usize>,
+	// rows starting the template's sections, in the order it lists them
+	section_rows: Vec<Option<usize>>,
+}
+
+impl Markers {
+	// the row the table's rows run up to, its end marker or a section
+	// starting below it
+	fn table_end(&self) -> usize {
+		let header_row = self.header_row.unwrap_or_default();
+		self
+			.section_rows
+			.iter()
+			.flatten()
+			.copied()
+			.filter(|row| *row > header_row)
+			.chain(self.end_row)
+			.min()
+			.unwrap_or(usize::MAX)
+	}
+}
+
+fn scan_markers(r: &Range<Data>, template: &ReportTemplate) -> Markers {
+	// might want to search more than just the first cell
+	let header_row = r.rows().position(|row| {
+		row
+			.first()
+			.and_then(|c| c.as_string())
+			.is_some_and(|value| value == template.data_start)
+	});
+
+	match header_row {
+		Some(header_row) => scan_markers_from(r, header_row, template),
+		None => Markers::default(),
+	}
+}
+
+// finds the remaining markers once the header row is known, and where
+// every section starts in the same pass, above the table or below it
+fn scan_markers_from(r: &Range<Data>, header_row: usize, template: &ReportTemplate) -> Markers {
+	let mut markers = Markers {
+		header_row: Some(header_row),
+		header_rows: template.header_rows,
+		end_row: None,
+		remarks_row: None,
+		section_rows: vec![None; template.sections.len()],
+	};
+
+	for (row_idx, row) in r.rows().enumerate() {
+		let first_cell = row.first().unwrap_or(&Data::Empty);
+		let Some(value) = first_cell.as_string() else {
+			continue;
+		};
+
+		for (section, section_row) in template.sections.iter().zip(&mut markers.section_rows) {
+			if section_row.is_none() && value == section.start {
+				*section_row = Some(row_idx);
+			}
+		}
+
+		if row_idx <= header_row {
+			continue;
+		}
+
+		if markers.end_row.is_none() && value == template.data_end {
+			markers.end_row = Some(row_idx);
+		}
+
+		if markers.remarks_row.is_none() && value == template.remarks_start {
+			markers.remarks_row = Some(row_idx);
+		}
+	}
+
+	markers
+}
+
+// rows of a block laid out like a table, from below its header rows at
+// `start` to its end marker. columns without a header are left out and
+// empty rows skipped
+fn read_section(
+	r: &Range<Data>,
+	section: &Section,
+	start: usize,
+	header_style: &HeaderStyle,
+	date: &str,
+	date_times: &DateTimes,
+) -> SectionRows {
+	let sheet_headers = read_headers(r, start + 1, section.header_rows);
+	let cols: Vec<_> = (0..sheet_headers.len())
+		.filter(|col| sheet_headers[*col].trim().is_empty().not())
+		.collect();
+	let mut headers: Vec<_> = cols
+		.iter()
+		.map(|col| {
+			let header = header_style.format(&sheet_headers[*col]);
+			section.columns.get(&header).cloned().unwrap_or(header)
+		})
+		.collect();
+	headers.push("date".to_string());
+
+	// where every column written is among the ones read, the date last
+	let picked: Vec<Option<usize>> = match section.keep.is_empty() {
+		true => (0..headers.len()).map(Some).collect(),
+		false => section
+			.keep
+			.iter()
+			.map(|column| headers.iter().position(|h| h == column))
+			.collect(),
+	};
+
+	let mut rows = Vec::new();
+	for row in r.rows().skip(start + 1 + section.header_rows) {
+		let empty = row.iter().all(ToCellDeserializer::is_empty);
+		let first_cell = row.first().and_then(|c| c.as_string());
+		let ended = match section.end.is_empty() {
+			true => empty,
+			false => first_cell.is_some_and(|value| value == section.end),
+		};
+		if ended {
+			break;
+		}
+
+		if empty.not() {
+			let mut values: Vec<_> = cols.iter().map(|col| date_times.cell(&row[*col])).collect();
+			values.push(date.to_string());
+			let values = picked
+				.iter()
+				.map(|idx| idx.map_or(String::new(), |idx| values[idx].clone()))
+				.collect();
+			rows.push(values);
+		}
+	}
+
+	if section.keep.is_empty().not() {
+		headers = section.keep.clone();
+	}
+
+	SectionRows {
+		name: section.name.clone(),
+		headers,
+		rows,
+	}
+}
+
+fn read_headers(r: &Range<Data>, header_row: usize, header_rows: usize) -> Vec<String> {
+	let rows: Vec<_> = r.rows().skip(header_row).take(header_rows).collect();
+	merge_headers(&rows)
+}
+
+/// Merges the header rows of a table into a single name per column and
+/// formats them with `style`, applying the template's column renames.
+pub fn build_headers(
+	rows: &[&[Data]],
+	template: &ReportTemplate,
+	style: &HeaderStyle,
+) -> Vec<String> {
+	format_headers(&merge_headers(rows), template, style)
+}
+
+// header text as written in the workbook, formatting is left to the export's
+// style so cached layouts are shared between styles. headers spanning several
+// columns are only filled in on the first one, so every row but the last
+// carries its header over until the next one, and a lower row's is appended
+// with an underscore
+fn merge_headers(rows: &[&[Data]]) -> Vec<String> {
+	let width = rows.first().map_or(0, |row| row.len());
+	let mut spans = vec![String::new(); rows.len()];
+	(0..width)
+		.map(|i| {
+			let mut header = String::new();
+			for (level, row) in rows.iter().enumerate() {
+				let mut cell = row.get(i).and_then(|c| c.as_string()).unwrap_or_default();
+				if level + 1 < rows.len() {
+					if cell.is_empty() {
+						cell.clone_from(&spans[level]);
+					} else if cell != spans[level] {
+						spans[level].clone_from(&cell);
+						// a new span starts over every span below it
+						for span in &mut spans[level + 1..] {
+							span.clear();
+						}
+					}
+				}
+
+				if level == 0 {
+					header = cell;
+				} else if cell.is_empty().not() {
+					header = format!("{}_{}", header, cell);
+				}
+			}
+
+			header
+		})
+		.collect()
+}
+
+fn format_headers(
+	headers: &[String],
+	template: &ReportTemplate,
+	style: &HeaderStyle,
+) -> Vec<String> {
+	let mut formatted: Vec<_> = headers
+		.iter()
+		.map(|h| {
+			let header = style.format(h);
+			match template.columns.get(&header) {
+				Some(renamed) => renamed.clone(),
+				None => header,
+			}
+		})
+		.collect();
+	formatted.push("date".to_string());
+	formatted
+}
+
+// text of the template's date cell, or the date in the file's name when
+// the template takes it from there, empty without either
+fn report_date(
+	r: &Range<Data>,
+	path: &Path,
+	template: &ReportTemplate,
+	warnings: &mut Vec<String>,
+) -> String {
+	let cell = cell_text(r, template.date_position());
+	let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+	let Some(named) = template.file_date(&file_name) else {
+		return cell;
+	};
+
+	if cell.trim().is_empty() {
+		return named;
+	}
+
+	// a date typed in the cell is only told apart from a stale one copied
+	// from yesterday's report by comparing the two
+	let differ = match (
+		ValueType::Date.coerce(&cell),
+		ValueType::Date.coerce(&named),
+	) {
+		(Some(cell), Some(named)) => cell != named,
+		_ => false,
+	};
+	match template.file_date_mode {
+		FileDate::Fallback if differ => {
+			warnings.push(format!(
+				"the date cell says {}, but the file name says {}",
+				cell.trim(),
+				named
+			));
+			cell
+		}
+		FileDate::Fallback => cell,
+		FileDate::Override => named,
+	}
+}
+
+fn cell_text(r: &Range<Data>, position: Option<(usize, usize)>) -> String {
+	position
+		.and_then(|position| r.get(position))
+		.and_then(|data| data.as_string())
+		.unwrap_or_default()
+}
+
+// day or night when the shift cell says which, like "Night Shift" or "DS",
+// its text as it is otherwise
+fn shift_name(value: &str) -> String {
+	let words: Vec<_> = value
+		.split(|c: char| c.is_alphanumeric().not())
+		.map(|word| word.to_lowercase())
+		.collect();
+	let says = |names: &[&str]| words.iter().any(|word| names.contains(&word.as_str()));
+	if says(&["night", "n", "ns"]) {
+		return "night".to_string();
+	}
+
+	if says(&["day", "d", "ds"]) {
+		return "day".to_string();
+	}
+
+	value.trim().to_string()
+}
+
+fn read_rows(
+	r: &Range<Data>,
+	markers: &Markers,
+	template: &ReportTemplate,
+	report_date: &str,
+	sheet: &Sheet,
+	url_columns: &[usize],
+) -> Vec<Vec<String>> {
+	let Some(header_row) = markers.header_row else {
+		return Vec::new();
+	};
+
+	let mut end_row = markers.table_end();
+	let mut skipped_row = None;
+	match template.remarks {
+		Remarks::Skip => skipped_row = markers.remarks_row,
+		Remarks::End => end_row = end_row.min(markers.remarks_row.unwrap_or(usize::MAX)),
+		Remarks::Keep => {}
+	}
+
+	// where a row and column of the table are in the sheet
+	let (first_row, first_col) = r.start().unwrap_or_default();
+	let position =
+		|row_idx: usize, col: usize| (first_row as usize + row_idx, first_col as usize + col);
+	let voided = |row_idx: usize| sheet.voided.contains(&position(row_idx, 0));
+
+	r.rows()
+		.enumerate()
+		.skip(header_row + markers.header_rows)
+		.take_while(|(row_idx, _)| *row_idx < end_row)
+		.filter(|(row_idx, row)| {
+			let first_cell = row.first().unwrap_or(&Data::Empty);
+			Some(*row_idx) != skipped_row
+				&& ToCellDeserializer::is_empty(first_cell).not()
+				&& (template.voided_rows == VoidedRows::Skip && voided(*row_idx)).not()
+		})
+		.map(|(row_idx, row)| {
+			let mut row_data: Vec<_> = row
+				.iter()
+				.enumerate()
+				.map(|(col, c)| sheet.cell(position(row_idx, col), c))
+				.collect();
+			let (row, _) = position(row_idx, 0);
+			row_data.extend(
+				url_columns
+					.iter()
+					.map(|col| sheet.links.url((row, *col)).to_string()),
+			);
+			if template.flags_voided() {
+				row_data.push(voided(row_idx).to_string());
+			}
+			row_data.push(report_date.to_string());
+			row_data
+		})
+		.collect()
+}
+
+// a warning for every column whose number in the row ending the table isn't
+// the sum of the rows read, which usually means rows were missed or the
+// template no longer fits. sub-totals are typically rounded to a couple of
+// decimals, so closer than that counts as adding up
+fn check_subtotals(
+	r: &Range<Data>,
+	end_row: usize,
+	headers: &[String],
+	rows: &[Vec<String>],
+	template: &ReportTemplate,
+) -> Vec<String> {
+	let Some(subtotals) = r.rows().nth(end_row) else {
+		return Vec::new();
+	};
+
+	let mut warnings = Vec::new();
+	// the first cell holds the marker
+	for (col, cell) in subtotals.iter().enumerate().skip(1) {
+		let Ok(subtotal) = cell.to_string().trim().parse::<f64>() else {
+			continue;
+		};
+
+		let sum: f64 = rows
+			.iter()
+			.filter_map(|row| row.get(col)?.trim().parse::<f64>().ok())
+			.sum();
+		if (sum - subtotal).abs() > 0.005 {
+			warnings.push(format!(
+				"sub-totals: {} adds up to {}, but the {} row says {}",
+				headers.get(col).map_or("", |h| h.as_str()),
+				aggregate::format_number(sum),
+				template.data_end,
+				aggregate::format_number(subtotal)
+			));
+		}
+	}
+
+	warnings
+}
+
+// what's gathered about the rows as files are staged, for what needs all of
+// them read before the first one is written
+struct RowStats<'a> {
+	qa: QaStats,
+	cumulative: Option<RunningMeters<'a>>,
+}
+
+// what's left to write once the rows are, the aggregations and the rows of
+// the violations and QA reports, with the warnings about settings naming
+// columns that aren't there
+#[derive(Default)]
+struct UnionReports<'a> {
+	warnings: Vec<String>,
+	aggregators: Vec<Aggregator<'a>>,
+	violations: Vec<Vec<String>>,
+	qa: Vec<Vec<String>>,
+	qa_flagged: usize,
+	intervals: Vec<Vec<String>>,
+	report: Option<ReportTotals<'a>>,
+}
+
+// writes the union of all header layouts followed by every staged row
+// mapped onto it, columns missing from a row's layout are left empty. rows
+// are joined with the lookups, the ones outside the date range or not
+// matching the filter left out and the rest validated and QA checked,
+// totals rows written and the aggregations and depth intervals computed on
+// the way
+fn write_union<'a>(
+	sink: &mut dyn OutputSink,
+	schemas: &[Vec<String>],
+	stage: RowStage,
+	options: &'a ExportOptions,
+	files: &[WorkbookFile],
+	revisions: &Revisions,
+	stats: &RowStats,
+) -> io::Result<UnionReports<'a>> {
+	if schemas.is_empty() {
+		return Ok(UnionReports::default());
+	}
+
+	let mut warnings = Vec::new();
+
+	// columns are keyed by name and occurrence, so a layout that repeats a
+	// header name keeps all of its columns
+	let keyed = |schema: &[String]| -> Vec<(String, usize)> {
+		let mut seen: Vec<&String> = Vec::new();
+		schema
+			.iter()
+			.map(|name| {
+				let occurrence = seen.iter().filter(|s| **s == name).count();
+				seen.push(name);
+				(name.clone(), occurrence)
+			})
+			.collect()
+	};
+
+	let mut union: Vec<(String, usize)> = Vec::new();
+	for schema in schemas {
+		for key in keyed(schema) {
+			if union.contains(&key).not() {
+				union.push(key);
+			}
+		}
+	}
+
+	// the date column always comes last, as it did before layouts were merged
+	if let Some(pos) = union.iter().position(|(name, _)| name == "date") {
+		let date = union.remove(pos);
+		union.push(date);
+	}
+
+	// every occurrence of a column goes where it's ordered, in the order
+	// they're merged
+	let mut ordered = Vec::with_capacity(union.len());
+	for name in &options.column_order {
+		let len = ordered.len();
+		ordered.extend(union.extract_if(.., |(column, _)| column == name));
+		if ordered.len() == len {
+			warnings.push(format!("column order: there's no {} column", name));
+		}
+	}
+	ordered.append(&mut union);
+	let union = ordered;
+
+	let mappings: Vec<Vec<Option<usize>>> = schemas
+		.iter()
+		.map(|schema| {
+			let keys = keyed(schema);
+			union
+				.iter()
+				.map(|key| keys.iter().position(|k| k == key))
+				.collect()
+		})
+		.collect();
+
+	let mut header: Vec<_> = union.iter().map(|(name, _)| name.clone()).collect();
+	let date_col = match options.date_range.is_open() {
+		true => None,
+		false => {
+			let date_col = header.iter().position(|h| h == "date");
+			if date_col.is_none() {
+				warnings.push("date range: there's no date column".to_string());
+			}
+
+			date_col
+		}
+	};
+	// file name and the name reports go by of every file, by the index rows
+	// are staged with. the sheet is staged with every row, as a workbook can
+	// have several
+	let sources: Vec<_> = files
+		.iter()
+		.map(|f| {
+			let file_name = f.path.file_name().unwrap_or_default().to_string_lossy();
+			(file_name, worksheet_name(&f.path))
+		})
+		.collect();
+	if options.source_columns {
+		header.extend(["source_file", "source_sheet"].map(String::from));
+	}
+
+	let revision_numbers: Vec<_> = (0..files.len())
+		.map(|idx| revisions.number(idx).to_string())
+		.collect();
+	let numbered_revisions = options.duplicate_reports == DuplicateReports::Revision;
+	if numbered_revisions {
+		header.push("revision".to_string());
+	}
+
+	if stats.cumulative.is_some() {
+		header.push("cumulative_meters".to_string());
+	}
+
+	// lookup columns go after the rest, in the order the lookups are listed
+	let mut joiners = Vec::new();
+	for lookup in &options.lookups {
+		match Joiner::new(lookup, &header) {
+			Ok(joiner) => {
+				header.extend(lookup.columns().iter().cloned());
+				joiners.push(joiner);
+			}
+			Err(warning) => warnings.push(warning),
+		}
+	}
+
+	let filter = options
+		.filter
+		.as_ref()
+		.and_then(|filter| match FilterCheck::new(filter, &header) {
+			Ok(check) => Some(check),
+			Err(warning) => {
+				warnings.push(warning);
+				None
+			}
+		});
+
+	let mut aggregators = Vec::new();
+	for aggregation in &options.aggregations {
+		match Aggregator::new(aggregation, &header) {
+			Ok(aggregator) => aggregators.push(aggregator),
+			Err(warning) => warnings.push(warning),
+		}
+	}
+
+	let report = options
+		.pdf_report
+		.as_ref()
+		.and_then(|report| match report.totals(&header) {
+			Ok(totals) => Some(totals),
+			Err(warning) => {
+				warnings.push(warning);
+				None
+			}
+		});
+
+	let mut intervals =
+		options.depth_intervals.as_ref().and_then(|intervals| {
+			match IntervalCheck::new(intervals, &header) {
+				Ok(check) => Some(check),
+				Err(warning) => {
+					warnings.push(warning);
+					None
+				}
+			}
+		});
+
+	// staged rows sorted by the union columns the sort keys name
+	let mut sort_keys = Vec::new();
+	for key in &options.sort_by {
+		match union.iter().position(|(name, _)| *name == key.column) {
+			Some(col) => sort_keys.push((col, key)),
+			None => warnings.push(format!("sort: there's no {} column", key.column)),
+		}
+	}
+
+	let rows: Box<dyn Iterator<Item = io::Result<Vec<String>>>> = match sort_keys.is_empty() {
+		true => Box::new(stage.drain()?),
+		false => Box::new(stage.drain_sorted(|a, b| {
+			sort_keys
+				.iter()
+				.map(|(col, key)| {
+					key.compare(
+						staged_value(&mappings, a, *col),
+						staged_value(&mappings, b, *col),
+					)
+				})
+				.find(|ordering| ordering.is_ne())
+				.unwrap_or(cmp::Ordering::Equal)
+		})?),
+	};
+
+	// a file's rows are spread through sorted ones, so they only get one
+	// totals row at the end
+	let mut totals = options.totals;
+	if totals == Totals::PerFile && sort_keys.is_empty().not() {
+		warnings.push("totals: sorted rows only get totals at the end".to_string());
+		totals = Totals::End;
+	}
+
+	let (mut total, missing) = TotalRow::new(&options.total_columns, &header);
+	if totals != Totals::None {
+		for column in missing {
+			warnings.push(format!("totals: there's no {} column", column));
+		}
+	}
+
+	let mut validator = None;
+	if let Some(validation) = &options.validation {
+		let (rules, missing) = Validator::new(validation, &header);
+		for column in missing {
+			warnings.push(format!("validation: there's no {} column", column));
+		}
+
+		validator = Some(rules);
+	}
+
+	let (qa, missing) = QaChecker::new(&options.qa_checks, &header, &stats.qa);
+	for column in missing {
+		warnings.push(format!("qa: there's no {} column", column));
+	}
+
+	let invalid_rows = options
+		.validation
+		.as_ref()
+		.map(|v| v.invalid_rows)
+		.unwrap_or_default();
+	let numbered = options.row_ids != RowIds::None;
+	let tagged = totals != Totals::None;
+	let flagged = invalid_rows == InvalidRows::Flag;
+	let qa_column = options.qa_checks.is_empty().not() && options.qa_output == QaOutput::Column;
+
+	// rows are numbered in a leading row_id column, data and totals rows told
+	// apart by a row_type column after it, flagged rows list the rules they
+	// break in a trailing violations column and the QA checks catching their
+	// values in a qa_flags column after that
+	let write_row =
+		|sink: &mut dyn OutputSink, id: &str, row_type: &str, row: &[&str], flags: [&str; 2]| {
+			if numbered.not() && tagged.not() && flagged.not() && qa_column.not() {
+				return sink.write_row(row);
+			}
+
+			let mut values = Vec::with_capacity(row.len() + 4);
+			if numbered {
+				values.push(id);
+			}
+
+			if tagged {
+				values.push(row_type);
+			}
+
+			values.extend_from_slice(row);
+			if flagged {
+				values.push(flags[0]);
+			}
+
+			if qa_column {
+				values.push(flags[1]);
+			}
+
+			sink.write_row(&values)
+		};
+	let write_total = |sink: &mut dyn OutputSink, total: &mut TotalRow| -> io::Result<()> {
+		let Some(row) = total.take(header.len()) else {
+			return Ok(());
+		};
+
+		let row: Vec<_> = row.iter().map(|v| v.as_str()).collect();
+		write_row(sink, "", totals.row_type(), &row, ["", ""])
+	};
+
+	let mut full_header = Vec::with_capacity(header.len() + 4);
+	if numbered {
+		full_header.push("row_id".to_string());
+	}
+
+	if tagged {
+		full_header.push("row_type".to_string());
+	}
+
+	full_header.extend(header.iter().cloned());
+	if flagged {
+		full_header.push("violations".to_string());
+	}
+
+	if qa_column {
+		full_header.push("qa_flags".to_string());
+	}
+
+	sink.write_header(&full_header)?;
+
+	let mut reports = UnionReports {
+		aggregators,
+		report,
+		..Default::default()
+	};
+	let mut current_file = None;
+	let mut written = 0;
+	let mut undated = 0;
+	for row in rows {
+		let row = row?;
+		// rows are numbered within their file, counting from its first data row
+		let prefix = |i: usize| -> usize { row.get(i).and_then(|idx| idx.parse().ok()).unwrap_or(0) };
+		let (schema_idx, file_idx, file_row) = (prefix(0), prefix(1), prefix(2));
+		if options.duplicate_reports == DuplicateReports::KeepNewest
+			&& revisions.is_latest(file_idx).not()
+		{
+			continue;
+		}
+
+		if totals == Totals::PerFile && current_file.is_some_and(|f| f != file_idx) {
+			write_total(sink, &mut total)?;
+		}
+
+		current_file = Some(file_idx);
+		let values = row.get(4..).unwrap_or_default();
+		let mut mapped: Vec<_> = mappings[schema_idx]
+			.iter()
+			.map(|source| {
+				source
+					.and_then(|i| values.get(i))
+					.map_or("", |v| v.as_str())
+			})
+			.collect();
+		if let Some(col) = date_col {
+			match options.date_range.contains(mapped[col]) {
+				Some(true) => {}
+				Some(false) => continue,
+				None => {
+					undated += 1;
+					continue;
+				}
+			}
+		}
+
+		if options.source_columns {
+			let file_name = sources.get(file_idx).map_or("", |(f, _)| f);
+			mapped.extend([file_name, row.get(3).map_or("", |s| s.as_str())]);
+		}
+
+		if numbered_revisions {
+			mapped.push(revision_numbers.get(file_idx).map_or("", |n| n.as_str()));
+		}
+
+		if let Some(cumulative) = &stats.cumulative {
+			mapped.push(cumulative.get(file_idx, file_row));
+		}
+
+		for joiner in &mut joiners {
+			joiner.join(&mut mapped);
+		}
+
+		if filter.as_ref().is_some_and(|f| f.matches(&mapped).not()) {
+			continue;
+		}
+
+		let broken = validator
+			.as_ref()
+			.map(|v| v.check(&mapped))
+			.unwrap_or_default();
+		let sheet = sources.get(file_idx).map_or("", |(_, name)| name);
+		for violation in &broken {
+			reports.violations.push(vec![
+				sheet.to_string(),
+				file_row.to_string(),
+				violation.column.to_string(),
+				violation.rule.to_string(),
+				violation.value.to_string(),
+			]);
+		}
+
+		if invalid_rows == InvalidRows::Exclude && broken.is_empty().not() {
+			continue;
+		}
+
+		let flags: Vec<_> = broken
+			.iter()
+			.map(|v| format!("{} {}", v.column, v.rule))
+			.collect();
+		let caught = qa.check(&mapped);
+		reports.qa_flagged += caught.len();
+		if options.qa_output == QaOutput::Report {
+			for flag in &caught {
+				reports.qa.push(vec![
+					sheet.to_string(),
+					file_row.to_string(),
+					flag.column.to_string(),
+					flag.reason.clone(),
+					flag.value.to_string(),
+				]);
+			}
+		}
+
+		let qa_flags: Vec<_> = caught
+			.iter()
+			.map(|f| format!("{} {}", f.column, f.reason))
+			.collect();
+		written += 1;
+		let id = match options.row_ids {
+			RowIds::PerFile => file_row,
+			_ => written,
+		};
+		let flags = [flags.join("; "), qa_flags.join("; ")];
+		write_row(
+			sink,
+			&id.to_string(),
+			"data",
+			&mapped,
+			flags.each_ref().map(|f| f.as_str()),
+		)?;
+		total.add(&mapped);
+		for aggregator in &mut reports.aggregators {
+			aggregator.add(&mapped);
+		}
+
+		if let Some(report) = &mut reports.report {
+			report.add(&mapped);
+		}
+
+		if let Some(intervals) = &mut intervals {
+			intervals.add(&mapped, file_idx, file_row);
+		}
+	}
+
+	if totals != Totals::None {
+		write_total(sink, &mut total)?;
+	}
+
+	if undated > 0 {
+		warnings.push(format!(
+			"date range: left out {} rows without a date",
+			undated
+		));
+	}
+
+	warnings.extend(joiners.iter().filter_map(|joiner| joiner.warning()));
+	if let Some(intervals) = intervals {
+		let sheets: Vec<_> = sources.iter().map(|(_, name)| *name).collect();
+		reports.intervals = intervals.issues(&sheets);
+	}
+
+	reports.warnings = warnings;
+	Ok(reports)
+}
+
+// value of a staged row in a column of the union
+fn staged_value<'r>(mappings: &[Vec<Option<usize>>], row: &'r [String], col: usize) -> &'r str {
+	let schema_idx = row
+		.first()
+		.and_then(|idx| idx.parse::<usize>().ok())
+		.unwrap_or(0);
+	mappings[schema_idx][col]
+		.and_then(|i| row.get(i + 4))
+		.map_or("", |v| v.as_str())
+}
+
+// writes the schema next to the output, like out.schema.json and
+// out.schema.sql
+fn write_schema(
+	schema: &OutputSchema,
+	output_path: &Path,
+	sidecar: SchemaSidecar,
+) -> Result<(), OxideError> {
+	let stem = output_path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let mut sidecars = Vec::new();
+	if sidecar.json() {
+		sidecars.push(("json", schema.to_json()));
+	}
+
+	if sidecar.sql() {
+		sidecars.push(("sql", schema.to_sql()));
+	}
+
+	for (extension, contents) in sidecars {
+		let path = output_path.with_file_name(format!("{}.schema.{}", stem, extension));
+		recovery::write_file(&path, contents).map_err(|e| OxideError::io(&path, e))?;
+	}
+
+	Ok(())
+}
+
+// a table written alongside the rows, like a summary
+struct Table<'a, R: Iterator<Item = Vec<String>>> {
+	name: &'a str,
+	header: Vec<String>,
+	rows: R,
+}
+
+// writes a table into the sink when it holds several of them, otherwise
+// into a file of its own next to the output, like out.per_hole.csv
+fn write_table(
+	sink: &mut dyn OutputSink,
+	table: Table<impl Iterator<Item = Vec<String>>>,
+	output_path: &Path,
+	options: &ExportOptions,
+) -> Result<(), OxideError> {
+	let name = table.name;
+	let write = |sink: &mut dyn OutputSink| -> io::Result<()> {
+		sink.write_header(&table.header)?;
+		for row in table.rows {
+			let values: Vec<_> = row.iter().map(|v| v.as_str()).collect();
+			sink.write_row(&values)?;
+		}
+
+		Ok(())
+	};
+
+	if sink
+		.add_table(name)
+		.map_err(|e| OxideError::sink(output_path, e))?
+	{
+		return write(sink).map_err(|e| OxideError::sink(output_path, e));
+	}
+
+	let stem = output_path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let file_name = match output_path.extension().and_then(|s| s.to_str()) {
+		Some(extension) => format!("{}.{}.{}", stem, name, extension),
+		None => format!("{}.{}", stem, name),
+	};
+
+	let path = output_path.with_file_name(file_name);
+	write_complete(&path, |partial| {
+		let mut own_sink = create_sink(partial, options)?;
+		let mut own_sink = NullValues::new(own_sink.as_mut(), &options.null_value);
+		write(&mut own_sink)
+			.and_then(|_| own_sink.finish())
+			.map_err(|e| OxideError::sink(&path, e))
+	})
+}
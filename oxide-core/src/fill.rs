@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::io;
+use std::ops::Not;
+
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::parts::{elements, sheet_path};
+use crate::source::ReadSeek;
+use crate::template::cell_position;
+
+/// What happens to rows whose first cell is filled in the template's
+/// `voided_fill` colour, the way site supervisors void rows rather than
+/// deleting them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum VoidedRows {
+	/// They're left out.
+	#[default]
+	Skip,
+	/// They're kept, with `true` in a `voided` column.
+	Flag,
+}
+
+/// The colour as six uppercase hex digits, given like `FF0000`, `#ff0000`
+/// or with excel's alpha in front like `FFFF0000`. None when it isn't one.
+pub(crate) fn parse_color(color: &str) -> Option<String> {
+	let hex = color.trim().trim_start_matches('#');
+	if hex.bytes().all(|b| b.is_ascii_hexdigit()).not() {
+		return None;
+	}
+
+	match hex.len() {
+		6 => Some(hex.to_ascii_uppercase()),
+		8 => Some(hex[2..].to_ascii_uppercase()),
+		_ => None,
+	}
+}
+
+/// Zero-based rows and columns of the cells of the workbook's `sheet`
+/// filled with `color`. Only fills given as a colour of their own are
+/// matched, like the standard colours and the ones picked under more
+/// colours, not the theme's.
+pub(crate) fn filled_cells(
+	reader: &mut dyn ReadSeek,
+	sheet: &str,
+	color: &str,
+) -> io::Result<HashSet<(usize, usize)>> {
+	let mut archive = ZipArchive::new(reader).map_err(io::Error::other)?;
+	let Some(sheet_path) = sheet_path(&mut archive, sheet)? else {
+		return Ok(HashSet::new());
+	};
+
+	// a cell's style is an index into cellXfs, whose fillId is one into fills
+	let mut fills: Vec<Option<String>> = Vec::new();
+	let mut style_fills: Vec<usize> = Vec::new();
+	let mut section = String::new();
+	let names = [
+		"fills",
+		"fill",
+		"fgColor",
+		"cellStyleXfs",
+		"cellXfs",
+		"xf",
+		"dxfs",
+	];
+	for element in elements(&mut archive, "xl/styles.xml", &names)? {
+		match (section.as_str(), element.name.as_str()) {
+			(_, "fills" | "cellStyleXfs" | "cellXfs" | "dxfs") => section.clone_from(&element.name),
+			("fills", "fill") => fills.push(None),
+			("fills", "fgColor") => {
+				if let Some(fill) = fills.last_mut() {
+					*fill = element.attribute("rgb").and_then(parse_color);
+				}
+			}
+			("cellXfs", "xf") => {
+				let fill = element.attribute("fillId").and_then(|id| id.parse().ok());
+				style_fills.push(fill.unwrap_or_default());
+			}
+			_ => (),
+		}
+	}
+
+	let mut cells = HashSet::new();
+	for cell in elements(&mut archive, &sheet_path, &["c"])? {
+		let Some(position) = cell.attribute("r").and_then(cell_position) else {
+			continue;
+		};
+		let style: usize = cell
+			.attribute("s")
+			.and_then(|s| s.parse().ok())
+			.unwrap_or_default();
+		let filled = style_fills
+			.get(style)
+			.and_then(|fill| fills.get(*fill)?.as_deref());
+		if filled == Some(color) {
+			cells.insert(position);
+		}
+	}
+
+	Ok(cells)
+}
@@ -0,0 +1,323 @@
+use std::ops::Not;
+
+use crate::coerce;
+
+/// Report dates rows have to fall within to be exported, either end left
+/// open when unset.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct DateRange {
+	from: Option<(i64, u32, u32)>,
+	to: Option<(i64, u32, u32)>,
+}
+
+impl DateRange {
+	/// Takes dates written like `2024-05-17` or `17/05/2024`, failing on
+	/// anything else. An empty one leaves that end open.
+	pub fn new(from: &str, to: &str) -> Result<DateRange, String> {
+		let parse = |date: &str| -> Result<_, String> {
+			if date.trim().is_empty() {
+				return Ok(None);
+			}
+
+			coerce::parse_date(date.trim())
+				.map(Some)
+				.ok_or_else(|| format!("{} isn't a date, expected one like 2024-05-17", date))
+		};
+
+		let range = DateRange {
+			from: parse(from)?,
+			to: parse(to)?,
+		};
+		if let (Some(from), Some(to)) = (range.from, range.to)
+			&& from > to
+		{
+			return Err(format!(
+				"the range starts after it ends, {} to {}",
+				format_date(from),
+				format_date(to)
+			));
+		}
+
+		Ok(range)
+	}
+
+	/// Whether it lets every row through.
+	pub fn is_open(&self) -> bool {
+		self.from.is_none() && self.to.is_none()
+	}
+
+	/// Whether the date is within the range, none if it isn't a date.
+	pub(crate) fn contains(&self, value: &str) -> Option<bool> {
+		let date = coerce::parse_date(value.trim())?;
+		Some(self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to))
+	}
+}
+
+fn format_date((year, month, day): (i64, u32, u32)) -> String {
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// An expression rows have to match to be exported, like
+/// `total_meters > 0 && rig == "RC-04"`. Columns are compared as numbers
+/// when both sides are numbers and as text otherwise, and a column on its
+/// own matches when it isn't empty, `0` or `false`.
+#[derive(Clone, Debug)]
+pub struct RowFilter {
+	expression: String,
+	// columns the expression names, operands refer to them by index
+	columns: Vec<String>,
+	root: Expr,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+	Or(Box<Expr>, Box<Expr>),
+	And(Box<Expr>, Box<Expr>),
+	Not(Box<Expr>),
+	Compare(Operand, CompareOp, Operand),
+	Truthy(Operand),
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+	Column(usize),
+	Literal(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum CompareOp {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+	Ident(String),
+	Literal(String),
+	Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")"];
+
+impl RowFilter {
+	pub fn parse(expression: &str) -> Result<RowFilter, String> {
+		let tokens = tokenize(expression)?;
+		let mut parser = Parser {
+			tokens,
+			pos: 0,
+			columns: Vec::new(),
+		};
+		let root = parser.or()?;
+		if let Some(token) = parser.tokens.get(parser.pos) {
+			return Err(format!("unexpected {} in filter", describe(token)));
+		}
+
+		Ok(RowFilter {
+			expression: expression.trim().to_string(),
+			columns: parser.columns,
+			root,
+		})
+	}
+
+	pub fn expression(&self) -> &str {
+		&self.expression
+	}
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	pos: usize,
+	columns: Vec<String>,
+}
+
+impl Parser {
+	fn eat(&mut self, op: &str) -> bool {
+		let matched = matches!(self.tokens.get(self.pos), Some(Token::Op(o)) if *o == op);
+		if matched {
+			self.pos += 1;
+		}
+
+		matched
+	}
+
+	fn or(&mut self) -> Result<Expr, String> {
+		let mut expr = self.and()?;
+		while self.eat("||") {
+			expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+		}
+
+		Ok(expr)
+	}
+
+	fn and(&mut self) -> Result<Expr, String> {
+		let mut expr = self.unary()?;
+		while self.eat("&&") {
+			expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+		}
+
+		Ok(expr)
+	}
+
+	fn unary(&mut self) -> Result<Expr, String> {
+		if self.eat("!") {
+			return Ok(Expr::Not(Box::new(self.unary()?)));
+		}
+
+		if self.eat("(") {
+			let expr = self.or()?;
+			if self.eat(")").not() {
+				return Err("missing ) in filter".to_string());
+			}
+
+			return Ok(expr);
+		}
+
+		let left = self.operand()?;
+		let op = match self.tokens.get(self.pos) {
+			Some(Token::Op("==")) => CompareOp::Eq,
+			Some(Token::Op("!=")) => CompareOp::Ne,
+			Some(Token::Op("<")) => CompareOp::Lt,
+			Some(Token::Op("<=")) => CompareOp::Le,
+			Some(Token::Op(">")) => CompareOp::Gt,
+			Some(Token::Op(">=")) => CompareOp::Ge,
+			_ => return Ok(Expr::Truthy(left)),
+		};
+
+		self.pos += 1;
+		Ok(Expr::Compare(left, op, self.operand()?))
+	}
+
+	fn operand(&mut self) -> Result<Operand, String> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		match token {
+			Some(Token::Literal(value)) => Ok(Operand::Literal(value)),
+			Some(Token::Ident(name)) => {
+				let idx = match self.columns.iter().position(|c| *c == name) {
+					Some(idx) => idx,
+					None => {
+						self.columns.push(name);
+						self.columns.len() - 1
+					}
+				};
+				Ok(Operand::Column(idx))
+			}
+			Some(token) => Err(format!("unexpected {} in filter", describe(&token))),
+			None => Err("filter ends too early".to_string()),
+		}
+	}
+}
+
+fn describe(token: &Token) -> String {
+	match token {
+		Token::Ident(name) => name.clone(),
+		Token::Literal(value) => format!("\"{}\"", value),
+		Token::Op(op) => op.to_string(),
+	}
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = Vec::new();
+	let mut rest = expression.trim_start();
+	while let Some(c) = rest.chars().next() {
+		let len = if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+			tokens.push(Token::Op(op));
+			op.len()
+		} else if c == '"' || c == '\'' {
+			let end = rest[1..]
+				.find(c)
+				.ok_or_else(|| "unterminated string in filter".to_string())?;
+			tokens.push(Token::Literal(rest[1..end + 1].to_string()));
+			end + 2
+		} else if c.is_ascii_digit() || c == '-' || c == '.' {
+			let end = rest
+				.find(|c: char| (c.is_ascii_alphanumeric() || c == '.' || c == '-').not())
+				.unwrap_or(rest.len());
+			tokens.push(Token::Literal(rest[..end].to_string()));
+			end
+		} else if c.is_alphabetic() || c == '_' {
+			let end = rest
+				.find(|c: char| (c.is_alphanumeric() || c == '_').not())
+				.unwrap_or(rest.len());
+			tokens.push(Token::Ident(rest[..end].to_string()));
+			end
+		} else {
+			return Err(format!("unexpected {} in filter", c));
+		};
+
+		rest = rest[len..].trim_start();
+	}
+
+	Ok(tokens)
+}
+
+/// Matches rows against a [`RowFilter`] as they're written.
+pub(crate) struct FilterCheck<'a> {
+	filter: &'a RowFilter,
+	// where each of the filter's columns is in the row
+	positions: Vec<usize>,
+}
+
+impl<'a> FilterCheck<'a> {
+	/// Fails when a column the filter names isn't in `header`.
+	pub fn new(filter: &'a RowFilter, header: &[String]) -> Result<FilterCheck<'a>, String> {
+		let positions = filter
+			.columns
+			.iter()
+			.map(|column| {
+				header
+					.iter()
+					.position(|h| h == column)
+					.ok_or_else(|| format!("filter: there's no {} column", column))
+			})
+			.collect::<Result<_, _>>()?;
+
+		Ok(FilterCheck { filter, positions })
+	}
+
+	pub fn matches(&self, row: &[&str]) -> bool {
+		self.eval(&self.filter.root, row)
+	}
+
+	fn eval(&self, expr: &Expr, row: &[&str]) -> bool {
+		match expr {
+			Expr::Or(a, b) => self.eval(a, row) || self.eval(b, row),
+			Expr::And(a, b) => self.eval(a, row) && self.eval(b, row),
+			Expr::Not(a) => self.eval(a, row).not(),
+			Expr::Truthy(operand) => {
+				let value = self.value(operand, row).trim();
+				["", "0", "false"].contains(&value).not()
+			}
+			Expr::Compare(left, op, right) => {
+				let (left, right) = (self.value(left, row).trim(), self.value(right, row).trim());
+				let ordering = match (left.parse::<f64>(), right.parse::<f64>()) {
+					(Ok(l), Ok(r)) => l.partial_cmp(&r),
+					_ => Some(left.cmp(right)),
+				};
+				let Some(ordering) = ordering else {
+					return *op == CompareOp::Ne;
+				};
+
+				match op {
+					CompareOp::Eq => ordering.is_eq(),
+					CompareOp::Ne => ordering.is_ne(),
+					CompareOp::Lt => ordering.is_lt(),
+					CompareOp::Le => ordering.is_le(),
+					CompareOp::Gt => ordering.is_gt(),
+					CompareOp::Ge => ordering.is_ge(),
+				}
+			}
+		}
+	}
+
+	fn value<'r>(&self, operand: &'r Operand, row: &[&'r str]) -> &'r str {
+		match operand {
+			Operand::Column(idx) => row.get(self.positions[*idx]).copied().unwrap_or_default(),
+			Operand::Literal(value) => value,
+		}
+	}
+}
@@ -0,0 +1,111 @@
+use std::ops::Not;
+
+use regex::Regex;
+
+/// How header cells are turned into column names. A sub header is joined to
+/// its main header with an underscore before the style is applied.
+#[derive(Clone, Default)]
+pub enum HeaderStyle {
+	/// `drilled_meters`, see [`format_header`].
+	#[default]
+	SnakeCase,
+	/// `drilledMeters`
+	CamelCase,
+	/// The header as written in the workbook, only trimmed.
+	Original,
+	/// Replacements applied in order to the trimmed header.
+	Replace(Vec<HeaderReplacement>),
+}
+
+impl HeaderStyle {
+	/// Names the styles go by in settings. `replace` takes its replacements
+	/// separately, see [`from_name`](HeaderStyle::from_name).
+	pub const NAMES: &[&str] = &["snake_case", "camelCase", "original", "replace"];
+
+	pub fn from_name(name: &str, replacements: Vec<HeaderReplacement>) -> Option<HeaderStyle> {
+		Some(match name {
+			"snake_case" => HeaderStyle::SnakeCase,
+			"camelCase" => HeaderStyle::CamelCase,
+			"original" => HeaderStyle::Original,
+			"replace" => HeaderStyle::Replace(replacements),
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			HeaderStyle::SnakeCase => "snake_case",
+			HeaderStyle::CamelCase => "camelCase",
+			HeaderStyle::Original => "original",
+			HeaderStyle::Replace(_) => "replace",
+		}
+	}
+
+	pub fn format(&self, header: &str) -> String {
+		match self {
+			HeaderStyle::SnakeCase => format_header(header.to_string()),
+			HeaderStyle::CamelCase => camel_case(header),
+			HeaderStyle::Original => header.trim().to_string(),
+			HeaderStyle::Replace(replacements) => {
+				replacements
+					.iter()
+					.fold(header.trim().to_string(), |header, r| {
+						r.pattern
+							.replace_all(&header, r.replacement.as_str())
+							.into_owned()
+					})
+			}
+		}
+	}
+}
+
+/// A regex replacement for [`HeaderStyle::Replace`].
+#[derive(Clone)]
+pub struct HeaderReplacement {
+	pattern: Regex,
+	replacement: String,
+}
+
+impl HeaderReplacement {
+	/// Every match of `pattern` is replaced, `replacement` can refer to its
+	/// capture groups as `$1` or `$name`.
+	pub fn new(pattern: &str, replacement: &str) -> Result<HeaderReplacement, regex::Error> {
+		Ok(HeaderReplacement {
+			pattern: Regex::new(pattern)?,
+			replacement: replacement.to_string(),
+		})
+	}
+}
+
+/// Normalizes a header to lowercase snake_case.
+pub fn format_header(header: String) -> String {
+	header
+		.trim()
+		.to_lowercase()
+		.replace(" ", "_")
+		.replace("-", "_")
+		.replace("\n", "_")
+}
+
+// splits on the same separators snake_case replaces
+fn camel_case(header: &str) -> String {
+	let mut formatted = String::with_capacity(header.len());
+	let words = header
+		.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+		.filter(|w| w.is_empty().not());
+	for (i, word) in words.enumerate() {
+		let lower = word.to_lowercase();
+		if i == 0 {
+			formatted.push_str(&lower);
+			continue;
+		}
+
+		let mut chars = lower.chars();
+		if let Some(first) = chars.next() {
+			formatted.extend(first.to_uppercase());
+			formatted.push_str(chars.as_str());
+		}
+	}
+
+	formatted
+}
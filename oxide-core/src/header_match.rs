@@ -0,0 +1,77 @@
+use std::ops::Not;
+
+/// A column of a report that looks like a typo of a column an earlier
+/// report has, like `depht_from` for `depth_from`. They're still written as
+/// columns of their own, it's up to the user to alias one to the other.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HeaderMatch {
+	pub column: String,
+	pub similar_to: String,
+}
+
+impl HeaderMatch {
+	/// How alike two column names have to be to be proposed, one minus
+	/// their edit distance over the longer one's length.
+	pub const SIMILARITY: f64 = 0.8;
+}
+
+// columns of the layouts that are close to one of the columns before them,
+// in the order the layouts are merged. a column isn't matched to one its
+// own layout has as well, as those are two columns for sure
+pub(crate) fn propose(schemas: &[Vec<String>]) -> Vec<HeaderMatch> {
+	let mut known: Vec<&String> = Vec::new();
+	let mut matches = Vec::new();
+	for schema in schemas {
+		let new: Vec<_> = schema
+			.iter()
+			.filter(|column| known.contains(column).not())
+			.collect();
+		for column in &new {
+			let closest = known
+				.iter()
+				.filter(|other| schema.contains(other).not())
+				.map(|other| (similarity(column, other), other))
+				.filter(|(similarity, _)| *similarity >= HeaderMatch::SIMILARITY)
+				.max_by(|a, b| a.0.total_cmp(&b.0));
+			if let Some((_, other)) = closest {
+				matches.push(HeaderMatch {
+					column: column.to_string(),
+					similar_to: other.to_string(),
+				});
+			}
+		}
+		known.extend(new);
+	}
+
+	matches
+}
+
+// one minus the edit distance over the longer name's length, swapping two
+// letters counting as a single edit
+fn similarity(a: &str, b: &str) -> f64 {
+	let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+	let longest = a.len().max(b.len());
+	if longest == 0 {
+		return 1.0;
+	}
+
+	// optimal string alignment distance, by rows of the usual table
+	let mut before: Vec<usize> = Vec::new();
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	for i in 1..=a.len() {
+		let mut current = vec![i; b.len() + 1];
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			current[j] = (previous[j] + 1)
+				.min(current[j - 1] + 1)
+				.min(previous[j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				current[j] = current[j].min(before[j - 2] + 1);
+			}
+		}
+		before = previous;
+		previous = current;
+	}
+
+	1.0 - previous[b.len()] as f64 / longest as f64
+}
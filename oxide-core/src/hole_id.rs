@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::ops::Not;
+
+use serde::{Deserialize, Serialize};
+
+/// Rewrites the hole IDs in a column to a single spelling, so `DH 12`,
+/// `DH-012` and `dh12` group and match up as the same hole. IDs are
+/// uppercased and their spaces dropped, and ones made of a prefix and a
+/// number are written as the prefix, `separator` and the number padded to
+/// `digits`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct HoleIds {
+	pub column: String,
+	pub separator: String,
+	/// Numbers shorter than this are padded with zeros, 0 leaves them as
+	/// they are.
+	pub digits: usize,
+	/// Prefixes replaced by another, like `DDH` by `DH`, whatever their
+	/// case.
+	pub prefixes: BTreeMap<String, String>,
+}
+
+impl Default for HoleIds {
+	fn default() -> Self {
+		HoleIds {
+			column: "hole_number".to_string(),
+			separator: "-".to_string(),
+			digits: 3,
+			prefixes: BTreeMap::new(),
+		}
+	}
+}
+
+impl HoleIds {
+	pub fn normalize(&self, id: &str) -> String {
+		let id: String = id
+			.chars()
+			.filter(|c| c.is_whitespace().not())
+			.flat_map(char::to_uppercase)
+			.collect();
+		let number_start = id.find(|c: char| c.is_ascii_digit()).unwrap_or(id.len());
+		let (prefix, number) = id.split_at(number_start);
+		let prefix = prefix.trim_end_matches(['-', '_', '.', '/']);
+		// ids like RC07A or ones without a number keep their own spelling
+		if prefix.is_empty()
+			|| number.is_empty()
+			|| prefix.chars().all(|c| c.is_ascii_alphabetic()).not()
+			|| number.chars().all(|c| c.is_ascii_digit()).not()
+		{
+			return id;
+		}
+
+		let prefix = self
+			.prefixes
+			.iter()
+			.find(|(from, _)| from.eq_ignore_ascii_case(prefix))
+			.map_or(prefix, |(_, to)| to.as_str());
+		let number = match self.digits {
+			0 => number,
+			_ => number.trim_start_matches('0'),
+		};
+		format!(
+			"{}{}{:0>width$}",
+			prefix,
+			self.separator,
+			if number.is_empty() { "0" } else { number },
+			width = self.digits
+		)
+	}
+
+	/// Normalizes the column's values in `rows`, leaving rows without one
+	/// alone.
+	pub(crate) fn apply(&self, headers: &[String], rows: &mut [Vec<String>]) {
+		let Some(col) = headers.iter().position(|h| *h == self.column) else {
+			return;
+		};
+
+		for value in rows.iter_mut().filter_map(|row| row.get_mut(col)) {
+			if value.trim().is_empty().not() {
+				*value = self.normalize(value);
+			}
+		}
+	}
+}
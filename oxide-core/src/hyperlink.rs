@@ -0,0 +1,103 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::parts::{elements, relationships, rels_path, sheet_path};
+use crate::source::ReadSeek;
+use crate::template::cell_position;
+
+/// What's written for a cell that links somewhere, like a hole number
+/// linked to its photos folder.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Hyperlinks {
+	/// The text the cell shows.
+	#[default]
+	Text,
+	/// Where it links to.
+	Url,
+	/// The text, with where it links to in a column of its own named after
+	/// the cell's column with `_url` after it.
+	Both,
+}
+
+/// Where a sheet's cells link to, keyed by their zero-based row and column
+/// in the sheet.
+#[derive(Default)]
+pub(crate) struct Links {
+	hyperlinks: Hyperlinks,
+	urls: HashMap<(usize, usize), String>,
+}
+
+impl Links {
+	/// The links of the workbook's `sheet`.
+	pub fn read(reader: &mut dyn ReadSeek, sheet: &str, hyperlinks: Hyperlinks) -> io::Result<Links> {
+		let mut links = Links {
+			hyperlinks,
+			urls: HashMap::new(),
+		};
+		let mut archive = ZipArchive::new(reader).map_err(io::Error::other)?;
+		let Some(sheet_path) = sheet_path(&mut archive, sheet)? else {
+			return Ok(links);
+		};
+		let targets = relationships(&mut archive, &rels_path(&sheet_path))?;
+
+		for link in elements(&mut archive, &sheet_path, &["hyperlink"])? {
+			// a link within the workbook has a location rather than a target
+			let url = match (link.attribute("id"), link.attribute("location")) {
+				(Some(id), _) => targets.get(id).cloned(),
+				(None, Some(location)) => Some(format!("#{}", location)),
+				(None, None) => None,
+			};
+			let (Some(url), Some(cells)) = (url, link.attribute("ref")) else {
+				continue;
+			};
+
+			// a link can cover a whole range of cells, like A6:A9
+			let (first, last) = cells.split_once(':').unwrap_or((cells, cells));
+			let (Some(first), Some(last)) = (cell_position(first), cell_position(last)) else {
+				continue;
+			};
+			for row in first.0..=last.0 {
+				for col in first.1..=last.1 {
+					links.urls.insert((row, col), url.clone());
+				}
+			}
+		}
+
+		Ok(links)
+	}
+
+	/// The cell's value as it's written, its link in place of its text when
+	/// that's what's written.
+	pub fn value(&self, position: (usize, usize), text: String) -> String {
+		match (self.hyperlinks, self.urls.get(&position)) {
+			(Hyperlinks::Url, Some(url)) => url.clone(),
+			_ => text,
+		}
+	}
+
+	/// Columns whose links are written in a column of their own, the ones
+	/// of `columns` with a link from `first_row` down.
+	pub fn url_columns(&self, first_row: usize, columns: ops::Range<usize>) -> Vec<usize> {
+		if self.hyperlinks != Hyperlinks::Both {
+			return Vec::new();
+		}
+
+		let columns: BTreeSet<_> = self
+			.urls
+			.keys()
+			.filter(|(row, col)| *row >= first_row && columns.contains(col))
+			.map(|(_, col)| *col)
+			.collect();
+		columns.into_iter().collect()
+	}
+
+	/// Where the cell links to, empty when it doesn't.
+	pub fn url(&self, position: (usize, usize)) -> &str {
+		self.urls.get(&position).map_or("", |url| url.as_str())
+	}
+}
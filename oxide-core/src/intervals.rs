@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use crate::aggregate;
+
+/// Columns holding the hole and the depths every row's interval runs
+/// between, checked for gaps and overlaps across the whole export.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DepthIntervals {
+	pub hole: String,
+	pub from: String,
+	pub to: String,
+}
+
+impl Default for DepthIntervals {
+	fn default() -> Self {
+		DepthIntervals {
+			hole: "hole_number".to_string(),
+			from: "depth_from".to_string(),
+			to: "depth_to".to_string(),
+		}
+	}
+}
+
+// an interval and where it was read from
+struct Interval {
+	from: f64,
+	to: f64,
+	file_idx: usize,
+	file_row: usize,
+}
+
+/// Collects the intervals of every hole as rows are written, to find where
+/// they don't follow on from each other once they all are.
+pub(crate) struct IntervalCheck {
+	cols: [usize; 3],
+	holes: BTreeMap<String, Vec<Interval>>,
+}
+
+impl IntervalCheck {
+	/// Fails when a column isn't in `header`.
+	pub fn new(intervals: &DepthIntervals, header: &[String]) -> Result<IntervalCheck, String> {
+		let position = |column: &String| {
+			header
+				.iter()
+				.position(|h| h == column)
+				.ok_or_else(|| format!("intervals: there's no {} column", column))
+		};
+
+		Ok(IntervalCheck {
+			cols: [
+				position(&intervals.hole)?,
+				position(&intervals.from)?,
+				position(&intervals.to)?,
+			],
+			holes: BTreeMap::new(),
+		})
+	}
+
+	// rows without a hole or with depths that aren't numbers are left out
+	pub fn add(&mut self, row: &[&str], file_idx: usize, file_row: usize) {
+		let value = |col: usize| row.get(col).copied().unwrap_or_default().trim();
+		let [hole, from, to] = self.cols.map(value);
+		let (Ok(from), Ok(to)) = (from.parse::<f64>(), to.parse::<f64>()) else {
+			return;
+		};
+
+		if hole.is_empty() {
+			return;
+		}
+
+		self
+			.holes
+			.entry(hole.to_string())
+			.or_default()
+			.push(Interval {
+				from: from.min(to),
+				to: from.max(to),
+				file_idx,
+				file_row,
+			});
+	}
+
+	/// A row for every gap and overlap, by hole and depth, naming the file
+	/// and row of the interval after it. `files` names the files by the
+	/// index rows were staged with.
+	pub fn issues(mut self, files: &[&str]) -> Vec<Vec<String>> {
+		let mut issues = Vec::new();
+		for (hole, intervals) in &mut self.holes {
+			intervals.sort_by(|a, b| a.from.total_cmp(&b.from).then(a.to.total_cmp(&b.to)));
+			let mut reached: Option<f64> = None;
+			for interval in intervals.iter() {
+				let mut issue = |kind: &str, from: f64, to: f64| {
+					issues.push(vec![
+						hole.clone(),
+						kind.to_string(),
+						aggregate::format_number(from),
+						aggregate::format_number(to),
+						files
+							.get(interval.file_idx)
+							.copied()
+							.unwrap_or_default()
+							.to_string(),
+						interval.file_row.to_string(),
+					]);
+				};
+
+				// depths are written with a few decimals at most, anything
+				// closer than this is float noise
+				match reached {
+					Some(end) if interval.from > end + 1e-6 => issue("gap", end, interval.from),
+					Some(end) if interval.from < end - 1e-6 => {
+						issue("overlap", interval.from, end.min(interval.to))
+					}
+					_ => {}
+				}
+
+				reached = Some(reached.map_or(interval.to, |end| end.max(interval.to)));
+			}
+		}
+
+		issues
+	}
+}
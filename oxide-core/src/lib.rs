@@ -0,0 +1,119 @@
+//! Aggregation pipeline behind oxide.
+//!
+//! Reads the daily report table out of every workbook in a folder and
+//! writes them into a single table, see [`generate_output`]. The gui is a thin
+//! frontend over this crate, so other tools can run the same aggregation.
+
+mod aggregate;
+mod archive;
+mod checkpoint;
+mod clipboard;
+mod coerce;
+mod coordinate;
+mod cumulative;
+mod datetime;
+mod duplicates;
+mod error;
+mod events;
+mod export;
+mod fill;
+mod filter;
+mod header;
+mod header_match;
+mod hole_id;
+mod hyperlink;
+mod intervals;
+mod lookup;
+mod manifest;
+mod merge;
+mod number_format;
+mod option_keys;
+mod output_schema;
+mod parts;
+mod plugin;
+mod preset;
+mod preview;
+mod provenance;
+mod qa;
+mod quarantine;
+mod recovery;
+mod region;
+mod remarks;
+mod report;
+mod retry;
+mod rich_text;
+mod sample;
+mod schema_cache;
+#[cfg(feature = "scripting")]
+mod script;
+mod section;
+mod sink;
+mod sort;
+mod source;
+mod split;
+mod staging;
+mod summary;
+mod template;
+mod unpivot;
+mod validation;
+mod workbook;
+
+pub use aggregate::{AggregateFn, Aggregation, Measure, Totals};
+pub use archive::Archive;
+pub use checkpoint::Checkpoint;
+pub use clipboard::{CLIPBOARD_ROWS, ClipboardTable, clipboard_table};
+pub use coerce::ValueType;
+pub use cumulative::CumulativeMeters;
+pub use datetime::DateTimes;
+pub use duplicates::{DuplicateReports, IdenticalFile};
+pub use error::OxideError;
+pub use events::ExportEvent;
+pub use export::{
+	DATA_END_ID, DATA_START_ID, ExportOptions, INTERVALS_TABLE, ParsedFile, QA_TABLE,
+	REMARKS_START_ID, REMARKS_TABLE, RowIds, StageTimings, VIOLATIONS_TABLE, build_headers,
+	generate_output, parse_file, write_output,
+};
+pub use fill::VoidedRows;
+pub use filter::{DateRange, RowFilter};
+pub use header::{HeaderReplacement, HeaderStyle, format_header};
+pub use header_match::HeaderMatch;
+pub use hole_id::HoleIds;
+pub use hyperlink::Hyperlinks;
+pub use intervals::DepthIntervals;
+pub use lookup::Lookup;
+pub use manifest::file_sha256;
+pub use merge::MergeCounts;
+pub use number_format::NumberFormat;
+pub use option_keys::{check_name, parse_flag};
+pub use output_schema::{ColumnSchema, ColumnType, OutputSchema, SchemaSidecar};
+pub use plugin::{Plugins, RowTransform, SinkPlugin};
+pub use preset::ExportPreset;
+pub use preview::{PREVIEW_ROWS, Preview, read_preview};
+pub use provenance::Provenance;
+pub use qa::{QaCheck, QaOutput};
+pub use recovery::{StaleExport, remove_stale_spills};
+pub use remarks::Remark;
+pub use report::PdfReport;
+pub use retry::OpenRetries;
+pub use sample::{SampleReport, check_sample};
+pub use schema_cache::{CacheLookup, CachedSchema, SchemaCache, fingerprint};
+#[cfg(feature = "scripting")]
+pub use script::RowScript;
+pub use section::{Section, SectionRows};
+#[cfg(feature = "parquet")]
+pub use sink::ParquetSink;
+#[cfg(feature = "sqlite")]
+pub use sink::SqliteSink;
+#[cfg(feature = "xlsx")]
+pub use sink::XlsxSink;
+pub use sink::{CsvDialect, CsvSink, OutputFormat, OutputSink};
+pub use sort::SortKey;
+#[cfg(feature = "remote")]
+pub use source::RemoteSource;
+pub use source::{LocalDir, MemorySource, ReadSeek, SpreadsheetSource, Workbook, ZipSource};
+pub use split::ColumnSplit;
+pub use summary::{FileTiming, RunSummary};
+pub use template::{FileDate, Remarks, ReportTemplate, Sheets, TemplateRule};
+pub use unpivot::Unpivot;
+pub use validation::{ColumnRule, InvalidRows, Validation};
+pub use workbook::{FileMetadata, WorkbookFile, list_workbooks, read_metadata, worksheet_name};
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Not;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::OxideError;
+
+// unmatched keys named in a warning, the rest are only counted
+const MAX_LISTED_KEYS: usize = 5;
+
+/// A table joined onto every exported row by a key column both have, like
+/// planned depths and coordinates by hole number. Its other columns are
+/// added to the export, left empty for rows without a match.
+#[derive(Clone)]
+pub struct Lookup {
+	key: String,
+	columns: Vec<String>,
+	// values of the other columns by key, shared between clones of the
+	// export options
+	rows: Arc<HashMap<String, Vec<String>>>,
+}
+
+impl Lookup {
+	/// Reads a csv file with a header row holding the `key` column.
+	pub fn load(path: &Path, key: &str) -> Result<Lookup, OxideError> {
+		let file = File::open(path).map_err(|e| OxideError::io(path, e))?;
+		Lookup::from_reader(file, key)
+			.map_err(|e| OxideError::io(path, io::Error::new(io::ErrorKind::InvalidData, e)))
+	}
+
+	/// Same as [`Lookup::load`], reading the csv from `reader`.
+	pub fn from_reader(reader: impl Read, key: &str) -> Result<Lookup, String> {
+		let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+		let header: Vec<String> = reader
+			.headers()
+			.map_err(|e| e.to_string())?
+			.iter()
+			.map(|h| h.trim().to_string())
+			.collect();
+		let key_col = header
+			.iter()
+			.position(|h| h == key)
+			.ok_or_else(|| format!("there's no {} column", key))?;
+
+		let mut rows = HashMap::new();
+		for record in reader.records() {
+			let record = record.map_err(|e| e.to_string())?;
+			let value = |col: usize| record.get(col).unwrap_or_default().trim().to_string();
+			let values = (0..header.len())
+				.filter(|col| *col != key_col)
+				.map(value)
+				.collect();
+			// the first row for a key wins, like a lookup in a spreadsheet
+			rows.entry(value(key_col)).or_insert(values);
+		}
+
+		let mut columns = header;
+		columns.remove(key_col);
+		Ok(Lookup {
+			key: key.to_string(),
+			columns,
+			rows: Arc::new(rows),
+		})
+	}
+
+	pub fn key(&self) -> &str {
+		&self.key
+	}
+
+	/// Columns added to the export.
+	pub fn columns(&self) -> &[String] {
+		&self.columns
+	}
+}
+
+/// Joins a [`Lookup`] onto rows as they're written.
+pub(crate) struct Joiner<'a> {
+	lookup: &'a Lookup,
+	key_col: usize,
+	// keys without a row, and the first few of them in the order they came
+	unmatched: HashSet<String>,
+	listed: Vec<String>,
+}
+
+impl<'a> Joiner<'a> {
+	/// Fails when the key column isn't in `header`.
+	pub fn new(lookup: &'a Lookup, header: &[String]) -> Result<Joiner<'a>, String> {
+		let key_col = header
+			.iter()
+			.position(|h| *h == lookup.key)
+			.ok_or_else(|| format!("lookup on {}: there's no {} column", lookup.key, lookup.key))?;
+
+		Ok(Joiner {
+			lookup,
+			key_col,
+			unmatched: HashSet::new(),
+			listed: Vec::new(),
+		})
+	}
+
+	/// Appends the lookup's values for the row's key to `row`.
+	pub fn join<'r>(&mut self, row: &mut Vec<&'r str>)
+	where
+		'a: 'r,
+	{
+		let key = row.get(self.key_col).copied().unwrap_or_default().trim();
+		match self.lookup.rows.get(key) {
+			Some(values) => row.extend(values.iter().map(|v| v.as_str())),
+			None => {
+				if key.is_empty().not()
+					&& self.unmatched.insert(key.to_string())
+					&& self.listed.len() < MAX_LISTED_KEYS
+				{
+					self.listed.push(key.to_string());
+				}
+
+				row.extend(self.lookup.columns.iter().map(|_| ""));
+			}
+		}
+	}
+
+	/// Says which keys had no row in the lookup, if any.
+	pub fn warning(&self) -> Option<String> {
+		if self.unmatched.is_empty() {
+			return None;
+		}
+
+		let mut warning = format!(
+			"lookup on {}: no row for {}",
+			self.lookup.key,
+			self.listed.join(", ")
+		);
+		if self.unmatched.len() > self.listed.len() {
+			warning.push_str(&format!(
+				" and {} more",
+				self.unmatched.len() - self.listed.len()
+			));
+		}
+
+		Some(warning)
+	}
+}
@@ -0,0 +1,120 @@
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::error::OxideError;
+use crate::recovery;
+use crate::source::SpreadsheetSource;
+use crate::summary::RunSummary;
+
+/// Writes the output's hash next to it as `out.csv.sha256`, in the format
+/// `sha256sum -c` checks, and `out.manifest.json` listing the hash of every
+/// workbook its rows were read from. Returns the output's hash.
+pub(crate) fn write_manifest(
+	source: &dyn SpreadsheetSource,
+	output_path: &Path,
+	summary: &RunSummary,
+) -> Result<String, OxideError> {
+	let file_name = output_path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default();
+	let output_hash = file_sha256(output_path).map_err(|e| OxideError::io(output_path, e))?;
+
+	let mut files: Vec<_> = summary.files.iter().collect();
+	files.sort_by(|a, b| a.path.cmp(&b.path));
+	let mut sources = Vec::with_capacity(files.len());
+	for file in files {
+		let mut reader = source.reader(&file.path)?;
+		let hash = sha256(&mut reader).map_err(|e| OxideError::io(&file.path, e))?;
+		sources.push(json!({
+			"file": file.path.to_string_lossy(),
+			"sha256": hash,
+			"rows": file.rows,
+		}));
+	}
+
+	let manifest = json!({
+		"output": {
+			"file": file_name,
+			"sha256": output_hash,
+			"rows": summary.total_rows(),
+		},
+		"sources": sources,
+	});
+
+	write_checksum(output_path, &output_hash)?;
+
+	let stem = output_path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let manifest_path = output_path.with_file_name(format!("{}.manifest.json", stem));
+	let manifest = serde_json::to_string_pretty(&manifest).map_err(io::Error::other);
+	manifest
+		.and_then(|manifest| recovery::write_file(&manifest_path, manifest))
+		.map_err(|e| OxideError::io(&manifest_path, e))?;
+
+	Ok(output_hash)
+}
+
+// writes the output's hash next to it as `out.csv.sha256`
+pub(crate) fn write_checksum(output_path: &Path, hash: &str) -> Result<(), OxideError> {
+	let file_name = output_path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default();
+	let checksum_path = output_path.with_file_name(format!("{}.sha256", file_name));
+	let checksum = format!("{}  {}\n", hash, file_name);
+	recovery::write_file(&checksum_path, checksum).map_err(|e| OxideError::io(&checksum_path, e))
+}
+
+// keeps a copy of the output as `out.csv.edited` when it isn't the one the
+// last export wrote, as someone edited it by hand since and merging into it
+// would overwrite their changes. returns a warning saying so
+pub(crate) fn keep_edited_output(output_path: &Path) -> Result<Option<String>, OxideError> {
+	let file_name = output_path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default();
+	let checksum_path = output_path.with_file_name(format!("{}.sha256", file_name));
+	// outputs from before checksums were kept can't be told apart
+	let Ok(checksum) = fs::read_to_string(&checksum_path) else {
+		return Ok(None);
+	};
+	let Ok(hash) = file_sha256(output_path) else {
+		return Ok(None);
+	};
+	if checksum.split_whitespace().next() == Some(hash.as_str()) {
+		return Ok(None);
+	}
+
+	let edited_path = output_path.with_file_name(format!("{}.edited", file_name));
+	fs::copy(output_path, &edited_path).map_err(|e| OxideError::io(&edited_path, e))?;
+	Ok(Some(format!(
+		"{} was changed since the last export, it was merged into anyway and the changed file kept as {}",
+		file_name,
+		edited_path.display()
+	)))
+}
+
+/// The sha256 of the file at `path`, in lowercase hex like `sha256sum`
+/// prints it.
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+	sha256(&mut File::open(path)?)
+}
+
+pub(crate) fn sha256(reader: &mut impl io::Read) -> io::Result<String> {
+	let mut hasher = Sha256::new();
+	io::copy(reader, &mut hasher)?;
+	let mut hex = String::with_capacity(64);
+	for byte in hasher.finalize() {
+		let _ = write!(hex, "{:02x}", byte);
+	}
+
+	Ok(hex)
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Not;
+use std::path::Path;
+
+use crate::error::OxideError;
+use crate::sink::{CsvDialect, OutputSink};
+
+/// How many rows of an earlier export a merge changed, see
+/// [`ExportOptions::merge_key`](crate::ExportOptions::merge_key).
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MergeCounts {
+	pub updated: usize,
+	pub added: usize,
+	pub unchanged: usize,
+}
+
+/// The report table of an earlier csv export.
+pub(crate) struct ExistingOutput {
+	pub header: Vec<String>,
+	pub rows: Vec<Vec<String>>,
+}
+
+impl ExistingOutput {
+	/// None when there's no output yet, laid out like `dialect`.
+	pub fn read(path: &Path, dialect: &CsvDialect) -> Result<Option<ExistingOutput>, OxideError> {
+		let file = match File::open(path) {
+			Ok(file) => file,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(OxideError::io(path, e)),
+		};
+
+		// the byte order mark and the provenance preamble above the header
+		// aren't part of the table
+		let mut file = BufReader::new(file);
+		if file
+			.fill_buf()
+			.map_err(|e| OxideError::io(path, e))?
+			.starts_with("\u{feff}".as_bytes())
+		{
+			file.consume(3);
+		}
+		while file
+			.fill_buf()
+			.map_err(|e| OxideError::io(path, e))?
+			.first()
+			== Some(&b'#')
+		{
+			file
+				.read_line(&mut String::new())
+				.map_err(|e| OxideError::io(path, e))?;
+		}
+
+		let invalid =
+			|e: csv::Error| OxideError::io(path, io::Error::new(io::ErrorKind::InvalidData, e));
+		let mut delimiter = [0; 4];
+		dialect.delimiter.encode_utf8(&mut delimiter);
+		let mut reader = csv::ReaderBuilder::new()
+			.flexible(true)
+			.delimiter(delimiter[0])
+			.from_reader(file);
+		let header: Vec<String> = reader
+			.headers()
+			.map_err(invalid)?
+			.iter()
+			.map(String::from)
+			.collect();
+		let rows = reader
+			.records()
+			.map(|record| {
+				let record = record.map_err(invalid)?;
+				let mut row: Vec<String> = record.iter().map(String::from).collect();
+				row.resize(header.len(), String::new());
+				Ok(row)
+			})
+			.collect::<Result<_, OxideError>>()?;
+
+		Ok(Some(ExistingOutput { header, rows }))
+	}
+}
+
+/// Merges the first table written to it into an [`ExistingOutput`], rows
+/// with the same values in the key columns replacing the existing ones and
+/// the rest added after them. Rows go to `sink` once the table is done.
+pub(crate) struct MergeSink<'s> {
+	sink: &'s mut dyn OutputSink,
+	key: &'s [String],
+	existing: Option<ExistingOutput>,
+	// where each column written is in the existing header, and of the key
+	// columns among them
+	positions: Vec<usize>,
+	key_cols: Vec<usize>,
+	// existing rows by their key values and how many rows before them had
+	// the same ones, so a hole drilled in several runs a day still matches up
+	index: HashMap<(Vec<String>, usize), usize>,
+	seen: HashMap<Vec<String>, usize>,
+	updated: Vec<bool>,
+	added: usize,
+}
+
+impl<'s> MergeSink<'s> {
+	pub fn new(
+		sink: &'s mut dyn OutputSink,
+		existing: ExistingOutput,
+		key: &'s [String],
+	) -> MergeSink<'s> {
+		MergeSink {
+			sink,
+			key,
+			updated: vec![false; existing.rows.len()],
+			existing: Some(existing),
+			positions: Vec::new(),
+			key_cols: Vec::new(),
+			index: HashMap::new(),
+			seen: HashMap::new(),
+			added: 0,
+		}
+	}
+
+	pub fn counts(&self) -> MergeCounts {
+		let updated = self.updated.iter().filter(|u| **u).count();
+		MergeCounts {
+			updated,
+			added: self.added,
+			unchanged: self.updated.len() - updated,
+		}
+	}
+
+	// writes the merged table, everything after it passes straight through
+	fn flush(&mut self) -> io::Result<()> {
+		let Some(existing) = self.existing.take() else {
+			return Ok(());
+		};
+
+		self.sink.write_header(&existing.header)?;
+		for row in &existing.rows {
+			let row: Vec<_> = row.iter().map(|v| v.as_str()).collect();
+			self.sink.write_row(&row)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl OutputSink for MergeSink<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		let Some(existing) = &mut self.existing else {
+			return self.sink.write_header(columns);
+		};
+
+		// columns the earlier export didn't have are added after its own
+		for column in columns {
+			if existing.header.contains(column).not() {
+				existing.header.push(column.clone());
+			}
+		}
+
+		let width = existing.header.len();
+		for row in &mut existing.rows {
+			row.resize(width, String::new());
+		}
+
+		self.positions = columns
+			.iter()
+			.map(|c| {
+				existing
+					.header
+					.iter()
+					.position(|h| h == c)
+					.unwrap_or_default()
+			})
+			.collect();
+		self.key_cols = self
+			.key
+			.iter()
+			.map(|key| {
+				columns.iter().position(|c| c == key).ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::InvalidInput,
+						format!("merge key {} isn't a column of the export", key),
+					)
+				})
+			})
+			.collect::<io::Result<_>>()?;
+
+		let mut seen: HashMap<Vec<String>, usize> = HashMap::new();
+		for (idx, row) in existing.rows.iter().enumerate() {
+			let key: Vec<_> = self
+				.key_cols
+				.iter()
+				.map(|col| row[self.positions[*col]].clone())
+				.collect();
+			let occurrence = seen.entry(key.clone()).or_default();
+			self.index.insert((key, *occurrence), idx);
+			*occurrence += 1;
+		}
+
+		Ok(())
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		let Some(existing) = &mut self.existing else {
+			return self.sink.write_row(values);
+		};
+
+		let key: Vec<_> = self
+			.key_cols
+			.iter()
+			.map(|col| values.get(*col).copied().unwrap_or_default().to_string())
+			.collect();
+		// rows without a key, like totals, can't be matched up
+		let matched = match key.iter().all(|v| v.is_empty()) {
+			true => None,
+			false => {
+				let occurrence = self.seen.entry(key.clone()).or_default();
+				*occurrence += 1;
+				self.index.get(&(key, *occurrence - 1)).copied()
+			}
+		};
+
+		match matched {
+			Some(idx) => {
+				let row = &mut existing.rows[idx];
+				for (value, position) in values.iter().zip(&self.positions) {
+					if row[*position] != *value {
+						row[*position] = value.to_string();
+						self.updated[idx] = true;
+					}
+				}
+			}
+			None => {
+				let mut row = vec![String::new(); existing.header.len()];
+				for (value, position) in values.iter().zip(&self.positions) {
+					row[*position] = value.to_string();
+				}
+
+				existing.rows.push(row);
+				self.added += 1;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.flush()?;
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.flush()?;
+		self.sink.add_table(name)
+	}
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
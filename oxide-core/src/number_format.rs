@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Not;
+
+use crate::sink::OutputSink;
+
+/// An excel style format for a column's numbers: `000` pads ids like `007`
+/// with leading zeros, `0.00` rounds to two decimals, `0.0#` to one or two,
+/// and `#,##0` adds thousands separators while leaving them out drops the
+/// ones values were read with. Values that aren't numbers are written as
+/// they are.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NumberFormat {
+	format: String,
+	// fewest digits before the decimal point, padded with zeros
+	digits: usize,
+	separators: bool,
+	// digits after it that are always written
+	fixed: usize,
+	// most digits after it, the ones past `fixed` only when they aren't zero
+	decimals: usize,
+}
+
+impl NumberFormat {
+	pub fn parse(format: &str) -> Result<NumberFormat, String> {
+		let invalid = || {
+			format!(
+				"invalid number format {}, expected one like 000, 0.00 or #,##0.0#",
+				format
+			)
+		};
+
+		let (int, frac) = format.split_once('.').unwrap_or((format, ""));
+		let fixed = frac.chars().take_while(|c| *c == '0').count();
+		if int.is_empty()
+			|| int.chars().any(|c| "0#,".contains(c).not())
+			|| frac[fixed..].chars().any(|c| c != '#')
+			|| (format.contains('.') && frac.is_empty())
+		{
+			return Err(invalid());
+		}
+
+		Ok(NumberFormat {
+			format: format.to_string(),
+			digits: int.chars().filter(|c| *c == '0').count(),
+			separators: int.contains(','),
+			fixed,
+			decimals: frac.len(),
+		})
+	}
+
+	/// The format as it was written, which excel understands as well.
+	pub fn as_str(&self) -> &str {
+		&self.format
+	}
+
+	/// The value as a number, read with or without thousands separators.
+	pub fn number(value: &str) -> Option<f64> {
+		let value = value.trim();
+		let number = match value.contains(',') {
+			true => {
+				let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+				let mut groups = int.trim_start_matches('-').split(',');
+				let first = groups.next()?;
+				let grouped = (1..=3).contains(&first.len())
+					&& groups.all(|group| group.len() == 3 && group.chars().all(|c| c.is_ascii_digit()));
+				if grouped.not() {
+					return None;
+				}
+
+				format!("{}.{}", int.replace(',', ""), frac)
+					.trim_end_matches('.')
+					.parse::<f64>()
+			}
+			false => value.parse::<f64>(),
+		};
+
+		number.ok().filter(|number| number.is_finite())
+	}
+
+	/// The value written in this format, none if it isn't a number.
+	pub fn apply(&self, value: &str) -> Option<String> {
+		let number = NumberFormat::number(value)?;
+		// halves are rounded away from zero like excel does, rather than to
+		// the even digit
+		let factor = 10f64.powi(self.decimals as i32);
+		let rounded = format!(
+			"{:.*}",
+			self.decimals,
+			(number.abs() * factor).round() / factor
+		);
+		let (int, frac) = rounded.split_once('.').unwrap_or((&rounded, ""));
+		let frac = frac.trim_end_matches('0');
+		let frac = format!("{:0<width$}", frac, width = self.fixed);
+
+		let int = format!("{:0>width$}", int, width = self.digits);
+		let int = match self.separators {
+			true => separate(&int),
+			false => int,
+		};
+		// rounded down to nothing is written without a sign
+		let negative = number < 0.0 && rounded.chars().any(|c| c.is_ascii_digit() && c != '0');
+
+		let mut formatted = String::with_capacity(int.len() + frac.len() + 2);
+		if negative {
+			formatted.push('-');
+		}
+		formatted.push_str(&int);
+		if frac.is_empty().not() {
+			formatted.push('.');
+			formatted.push_str(&frac);
+		}
+
+		Some(formatted)
+	}
+}
+
+fn separate(int: &str) -> String {
+	let mut separated = String::with_capacity(int.len() + int.len() / 3);
+	for (i, c) in int.chars().enumerate() {
+		if i > 0 && (int.len() - i).is_multiple_of(3) {
+			separated.push(',');
+		}
+		separated.push(c);
+	}
+
+	separated
+}
+
+/// Writes the first table's values in the format of their column, formats
+/// that keep numbers as numbers with a format of their own get the values
+/// as they are along with the formats instead.
+pub(crate) struct NumberFormats<'s> {
+	sink: &'s mut dyn OutputSink,
+	formats: &'s BTreeMap<String, NumberFormat>,
+	// format of every column of the table being written, empty once the
+	// first table is done or when the sink formats them itself
+	columns: Vec<Option<&'s NumberFormat>>,
+	first_table: bool,
+}
+
+impl<'s> NumberFormats<'s> {
+	pub fn new(
+		sink: &'s mut dyn OutputSink,
+		formats: &'s BTreeMap<String, NumberFormat>,
+	) -> NumberFormats<'s> {
+		NumberFormats {
+			sink,
+			formats,
+			columns: Vec::new(),
+			first_table: true,
+		}
+	}
+}
+
+impl OutputSink for NumberFormats<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		if self.first_table && self.formats.is_empty().not() {
+			let formats: Vec<_> = columns
+				.iter()
+				.map(|column| self.formats.get(column))
+				.collect();
+			if self.sink.number_formats(&formats).not() {
+				self.columns = formats;
+			}
+		}
+
+		self.sink.write_header(columns)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		if self.columns.iter().all(Option::is_none) {
+			return self.sink.write_row(values);
+		}
+
+		let formatted: Vec<_> = values
+			.iter()
+			.zip(&self.columns)
+			.map(|(value, format)| format.and_then(|format| format.apply(value)))
+			.collect();
+		let values: Vec<_> = values
+			.iter()
+			.zip(&formatted)
+			.map(|(value, formatted)| formatted.as_deref().unwrap_or(value))
+			.collect();
+		self.sink.write_row(&values)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.first_table = false;
+		self.columns.clear();
+		self.sink.add_table(name)
+	}
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
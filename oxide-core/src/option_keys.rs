@@ -0,0 +1,245 @@
+use std::ops::Not;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cumulative::CumulativeMeters;
+use crate::duplicates::DuplicateReports;
+use crate::export::{ExportOptions, RowIds};
+use crate::filter::RowFilter;
+use crate::header::HeaderStyle;
+use crate::intervals::DepthIntervals;
+use crate::output_schema::SchemaSidecar;
+use crate::preset::ExportPreset;
+use crate::provenance::Provenance;
+use crate::qa::QaOutput;
+use crate::report::PdfReport;
+use crate::sink::CsvDialect;
+use crate::sort::SortKey;
+use crate::template::ReportTemplate;
+
+/// `true` or `false` as the value of the option `key`, failing on anything
+/// else.
+pub fn parse_flag(key: &str, value: &str) -> Result<bool, String> {
+	match value {
+		"true" => Ok(true),
+		"false" => Ok(false),
+		_ => Err(format!("expected true or false for {}, not {}", key, value)),
+	}
+}
+
+/// Fails unless `value` is one of `names`, a `kind` of value like csv
+/// dialect, with the names it could have been.
+pub fn check_name(kind: &str, names: &[&str], value: &str) -> Result<(), String> {
+	match names.contains(&value) {
+		true => Ok(()),
+		false => Err(format!(
+			"unknown {} {}, expected one of: {}",
+			kind,
+			value,
+			names.join(", ")
+		)),
+	}
+}
+
+// the value picked by one of `names`, or `default` when it's empty
+fn named<T>(
+	kind: &str,
+	names: &[&str],
+	value: &str,
+	from_name: impl FnOnce(&str) -> Option<T>,
+	default: T,
+) -> Result<T, String> {
+	if value.is_empty() {
+		return Ok(default);
+	}
+
+	check_name(kind, names, value)?;
+	from_name(value).ok_or_else(|| format!("unknown {} {}", kind, value))
+}
+
+// comma separated names, like the columns of a merge key
+fn list(value: &str) -> Vec<String> {
+	value
+		.split(',')
+		.map(str::trim)
+		.filter(|name| name.is_empty().not())
+		.map(String::from)
+		.collect()
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<Option<T>, String> {
+	match value.is_empty() {
+		true => Ok(None),
+		false => value
+			.parse()
+			.map(Some)
+			.map_err(|_| format!("expected a number for {}, not {}", key, value)),
+	}
+}
+
+impl ExportOptions {
+	/// Names of the options [`ExportOptions::set`] takes.
+	pub const KEYS: &[&str] = &[
+		"template",
+		"header-style",
+		"preset",
+		"csv-dialect",
+		"provenance",
+		"profile",
+		"null-value",
+		"schema-sidecar",
+		"row-ids",
+		"duplicate-reports",
+		"qa-output",
+		"source-columns",
+		"remarks-output",
+		"depth-intervals",
+		"cumulative-meters",
+		"manifest",
+		"pdf-report",
+		"keep-identical",
+		"merge-key",
+		"column-order",
+		"filter",
+		"sort-by",
+		"settle-time",
+		"max-concurrent-files",
+		"quarantine-folder",
+		"resume",
+		"row-limit",
+	];
+
+	/// Sets one of the options by name, from the text `oxide config set`
+	/// takes for it: a name like `excel-germany`, `true` or `false`, a
+	/// comma separated list of columns or a number, with an empty value for
+	/// the default. `template` is the path of a template file.
+	pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+		match key {
+			"template" => {
+				self.template = match value.is_empty() {
+					true => ReportTemplate::default(),
+					false => ReportTemplate::load(Path::new(value)).map_err(|e| e.to_string())?,
+				}
+			}
+			"header-style" => {
+				self.header_style = named(
+					"header style",
+					HeaderStyle::NAMES,
+					value,
+					|name| HeaderStyle::from_name(name, Vec::new()),
+					HeaderStyle::default(),
+				)?
+			}
+			"preset" => {
+				self.preset = named(
+					"preset",
+					ExportPreset::NAMES,
+					value,
+					ExportPreset::from_name,
+					ExportPreset::None,
+				)?
+			}
+			"csv-dialect" => {
+				self.csv_dialect = named(
+					"csv dialect",
+					CsvDialect::NAMES,
+					value,
+					CsvDialect::from_name,
+					CsvDialect::default(),
+				)?
+			}
+			"provenance" => {
+				self.provenance = named(
+					"provenance",
+					Provenance::NAMES,
+					value,
+					Provenance::from_name,
+					Provenance::None,
+				)?
+			}
+			"profile" => self.profile = value.to_string(),
+			"null-value" => self.null_value = value.to_string(),
+			"schema-sidecar" => {
+				self.schema_sidecar = named(
+					"schema sidecar",
+					SchemaSidecar::NAMES,
+					value,
+					SchemaSidecar::from_name,
+					SchemaSidecar::None,
+				)?
+			}
+			"row-ids" => {
+				self.row_ids = named(
+					"row ids",
+					RowIds::NAMES,
+					value,
+					RowIds::from_name,
+					RowIds::None,
+				)?
+			}
+			"duplicate-reports" => {
+				self.duplicate_reports = named(
+					"duplicate reports",
+					DuplicateReports::NAMES,
+					value,
+					DuplicateReports::from_name,
+					DuplicateReports::Warn,
+				)?
+			}
+			"qa-output" => {
+				self.qa_output = named(
+					"qa output",
+					QaOutput::NAMES,
+					value,
+					QaOutput::from_name,
+					QaOutput::Column,
+				)?
+			}
+			"source-columns" => self.source_columns = parse_flag(key, value)?,
+			"remarks-output" => self.remarks_output = parse_flag(key, value)?,
+			"depth-intervals" => {
+				self.depth_intervals = parse_flag(key, value)?.then(DepthIntervals::default)
+			}
+			"cumulative-meters" => {
+				self.cumulative_meters = parse_flag(key, value)?.then(CumulativeMeters::default)
+			}
+			"manifest" => self.manifest = parse_flag(key, value)?,
+			"pdf-report" => self.pdf_report = parse_flag(key, value)?.then(PdfReport::default),
+			"keep-identical" => self.skip_identical = parse_flag(key, value)?.not(),
+			"merge-key" => self.merge_key = list(value),
+			"column-order" => self.column_order = list(value),
+			"filter" => {
+				self.filter = match value.trim().is_empty() {
+					true => None,
+					false => Some(RowFilter::parse(value)?),
+				}
+			}
+			"sort-by" => self.sort_by = SortKey::parse_list(value)?,
+			"settle-time" => {
+				self.settle_time = match number(key, value)? {
+					Some(secs) => Duration::from_secs(secs),
+					None => ExportOptions::default().settle_time,
+				}
+			}
+			"max-concurrent-files" => {
+				self.max_concurrent_files = match number(key, value)? {
+					Some(0) => return Err("max-concurrent-files can't be 0".to_string()),
+					Some(files) => files,
+					None => ExportOptions::default().max_concurrent_files,
+				}
+			}
+			"quarantine-folder" => self.quarantine = value.is_empty().not().then(|| value.into()),
+			"resume" => self.resume = parse_flag(key, value)?,
+			"row-limit" => self.row_limit = number(key, value)?,
+			_ => {
+				return Err(format!(
+					"unknown option {}, expected one of: {}",
+					key,
+					ExportOptions::KEYS.join(", ")
+				));
+			}
+		}
+
+		Ok(())
+	}
+}
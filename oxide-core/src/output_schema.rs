@@ -0,0 +1,270 @@
+use std::fmt::Write;
+use std::io;
+use std::ops::{Not, RangeInclusive};
+
+use serde::Serialize;
+
+use crate::sink::{OutputSink, REPORT_TABLE};
+
+// example values kept for every column
+const MAX_EXAMPLES: usize = 3;
+
+/// Which schema sidecars are written next to the output, see
+/// [`OutputSchema`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum SchemaSidecar {
+	#[default]
+	None,
+	Json,
+	Sql,
+	Both,
+}
+
+impl SchemaSidecar {
+	pub const NAMES: &[&str] = &["none", "json", "sql", "both"];
+
+	pub fn from_name(name: &str) -> Option<SchemaSidecar> {
+		Some(match name {
+			"none" => SchemaSidecar::None,
+			"json" => SchemaSidecar::Json,
+			"sql" => SchemaSidecar::Sql,
+			"both" => SchemaSidecar::Both,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			SchemaSidecar::None => "none",
+			SchemaSidecar::Json => "json",
+			SchemaSidecar::Sql => "sql",
+			SchemaSidecar::Both => "both",
+		}
+	}
+
+	pub fn json(&self) -> bool {
+		matches!(self, SchemaSidecar::Json | SchemaSidecar::Both)
+	}
+
+	pub fn sql(&self) -> bool {
+		matches!(self, SchemaSidecar::Sql | SchemaSidecar::Both)
+	}
+}
+
+/// Type of a column, the narrowest one every value in it fits.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+	Integer,
+	Number,
+	Boolean,
+	/// Dates written as `DD/MM/YYYY`.
+	Date,
+	/// Dates written as `YYYY-MM-DD`.
+	IsoDate,
+	Text,
+}
+
+impl ColumnType {
+	fn of(value: &str) -> ColumnType {
+		if value.parse::<i64>().is_ok() {
+			ColumnType::Integer
+		} else if value.parse::<f64>().is_ok_and(|n| n.is_finite())
+			&& value
+				.chars()
+				.all(|c| c.is_ascii_digit() || ".-+eE".contains(c))
+		{
+			ColumnType::Number
+		} else if value == "true" || value == "false" {
+			ColumnType::Boolean
+		} else if is_date(value, '/', [1..=2, 1..=2, 4..=4]) {
+			ColumnType::Date
+		} else if is_date(value, '-', [4..=4, 2..=2, 2..=2]) {
+			ColumnType::IsoDate
+		} else {
+			ColumnType::Text
+		}
+	}
+
+	// the type fitting values of both
+	fn widen(self, other: ColumnType) -> ColumnType {
+		match (self, other) {
+			(a, b) if a == b => a,
+			(ColumnType::Integer, ColumnType::Number) | (ColumnType::Number, ColumnType::Integer) => {
+				ColumnType::Number
+			}
+			_ => ColumnType::Text,
+		}
+	}
+
+	fn sql(&self) -> &'static str {
+		match self {
+			ColumnType::Integer => "BIGINT",
+			ColumnType::Number => "DOUBLE PRECISION",
+			ColumnType::Boolean => "BOOLEAN",
+			ColumnType::IsoDate => "DATE",
+			// day first dates aren't understood by most databases as they are
+			ColumnType::Date | ColumnType::Text => "TEXT",
+		}
+	}
+}
+
+// digits separated by `separator`, with each part's length in its range
+fn is_date(value: &str, separator: char, lengths: [RangeInclusive<usize>; 3]) -> bool {
+	let parts: Vec<_> = value.split(separator).collect();
+	parts.len() == 3
+		&& parts
+			.iter()
+			.zip(lengths)
+			.all(|(part, len)| len.contains(&part.len()) && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// A column of the exported table, as inferred from the values written to it.
+#[derive(Serialize, Clone, Debug)]
+pub struct ColumnSchema {
+	pub name: String,
+	#[serde(rename = "type")]
+	pub column_type: ColumnType,
+	/// Whether any value is empty.
+	pub nullable: bool,
+	/// The first few distinct values.
+	pub examples: Vec<String>,
+}
+
+/// Columns of the exported table, written as sidecars next to the output so
+/// loading it into a database can be generated rather than written by hand.
+#[derive(Serialize, Clone, Debug)]
+pub struct OutputSchema {
+	pub table: String,
+	pub rows: usize,
+	/// Written in place of empty values, if anything.
+	#[serde(skip_serializing_if = "String::is_empty")]
+	pub null_value: String,
+	pub columns: Vec<ColumnSchema>,
+}
+
+impl OutputSchema {
+	pub fn to_json(&self) -> String {
+		// only strings, numbers and bools, which can't fail to serialize
+		let mut json = serde_json::to_string_pretty(self).unwrap_or_default();
+		json.push('\n');
+		json
+	}
+
+	/// A `CREATE TABLE` statement for the table.
+	pub fn to_sql(&self) -> String {
+		let mut sql = format!("CREATE TABLE {} (\n", quote_identifier(&self.table));
+		for (i, column) in self.columns.iter().enumerate() {
+			let _ = write!(
+				sql,
+				"\t{} {}",
+				quote_identifier(&column.name),
+				column.column_type.sql()
+			);
+			if column.nullable.not() {
+				sql.push_str(" NOT NULL");
+			}
+
+			sql.push_str(if i + 1 < self.columns.len() {
+				",\n"
+			} else {
+				"\n"
+			});
+		}
+
+		sql.push_str(");\n");
+		sql
+	}
+}
+
+fn quote_identifier(name: &str) -> String {
+	format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Passes everything through to `sink`, inferring the schema of the first
+/// table written to it on the way.
+pub(crate) struct SchemaRecorder<'s> {
+	sink: &'s mut dyn OutputSink,
+	schema: Option<OutputSchema>,
+	// stops at the first extra table
+	recording: bool,
+}
+
+impl<'s> SchemaRecorder<'s> {
+	/// Only forwards to `sink` unless `enabled`.
+	pub fn new(sink: &'s mut dyn OutputSink, enabled: bool) -> SchemaRecorder<'s> {
+		SchemaRecorder {
+			sink,
+			schema: None,
+			recording: enabled,
+		}
+	}
+
+	/// The inferred schema, none if it wasn't enabled or nothing was written.
+	pub fn into_schema(self) -> Option<OutputSchema> {
+		self.schema
+	}
+}
+
+impl OutputSink for SchemaRecorder<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		if self.recording {
+			self.schema = Some(OutputSchema {
+				table: REPORT_TABLE.to_string(),
+				rows: 0,
+				null_value: String::new(),
+				columns: columns
+					.iter()
+					.map(|name| ColumnSchema {
+						name: name.clone(),
+						column_type: ColumnType::Text,
+						nullable: false,
+						examples: Vec::new(),
+					})
+					.collect(),
+			});
+		}
+
+		self.sink.write_header(columns)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		if self.recording
+			&& let Some(schema) = &mut self.schema
+		{
+			schema.rows += 1;
+			for (column, value) in schema.columns.iter_mut().zip(values) {
+				if value.is_empty() {
+					column.nullable = true;
+					continue;
+				}
+
+				// every value is kept as an example until there's one, so a
+				// column without any is one without a value yet
+				let column_type = ColumnType::of(value);
+				column.column_type = match column.examples.is_empty() {
+					true => column_type,
+					false => column.column_type.widen(column_type),
+				};
+				if column.examples.len() < MAX_EXAMPLES && column.examples.iter().any(|e| e == value).not()
+				{
+					column.examples.push(value.to_string());
+				}
+			}
+		}
+
+		self.sink.write_row(values)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.recording = false;
+		self.sink.add_table(name)
+	}
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::ops::Not;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::source::ReadSeek;
+
+// the parts of a workbook calamine doesn't read, like its links and cell
+// styles, are read straight out of the xml files it's zipped up from
+
+pub(crate) type Archive<'r> = ZipArchive<&'r mut dyn ReadSeek>;
+
+/// An element of one of the workbook's xml files, named without its
+/// namespace, as are its attributes.
+pub(crate) struct Element {
+	pub name: String,
+	attributes: Vec<(String, String)>,
+}
+
+impl Element {
+	pub fn attribute(&self, name: &str) -> Option<&str> {
+		self
+			.attributes
+			.iter()
+			.find(|(key, _)| key == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+// path in the archive of the sheet called `sheet`, none when there isn't one
+pub(crate) fn sheet_path(archive: &mut Archive, sheet: &str) -> io::Result<Option<String>> {
+	let targets = relationships(archive, "xl/_rels/workbook.xml.rels")?;
+	let path = elements(archive, "xl/workbook.xml", &["sheet"])?
+		.iter()
+		.find(|element| element.attribute("name") == Some(sheet))
+		.and_then(|element| targets.get(element.attribute("id")?))
+		.map(|target| match target.strip_prefix('/') {
+			Some(absolute) => absolute.to_string(),
+			None => format!("xl/{}", target),
+		});
+	Ok(path)
+}
+
+// path of the relationships of the part at `path`, like
+// xl/worksheets/_rels/sheet1.xml.rels for xl/worksheets/sheet1.xml
+pub(crate) fn rels_path(path: &str) -> String {
+	let (dir, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+	format!("{}/_rels/{}.rels", dir, file_name)
+}
+
+// targets of a part's relationships, keyed by their id
+pub(crate) fn relationships(
+	archive: &mut Archive,
+	path: &str,
+) -> io::Result<HashMap<String, String>> {
+	let targets = elements(archive, path, &["Relationship"])?
+		.into_iter()
+		.filter_map(|element| {
+			let id = element.attribute("Id")?.to_string();
+			Some((id, element.attribute("Target")?.to_string()))
+		})
+		.collect();
+	Ok(targets)
+}
+
+// every element called one of `names` in the archive's xml file at `path`,
+// in the order they're in, none when there's no file
+pub(crate) fn elements(
+	archive: &mut Archive,
+	path: &str,
+	names: &[&str],
+) -> io::Result<Vec<Element>> {
+	let mut xml = String::new();
+	match archive.by_name(path) {
+		Ok(mut file) => file.read_to_string(&mut xml)?,
+		Err(ZipError::FileNotFound) => return Ok(Vec::new()),
+		Err(e) => return Err(io::Error::other(e)),
+	};
+
+	let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e));
+	let mut reader = Reader::from_str(&xml);
+	let mut found = Vec::new();
+	loop {
+		match reader.read_event().map_err(|e| invalid(e.to_string()))? {
+			Event::Start(e) | Event::Empty(e) => {
+				let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+				if names.contains(&name.as_str()).not() {
+					continue;
+				}
+
+				let mut attributes = Vec::new();
+				for attr in e.attributes() {
+					let attr = attr.map_err(|e| invalid(e.to_string()))?;
+					let value = attr
+						.decode_and_unescape_value(reader.decoder())
+						.map_err(|e| invalid(e.to_string()))?;
+					let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_string();
+					attributes.push((key, value.to_string()));
+				}
+				found.push(Element { name, attributes });
+			}
+			Event::Eof => break,
+			_ => (),
+		}
+	}
+
+	Ok(found)
+}
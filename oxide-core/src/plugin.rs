@@ -0,0 +1,90 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::OxideError;
+use crate::export::ParsedFile;
+use crate::sink::OutputSink;
+
+/// An output format added from outside oxide-core, picked over the built in
+/// ones when the output file has its extension.
+pub trait SinkPlugin: Send + Sync {
+	/// Extension of the files it writes, without the leading dot.
+	fn extension(&self) -> &str;
+
+	fn create_sink(&self, path: &Path) -> io::Result<Box<dyn OutputSink>>;
+}
+
+/// Changes the table read from each workbook before it's written.
+pub trait RowTransform: Send + Sync {
+	/// `headers` names the values of every row in `rows`. Both can be
+	/// changed freely, as long as every row is left with one value per
+	/// header. A failing transform skips the workbook.
+	fn transform(
+		&self,
+		file: &Path,
+		headers: &mut Vec<String>,
+		rows: &mut Vec<Vec<String>>,
+	) -> Result<(), String>;
+}
+
+/// Sinks and transforms an export runs with, on top of what's built in.
+/// Transforms run in the order they were added.
+#[derive(Clone, Default)]
+pub struct Plugins {
+	sinks: Vec<Arc<dyn SinkPlugin>>,
+	transforms: Vec<Arc<dyn RowTransform>>,
+}
+
+impl Plugins {
+	pub fn add_sink(&mut self, sink: impl SinkPlugin + 'static) -> &mut Plugins {
+		self.sinks.push(Arc::new(sink));
+		self
+	}
+
+	pub fn add_transform(&mut self, transform: impl RowTransform + 'static) -> &mut Plugins {
+		self.transforms.push(Arc::new(transform));
+		self
+	}
+
+	/// Extensions of the formats added by sink plugins.
+	pub fn sink_extensions(&self) -> impl Iterator<Item = &str> {
+		self.sinks.iter().map(|s| s.extension())
+	}
+
+	// none when no plugin writes files with `path`'s extension
+	pub(crate) fn create_sink(&self, path: &Path) -> Option<io::Result<Box<dyn OutputSink>>> {
+		let extension = path.extension()?.to_str()?;
+		let sink = self
+			.sinks
+			.iter()
+			.find(|s| s.extension().eq_ignore_ascii_case(extension))?;
+		Some(sink.create_sink(path))
+	}
+
+	pub(crate) fn transform(&self, path: &Path, parsed: &mut ParsedFile) -> Result<(), OxideError> {
+		let transform_error = |message| OxideError::Transform {
+			file: path.to_path_buf(),
+			message,
+		};
+
+		for transform in &self.transforms {
+			transform
+				.transform(path, &mut parsed.headers, &mut parsed.rows)
+				.map_err(transform_error)?;
+		}
+
+		// sinks rely on rows lining up with the header
+		let width = parsed.headers.len();
+		if let Some(i) = parsed.rows.iter().position(|row| row.len() != width) {
+			return Err(transform_error(format!(
+				"data row {} has {} values for {} columns",
+				i + 1,
+				parsed.rows[i].len(),
+				width
+			)));
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,151 @@
+use std::io;
+use std::ops::Not;
+
+use crate::coerce::ValueType;
+use crate::sink::OutputSink;
+
+/// Column layouts drilling data systems import, the exported columns are
+/// renamed and ordered to match and the rest left out.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ExportPreset {
+	/// The columns as they are.
+	#[default]
+	None,
+	/// A drillhole interval import, like acquire's: `HOLEID`, `FROM`, `TO`,
+	/// `LENGTH`, `DRILL_DATE` as `YYYY-MM-DD` and `COMMENTS`. Rows without a
+	/// hole, like totals rows, are left out.
+	IntervalImport,
+}
+
+impl ExportPreset {
+	pub const NAMES: &[&str] = &["none", "interval_import"];
+
+	pub fn from_name(name: &str) -> Option<ExportPreset> {
+		Some(match name {
+			"none" => ExportPreset::None,
+			"interval_import" => ExportPreset::IntervalImport,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			ExportPreset::None => "none",
+			ExportPreset::IntervalImport => "interval_import",
+		}
+	}
+
+	// the preset's columns with the exported column each is taken from, the
+	// first is the one rows can't go without
+	fn columns(&self) -> &'static [(&'static str, &'static str)] {
+		match self {
+			ExportPreset::None => &[],
+			ExportPreset::IntervalImport => &[
+				("HOLEID", "hole_number"),
+				("FROM", "depth_from"),
+				("TO", "depth_to"),
+				("LENGTH", "meters"),
+				("DRILL_DATE", "date"),
+				("COMMENTS", "comment"),
+			],
+		}
+	}
+}
+
+/// Writes the first table in a preset's layout, the tables after it are
+/// written as they are.
+pub(crate) struct PresetSink<'s> {
+	sink: &'s mut dyn OutputSink,
+	preset: ExportPreset,
+	// exported column of every preset column, none when it's missing
+	sources: Vec<Option<usize>>,
+	date_col: Option<usize>,
+	first_table: bool,
+	missing: Vec<&'static str>,
+}
+
+impl<'s> PresetSink<'s> {
+	pub fn new(sink: &'s mut dyn OutputSink, preset: ExportPreset) -> PresetSink<'s> {
+		PresetSink {
+			sink,
+			preset,
+			sources: Vec::new(),
+			date_col: None,
+			first_table: true,
+			missing: Vec::new(),
+		}
+	}
+
+	/// Exported columns the preset takes a column from but weren't there,
+	/// the preset's column is left empty.
+	pub fn missing(&self) -> &[&'static str] {
+		&self.missing
+	}
+
+	fn applies(&self) -> bool {
+		self.first_table && self.preset != ExportPreset::None
+	}
+}
+
+impl OutputSink for PresetSink<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		if self.applies().not() {
+			return self.sink.write_header(columns);
+		}
+
+		let mut header = Vec::new();
+		for (name, source) in self.preset.columns() {
+			let col = columns.iter().position(|c| c == source);
+			if col.is_none() {
+				self.missing.push(source);
+			}
+
+			if *source == "date" {
+				self.date_col = col;
+			}
+
+			self.sources.push(col);
+			header.push(name.to_string());
+		}
+
+		self.sink.write_header(&header)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		if self.applies().not() {
+			return self.sink.write_row(values);
+		}
+
+		let value = |col: Option<usize>| col.and_then(|col| values.get(col)).map_or("", |v| v);
+		if self
+			.sources
+			.first()
+			.is_some_and(|col| value(*col).is_empty())
+		{
+			return Ok(());
+		}
+
+		let date = ValueType::Date.coerce(value(self.date_col));
+		let row: Vec<_> = self
+			.sources
+			.iter()
+			.map(|col| match (&date, *col == self.date_col) {
+				(Some(date), true) => date.as_str(),
+				_ => value(*col),
+			})
+			.collect();
+		self.sink.write_row(&row)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.first_table = false;
+		self.sink.add_table(name)
+	}
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
@@ -0,0 +1,175 @@
+use std::ops::Not;
+use std::path::Path;
+
+use calamine::{Data, DataType, Reader};
+
+use crate::datetime::DateTimes;
+use crate::export::build_headers;
+use crate::header::HeaderStyle;
+use crate::source::SpreadsheetSource;
+use crate::template::{Remarks, ReportTemplate};
+use crate::workbook::worksheet_name;
+
+/// Default number of data rows read for a preview.
+pub const PREVIEW_ROWS: usize = 20;
+
+/// The first few rows of a workbook's report table.
+#[derive(Clone)]
+pub struct Preview {
+	pub headers: Vec<String>,
+	pub rows: Vec<Vec<String>>,
+}
+
+/// Streams cells from the worksheet and stops as soon as `max_rows` data rows
+/// have been read, so multi-hundred-mb workbooks never get fully decoded.
+pub fn read_preview(
+	source: &dyn SpreadsheetSource,
+	path: &Path,
+	max_rows: usize,
+	template: &ReportTemplate,
+	header_style: &HeaderStyle,
+) -> Result<Preview, String> {
+	let mut workbook = source.open(path).map_err(|e| e.to_string())?;
+	// a workbook with a sheet per day is previewed from its first one
+	let sheets = template.select_sheets(worksheet_name(path), &workbook.sheet_names());
+	let Some(sheet) = sheets.first() else {
+		return Err(template.no_sheets().to_string());
+	};
+
+	let mut reader = workbook
+		.worksheet_cells_reader(sheet)
+		.map_err(|e| e.to_string())?;
+
+	// cell positions are absolute, while the export works relative to the
+	// first used cell, so shift everything to match
+	let (first_row, first_col) = reader.dimensions().start;
+	let mut builder = PreviewBuilder {
+		max_rows,
+		template,
+		header_style,
+		report_date: String::new(),
+		header_row: None,
+		header: Vec::new(),
+		preview: Preview {
+			headers: Vec::new(),
+			rows: Vec::new(),
+		},
+	};
+
+	let mut current_row: Option<u32> = None;
+	let mut cells: Vec<Data> = Vec::new();
+	loop {
+		let cell = reader.next_cell().map_err(|e| e.to_string())?;
+		let row_idx = cell.as_ref().map(|c| c.get_position().0);
+		if let Some(prev_row) = current_row
+			&& row_idx != current_row
+		{
+			let row = std::mem::take(&mut cells);
+			if builder.accept_row(prev_row.saturating_sub(first_row), row) {
+				break;
+			}
+		}
+
+		let Some(cell) = cell else {
+			break;
+		};
+
+		current_row = row_idx;
+		let (_, col) = cell.get_position();
+		let Some(col) = col.checked_sub(first_col) else {
+			continue;
+		};
+
+		let col = col as usize;
+		if cells.len() <= col {
+			cells.resize(col + 1, Data::Empty);
+		}
+
+		cells[col] = Data::from(cell.get_value().clone());
+	}
+
+	Ok(builder.preview)
+}
+
+struct PreviewBuilder<'a> {
+	max_rows: usize,
+	template: &'a ReportTemplate,
+	header_style: &'a HeaderStyle,
+	report_date: String,
+	// the header rows read so far, kept until all of them have been seen
+	header_row: Option<u32>,
+	header: Vec<Vec<Data>>,
+	preview: Preview,
+}
+
+impl PreviewBuilder<'_> {
+	// feeds one decoded row through the same marker logic the export uses,
+	// returning true once no further rows are needed. rows without any cells
+	// never reach this, the cell reader skips them entirely
+	fn accept_row(&mut self, row_idx: u32, row: Vec<Data>) -> bool {
+		let first_cell = row.first().and_then(|c| c.as_string()).unwrap_or_default();
+		if let Some((date_row, date_col)) = self.template.date_position()
+			&& row_idx as usize == date_row
+		{
+			self.report_date = row
+				.get(date_col)
+				.and_then(|c| c.as_string())
+				.unwrap_or_default();
+		}
+
+		if self.preview.headers.is_empty() {
+			let header_row = match self.header_row {
+				Some(header_row) => header_row,
+				None if first_cell == self.template.data_start => {
+					self.header_row = Some(row_idx);
+					row_idx
+				}
+				None => return false,
+			};
+
+			let level = (row_idx - header_row) as usize;
+			if level < self.template.header_rows {
+				// header rows without any cells never show up, leave them empty
+				self.header.resize(level, Vec::new());
+				self.header.push(row);
+				if self.header.len() == self.template.header_rows {
+					self.build_headers();
+				}
+
+				return false;
+			}
+
+			// the last header rows were empty, so this is already data
+			self.build_headers();
+		}
+
+		if first_cell == self.template.data_end {
+			return true;
+		}
+
+		let is_remarks = first_cell == self.template.remarks_start;
+		if is_remarks && self.template.remarks == Remarks::End {
+			return true;
+		}
+
+		let skipped = is_remarks && self.template.remarks == Remarks::Skip;
+		if skipped.not() && matches!(row.first(), None | Some(Data::Empty)).not() {
+			let mut row_data: Vec<_> = row.iter().map(|c| DateTimes::default().cell(c)).collect();
+			let width = self.preview.headers.len().saturating_sub(1);
+			if row_data.len() < width {
+				row_data.resize(width, String::new());
+			}
+
+			row_data.push(self.report_date.clone());
+			self.preview.rows.push(row_data);
+		}
+
+		self.preview.rows.len() >= self.max_rows
+	}
+
+	fn build_headers(&mut self) {
+		self.header.resize(self.template.header_rows, Vec::new());
+		let rows: Vec<_> = self.header.iter().map(|row| row.as_slice()).collect();
+		self.preview.headers = build_headers(&rows, self.template, self.header_style);
+	}
+}
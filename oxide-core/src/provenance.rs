@@ -0,0 +1,174 @@
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::error::OxideError;
+use crate::recovery;
+use crate::sink::OutputSink;
+
+/// Where an export says what wrote it, so an output passed around the
+/// office can be traced back to the settings and the day it came from.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Provenance {
+	#[default]
+	None,
+	/// `# key: value` lines above the header. Only csv outputs can hold
+	/// them, the rest get the sidecar instead.
+	Preamble,
+	/// A column of the same value in every row for each of them.
+	Column,
+	/// `out.provenance.json` next to the output.
+	Sidecar,
+}
+
+impl Provenance {
+	pub const NAMES: &[&str] = &["none", "preamble", "column", "sidecar"];
+
+	pub fn from_name(name: &str) -> Option<Provenance> {
+		Some(match name {
+			"none" => Provenance::None,
+			"preamble" => Provenance::Preamble,
+			"column" => Provenance::Column,
+			"sidecar" => Provenance::Sidecar,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Provenance::None => "none",
+			Provenance::Preamble => "preamble",
+			Provenance::Column => "column",
+			Provenance::Sidecar => "sidecar",
+		}
+	}
+}
+
+// what's written about the export, as names and values in this order
+pub(crate) fn fields(
+	profile: &str,
+	exported_at: &str,
+	source_files: usize,
+) -> Vec<(String, String)> {
+	let profile = match profile.is_empty() {
+		true => "default",
+		false => profile,
+	};
+	[
+		("oxide_version", env!("CARGO_PKG_VERSION").to_string()),
+		("profile", profile.to_string()),
+		("exported_at", exported_at.to_string()),
+		("source_files", source_files.to_string()),
+	]
+	.into_iter()
+	.map(|(name, value)| (name.to_string(), value))
+	.collect()
+}
+
+// writes the fields as `out.provenance.json` next to the output
+pub(crate) fn write_sidecar(
+	fields: &[(String, String)],
+	output_path: &Path,
+) -> Result<PathBuf, OxideError> {
+	let stem = output_path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let path = output_path.with_file_name(format!("{}.provenance.json", stem));
+	let object: Map<String, Value> = fields
+		.iter()
+		.map(|(name, value)| (name.clone(), Value::String(value.clone())))
+		.collect();
+	let json = serde_json::to_string_pretty(&object).map_err(io::Error::other);
+	json
+		.and_then(|json| recovery::write_file(&path, json))
+		.map_err(|e| OxideError::io(&path, e))?;
+	Ok(path)
+}
+
+/// Writes the provenance into the export's own table, as a preamble above
+/// its header or as columns after its own. Summaries and reports after it
+/// are passed through as they are.
+pub(crate) struct ProvenanceSink<'s> {
+	sink: &'s mut dyn OutputSink,
+	provenance: Provenance,
+	fields: Vec<(String, String)>,
+	first_table: bool,
+	// the sink couldn't hold a preamble, so it's left to the sidecar
+	no_preamble: bool,
+}
+
+impl<'s> ProvenanceSink<'s> {
+	pub fn new(
+		sink: &'s mut dyn OutputSink,
+		provenance: Provenance,
+		fields: Vec<(String, String)>,
+	) -> ProvenanceSink<'s> {
+		ProvenanceSink {
+			sink,
+			provenance,
+			fields,
+			first_table: true,
+			no_preamble: false,
+		}
+	}
+
+	/// Whether the preamble didn't fit the output's format.
+	pub fn no_preamble(&self) -> bool {
+		self.no_preamble
+	}
+
+	fn columns(&self) -> bool {
+		self.first_table && self.provenance == Provenance::Column
+	}
+}
+
+impl OutputSink for ProvenanceSink<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		if self.first_table && self.provenance == Provenance::Preamble {
+			for (name, value) in &self.fields {
+				if self
+					.sink
+					.write_comment(&format!("{}: {}", name, value))?
+					.not()
+				{
+					self.no_preamble = true;
+					break;
+				}
+			}
+		}
+
+		if self.columns().not() {
+			return self.sink.write_header(columns);
+		}
+
+		let mut header = columns.to_vec();
+		header.extend(self.fields.iter().map(|(name, _)| name.clone()));
+		self.sink.write_header(&header)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		if self.columns().not() {
+			return self.sink.write_row(values);
+		}
+
+		let mut row = values.to_vec();
+		row.extend(self.fields.iter().map(|(_, value)| value.as_str()));
+		self.sink.write_row(&row)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.first_table = false;
+		self.sink.add_table(name)
+	}
+
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
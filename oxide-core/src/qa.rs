@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Checks on a numeric column's values, as listed in the settings. Values
+/// they catch are flagged rather than left out, and values that aren't
+/// numbers are left to the validation rules.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct QaCheck {
+	pub column: String,
+	pub min: Option<f64>,
+	pub max: Option<f64>,
+	// values further than this many standard deviations from the column's
+	// mean, taken over every row read, are flagged
+	pub deviations: Option<f64>,
+}
+
+/// Where the values the QA checks catch end up.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum QaOutput {
+	/// Listed in a trailing `qa_flags` column of the export.
+	#[default]
+	Column,
+	/// Written to a separate QA report, like the violations report.
+	Report,
+}
+
+impl QaOutput {
+	pub const NAMES: &[&str] = &["column", "report"];
+
+	pub fn from_name(name: &str) -> Option<QaOutput> {
+		Some(match name {
+			"column" => QaOutput::Column,
+			"report" => QaOutput::Report,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			QaOutput::Column => "column",
+			QaOutput::Report => "report",
+		}
+	}
+}
+
+/// Mean and spread of the columns checked for deviations, gathered as files
+/// are staged since a column's mean is only known once every row is read.
+pub(crate) struct QaStats {
+	// count, mean and sum of squared differences from the mean of every
+	// column, updated a value at a time
+	columns: BTreeMap<String, (usize, f64, f64)>,
+}
+
+impl QaStats {
+	pub fn new(checks: &[QaCheck]) -> QaStats {
+		let columns = checks
+			.iter()
+			.filter(|check| check.deviations.is_some())
+			.map(|check| (check.column.clone(), (0, 0.0, 0.0)))
+			.collect();
+
+		QaStats { columns }
+	}
+
+	pub fn add(&mut self, headers: &[String], rows: &[Vec<String>]) {
+		for (column, (count, mean, squares)) in &mut self.columns {
+			let Some(col) = headers.iter().position(|h| h == column) else {
+				continue;
+			};
+
+			for row in rows {
+				let Some(value) = row.get(col).and_then(|v| v.trim().parse::<f64>().ok()) else {
+					continue;
+				};
+
+				*count += 1;
+				let delta = value - *mean;
+				*mean += delta / *count as f64;
+				*squares += delta * (value - *mean);
+			}
+		}
+	}
+
+	// none with fewer than two values
+	fn spread(&self, column: &str) -> Option<Spread> {
+		let (count, mean, squares) = self.columns.get(column)?;
+		if *count < 2 {
+			return None;
+		}
+
+		Some(Spread {
+			mean: *mean,
+			sd: (squares / (*count - 1) as f64).sqrt(),
+		})
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Spread {
+	mean: f64,
+	sd: f64,
+}
+
+/// A value a QA check caught.
+pub(crate) struct QaFlag<'a, 'r> {
+	pub column: &'a str,
+	pub reason: String,
+	pub value: &'r str,
+}
+
+/// Checks rows against the [`QaCheck`]s as they're written.
+pub(crate) struct QaChecker<'a> {
+	checks: Vec<(usize, &'a QaCheck, Option<Spread>)>,
+}
+
+impl<'a> QaChecker<'a> {
+	/// Checks on columns that aren't in `header` are left out, and returned
+	/// so they can be warned about.
+	pub fn new(
+		checks: &'a [QaCheck],
+		header: &[String],
+		stats: &QaStats,
+	) -> (QaChecker<'a>, Vec<&'a str>) {
+		let mut found = Vec::new();
+		let mut missing = Vec::new();
+		for check in checks {
+			match header.iter().position(|h| *h == check.column) {
+				Some(col) => found.push((col, check, stats.spread(&check.column))),
+				None if missing.contains(&check.column.as_str()) => {}
+				None => missing.push(check.column.as_str()),
+			}
+		}
+
+		(QaChecker { checks: found }, missing)
+	}
+
+	pub fn check<'r>(&self, row: &[&'r str]) -> Vec<QaFlag<'a, 'r>> {
+		let mut flags = Vec::new();
+		for (col, check, spread) in &self.checks {
+			let value = row.get(*col).copied().unwrap_or_default();
+			let Ok(number) = value.trim().parse::<f64>() else {
+				continue;
+			};
+
+			let mut flag = |reason| {
+				flags.push(QaFlag {
+					column: &check.column,
+					reason,
+					value,
+				})
+			};
+
+			if let Some(min) = check.min
+				&& number < min
+			{
+				flag(format!("below {}", min));
+			}
+
+			if let Some(max) = check.max
+				&& number > max
+			{
+				flag(format!("above {}", max));
+			}
+
+			if let (Some(deviations), Some(spread)) = (check.deviations, spread) {
+				let distance = (number - spread.mean).abs() / spread.sd;
+				if spread.sd > 0.0 && distance > deviations {
+					flag(format!("{:.1} sd from the mean", distance));
+				}
+			}
+		}
+
+		flags
+	}
+}
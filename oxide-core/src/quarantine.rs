@@ -0,0 +1,42 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::error::OxideError;
+use crate::source::SpreadsheetSource;
+use crate::workbook::worksheet_name;
+
+// copies the workbook at `path` that failed to parse with `error` into
+// `folder`, relative to the folder it's read from unless it's absolute,
+// next to a text file of the same name saying why, so it can be sent back
+// to whoever wrote it. workbooks that aren't files of their own, like a
+// zip's, are left out
+pub(crate) fn quarantine(
+	source: &dyn SpreadsheetSource,
+	folder: &Path,
+	path: &Path,
+	error: &OxideError,
+	now: NaiveDateTime,
+) -> io::Result<()> {
+	let Some(path) = source.local_path(path) else {
+		return Ok(());
+	};
+	let Some(file_name) = path.file_name() else {
+		return Ok(());
+	};
+
+	let folder = path.parent().unwrap_or(Path::new("")).join(folder);
+	fs::create_dir_all(&folder)?;
+	fs::copy(&path, folder.join(file_name))?;
+	let reason = format!(
+		"{} couldn't be exported on {}:\n\n{}\n",
+		file_name.to_string_lossy(),
+		now.format("%Y-%m-%d at %H:%M:%S"),
+		error
+	);
+	let reason_path = folder.join(format!("{}.error.txt", worksheet_name(&path)));
+	fs::write(reason_path, reason)?;
+	Ok(())
+}
@@ -0,0 +1,115 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::checkpoint::Checkpoint;
+
+// as the row stage names the files it spills rows to
+const SPILL_PREFIXES: [&str; 2] = ["oxide-stage-", "oxide-sort-"];
+
+/// What an export that was killed before it finished left next to its
+/// output. The output itself is as the last export that finished left it,
+/// as it's only replaced once the new one is complete.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StaleExport {
+	/// The output it was writing, which can't be picked up again.
+	pub partial: Option<PathBuf>,
+	/// Its checkpoint, which the next export can resume from.
+	pub checkpoint: Option<PathBuf>,
+}
+
+impl StaleExport {
+	/// What an unfinished export to `output_file` left behind, None when
+	/// there's nothing.
+	pub fn find(output_file: &str) -> Option<StaleExport> {
+		let partial = partial_path(Path::new(output_file));
+		let checkpoint = Checkpoint::path_for(output_file);
+		let stale = StaleExport {
+			partial: partial.exists().then_some(partial),
+			checkpoint: checkpoint.exists().then_some(checkpoint),
+		};
+		(stale.partial.is_some() || stale.checkpoint.is_some()).then_some(stale)
+	}
+
+	/// Removes the partial output, keeping the checkpoint to resume from.
+	pub fn remove_partial(&self) -> io::Result<()> {
+		remove(&self.partial)
+	}
+
+	/// Removes everything, for the next export to start over.
+	pub fn discard(&self) -> io::Result<()> {
+		remove(&self.partial)?;
+		remove(&self.checkpoint)
+	}
+}
+
+// removes the file unless it's already gone
+fn remove(path: &Option<PathBuf>) -> io::Result<()> {
+	let Some(path) = path else {
+		return Ok(());
+	};
+
+	match fs::remove_file(path) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+		result => result,
+	}
+}
+
+// where the output is written until it's complete, keeping its extension
+// so it's written in the same format, like `out.partial.csv`
+pub(crate) fn partial_path(path: &Path) -> PathBuf {
+	let stem = path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	let file_name = match path.extension().and_then(|s| s.to_str()) {
+		Some(extension) => format!("{}.partial.{}", stem, extension),
+		None => format!("{}.partial", stem),
+	};
+	path.with_file_name(file_name)
+}
+
+// writes `contents` to the partial path of `path`, renaming it over `path`
+// once it's all there, like outputs are
+pub(crate) fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+	let partial = partial_path(path);
+	let result = fs::write(&partial, contents).and_then(|_| fs::rename(&partial, path));
+	if result.is_err() {
+		let _ = fs::remove_file(&partial);
+	}
+
+	result
+}
+
+/// Removes the temporary files exports spill rows to that haven't been
+/// touched in `older_than`, which only exports that were killed leave
+/// behind, returning how many were.
+pub fn remove_stale_spills(older_than: Duration) -> usize {
+	let Ok(entries) = fs::read_dir(env::temp_dir()) else {
+		return 0;
+	};
+
+	let now = SystemTime::now();
+	let mut removed = 0;
+	for entry in entries.flatten() {
+		let file_name = entry.file_name();
+		let Some(file_name) = file_name.to_str() else {
+			continue;
+		};
+		let spill = file_name.ends_with(".tmp")
+			&& SPILL_PREFIXES
+				.iter()
+				.any(|prefix| file_name.starts_with(prefix));
+		let stale = entry
+			.metadata()
+			.and_then(|m| m.modified())
+			.is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= older_than);
+		if spill && stale && fs::remove_file(entry.path()).is_ok() {
+			removed += 1;
+		}
+	}
+
+	removed
+}
@@ -0,0 +1,71 @@
+use std::ops::Not;
+
+use calamine::{Data, Table};
+
+use crate::template::cell_position;
+
+/// Where a sheet's table is, when the workbook or the template says rather
+/// than leaving it to the markers: its cells from the top header row down,
+/// by their zero-based rows and columns in the sheet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Region {
+	pub top: usize,
+	pub left: usize,
+	pub bottom: usize,
+	pub right: usize,
+	/// Names of the columns of an excel table, whose single header row
+	/// they're read from. Empty to read them from the sheet.
+	pub headers: Vec<String>,
+}
+
+impl Region {
+	/// The cells of an A1 style range like `A7:N45`, with or without `$`s.
+	pub fn parse(cells: &str) -> Option<Region> {
+		let (first, last) = cells.split_once(':').unwrap_or((cells, cells));
+		let (top, left) = cell_position(&first.trim().replace('$', ""))?;
+		let (bottom, right) = cell_position(&last.trim().replace('$', ""))?;
+		(top <= bottom && left <= right).then_some(Region {
+			top,
+			left,
+			bottom,
+			right,
+			headers: Vec::new(),
+		})
+	}
+
+	/// The cells of an excel table, from its header row down to its last row
+	/// before the totals.
+	pub fn table(table: &Table<Data>) -> Option<Region> {
+		let data = table.data();
+		let (start, end) = data.start().zip(data.end())?;
+		let headers = table.columns().to_vec();
+		(start.0 > 0 && headers.is_empty().not()).then_some(Region {
+			top: start.0 as usize - 1,
+			left: start.1 as usize,
+			bottom: end.0 as usize,
+			right: end.1 as usize,
+			headers,
+		})
+	}
+
+	/// The cells of the name the workbook defines on `sheet`, given as the
+	/// workbook's defined names and what they refer to, like
+	/// `'DDR 17-05'!$A$4:$N$45`. Names are matched ignoring case, like excel
+	/// does.
+	pub fn named(defined_names: &[(String, String)], name: &str, sheet: &str) -> Option<Region> {
+		defined_names
+			.iter()
+			.filter(|(defined, _)| defined.eq_ignore_ascii_case(name))
+			.find_map(|(_, refers_to)| {
+				let (named_sheet, cells) = refers_to.trim_start_matches('=').rsplit_once('!')?;
+				let named_sheet = match named_sheet.strip_prefix('\'') {
+					Some(quoted) => quoted.strip_suffix('\'')?.replace("''", "'"),
+					None => named_sheet.to_string(),
+				};
+				match named_sheet == sheet {
+					true => Region::parse(cells),
+					false => None,
+				}
+			})
+	}
+}
@@ -0,0 +1,53 @@
+use std::ops::Not;
+
+use calamine::{Data, DataType};
+
+/// A line of the remarks block below a report's table.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Remark {
+	/// The report's date, typed like the date column.
+	pub date: String,
+	/// Hole number or section heading the remark is about, empty when it
+	/// doesn't name one.
+	pub hole: String,
+	pub text: String,
+}
+
+// reads the rows of a remarks block, skipping empty ones. a row with several
+// cells is about the hole or section in its first one, and a single cell is
+// only about a hole when it starts with one of the report's hole numbers
+// and a colon, like "DH-01: lost circulation at 40m"
+pub(crate) fn read_remarks<'r>(
+	rows: impl Iterator<Item = &'r [Data]>,
+	date: &str,
+	holes: &[&str],
+) -> Vec<Remark> {
+	let mut remarks = Vec::new();
+	for row in rows {
+		let cells: Vec<_> = row
+			.iter()
+			.filter_map(|c| c.as_string())
+			.map(|c| c.trim().to_string())
+			.filter(|c| c.is_empty().not())
+			.collect();
+
+		let (hole, text) = match cells.as_slice() {
+			[] => continue,
+			[cell] => match cell.split_once(':') {
+				Some((hole, text)) if holes.contains(&hole.trim()) => {
+					(hole.trim().to_string(), text.trim().to_string())
+				}
+				_ => (String::new(), cell.clone()),
+			},
+			[hole, text @ ..] => (hole.clone(), text.join(" ")),
+		};
+
+		remarks.push(Remark {
+			date: date.to_string(),
+			hole,
+			text,
+		});
+	}
+
+	remarks
+}
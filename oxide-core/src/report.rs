@@ -0,0 +1,334 @@
+use std::fmt::Write;
+use std::iter;
+use std::mem;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use crate::aggregate::{AggregateFn, Aggregation, Aggregator, Measure};
+use crate::summary::RunSummary;
+
+/// A short pdf written next to the output as `out.report.pdf`, for
+/// attaching to an email: the rows read from every workbook, the meters
+/// and rows per hole and per day, and the warnings.
+#[derive(Clone)]
+pub struct PdfReport {
+	hole: String,
+	meters: String,
+	date: String,
+	holes: Aggregation,
+	days: Aggregation,
+}
+
+impl Default for PdfReport {
+	fn default() -> Self {
+		PdfReport::new("hole_number", "meters", "date")
+	}
+}
+
+impl PdfReport {
+	/// Totals the `meters` column per value of the `hole` and `date` ones.
+	pub fn new(hole: &str, meters: &str, date: &str) -> PdfReport {
+		let totals = |name: &str, group: &str| Aggregation {
+			name: name.to_string(),
+			group_by: vec![group.to_string()],
+			measures: vec![
+				Measure {
+					column: meters.to_string(),
+					function: AggregateFn::Sum,
+				},
+				Measure {
+					column: group.to_string(),
+					function: AggregateFn::Count,
+				},
+			],
+		};
+
+		PdfReport {
+			hole: hole.to_string(),
+			meters: meters.to_string(),
+			date: date.to_string(),
+			holes: totals("holes", hole),
+			days: totals("days", date),
+		}
+	}
+
+	/// Totals per hole and per day over the rows as they're written, or
+	/// why they can't be computed.
+	pub(crate) fn totals(&self, header: &[String]) -> Result<ReportTotals<'_>, String> {
+		if let Some(column) = [&self.hole, &self.meters, &self.date]
+			.into_iter()
+			.find(|column| header.contains(column).not())
+		{
+			return Err(format!(
+				"pdf report: there's no {} column, it's left without totals",
+				column
+			));
+		}
+
+		Ok(ReportTotals {
+			holes: Aggregator::new(&self.holes, header)?,
+			days: Aggregator::new(&self.days, header)?,
+		})
+	}
+}
+
+pub(crate) struct ReportTotals<'a> {
+	holes: Aggregator<'a>,
+	days: Aggregator<'a>,
+}
+
+impl ReportTotals<'_> {
+	pub fn add(&mut self, row: &[&str]) {
+		self.holes.add(row);
+		self.days.add(row);
+	}
+}
+
+/// Path of the report written for the output at `output_path`.
+pub(crate) fn report_path(output_path: &Path) -> PathBuf {
+	let stem = output_path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default();
+	output_path.with_file_name(format!("{}.report.pdf", stem))
+}
+
+/// The report's pdf for a finished export, the totals are left out when
+/// the columns they need weren't there.
+pub(crate) fn render(
+	output_path: &Path,
+	summary: &RunSummary,
+	totals: Option<&ReportTotals>,
+) -> Vec<u8> {
+	let file_name = output_path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default();
+	let mut lines = vec![
+		Line::Heading(format!("Export report: {}", file_name)),
+		Line::Text(format!(
+			"{} rows from {} files, {} warnings",
+			summary.total_rows(),
+			summary.files.len(),
+			summary.warnings.len()
+		)),
+		Line::Heading("Rows per file".to_string()),
+	];
+
+	let files: Vec<_> = summary
+		.files
+		.iter()
+		.map(|file| vec![file.file_name.clone(), file.rows.to_string()])
+		.collect();
+	lines.extend(table(&["file", "rows"], &files));
+
+	if let Some(totals) = totals {
+		// rows without a hole, like standby, still count towards their day
+		let holes: Vec<_> = totals
+			.holes
+			.rows()
+			.filter(|row| row[0].is_empty().not())
+			.collect();
+		lines.push(Line::Heading("Totals per hole".to_string()));
+		lines.extend(table(&["hole", "meters", "rows"], &holes));
+
+		let days: Vec<_> = totals.days.rows().collect();
+		lines.push(Line::Heading("Totals per day".to_string()));
+		lines.extend(table(&["date", "meters", "rows"], &days));
+	}
+
+	if summary.warnings.is_empty().not() {
+		lines.push(Line::Heading("Warnings".to_string()));
+		for warning in &summary.warnings {
+			lines.extend(wrap(warning).into_iter().map(Line::Text));
+		}
+	}
+
+	pdf(&lines)
+}
+
+enum Line {
+	Heading(String),
+	// set in a fixed width font, so tables line up
+	Text(String),
+}
+
+// an a4 page in points
+const PAGE_WIDTH: usize = 595;
+const PAGE_HEIGHT: usize = 842;
+const MARGIN: usize = 50;
+// characters of 9pt courier fitting between the margins
+const LINE_WIDTH: usize = 90;
+// longest value in a table's first column before it's cut short
+const CELL_WIDTH: usize = 48;
+
+// the first column is left aligned and the rest, numbers, right aligned
+fn table(header: &[&str], rows: &[Vec<String>]) -> Vec<Line> {
+	let cell = |value: &str| -> String {
+		match value.chars().count() > CELL_WIDTH {
+			true => value
+				.chars()
+				.take(CELL_WIDTH - 3)
+				.chain("...".chars())
+				.collect(),
+			false => match value.is_empty() {
+				true => "-".to_string(),
+				false => value.to_string(),
+			},
+		}
+	};
+
+	let rows: Vec<Vec<String>> = iter::once(header.iter().map(|h| h.to_string()).collect())
+		.chain(rows.iter().map(|row| row.iter().map(|v| cell(v)).collect()))
+		.collect();
+	let mut widths = vec![0; header.len()];
+	for row in &rows {
+		for (width, value) in widths.iter_mut().zip(row) {
+			*width = (*width).max(value.chars().count());
+		}
+	}
+
+	rows
+		.iter()
+		.map(|row| {
+			let mut line = String::new();
+			for (i, (value, width)) in row.iter().zip(&widths).enumerate() {
+				let _ = match i {
+					0 => write!(line, "{:<width$}", value, width = width),
+					_ => write!(line, "  {:>width$}", value, width = width),
+				};
+			}
+
+			Line::Text(line)
+		})
+		.collect()
+}
+
+fn wrap(text: &str) -> Vec<String> {
+	let mut lines = Vec::new();
+	let mut line = String::new();
+	for word in text.split_whitespace() {
+		if line.is_empty().not() && line.chars().count() + 1 + word.chars().count() > LINE_WIDTH {
+			lines.push(mem::take(&mut line));
+		}
+
+		if line.is_empty().not() {
+			line.push(' ');
+		}
+		line.push_str(word);
+	}
+
+	if line.is_empty().not() {
+		lines.push(line);
+	}
+
+	lines
+}
+
+// lays the lines out on as many pages as they need, with the fonts every
+// pdf reader has built in so nothing has to be embedded
+fn pdf(lines: &[Line]) -> Vec<u8> {
+	let mut pages = Vec::new();
+	let mut content = String::new();
+	let mut y = PAGE_HEIGHT - MARGIN;
+	for line in lines {
+		let (font, size, leading, text) = match line {
+			Line::Heading(text) => ("F1", 12, 24, text),
+			Line::Text(text) => ("F2", 9, 12, text),
+		};
+		if y < MARGIN + leading {
+			pages.push(mem::take(&mut content));
+			y = PAGE_HEIGHT - MARGIN;
+		}
+
+		y -= leading;
+		let _ = writeln!(
+			content,
+			"BT /{} {} Tf {} {} Td ({}) Tj ET",
+			font,
+			size,
+			MARGIN,
+			y,
+			escape(text)
+		);
+	}
+	pages.push(content);
+
+	// the catalog, the page tree and the two fonts come first, then every
+	// page followed by its contents
+	let kids: Vec<_> = (0..pages.len())
+		.map(|i| format!("{} 0 R", 5 + i * 2))
+		.collect();
+	let mut objects = vec![
+		"<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+		format!(
+			"<< /Type /Pages /Kids [{}] /Count {} >>",
+			kids.join(" "),
+			pages.len()
+		),
+		font("Helvetica-Bold"),
+		font("Courier"),
+	];
+	for content in &pages {
+		objects.push(format!(
+			"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+			PAGE_WIDTH,
+			PAGE_HEIGHT,
+			objects.len() + 2
+		));
+		let content = content.trim_end();
+		objects.push(format!(
+			"<< /Length {} >>\nstream\n{}\nendstream",
+			content.len(),
+			content
+		));
+	}
+
+	let mut pdf = String::from("%PDF-1.4\n");
+	let mut offsets = Vec::with_capacity(objects.len());
+	for (i, object) in objects.iter().enumerate() {
+		offsets.push(pdf.len());
+		let _ = write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object);
+	}
+
+	let xref = pdf.len();
+	let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+	for offset in offsets {
+		let _ = writeln!(pdf, "{:010} 00000 n ", offset);
+	}
+	let _ = write!(
+		pdf,
+		"trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+		objects.len() + 1,
+		xref
+	);
+	pdf.into_bytes()
+}
+
+fn font(name: &str) -> String {
+	format!(
+		"<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+		name
+	)
+}
+
+// the built in fonts only cover latin-1, which is written out as octal
+// escapes so the file stays ascii, anything past it shows as a ?
+fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'\\' | '(' | ')' => {
+				escaped.push('\\');
+				escaped.push(c);
+			}
+			' '..='~' => escaped.push(c),
+			'\u{a0}'..='\u{ff}' => {
+				let _ = write!(escaped, "\\{:03o}", c as u32);
+			}
+			_ => escaped.push('?'),
+		}
+	}
+
+	escaped
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::OxideError;
+
+/// How often a workbook another program has open, like excel on the share,
+/// is tried again before it's skipped as in use.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OpenRetries {
+	/// Tries after the first one.
+	pub retries: u32,
+	/// Wait before the first retry, doubled before every one after it.
+	pub delay: Duration,
+}
+
+impl Default for OpenRetries {
+	fn default() -> Self {
+		OpenRetries {
+			retries: 3,
+			delay: Duration::from_millis(500),
+		}
+	}
+}
+
+impl OpenRetries {
+	// reads the workbook at `path` with `read`, again while it's in use,
+	// failing with FileInUse when it still is after the last retry
+	pub(crate) fn run<T>(
+		&self,
+		path: &Path,
+		mut read: impl FnMut() -> Result<T, OxideError>,
+	) -> Result<T, OxideError> {
+		let mut delay = self.delay;
+		for _ in 0..self.retries {
+			match read() {
+				Err(e) if e.in_use() => {
+					thread::sleep(delay);
+					delay = delay.saturating_mul(2);
+				}
+				result => return result,
+			}
+		}
+
+		read().map_err(|e| match e.in_use() {
+			true => OxideError::FileInUse {
+				file: path.to_path_buf(),
+			},
+			false => e,
+		})
+	}
+}
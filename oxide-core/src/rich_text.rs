@@ -0,0 +1,151 @@
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::ops::Not;
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use zip::write::{SimpleFileOptions, ZipWriter};
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::source::ReadSeek;
+
+const SHARED_STRINGS: &str = "xl/sharedStrings.xml";
+
+/// The workbook with every text cell's runs of formatting, like a bold
+/// "DH-01:" in front of a remark, joined into one plain string. calamine
+/// keeps only the first piece of a string that starts without a run and
+/// leaves excel's `_x000D_` escapes in, so the workbook's strings are
+/// rewritten before it's read. Workbooks without such strings, or that
+/// can't be read as one, are returned as they are.
+pub(crate) fn flatten_rich_text(mut reader: Box<dyn ReadSeek>) -> io::Result<Box<dyn ReadSeek>> {
+	let flattened = rewrite(reader.as_mut())?;
+	reader.rewind()?;
+	Ok(match flattened {
+		Some(workbook) => Box::new(Cursor::new(workbook)),
+		None => reader,
+	})
+}
+
+fn rewrite(reader: &mut dyn ReadSeek) -> io::Result<Option<Vec<u8>>> {
+	let Ok(mut archive) = ZipArchive::new(reader) else {
+		return Ok(None);
+	};
+	let mut xml = String::new();
+	match archive.by_name(SHARED_STRINGS) {
+		Ok(mut file) => file.read_to_string(&mut xml)?,
+		Err(_) => return Ok(None),
+	};
+	let Some(strings) = flatten_strings(&xml) else {
+		return Ok(None);
+	};
+
+	// everything but the strings is copied without being decompressed
+	let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+	for i in 0..archive.len() {
+		let file = archive.by_index_raw(i).map_err(io::Error::other)?;
+		match file.name() == SHARED_STRINGS {
+			true => {
+				let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+				writer
+					.start_file(SHARED_STRINGS, options)
+					.map_err(io::Error::other)?;
+				writer.write_all(strings.as_bytes())?;
+			}
+			false => writer.raw_copy_file(file).map_err(io::Error::other)?,
+		}
+	}
+
+	let workbook = writer.finish().map_err(io::Error::other)?;
+	Ok(Some(workbook.into_inner()))
+}
+
+// the shared strings with each one as a single piece of text, none when
+// there's nothing to join or unescape or the xml can't be read
+fn flatten_strings(xml: &str) -> Option<String> {
+	let mut reader = Reader::from_str(xml);
+	let mut writer = Writer::new(Vec::with_capacity(xml.len()));
+	let mut changed = false;
+	loop {
+		match reader.read_event().ok()? {
+			Event::Start(si) if si.local_name().as_ref() == b"si" => {
+				let end = si.to_end().into_owned();
+				let (text, flattened) = read_string(&mut reader, end.name().as_ref())?;
+				changed |= flattened;
+				let t = BytesStart::new("t").with_attributes([("xml:space", "preserve")]);
+				writer.write_event(Event::Start(si)).ok()?;
+				writer.write_event(Event::Start(t)).ok()?;
+				writer
+					.write_event(Event::Text(BytesText::new(&text)))
+					.ok()?;
+				writer.write_event(Event::End(BytesEnd::new("t"))).ok()?;
+				writer.write_event(Event::End(end)).ok()?;
+			}
+			Event::Eof => break,
+			event => writer.write_event(event).ok()?,
+		}
+	}
+
+	match changed {
+		true => String::from_utf8(writer.into_inner()).ok(),
+		false => None,
+	}
+}
+
+// the text of every piece of a string up to its closing tag, leaving out
+// the phonetic guides over japanese text, and whether it had to be joined
+// or unescaped
+fn read_string(reader: &mut Reader<&[u8]>, closing: &[u8]) -> Option<(String, bool)> {
+	let mut text = String::new();
+	let mut pieces = 0;
+	let mut in_t = false;
+	let mut phonetic = false;
+	loop {
+		match reader.read_event().ok()? {
+			Event::Start(e) if e.local_name().as_ref() == b"t" && phonetic.not() => {
+				in_t = true;
+				pieces += 1;
+			}
+			Event::End(e) if e.local_name().as_ref() == b"t" => in_t = false,
+			Event::Start(e) if e.local_name().as_ref() == b"rPh" => phonetic = true,
+			Event::End(e) if e.local_name().as_ref() == b"rPh" => phonetic = false,
+			Event::Text(t) if in_t => text.push_str(&t.unescape().ok()?),
+			Event::CData(t) if in_t => text.push_str(std::str::from_utf8(&t).ok()?),
+			Event::End(e) if e.name().as_ref() == closing => break,
+			Event::Eof => return None,
+			_ => (),
+		}
+	}
+
+	let unescaped = unescape(&text);
+	let flattened = pieces > 1 || unescaped != text;
+	Some((unescaped, flattened))
+}
+
+// excel writes characters xml can't hold, like the carriage return of a
+// line break, as _x000D_, and an underscore starting one of these as _x005F_
+fn unescape(text: &str) -> String {
+	let mut unescaped = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find("_x") {
+		unescaped.push_str(&rest[..start]);
+		let escape = rest[start..]
+			.get(2..7)
+			.filter(|escape| {
+				escape.ends_with('_') && escape.as_bytes()[..4].iter().all(u8::is_ascii_hexdigit)
+			})
+			.and_then(|escape| u32::from_str_radix(&escape[..4], 16).ok())
+			.and_then(char::from_u32);
+		match escape {
+			Some(c) => {
+				unescaped.push(c);
+				rest = &rest[start + 7..];
+			}
+			None => {
+				unescaped.push_str("_x");
+				rest = &rest[start + 2..];
+			}
+		}
+	}
+	unescaped.push_str(rest);
+
+	unescaped
+}
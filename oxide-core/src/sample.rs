@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Not;
+
+use crate::coerce::ValueType;
+use crate::error::OxideError;
+use crate::export::{ExportOptions, parse_file};
+use crate::schema_cache::SchemaCache;
+use crate::source::SpreadsheetSource;
+use crate::validation::Validator;
+use crate::workbook::worksheet_name;
+
+/// What checking a folder found, see [`check_sample`].
+#[derive(Default)]
+pub struct SampleReport {
+	/// Workbooks in the folder.
+	pub files: usize,
+	/// Workbooks whose rows were checked as well, picked at random.
+	pub sampled: Vec<String>,
+	/// Rows of the sampled workbooks.
+	pub rows: usize,
+	/// Values of the sampled rows breaking the validation rules.
+	pub violations: usize,
+	/// Workbooks that couldn't be read, like ones without the markers, with
+	/// why.
+	pub failed: Vec<(String, String)>,
+	/// Workbooks whose columns aren't the ones most of them have.
+	pub other_layouts: Vec<String>,
+	/// Workbooks whose report date isn't a date.
+	pub bad_dates: Vec<String>,
+	/// Warnings about the sampled workbooks, like values that aren't of
+	/// their column's type.
+	pub warnings: Vec<String>,
+}
+
+impl SampleReport {
+	/// Share of the workbooks that passed their checks, from 0 to 1.
+	pub fn confidence(&self) -> f64 {
+		if self.files == 0 {
+			return 1.0;
+		}
+
+		let failed = self.failed.len() + self.other_layouts.len() + self.bad_dates.len();
+		1.0 - failed.min(self.files) as f64 / self.files as f64
+	}
+}
+
+impl fmt::Display for SampleReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{} files checked, {:.0}% look fine",
+			self.files,
+			self.confidence() * 100.0
+		)?;
+		writeln!(
+			f,
+			"{} rows read from a sample of {} files, {} values break validation rules",
+			self.rows,
+			self.sampled.len(),
+			self.violations
+		)?;
+
+		for (file_name, error) in &self.failed {
+			writeln!(f, "{}: can't be read, {}", file_name, error)?;
+		}
+		for file_name in &self.other_layouts {
+			writeln!(
+				f,
+				"{}: its columns aren't the same as most files'",
+				file_name
+			)?;
+		}
+		for file_name in &self.bad_dates {
+			writeln!(f, "{}: its report date isn't a date", file_name)?;
+		}
+
+		if self.warnings.is_empty().not() {
+			writeln!(f, "\nwarnings in the sample:")?;
+			for warning in &self.warnings {
+				writeln!(f, "{}", warning)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Checks every workbook in `source` is read the way `options` say without
+/// writing anything: that its markers are found, that it has the columns
+/// most of them have and that its report date is a date. The rows of
+/// `sample` workbooks picked at random with `seed` are checked as well,
+/// against the validation rules and the template's types, for a quick idea
+/// of how an export of a very large archive would go.
+pub fn check_sample(
+	source: &dyn SpreadsheetSource,
+	options: &ExportOptions,
+	sample: usize,
+	seed: u64,
+) -> Result<SampleReport, OxideError> {
+	let files = source.list()?;
+	let sampled = pick(files.len(), sample, seed);
+	let cache = options
+		.schema_cache
+		.as_deref()
+		.map(SchemaCache::load)
+		.unwrap_or_default();
+
+	let mut report = SampleReport {
+		files: files.len(),
+		..Default::default()
+	};
+	let mut layouts: Vec<(String, Vec<String>)> = Vec::new();
+	for (idx, file) in files.iter().enumerate() {
+		let file_name = worksheet_name(&file.path).to_string();
+		let parsed = parse_file(
+			source,
+			&file.path,
+			&cache,
+			options.template_for(&file.path),
+			&options.header_style,
+			&options.date_times,
+		);
+		let parsed = match parsed {
+			Ok(parsed) => parsed,
+			Err(e) => {
+				report.failed.push((file_name, e.to_string()));
+				continue;
+			}
+		};
+
+		let date = parsed
+			.headers
+			.iter()
+			.rposition(|h| h == "date")
+			.and_then(|col| parsed.rows.first()?.get(col));
+		if let Some(date) = date
+			&& ValueType::Date.coerce(date).is_none()
+		{
+			report.bad_dates.push(file_name.clone());
+		}
+
+		if sampled.contains(&idx) {
+			report.rows += parsed.rows.len();
+			if let Some(validation) = &options.validation {
+				let (validator, _) = Validator::new(validation, &parsed.headers);
+				for row in &parsed.rows {
+					let row: Vec<_> = row.iter().map(String::as_str).collect();
+					report.violations += validator.check(&row).len();
+				}
+			}
+			report.warnings.extend(
+				parsed
+					.warnings
+					.iter()
+					.map(|warning| format!("{}: {}", file_name, warning)),
+			);
+			report.sampled.push(file_name.clone());
+		}
+
+		layouts.push((file_name, parsed.headers));
+	}
+
+	// the layout most workbooks have is the one the rest are held to
+	let mut counts: HashMap<&Vec<String>, usize> = HashMap::new();
+	for (_, headers) in &layouts {
+		*counts.entry(headers).or_default() += 1;
+	}
+	let common = layouts
+		.iter()
+		.map(|(_, headers)| headers)
+		.max_by_key(|headers| counts[headers]);
+	report.other_layouts = layouts
+		.iter()
+		.filter(|(_, headers)| Some(headers) != common)
+		.map(|(file_name, _)| file_name.clone())
+		.collect();
+
+	Ok(report)
+}
+
+// indices of `count` of `len` items picked at random, from a shuffle seeded
+// with `seed`
+fn pick(len: usize, count: usize, seed: u64) -> Vec<usize> {
+	let mut indices: Vec<usize> = (0..len).collect();
+	// xorshift, as the sample only has to be spread out, not unpredictable
+	let mut state = seed | 1;
+	let count = count.min(len);
+	for i in 0..count {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		let j = i + (state % (len - i) as u64) as usize;
+		indices.swap(i, j);
+	}
+
+	indices.truncate(count);
+	indices
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use calamine::{Data, DataType, Range};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::template::ReportTemplate;
+
+// oldest templates are forgotten once the cache grows past this
+const MAX_ENTRIES: usize = 64;
+
+// bumped whenever what's cached changes meaning, older caches are dropped
+// 1: headers are cached unformatted
+// 2: entries record how many header rows they span
+const CACHE_VERSION: u32 = 2;
+
+/// A header layout detected in an earlier run.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedSchema {
+	pub header_row: usize,
+	pub header_rows: usize,
+	pub fingerprint: u64,
+	// as written in the workbook, before the export's header style
+	pub headers: Vec<String>,
+}
+
+pub enum CacheLookup {
+	Hit(CachedSchema),
+	/// A known template's header marker is where we expected it, but the
+	/// header cells around it no longer match what was cached.
+	Drift(usize),
+	Miss,
+}
+
+/// Header layouts detected in previous runs, keyed by a fingerprint of the
+/// header rows so known templates can skip header detection entirely.
+#[derive(Serialize, Deserialize)]
+pub struct SchemaCache {
+	#[serde(default)]
+	version: u32,
+	entries: Vec<CachedSchema>,
+}
+
+impl Default for SchemaCache {
+	fn default() -> Self {
+		SchemaCache {
+			version: CACHE_VERSION,
+			entries: Vec::new(),
+		}
+	}
+}
+
+impl SchemaCache {
+	/// Where the cache is kept by default, in the user's cache directory.
+	pub fn default_path() -> Option<PathBuf> {
+		let dirs = ProjectDirs::from("", "", "oxide")?;
+		Some(dirs.cache_dir().join("schema-cache.json"))
+	}
+
+	/// Loads the cache saved at `path`, empty if there is none.
+	pub fn load(path: &Path) -> SchemaCache {
+		fs::read_to_string(path)
+			.ok()
+			.and_then(|contents| serde_json::from_str::<SchemaCache>(&contents).ok())
+			.filter(|cache| cache.version == CACHE_VERSION)
+			.unwrap_or_default()
+	}
+
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		fs::write(path, serde_json::to_string(self)?)
+	}
+
+	pub fn lookup(&self, r: &Range<Data>, template: &ReportTemplate) -> CacheLookup {
+		let mut drifted = None;
+		// layouts read with a different number of header rows belong to
+		// another template
+		let entries = self
+			.entries
+			.iter()
+			.filter(|e| e.header_rows == template.header_rows);
+		for entry in entries {
+			let first_cell = r.get((entry.header_row, 0)).and_then(|c| c.as_string());
+			if first_cell.as_deref() != Some(template.data_start.as_str()) {
+				continue;
+			}
+
+			if fingerprint(r, entry.header_row, template.header_rows) == entry.fingerprint {
+				return CacheLookup::Hit(entry.clone());
+			}
+
+			drifted = Some(entry.header_row);
+		}
+
+		drifted.map_or(CacheLookup::Miss, CacheLookup::Drift)
+	}
+
+	pub fn insert(&mut self, entry: CachedSchema) {
+		if self
+			.entries
+			.iter()
+			.any(|e| e.fingerprint == entry.fingerprint)
+		{
+			return;
+		}
+
+		self.entries.push(entry);
+		if self.entries.len() > MAX_ENTRIES {
+			self.entries.remove(0);
+		}
+	}
+}
+
+/// Hashes the `header_rows` rows starting at `header_row`.
+// fnv-1a, std's hasher isn't guaranteed to be stable between releases and
+// the cache outlives the binary
+pub fn fingerprint(r: &Range<Data>, header_row: usize, header_rows: usize) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	let mut feed = |bytes: &[u8]| {
+		for b in bytes {
+			hash ^= *b as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+	};
+
+	feed(&header_row.to_le_bytes());
+	for row in r.rows().skip(header_row).take(header_rows) {
+		for cell in row {
+			feed(cell.as_string().unwrap_or_default().as_bytes());
+			feed(&[0]);
+		}
+
+		feed(&[0xff]);
+	}
+
+	hash
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::Path;
+use std::sync::Arc;
+
+use rhai::{AST, Array, Dynamic, Engine, Map, Scope};
+
+use crate::error::OxideError;
+use crate::plugin::RowTransform;
+
+// a script stuck in a loop would otherwise hang the export for good
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+const TRANSFORM_FN: &str = "transform";
+
+/// A rhai script every parsed row goes through before it's written. The
+/// script defines `fn transform(row)`, which gets the row as a map of column
+/// name to value and returns the row to write, an array of rows to split it
+/// into, or `()` to drop it. Columns the script adds are appended after the
+/// workbook's own.
+#[derive(Clone)]
+pub struct RowScript {
+	engine: Arc<Engine>,
+	ast: Arc<AST>,
+}
+
+impl RowScript {
+	pub fn load(path: &Path) -> Result<RowScript, OxideError> {
+		let source = fs::read_to_string(path).map_err(|e| OxideError::io(path, e))?;
+		RowScript::compile(&source)
+			.map_err(|e| OxideError::io(path, io::Error::new(io::ErrorKind::InvalidData, e)))
+	}
+
+	pub fn compile(source: &str) -> Result<RowScript, String> {
+		let mut engine = Engine::new();
+		engine.set_max_operations(MAX_OPERATIONS);
+		let ast = engine.compile(source).map_err(|e| e.to_string())?;
+		let has_transform = ast
+			.iter_functions()
+			.any(|f| f.name == TRANSFORM_FN && f.params.len() == 1);
+		if has_transform.not() {
+			return Err(format!("script has no {}(row) function", TRANSFORM_FN));
+		}
+
+		Ok(RowScript {
+			engine: Arc::new(engine),
+			ast: Arc::new(ast),
+		})
+	}
+}
+
+// a failing row fails the whole file, so it's never written half transformed
+impl RowTransform for RowScript {
+	fn transform(
+		&self,
+		_file: &Path,
+		headers: &mut Vec<String>,
+		rows: &mut Vec<Vec<String>>,
+	) -> Result<(), String> {
+		let mut out = Vec::with_capacity(rows.len());
+		for (i, row) in std::mem::take(rows).into_iter().enumerate() {
+			let row_map: Map = headers
+				.iter()
+				.zip(row)
+				.map(|(header, value)| (header.into(), value.into()))
+				.collect();
+
+			let script_error = |message: String| format!("data row {}: {}", i + 1, message);
+			let result: Dynamic = self
+				.engine
+				.call_fn(&mut Scope::new(), &self.ast, TRANSFORM_FN, (row_map,))
+				.map_err(|e| script_error(e.to_string()))?;
+
+			let out_rows: Array = if result.is_unit() {
+				Array::new()
+			} else if result.is_map() {
+				vec![result]
+			} else if result.is_array() {
+				result.cast::<Array>()
+			} else {
+				return Err(script_error(format!(
+					"{} returned {}, expected a row, an array of rows or ()",
+					TRANSFORM_FN,
+					result.type_name()
+				)));
+			};
+
+			for out_row in out_rows {
+				let type_name = out_row.type_name();
+				let Some(out_row) = out_row.try_cast::<Map>() else {
+					return Err(script_error(format!(
+						"{} returned an array holding {}, expected rows",
+						TRANSFORM_FN, type_name
+					)));
+				};
+
+				out.push(to_values(headers, out_row));
+			}
+		}
+
+		// rows from before a column was added are short of it
+		for row in &mut out {
+			row.resize(headers.len(), String::new());
+		}
+
+		*rows = out;
+		Ok(())
+	}
+}
+
+// lines the row's values up with `headers`, adding any column it introduces
+fn to_values(headers: &mut Vec<String>, mut row: Map) -> Vec<String> {
+	let mut values: Vec<String> = headers
+		.iter()
+		.map(|h| row.remove(h.as_str()).map(to_string).unwrap_or_default())
+		.collect();
+
+	for (header, value) in row {
+		headers.push(header.to_string());
+		values.push(to_string(value));
+	}
+
+	values
+}
+
+fn to_string(value: Dynamic) -> String {
+	if value.is_unit() {
+		return String::new();
+	}
+
+	// numbers and the like are written the way the script would print them
+	if value.is_string() {
+		return value.cast::<String>();
+	}
+
+	value.to_string()
+}
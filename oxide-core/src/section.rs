@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coerce::ValueType;
+use crate::export::{INTERVALS_TABLE, QA_TABLE, REMARKS_TABLE, VIOLATIONS_TABLE};
+
+/// A block of the report besides its table, like the consumables used or
+/// the surveys below the drilled intervals, laid out as a table of its own
+/// and written to a table called `name`. A section starting below the
+/// report's table ends it, when its end marker doesn't come first.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Section {
+	pub name: String,
+	/// First-column text of the row starting the block, its header rows
+	/// follow it.
+	pub start: String,
+	/// First-column text of the row ending the block, empty to end it at the
+	/// first empty row.
+	pub end: String,
+	pub header_rows: usize,
+	/// Renames columns, keyed by the formatted header name.
+	pub columns: BTreeMap<String, String>,
+	/// Columns written, in this order and named as they are after renames,
+	/// like `date` first for a timesheet. Empty writes every column, with
+	/// the date last.
+	pub keep: Vec<String>,
+	/// Types values are coerced to like the table's, keyed by the column
+	/// name after renames.
+	pub types: BTreeMap<String, ValueType>,
+}
+
+impl Default for Section {
+	fn default() -> Self {
+		Section {
+			name: String::new(),
+			start: String::new(),
+			end: String::new(),
+			header_rows: 1,
+			columns: BTreeMap::new(),
+			keep: Vec::new(),
+			types: BTreeMap::new(),
+		}
+	}
+}
+
+impl Section {
+	pub(crate) fn validate(&self) -> Result<(), String> {
+		if self.name.is_empty() {
+			return Err("section name can't be empty".to_string());
+		}
+
+		if let Some(c) = self.name.chars().find(|c| "/\\:*?\"<>|[]".contains(*c)) {
+			return Err(format!("section name {} can't contain {}", self.name, c));
+		}
+
+		if [VIOLATIONS_TABLE, QA_TABLE, REMARKS_TABLE, INTERVALS_TABLE].contains(&self.name.as_str()) {
+			return Err(format!(
+				"section name {} is taken by another report",
+				self.name
+			));
+		}
+
+		if self.start.is_empty() {
+			return Err(format!("section {} has no start marker", self.name));
+		}
+
+		if self.header_rows == 0 {
+			return Err(format!("section {} needs at least 1 header row", self.name));
+		}
+
+		Ok(())
+	}
+}
+
+/// The rows read from a section of a single report, each ending with the
+/// report's date like the table's.
+#[derive(Clone, Default, Debug)]
+pub struct SectionRows {
+	pub name: String,
+	pub headers: Vec<String>,
+	pub rows: Vec<Vec<String>>,
+}
+
+/// A section's rows from every report, under every column any of them had.
+pub(crate) struct SectionTable {
+	pub name: String,
+	pub header: Vec<String>,
+	rows: Vec<Vec<String>>,
+}
+
+impl SectionTable {
+	/// Adds a report's rows to the table of their section, started when
+	/// it's the first to have it, with the report's file in a last column.
+	pub fn add(tables: &mut Vec<SectionTable>, section: SectionRows, file_name: &str) {
+		let idx = match tables.iter().position(|t| t.name == section.name) {
+			Some(idx) => idx,
+			None => {
+				tables.push(SectionTable {
+					name: section.name.clone(),
+					header: vec!["file".to_string()],
+					rows: Vec::new(),
+				});
+				tables.len() - 1
+			}
+		};
+
+		let table = &mut tables[idx];
+		let cols: Vec<_> = section
+			.headers
+			.iter()
+			.map(
+				|header| match table.header.iter().position(|h| h == header) {
+					Some(col) => col,
+					None => {
+						table.header.push(header.clone());
+						table.header.len() - 1
+					}
+				},
+			)
+			.collect();
+
+		for values in section.rows {
+			let mut row = vec![String::new(); table.header.len()];
+			row[0] = file_name.to_string();
+			for (col, value) in cols.iter().zip(values) {
+				row[*col] = value;
+			}
+
+			table.rows.push(row);
+		}
+	}
+
+	/// The rows, padded out to columns added after them, with the file
+	/// column moved to the end.
+	pub fn into_rows(self) -> (Vec<String>, impl Iterator<Item = Vec<String>>) {
+		let width = self.header.len();
+		let move_file = move |mut row: Vec<String>| {
+			row.resize(width, String::new());
+			row.rotate_left(1);
+			row
+		};
+
+		let header = move_file(self.header);
+		let rows = self.rows.into_iter().map(move_file);
+		(header, rows)
+	}
+}
@@ -0,0 +1,391 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::Not;
+use std::path::Path;
+
+use crate::number_format::NumberFormat;
+
+#[cfg(feature = "parquet")]
+mod parquet;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+#[cfg(feature = "parquet")]
+pub use parquet::ParquetSink;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSink;
+#[cfg(feature = "xlsx")]
+pub use xlsx::XlsxSink;
+
+// name of the aggregated table in formats holding several
+pub(crate) const REPORT_TABLE: &str = "report";
+
+/// Where the aggregated table is written to. The header is always written
+/// once before any row, and every row has one value per header column.
+pub trait OutputSink {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()>;
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()>;
+
+	/// Flushes whatever is still buffered, nothing is written after this.
+	fn finish(&mut self) -> io::Result<()>;
+
+	/// Starts another table called `name` after the current one, which the
+	/// following header and rows go to. Formats holding a single table return
+	/// false, and the table is written to a file of its own instead.
+	fn add_table(&mut self, _name: &str) -> io::Result<bool> {
+		Ok(false)
+	}
+
+	/// Formats of the columns of the table whose header comes next. Formats
+	/// keeping numbers as numbers with a format of their own, like excel's,
+	/// return true and get the values as they are, the rest return false and
+	/// get them already formatted.
+	fn number_formats(&mut self, _formats: &[Option<&NumberFormat>]) -> bool {
+		false
+	}
+
+	/// Writes a line of text that isn't part of any table, like the
+	/// provenance above the header. Formats without comments return false
+	/// and nothing is written.
+	fn write_comment(&mut self, _line: &str) -> io::Result<bool> {
+		Ok(false)
+	}
+}
+
+/// The output formats oxide can write, picked from the output file's
+/// extension. Formats other than csv are behind cargo features of the same
+/// name.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OutputFormat {
+	#[default]
+	Csv,
+	#[cfg(feature = "xlsx")]
+	Xlsx,
+	#[cfg(feature = "parquet")]
+	Parquet,
+	#[cfg(feature = "sqlite")]
+	Sqlite,
+}
+
+impl OutputFormat {
+	/// Every format this build can write.
+	pub const ALL: &[OutputFormat] = &[
+		OutputFormat::Csv,
+		#[cfg(feature = "xlsx")]
+		OutputFormat::Xlsx,
+		#[cfg(feature = "parquet")]
+		OutputFormat::Parquet,
+		#[cfg(feature = "sqlite")]
+		OutputFormat::Sqlite,
+	];
+
+	pub fn extension(&self) -> &'static str {
+		match self {
+			OutputFormat::Csv => "csv",
+			#[cfg(feature = "xlsx")]
+			OutputFormat::Xlsx => "xlsx",
+			#[cfg(feature = "parquet")]
+			OutputFormat::Parquet => "parquet",
+			#[cfg(feature = "sqlite")]
+			OutputFormat::Sqlite => "sqlite",
+		}
+	}
+
+	/// The format matching `path`'s extension, defaulting to csv for
+	/// anything unknown.
+	pub fn from_path(path: &Path) -> OutputFormat {
+		let extension = path
+			.extension()
+			.and_then(|s| s.to_str())
+			.unwrap_or_default()
+			.to_lowercase();
+		OutputFormat::ALL
+			.iter()
+			.copied()
+			.find(|f| f.extension() == extension || (extension == "db" && f.extension() == "sqlite"))
+			.unwrap_or_default()
+	}
+
+	/// Creates a sink writing to `path`, replacing anything already there.
+	pub fn create_sink(&self, path: &Path) -> io::Result<Box<dyn OutputSink>> {
+		Ok(match self {
+			OutputFormat::Csv => Box::new(CsvSink::create(path)?),
+			#[cfg(feature = "xlsx")]
+			OutputFormat::Xlsx => Box::new(XlsxSink::create(path)?),
+			#[cfg(feature = "parquet")]
+			OutputFormat::Parquet => Box::new(ParquetSink::create(path)?),
+			#[cfg(feature = "sqlite")]
+			OutputFormat::Sqlite => Box::new(SqliteSink::create(path)?),
+		})
+	}
+}
+
+/// How a csv output is laid out, picked as a whole from one of
+/// [`CsvDialect::NAMES`] so the program it's opened in reads it right. The
+/// default is oxide's own, values joined by commas as they are.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CsvDialect {
+	pub delimiter: char,
+	/// Quotes values holding the delimiter, a quote or a line break,
+	/// doubling the quotes in them.
+	pub quote: bool,
+	/// Starts the file with a utf-8 byte order mark, without which excel
+	/// reads it in the system's code page.
+	pub bom: bool,
+	/// Ends lines with `\r\n` rather than `\n`.
+	pub crlf: bool,
+	/// Writes decimal numbers with a comma, like `10,5`.
+	pub decimal_comma: bool,
+}
+
+impl Default for CsvDialect {
+	fn default() -> Self {
+		CsvDialect {
+			delimiter: ',',
+			quote: false,
+			bom: false,
+			crlf: false,
+			decimal_comma: false,
+		}
+	}
+}
+
+impl CsvDialect {
+	pub const NAMES: &[&str] = &["plain", "rfc4180", "excel", "excel-germany", "unix"];
+
+	pub fn from_name(name: &str) -> Option<CsvDialect> {
+		let plain = CsvDialect::default();
+		let rfc4180 = CsvDialect {
+			quote: true,
+			crlf: true,
+			..plain
+		};
+		let excel = CsvDialect {
+			bom: true,
+			..rfc4180
+		};
+		Some(match name {
+			"plain" => plain,
+			"rfc4180" => rfc4180,
+			"excel" => excel,
+			// excel set to a locale writing decimals with a comma expects
+			// semicolons between values
+			"excel-germany" => CsvDialect {
+				delimiter: ';',
+				decimal_comma: true,
+				..excel
+			},
+			"unix" => CsvDialect {
+				quote: true,
+				..plain
+			},
+			_ => return None,
+		})
+	}
+
+	fn line(&self) -> &'static str {
+		if self.crlf { "\r\n" } else { "\n" }
+	}
+
+	// the value as it's written between delimiters, in `format` when its
+	// column has one
+	fn field<'v>(&self, value: &'v str, format: Option<&NumberFormat>) -> Cow<'v, str> {
+		let mut value = Cow::Borrowed(value);
+		if self.decimal_comma {
+			// formatted numbers swap their separators, like 1.234,50
+			match format.and_then(|format| format.apply(&value)) {
+				Some(formatted) => value = Cow::Owned(swap_separators(&formatted)),
+				None if value.contains('.') && value.parse::<f64>().is_ok() => {
+					value = Cow::Owned(value.replace('.', ","));
+				}
+				None => {}
+			}
+		}
+
+		let special = |c: char| c == self.delimiter || c == '"' || c == '\r' || c == '\n';
+		if self.quote && value.contains(special) {
+			value = Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")));
+		}
+
+		value
+	}
+}
+
+fn swap_separators(number: &str) -> String {
+	number
+		.chars()
+		.map(|c| match c {
+			'.' => ',',
+			',' => '.',
+			c => c,
+		})
+		.collect()
+}
+
+/// Comma separated values, or laid out like a [`CsvDialect`], written to a
+/// file or any other writer.
+pub struct CsvSink<W: Write = BufWriter<File>> {
+	writer: W,
+	dialect: CsvDialect,
+	// whether anything was written yet, the byte order mark goes first
+	started: bool,
+	// formats of the table's columns, which dialects writing decimals with a
+	// comma format numbers in themselves
+	formats: Vec<Option<NumberFormat>>,
+}
+
+impl CsvSink {
+	pub fn create(path: &Path) -> io::Result<CsvSink> {
+		CsvSink::create_with_dialect(path, CsvDialect::default())
+	}
+
+	pub fn create_with_dialect(path: &Path, dialect: CsvDialect) -> io::Result<CsvSink> {
+		let writer = BufWriter::new(File::create(path)?);
+		Ok(CsvSink::with_dialect(writer, dialect))
+	}
+}
+
+impl<W: Write> CsvSink<W> {
+	pub fn new(writer: W) -> CsvSink<W> {
+		CsvSink::with_dialect(writer, CsvDialect::default())
+	}
+
+	pub fn with_dialect(writer: W, dialect: CsvDialect) -> CsvSink<W> {
+		CsvSink {
+			writer,
+			dialect,
+			started: false,
+			formats: Vec::new(),
+		}
+	}
+
+	/// The writer the values went to.
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+
+	fn write_line(&mut self, line: &str) -> io::Result<()> {
+		if self.started.not() && self.dialect.bom {
+			self.writer.write_all("\u{feff}".as_bytes())?;
+		}
+
+		self.started = true;
+		self.writer.write_all(line.as_bytes())?;
+		self.writer.write_all(self.dialect.line().as_bytes())
+	}
+
+	fn write_fields<T: AsRef<str>>(&mut self, values: &[T], formatted: bool) -> io::Result<()> {
+		let fields: Vec<_> = values
+			.iter()
+			.enumerate()
+			.map(|(i, value)| {
+				let format = self.formats.get(i).and_then(Option::as_ref);
+				self
+					.dialect
+					.field(value.as_ref(), format.filter(|_| formatted))
+			})
+			.collect();
+		let line = fields.join(self.dialect.delimiter.encode_utf8(&mut [0; 4]));
+		self.write_line(&line)
+	}
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		self.write_fields(columns, false)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		self.write_fields(values, true)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+
+	fn number_formats(&mut self, formats: &[Option<&NumberFormat>]) -> bool {
+		if self.dialect.decimal_comma.not() {
+			return false;
+		}
+
+		self.formats = formats.iter().map(|format| format.cloned()).collect();
+		true
+	}
+
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.write_line(&format!("# {}", line))?;
+		Ok(true)
+	}
+}
+
+/// Writes `token` in place of empty values, so they can be told apart from
+/// empty strings. An empty token leaves them to the format, which writes
+/// nulls where it has them.
+pub(crate) struct NullValues<'s> {
+	sink: &'s mut dyn OutputSink,
+	token: &'s str,
+}
+
+impl<'s> NullValues<'s> {
+	pub fn new(sink: &'s mut dyn OutputSink, token: &'s str) -> NullValues<'s> {
+		NullValues { sink, token }
+	}
+}
+
+impl OutputSink for NullValues<'_> {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		self.sink.write_header(columns)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		if self.token.is_empty() {
+			return self.sink.write_row(values);
+		}
+
+		let values: Vec<_> = values
+			.iter()
+			.map(|v| if v.is_empty() { self.token } else { v })
+			.collect();
+		self.sink.write_row(&values)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.sink.finish()
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.sink.add_table(name)
+	}
+	fn write_comment(&mut self, line: &str) -> io::Result<bool> {
+		self.sink.write_comment(line)
+	}
+}
+
+// formats with named columns need them to be unique, so repeated headers
+// get a numbered suffix and empty ones are named after their position
+#[cfg(any(feature = "parquet", feature = "sqlite"))]
+fn unique_columns(columns: &[String]) -> Vec<String> {
+	let mut unique: Vec<String> = Vec::with_capacity(columns.len());
+	for (i, column) in columns.iter().enumerate() {
+		let base = if column.is_empty() {
+			format!("column_{}", i + 1)
+		} else {
+			column.clone()
+		};
+
+		let mut name = base.clone();
+		let mut n = 1;
+		while unique.contains(&name) {
+			n += 1;
+			name = format!("{}_{}", base, n);
+		}
+
+		unique.push(name);
+	}
+
+	unique
+}
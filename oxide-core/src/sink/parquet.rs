@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::io;
+use std::ops::Not;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+
+use super::{OutputSink, unique_columns};
+
+// rows buffered per column before they're written out as a row group
+const ROW_GROUP_SIZE: usize = 64 * 1024;
+
+/// Parquet with every column stored as an optional utf8 string. Empty
+/// values are written as nulls.
+pub struct ParquetSink {
+	file: Option<File>,
+	writer: Option<SerializedFileWriter<File>>,
+	columns: Vec<Vec<Option<String>>>,
+	buffered: usize,
+}
+
+impl ParquetSink {
+	pub fn create(path: &Path) -> io::Result<ParquetSink> {
+		Ok(ParquetSink {
+			file: Some(File::create(path)?),
+			writer: None,
+			columns: Vec::new(),
+			buffered: 0,
+		})
+	}
+
+	fn write_row_group(&mut self) -> io::Result<()> {
+		let Some(writer) = self.writer.as_mut() else {
+			return Ok(());
+		};
+
+		if self.buffered == 0 {
+			return Ok(());
+		}
+
+		let mut row_group = writer.next_row_group().map_err(io::Error::other)?;
+		for column in &mut self.columns {
+			let Some(mut column_writer) = row_group.next_column().map_err(io::Error::other)? else {
+				break;
+			};
+
+			let def_levels: Vec<i16> = column.iter().map(|v| v.is_some() as i16).collect();
+			let values: Vec<ByteArray> = column
+				.drain(..)
+				.flatten()
+				.map(|v| ByteArray::from(v.into_bytes()))
+				.collect();
+			column_writer
+				.typed::<ByteArrayType>()
+				.write_batch(&values, Some(&def_levels), None)
+				.map_err(io::Error::other)?;
+			column_writer.close().map_err(io::Error::other)?;
+		}
+
+		row_group.close().map_err(io::Error::other)?;
+		self.buffered = 0;
+		Ok(())
+	}
+}
+
+impl OutputSink for ParquetSink {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		let fields = unique_columns(columns)
+			.iter()
+			.map(|name| {
+				Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+					.with_repetition(Repetition::OPTIONAL)
+					.with_logical_type(Some(LogicalType::String))
+					.build()
+					.map(Arc::new)
+			})
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(io::Error::other)?;
+		let schema = Type::group_type_builder("report")
+			.with_fields(fields)
+			.build()
+			.map_err(io::Error::other)?;
+
+		let file = self
+			.file
+			.take()
+			.ok_or_else(|| io::Error::other("parquet header written twice"))?;
+		let properties = Arc::new(WriterProperties::builder().build());
+		self.writer = Some(
+			SerializedFileWriter::new(file, Arc::new(schema), properties).map_err(io::Error::other)?,
+		);
+		self.columns = vec![Vec::new(); columns.len()];
+		Ok(())
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		for (column, value) in self.columns.iter_mut().zip(values) {
+			column.push(value.is_empty().not().then(|| value.to_string()));
+		}
+
+		self.buffered += 1;
+		if self.buffered >= ROW_GROUP_SIZE {
+			self.write_row_group()?;
+		}
+
+		Ok(())
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.write_row_group()?;
+		if let Some(writer) = self.writer.take() {
+			writer.close().map_err(io::Error::other)?;
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::ops::Not;
+use std::path::Path;
+
+use rusqlite::{Connection, params_from_iter};
+
+use super::{OutputSink, REPORT_TABLE, unique_columns};
+
+/// A sqlite database holding a `report` table of text columns, and a table
+/// of its own for any extra table. Empty values are written as nulls.
+pub struct SqliteSink {
+	connection: Connection,
+	table: String,
+	insert: String,
+}
+
+impl SqliteSink {
+	pub fn create(path: &Path) -> io::Result<SqliteSink> {
+		if std::fs::exists(path)? {
+			std::fs::remove_file(path)?;
+		}
+
+		let connection = Connection::open(path).map_err(io::Error::other)?;
+		// everything goes into one transaction, committing every row
+		// separately is orders of magnitude slower
+		connection
+			.execute_batch("BEGIN")
+			.map_err(io::Error::other)?;
+		Ok(SqliteSink {
+			connection,
+			table: REPORT_TABLE.to_string(),
+			insert: String::new(),
+		})
+	}
+}
+
+fn quote_identifier(name: &str) -> String {
+	format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl OutputSink for SqliteSink {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		let columns = unique_columns(columns);
+		let definitions: Vec<_> = columns
+			.iter()
+			.map(|c| format!("{} TEXT", quote_identifier(c)))
+			.collect();
+		self
+			.connection
+			.execute(
+				&format!(
+					"CREATE TABLE {} ({})",
+					quote_identifier(&self.table),
+					definitions.join(", ")
+				),
+				(),
+			)
+			.map_err(io::Error::other)?;
+
+		let placeholders = vec!["?"; columns.len()];
+		self.insert = format!(
+			"INSERT INTO {} VALUES ({})",
+			quote_identifier(&self.table),
+			placeholders.join(", ")
+		);
+		Ok(())
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		let values = values
+			.iter()
+			.map(|v| Some(*v).filter(|v| v.is_empty().not()));
+		self
+			.connection
+			.prepare_cached(&self.insert)
+			.and_then(|mut statement| statement.execute(params_from_iter(values)))
+			.map_err(io::Error::other)?;
+		Ok(())
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self
+			.connection
+			.execute_batch("COMMIT")
+			.map_err(io::Error::other)
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.table = name.to_string();
+		Ok(true)
+	}
+}
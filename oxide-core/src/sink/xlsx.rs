@@ -0,0 +1,194 @@
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+use super::OutputSink;
+use crate::number_format::NumberFormat;
+
+// the most rows a single excel worksheet can hold
+const MAX_ROWS: u32 = 1_048_576;
+// widest a column is sized to, in characters, so a long comment doesn't
+// push the rest off the screen
+const MAX_WIDTH: usize = 60;
+
+/// An excel workbook with the report on its first worksheet, and any extra
+/// tables on worksheets of their own. Values that parse as numbers are
+/// written as numbers, so they can be summed without converting them first,
+/// unless they have leading zeros like ids, and dates are written as dates
+/// shown like they were exported. Every worksheet's header row is frozen
+/// with a filter on it, and its columns are as wide as their values.
+pub struct XlsxSink {
+	path: PathBuf,
+	workbook: Workbook,
+	sheet: usize,
+	row: u32,
+	// number format of every column of the current table, for the rows
+	// below its header
+	formats: Vec<Option<Format>>,
+	// characters of the longest value of every column of the current table
+	widths: Vec<usize>,
+	// formats of DD/MM/YYYY and YYYY-MM-DD dates
+	date_formats: [Format; 2],
+}
+
+impl XlsxSink {
+	pub fn create(path: &Path) -> io::Result<XlsxSink> {
+		// fail early if the file can't be written, instead of after parsing
+		std::fs::File::create(path)?;
+		let mut workbook = Workbook::new();
+		// rows are flushed to a temp file as they're written, rather than
+		// the whole sheet being kept in memory until saved
+		workbook.add_worksheet_with_constant_memory();
+		Ok(XlsxSink {
+			path: path.to_path_buf(),
+			workbook,
+			sheet: 0,
+			row: 0,
+			formats: Vec::new(),
+			widths: Vec::new(),
+			date_formats: [
+				Format::new().set_num_format("dd/mm/yyyy"),
+				Format::new().set_num_format("yyyy-mm-dd"),
+			],
+		})
+	}
+
+	// freezes the current table's header and puts a filter on it, and sizes
+	// its columns, once all of its rows are written
+	fn finish_table(&mut self) -> io::Result<()> {
+		if self.widths.is_empty() {
+			return Ok(());
+		}
+
+		let worksheet = self
+			.workbook
+			.worksheet_from_index(self.sheet)
+			.map_err(io::Error::other)?;
+		let last_col = (self.widths.len() - 1) as u16;
+		worksheet
+			.set_freeze_panes(1, 0)
+			.and_then(|worksheet| worksheet.autofilter(0, 0, self.row.saturating_sub(1), last_col))
+			.map_err(io::Error::other)?;
+		for (col, width) in self.widths.iter().enumerate() {
+			// room for the filter's arrow
+			let width = width.min(&MAX_WIDTH) + 3;
+			worksheet
+				.set_column_width(col as u16, width as f64)
+				.map_err(io::Error::other)?;
+		}
+
+		self.widths.clear();
+		Ok(())
+	}
+
+	fn write_cells<T: AsRef<str>>(&mut self, values: &[T], formatted: bool) -> io::Result<()> {
+		if self.row >= MAX_ROWS {
+			return Err(io::Error::other(format!(
+				"more than {} rows don't fit into an excel worksheet",
+				MAX_ROWS
+			)));
+		}
+
+		let worksheet = self
+			.workbook
+			.worksheet_from_index(self.sheet)
+			.map_err(io::Error::other)?;
+		if self.widths.len() < values.len() {
+			self.widths.resize(values.len(), 0);
+		}
+		for (i, value) in values.iter().enumerate() {
+			let value = value.as_ref();
+			let col = i as u16;
+			self.widths[i] = self.widths[i].max(value.chars().count());
+			let format = self
+				.formats
+				.get(i)
+				.filter(|_| formatted)
+				.and_then(Option::as_ref);
+			match format {
+				Some(format) => match NumberFormat::number(value) {
+					Some(number) => worksheet.write_number_with_format(self.row, col, number, format),
+					None => worksheet.write_string(self.row, col, value),
+				},
+				None => match (
+					value.parse::<f64>(),
+					parse_date(value).filter(|_| formatted),
+				) {
+					(Ok(number), _) if number.is_finite() && leading_zero(value).not() => {
+						worksheet.write_number(self.row, col, number)
+					}
+					(_, Some((date, style))) => {
+						let format = &self.date_formats[style];
+						worksheet.write_datetime_with_format(self.row, col, date, format)
+					}
+					_ => worksheet.write_string(self.row, col, value),
+				},
+			}
+			.map_err(io::Error::other)?;
+		}
+
+		self.row += 1;
+		Ok(())
+	}
+}
+
+impl OutputSink for XlsxSink {
+	fn write_header(&mut self, columns: &[String]) -> io::Result<()> {
+		self.write_cells(columns, false)
+	}
+
+	fn write_row(&mut self, values: &[&str]) -> io::Result<()> {
+		self.write_cells(values, true)
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		self.finish_table()?;
+		self.workbook.save(&self.path).map_err(io::Error::other)
+	}
+
+	fn add_table(&mut self, name: &str) -> io::Result<bool> {
+		self.finish_table()?;
+		self
+			.workbook
+			.add_worksheet_with_constant_memory()
+			.set_name(name)
+			.map_err(io::Error::other)?;
+		self.sheet += 1;
+		self.row = 0;
+		self.formats.clear();
+		Ok(true)
+	}
+
+	fn number_formats(&mut self, formats: &[Option<&NumberFormat>]) -> bool {
+		self.formats = formats
+			.iter()
+			.map(|format| format.map(|format| Format::new().set_num_format(format.as_str())))
+			.collect();
+		true
+	}
+}
+
+// a DD/MM/YYYY or YYYY-MM-DD date as excel's, with the index of the
+// format it's shown in
+fn parse_date(value: &str) -> Option<(ExcelDateTime, usize)> {
+	if value.len() != 10 {
+		return None;
+	}
+
+	let (date, style) = match NaiveDate::parse_from_str(value, "%d/%m/%Y") {
+		Ok(date) => (date, 0),
+		Err(_) => (NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?, 1),
+	};
+	let year = u16::try_from(date.year()).ok()?;
+	let date = ExcelDateTime::from_ymd(year, date.month() as u8, date.day() as u8).ok()?;
+	Some((date, style))
+}
+
+// numbers like 007 are ids, which excel would show as 7
+fn leading_zero(value: &str) -> bool {
+	let digits = value.trim_start_matches('-');
+	digits.len() > 1 && digits.starts_with('0') && digits[1..].starts_with('.').not()
+}
@@ -0,0 +1,64 @@
+use std::cmp::Ordering;
+use std::ops::Not;
+
+use crate::coerce;
+
+/// A column the exported rows are sorted by, written as its name, or with a
+/// leading `-` to sort it from the highest value down.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SortKey {
+	pub column: String,
+	pub descending: bool,
+}
+
+impl SortKey {
+	pub fn parse(key: &str) -> Result<SortKey, String> {
+		let key = key.trim();
+		let (column, descending) = match key.strip_prefix('-') {
+			Some(column) => (column.trim(), true),
+			None => (key, false),
+		};
+		if column.is_empty() {
+			return Err("sort keys need a column name".to_string());
+		}
+
+		Ok(SortKey {
+			column: column.to_string(),
+			descending,
+		})
+	}
+
+	/// Parses keys separated by commas, like `date,-meters`.
+	pub fn parse_list(keys: &str) -> Result<Vec<SortKey>, String> {
+		keys
+			.split(',')
+			.filter(|key| key.trim().is_empty().not())
+			.map(SortKey::parse)
+			.collect()
+	}
+
+	/// Orders numbers by value and dates by day, anything else as text.
+	/// Empty values go last in either direction.
+	pub(crate) fn compare(&self, a: &str, b: &str) -> Ordering {
+		let (a, b) = (a.trim(), b.trim());
+		match (a.is_empty(), b.is_empty()) {
+			(true, true) => Ordering::Equal,
+			(true, false) => Ordering::Greater,
+			(false, true) => Ordering::Less,
+			(false, false) if self.descending => compare_values(b, a),
+			(false, false) => compare_values(a, b),
+		}
+	}
+}
+
+pub(crate) fn compare_values(a: &str, b: &str) -> Ordering {
+	if let (Ok(a), Ok(b)) = (a.parse::<f64>(), b.parse::<f64>()) {
+		return a.total_cmp(&b);
+	}
+
+	if let (Some(a), Some(b)) = (coerce::parse_date(a), coerce::parse_date(b)) {
+		return a.cmp(&b);
+	}
+
+	a.cmp(b)
+}
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "remote")]
+use std::sync::Mutex;
+#[cfg(feature = "remote")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "remote")]
+use std::thread;
+use std::time::SystemTime;
+
+use calamine::{Reader, Xlsx, XlsxError};
+use zip::ZipArchive;
+
+use crate::error::OxideError;
+use crate::rich_text::flatten_rich_text;
+use crate::workbook::{WorkbookFile, list_workbooks};
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// An opened workbook, its sheets are listed with `sheet_names` and their
+/// rows read with `worksheet_range` or streamed with `worksheet_cells_reader`.
+pub type Workbook = Xlsx<Box<dyn ReadSeek>>;
+
+/// Somewhere workbooks are read from. Files are identified by the path
+/// returned from [`list`](SpreadsheetSource::list), which only has to mean
+/// something to the source itself, but its file stem must still name the
+/// report sheet.
+pub trait SpreadsheetSource: Send + Sync {
+	/// Every workbook the source holds, sorted by path.
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError>;
+
+	/// The workbook's file as it's stored, to open or hash.
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError>;
+
+	/// The workbook's own file on disk, for sources whose workbooks can be
+	/// moved once they're exported.
+	fn local_path(&self, _path: &Path) -> Option<PathBuf> {
+		None
+	}
+
+	fn open(&self, path: &Path) -> Result<Workbook, OxideError> {
+		open_xlsx(path, self.reader(path)?)
+	}
+}
+
+fn open_xlsx(path: &Path, reader: Box<dyn ReadSeek>) -> Result<Workbook, OxideError> {
+	let reader = flatten_rich_text(reader).map_err(|e| OxideError::io(path, e))?;
+	Xlsx::new(reader).map_err(|source: XlsxError| OxideError::WorkbookOpen {
+		file: path.to_path_buf(),
+		source,
+	})
+}
+
+/// The `.xlsx` files directly inside a local (or mounted) directory.
+pub struct LocalDir {
+	dir: PathBuf,
+}
+
+impl LocalDir {
+	pub fn new(dir: impl Into<PathBuf>) -> LocalDir {
+		LocalDir { dir: dir.into() }
+	}
+}
+
+impl SpreadsheetSource for LocalDir {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		list_workbooks(&self.dir).map_err(|e| OxideError::io(&self.dir, e))
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let file = File::open(path).map_err(|e| OxideError::io(path, e))?;
+		Ok(Box::new(BufReader::new(file)))
+	}
+
+	fn local_path(&self, path: &Path) -> Option<PathBuf> {
+		Some(path.to_path_buf())
+	}
+}
+
+/// The `.xlsx` entries of a zip archive, as reports are often mailed around
+/// zipped up.
+pub struct ZipSource {
+	archive: PathBuf,
+}
+
+impl ZipSource {
+	pub fn new(archive: impl Into<PathBuf>) -> ZipSource {
+		ZipSource {
+			archive: archive.into(),
+		}
+	}
+
+	fn open_archive(&self) -> Result<ZipArchive<BufReader<File>>, OxideError> {
+		let file = File::open(&self.archive).map_err(|e| OxideError::io(&self.archive, e))?;
+		ZipArchive::new(BufReader::new(file))
+			.map_err(|e| OxideError::io(&self.archive, io::Error::other(e)))
+	}
+}
+
+impl SpreadsheetSource for ZipSource {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		// entry timestamps have no time zone, so every entry takes the
+		// archive's own modified time instead
+		let modified = std::fs::metadata(&self.archive)
+			.and_then(|m| m.modified())
+			.unwrap_or(SystemTime::UNIX_EPOCH);
+
+		let archive = self.open_archive()?;
+		let mut files: Vec<_> = archive
+			.file_names()
+			.filter(|name| name.ends_with(".xlsx"))
+			.map(|name| WorkbookFile {
+				path: PathBuf::from(name),
+				modified,
+				// the archive is only read once it's complete, so its
+				// entries are too
+				size: None,
+			})
+			.collect();
+
+		files.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(files)
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let entry_error = |e| OxideError::io(&self.archive.join(path), e);
+		let mut archive = self.open_archive()?;
+		let mut entry = archive
+			.by_name(&path.to_string_lossy())
+			.map_err(|e| entry_error(io::Error::other(e)))?;
+
+		// entries can't be seeked, which xlsx needs, so read them up front
+		let mut data = Vec::with_capacity(entry.size() as usize);
+		entry.read_to_end(&mut data).map_err(entry_error)?;
+		Ok(Box::new(Cursor::new(data)))
+	}
+}
+
+/// Workbooks already loaded into memory, such as uploads or test fixtures.
+/// They have no modified time, so a resumed export reads them again.
+#[derive(Default)]
+pub struct MemorySource {
+	files: Vec<(PathBuf, Arc<[u8]>)>,
+}
+
+impl MemorySource {
+	pub fn new() -> MemorySource {
+		MemorySource::default()
+	}
+
+	/// Adds a workbook, `path`'s file stem names its report sheet like it
+	/// would for a file on disk.
+	pub fn add(&mut self, path: impl Into<PathBuf>, data: impl Into<Arc<[u8]>>) -> &mut MemorySource {
+		self.files.push((path.into(), data.into()));
+		self
+	}
+}
+
+impl SpreadsheetSource for MemorySource {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		let mut files: Vec<_> = self
+			.files
+			.iter()
+			.map(|(path, data)| WorkbookFile {
+				path: path.clone(),
+				modified: SystemTime::UNIX_EPOCH,
+				size: Some(data.len() as u64),
+			})
+			.collect();
+
+		files.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(files)
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let Some((_, data)) = self.files.iter().find(|(p, _)| p == path) else {
+			return Err(OxideError::io(
+				path,
+				io::Error::new(io::ErrorKind::NotFound, "no such workbook"),
+			));
+		};
+
+		Ok(Box::new(Cursor::new(data.clone())))
+	}
+}
+
+// head requests in flight at once while listing a remote source
+#[cfg(feature = "remote")]
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// Workbooks served over http(s), given as a list of urls.
+#[cfg(feature = "remote")]
+pub struct RemoteSource {
+	urls: Vec<String>,
+}
+
+#[cfg(feature = "remote")]
+impl RemoteSource {
+	pub fn new(urls: Vec<String>) -> RemoteSource {
+		RemoteSource { urls }
+	}
+}
+
+#[cfg(feature = "remote")]
+impl SpreadsheetSource for RemoteSource {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		// one request after another would wait out a round trip per url, so
+		// they're sent from a few threads at once like workbooks are parsed
+		let next_url = AtomicUsize::new(0);
+		let results = Mutex::new(Vec::with_capacity(self.urls.len()));
+		thread::scope(|scope| {
+			for _ in 0..MAX_CONCURRENT_REQUESTS.min(self.urls.len()) {
+				scope.spawn(|| {
+					loop {
+						let idx = next_url.fetch_add(1, Ordering::Relaxed);
+						let Some(url) = self.urls.get(idx) else {
+							break;
+						};
+
+						let file = remote_file(url);
+						if let Ok(mut results) = results.lock() {
+							results.push((idx, file));
+						}
+					}
+				});
+			}
+		});
+
+		// the first url that failed is reported, whichever finished first
+		let mut results = results.into_inner().unwrap_or_default();
+		results.sort_by_key(|(idx, _)| *idx);
+		let mut files = results
+			.into_iter()
+			.map(|(_, file)| file)
+			.collect::<Result<Vec<_>, _>>()?;
+		files.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(files)
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let fetch_error = |e| OxideError::io(path, io::Error::other(e));
+		let data = ureq::get(path.to_string_lossy().as_ref())
+			.call()
+			.map_err(fetch_error)?
+			.body_mut()
+			.with_config()
+			// workbooks easily outgrow the default 10mb limit
+			.limit(u64::MAX)
+			.read_to_vec()
+			.map_err(fetch_error)?;
+		Ok(Box::new(Cursor::new(data)))
+	}
+}
+
+#[cfg(feature = "remote")]
+fn remote_file(url: &str) -> Result<WorkbookFile, OxideError> {
+	let path = PathBuf::from(url);
+	let response = ureq::head(url)
+		.call()
+		.map_err(|e| OxideError::io(&path, io::Error::other(e)))?;
+	// servers that don't report a modified time never match a checkpoint,
+	// so their files are always fetched again
+	let modified = response
+		.headers()
+		.get("last-modified")
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| httpdate::parse_http_date(v).ok())
+		.unwrap_or(SystemTime::UNIX_EPOCH);
+	let size = response
+		.headers()
+		.get("content-length")
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| v.parse().ok());
+	Ok(WorkbookFile {
+		path,
+		modified,
+		size,
+	})
+}
@@ -0,0 +1,138 @@
+use std::ops::Not;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Splits the values of a column into several, like an interval written
+/// `12.5–15.0` into its from and to. Either `delimiter` or `pattern` is set.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct ColumnSplit {
+	/// Column to split, named as it is after renames.
+	pub column: String,
+	/// Names of the columns the parts go to, in place of the column.
+	pub into: Vec<String>,
+	/// Text between the parts, the last column gets whatever's left over.
+	pub delimiter: Option<String>,
+	/// Regex with a capture group for every column, matching the whole value.
+	pub pattern: Option<String>,
+	/// Keeps the column itself ahead of the parts.
+	pub keep: bool,
+}
+
+impl ColumnSplit {
+	/// Checks the split can be applied, compiling its pattern.
+	pub(crate) fn validate(&self) -> Result<Option<Regex>, String> {
+		if self.into.is_empty() {
+			return Err(format!(
+				"the split of {} has no columns to split into",
+				self.column
+			));
+		}
+
+		match (&self.delimiter, &self.pattern) {
+			(Some(delimiter), None) if delimiter.is_empty().not() => Ok(None),
+			(None, Some(pattern)) => {
+				// anchored, so the pattern has to match the whole value
+				let regex = Regex::new(&format!("^(?:{})$", pattern))
+					.map_err(|e| format!("invalid pattern for the split of {}: {}", self.column, e))?;
+				if regex.captures_len() - 1 != self.into.len() {
+					return Err(format!(
+						"the pattern for the split of {} has {} groups for {} columns",
+						self.column,
+						regex.captures_len() - 1,
+						self.into.len()
+					));
+				}
+
+				Ok(Some(regex))
+			}
+			_ => Err(format!(
+				"the split of {} needs either a delimiter or a pattern",
+				self.column
+			)),
+		}
+	}
+
+	// the value's parts, none when the pattern doesn't match it
+	fn parts(&self, regex: Option<&Regex>, value: &str) -> Option<Vec<String>> {
+		let value = value.trim();
+		let mut parts: Vec<_> = match (regex, &self.delimiter) {
+			(Some(regex), _) => {
+				let captures = regex.captures(value)?;
+				captures
+					.iter()
+					.skip(1)
+					.map(|part| part.map_or("", |p| p.as_str()).trim().to_string())
+					.collect()
+			}
+			(None, Some(delimiter)) => value
+				.splitn(self.into.len(), delimiter.as_str())
+				.map(|part| part.trim().to_string())
+				.collect(),
+			(None, None) => vec![value.to_string()],
+		};
+
+		parts.resize(self.into.len(), String::new());
+		Some(parts)
+	}
+}
+
+/// Applies every split to the table, keeping the values a pattern doesn't
+/// match in the first of the columns they'd go to. Returns a warning for
+/// each split that had any of those or whose column isn't there.
+pub(crate) fn split_columns(
+	headers: &mut Vec<String>,
+	rows: &mut [Vec<String>],
+	splits: &[ColumnSplit],
+) -> Vec<String> {
+	let mut warnings = Vec::new();
+	for split in splits {
+		// templates are validated when they're loaded
+		let Ok(regex) = split.validate() else {
+			continue;
+		};
+
+		let Some(col) = headers.iter().position(|h| *h == split.column) else {
+			warnings.push(format!("split: there's no {} column", split.column));
+			continue;
+		};
+
+		// the parts replace the column, or go right after it when it's kept
+		let replaced = match split.keep {
+			true => col + 1..col + 1,
+			false => col..col + 1,
+		};
+
+		// rows are numbered counting from the first data row
+		let mut unmatched = Vec::new();
+		for (row_idx, row) in rows.iter_mut().enumerate() {
+			row.resize(row.len().max(headers.len()), String::new());
+			let value = row[col].clone();
+			let parts = match value.trim().is_empty() {
+				true => vec![String::new(); split.into.len()],
+				false => split.parts(regex.as_ref(), &value).unwrap_or_else(|| {
+					unmatched.push((row_idx + 1, value.clone()));
+					let mut parts = vec![String::new(); split.into.len()];
+					parts[0] = value.clone();
+					parts
+				}),
+			};
+
+			row.splice(replaced.clone(), parts);
+		}
+
+		headers.splice(replaced, split.into.iter().cloned());
+		if let Some((row, value)) = unmatched.first() {
+			warnings.push(format!(
+				"{} values in {} don't match its split, the first in row {}: {}",
+				unmatched.len(),
+				split.column,
+				row,
+				value
+			));
+		}
+	}
+
+	warnings
+}
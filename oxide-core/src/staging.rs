@@ -0,0 +1,364 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// roughly how much row data is kept in memory before the buffer is
+// flushed to disk, the default of ExportOptions::memory_limit
+pub(crate) const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+static STAGE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// holds rows in insertion order, spilling them to a temporary file once
+// the in-memory buffer grows past the configured limit
+pub(crate) struct RowStage {
+	memory_limit: usize,
+	memory_used: usize,
+	rows: Vec<Vec<String>>,
+	spill_path: PathBuf,
+	spill: Option<BufWriter<File>>,
+	spilled_rows: usize,
+}
+
+impl RowStage {
+	pub fn new(memory_limit: usize) -> RowStage {
+		let id = STAGE_COUNTER.fetch_add(1, Ordering::Relaxed);
+		let spill_path = env::temp_dir().join(format!("oxide-stage-{}-{}.tmp", process::id(), id));
+
+		RowStage {
+			memory_limit,
+			memory_used: 0,
+			rows: Vec::new(),
+			spill_path,
+			spill: None,
+			spilled_rows: 0,
+		}
+	}
+
+	pub fn push(&mut self, row: Vec<String>) -> io::Result<()> {
+		self.memory_used += row.iter().map(|f| f.len()).sum::<usize>() + row.len();
+		self.rows.push(row);
+		if self.memory_used > self.memory_limit {
+			self.spill()?;
+		}
+
+		Ok(())
+	}
+
+	// yields all staged rows in the order they were pushed
+	pub fn drain(mut self) -> io::Result<StagedRows> {
+		let spilled = match self.spill.take() {
+			Some(mut writer) => {
+				writer.flush()?;
+				Some(BufReader::new(File::open(&self.spill_path)?))
+			}
+			None => None,
+		};
+
+		Ok(StagedRows {
+			spilled,
+			rows: std::mem::take(&mut self.rows).into_iter(),
+			_stage: self,
+		})
+	}
+
+	// yields all staged rows sorted by `compare`, rows comparing equal stay in
+	// the order they were pushed. rows past the memory limit are sorted in
+	// runs written to temporary files, which are merged as they're read
+	pub fn drain_sorted<F>(self, compare: F) -> io::Result<SortedRows<F>>
+	where
+		F: Fn(&[String], &[String]) -> CmpOrdering,
+	{
+		let memory_limit = self.memory_limit;
+		let mut runs = Vec::new();
+		let mut chunk = Vec::new();
+		let mut memory_used = 0;
+		for row in self.drain()? {
+			let row = row?;
+			memory_used += row.iter().map(|f| f.len()).sum::<usize>() + row.len();
+			chunk.push(row);
+			if memory_used > memory_limit {
+				chunk.sort_by(|a, b| compare(a, b));
+				runs.push(Run::spill(std::mem::take(&mut chunk))?);
+				memory_used = 0;
+			}
+		}
+
+		chunk.sort_by(|a, b| compare(a, b));
+		runs.push(Run::memory(chunk));
+		for run in &mut runs {
+			run.advance()?;
+		}
+
+		Ok(SortedRows { runs, compare })
+	}
+
+	fn spill(&mut self) -> io::Result<()> {
+		let writer = match &mut self.spill {
+			Some(writer) => writer,
+			None => self
+				.spill
+				.insert(BufWriter::new(File::create(&self.spill_path)?)),
+		};
+
+		for row in self.rows.drain(..) {
+			writeln!(writer, "{}", encode_row(&row))?;
+			self.spilled_rows += 1;
+		}
+
+		self.memory_used = 0;
+		Ok(())
+	}
+}
+
+impl Drop for RowStage {
+	fn drop(&mut self) {
+		self.spill = None;
+		if self.spilled_rows > 0 {
+			let _ = fs::remove_file(&self.spill_path);
+		}
+	}
+}
+
+pub(crate) struct StagedRows {
+	spilled: Option<BufReader<File>>,
+	rows: std::vec::IntoIter<Vec<String>>,
+	// keeps the spill file alive until iteration is done
+	_stage: RowStage,
+}
+
+impl Iterator for StagedRows {
+	type Item = io::Result<Vec<String>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(reader) = &mut self.spilled {
+			let mut line = String::new();
+			match reader.read_line(&mut line) {
+				Ok(0) => self.spilled = None,
+				Ok(_) => return Some(Ok(decode_row(line.trim_end_matches('\n')))),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+
+		self.rows.next().map(Ok)
+	}
+}
+
+// sorted rows, read back from a temporary file or kept in memory
+struct Run {
+	file: Option<(PathBuf, BufReader<File>)>,
+	rows: std::vec::IntoIter<Vec<String>>,
+	head: Option<Vec<String>>,
+}
+
+impl Run {
+	fn spill(rows: Vec<Vec<String>>) -> io::Result<Run> {
+		let id = STAGE_COUNTER.fetch_add(1, Ordering::Relaxed);
+		let path = env::temp_dir().join(format!("oxide-sort-{}-{}.tmp", process::id(), id));
+		let mut writer = BufWriter::new(File::create(&path)?);
+		for row in &rows {
+			writeln!(writer, "{}", encode_row(row))?;
+		}
+
+		writer.flush()?;
+		let reader = BufReader::new(File::open(&path)?);
+		Ok(Run {
+			file: Some((path, reader)),
+			rows: Vec::new().into_iter(),
+			head: None,
+		})
+	}
+
+	fn memory(rows: Vec<Vec<String>>) -> Run {
+		Run {
+			file: None,
+			rows: rows.into_iter(),
+			head: None,
+		}
+	}
+
+	// moves the next row into head, none once the run is done
+	fn advance(&mut self) -> io::Result<()> {
+		self.head = match &mut self.file {
+			Some((_, reader)) => {
+				let mut line = String::new();
+				match reader.read_line(&mut line)? {
+					0 => None,
+					_ => Some(decode_row(line.trim_end_matches('\n'))),
+				}
+			}
+			None => self.rows.next(),
+		};
+
+		Ok(())
+	}
+}
+
+impl Drop for Run {
+	fn drop(&mut self) {
+		if let Some((path, _)) = self.file.take() {
+			let _ = fs::remove_file(path);
+		}
+	}
+}
+
+pub(crate) struct SortedRows<F> {
+	runs: Vec<Run>,
+	compare: F,
+}
+
+impl<F> Iterator for SortedRows<F>
+where
+	F: Fn(&[String], &[String]) -> CmpOrdering,
+{
+	type Item = io::Result<Vec<String>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		// runs are in the order their rows were pushed, so taking the first of
+		// equal rows keeps the sort stable
+		let mut next: Option<usize> = None;
+		for (idx, run) in self.runs.iter().enumerate() {
+			let Some(head) = &run.head else {
+				continue;
+			};
+
+			let before = match next.and_then(|n| self.runs[n].head.as_ref()) {
+				Some(best) => (self.compare)(head, best).is_lt(),
+				None => true,
+			};
+			if before {
+				next = Some(idx);
+			}
+		}
+
+		let run = &mut self.runs[next?];
+		let row = run.head.take();
+		if let Err(e) = run.advance() {
+			return Some(Err(e));
+		}
+
+		row.map(Ok)
+	}
+}
+
+// fields are tab separated with backslash escapes, so a row always
+// occupies exactly one line in the spill file
+pub(crate) fn encode_row(row: &[String]) -> String {
+	let fields: Vec<_> = row
+		.iter()
+		.map(|field| {
+			field
+				.replace('\\', "\\\\")
+				.replace('\t', "\\t")
+				.replace('\n', "\\n")
+				.replace('\r', "\\r")
+		})
+		.collect();
+	fields.join("\t")
+}
+
+pub(crate) fn decode_row(line: &str) -> Vec<String> {
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\t' => fields.push(std::mem::take(&mut field)),
+			'\\' => match chars.next() {
+				Some('t') => field.push('\t'),
+				Some('n') => field.push('\n'),
+				Some('r') => field.push('\r'),
+				Some(other) => field.push(other),
+				None => {}
+			},
+			_ => field.push(c),
+		}
+	}
+
+	fields.push(field);
+	fields
+}
+
+#[cfg(test)]
+mod tests {
+	use std::ops::Not;
+
+	use super::*;
+
+	fn rows(count: usize) -> Vec<Vec<String>> {
+		(0..count)
+			.map(|n| vec![format!("DH-{:02}", n % 7), n.to_string()])
+			.collect()
+	}
+
+	fn temp_files(prefix: &str) -> usize {
+		let prefix = format!("{}-{}-", prefix, process::id());
+		fs::read_dir(env::temp_dir())
+			.unwrap()
+			.flatten()
+			.filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+			.count()
+	}
+
+	// a limit of a few rows, so most of them end up on disk
+	fn stage(rows: &[Vec<String>]) -> RowStage {
+		let mut stage = RowStage::new(40);
+		for row in rows {
+			stage.push(row.clone()).unwrap();
+		}
+
+		stage
+	}
+
+	#[test]
+	fn drains_spilled_rows_in_order() {
+		let rows = rows(100);
+		let stage = stage(&rows);
+		assert!(stage.spilled_rows > 50);
+		assert!(stage.spill_path.exists());
+
+		let spill_path = stage.spill_path.clone();
+		let drained: Vec<_> = stage.drain().unwrap().map(Result::unwrap).collect();
+		assert_eq!(drained, rows);
+		assert!(spill_path.exists().not());
+	}
+
+	#[test]
+	fn sorts_across_spilled_runs() {
+		let rows = rows(100);
+		let compare = |a: &[String], b: &[String]| a[0].cmp(&b[0]);
+		let before = temp_files("oxide-sort");
+		let mut sorted = stage(&rows).drain_sorted(compare).unwrap();
+		// every chunk over the limit is a run of its own on disk
+		assert!(temp_files("oxide-sort") >= before + 10);
+
+		let mut expected = rows.clone();
+		expected.sort_by(|a, b| compare(a, b));
+		let drained: Vec<_> = sorted.by_ref().map(Result::unwrap).collect();
+		assert_eq!(drained, expected);
+		drop(sorted);
+		assert_eq!(temp_files("oxide-sort"), before);
+	}
+
+	#[test]
+	fn round_trips_rows() {
+		let row: Vec<String> = [
+			"plain",
+			"",
+			"tab\there",
+			"two\nlines\r\n",
+			"back\\slash \\t not a tab",
+			"trailing\\",
+			"é ünïcode",
+		]
+		.map(String::from)
+		.to_vec();
+		let line = encode_row(&row);
+		assert!(line.contains('\n').not());
+		assert_eq!(line.split('\t').count(), row.len());
+		assert_eq!(decode_row(&line), row);
+	}
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::ops::Not;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::duplicates::IdenticalFile;
+use crate::header_match::HeaderMatch;
+use crate::merge::MergeCounts;
+
+/// How long a single workbook took to parse and stage.
+#[derive(Clone)]
+pub struct FileTiming {
+	pub file_name: String,
+	// where the workbook is in the source it was read from
+	pub path: PathBuf,
+	pub rows: usize,
+	pub parse: Duration,
+	pub write: Duration,
+}
+
+/// Row counts, timings and warnings collected over an export run.
+#[derive(Default, Clone)]
+pub struct RunSummary {
+	pub files: Vec<FileTiming>,
+	pub output: Duration,
+	pub elapsed: Duration,
+	pub warnings: Vec<String>,
+	// columns that look like a typo of another one
+	pub header_matches: Vec<HeaderMatch>,
+	// copies of workbooks that were read under another name
+	pub identical_files: Vec<IdenticalFile>,
+	// set when the rows were merged into an existing output
+	pub merged: Option<MergeCounts>,
+	// sha256 of the output, set when a manifest was written for it
+	pub output_sha256: Option<String>,
+	// set when a pdf report was written next to the output
+	pub report: Option<PathBuf>,
+}
+
+impl RunSummary {
+	pub fn total_rows(&self) -> usize {
+		self.files.iter().map(|f| f.rows).sum()
+	}
+
+	pub fn rows_per_second(&self) -> f64 {
+		let secs = self.elapsed.as_secs_f64();
+		if secs > 0.0 {
+			self.total_rows() as f64 / secs
+		} else {
+			0.0
+		}
+	}
+}
+
+impl fmt::Display for RunSummary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{} rows from {} files in {:.2}s ({:.0} rows/s)",
+			self.total_rows(),
+			self.files.len(),
+			self.elapsed.as_secs_f64(),
+			self.rows_per_second()
+		)?;
+		writeln!(f, "output written in {:.2}s", self.output.as_secs_f64())?;
+		if let Some(merged) = &self.merged {
+			writeln!(
+				f,
+				"merged into the existing output: {} rows updated, {} added, {} unchanged",
+				merged.updated, merged.added, merged.unchanged
+			)?;
+		}
+
+		for file in &self.files {
+			writeln!(
+				f,
+				"{}: {} rows, parse {:.2}s, write {:.2}s",
+				file.file_name,
+				file.rows,
+				file.parse.as_secs_f64(),
+				file.write.as_secs_f64()
+			)?;
+		}
+
+		if self.identical_files.is_empty().not() {
+			writeln!(f, "\nworkbooks left out as copies of another one:")?;
+			for file in &self.identical_files {
+				writeln!(
+					f,
+					"{} (same as {})",
+					file.path.display(),
+					file.same_as.display()
+				)?;
+			}
+		}
+
+		if self.header_matches.is_empty().not() {
+			writeln!(f, "\ncolumns that look like another one:")?;
+			for header_match in &self.header_matches {
+				writeln!(
+					f,
+					"{} (like {})",
+					header_match.column, header_match.similar_to
+				)?;
+			}
+		}
+
+		if self.warnings.is_empty().not() {
+			writeln!(f, "\nwarnings:")?;
+			for warning in &self.warnings {
+				writeln!(f, "{}", warning)?;
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::coerce::ValueType;
+use crate::error::OxideError;
+use crate::export::{DATA_END_ID, DATA_START_ID, REMARKS_START_ID};
+use crate::fill::{self, VoidedRows};
+use crate::header::HeaderStyle;
+use crate::hyperlink::Hyperlinks;
+use crate::region::Region;
+use crate::section::Section;
+use crate::split::ColumnSplit;
+use crate::unpivot::Unpivot;
+
+/// Where a report's table sits in its worksheet and how its columns are
+/// named. Saved as json so a new spreadsheet layout only needs a new
+/// template, anything left out takes the default layout's value.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReportTemplate {
+	/// Which of the workbook's sheets the report is read from.
+	pub sheets: Sheets,
+	/// Patterns of the names of the sheets read, like `Day *`, with `*` for
+	/// any run of characters and `?` for any one, ignoring case. Every sheet
+	/// matching one is read in the order they're in, rather than the ones
+	/// `sheets` picks.
+	pub sheet_include: Vec<String>,
+	/// Patterns of the names of sheets that are never read, like `Summary`
+	/// or `Rates*`, for tabs that happen to have the same markers.
+	pub sheet_exclude: Vec<String>,
+	/// First-column text of the row holding the table's top header row.
+	pub data_start: String,
+	/// First-column text of the row that ends the table.
+	pub data_end: String,
+	/// First-column text of the row that starts the remarks block.
+	pub remarks_start: String,
+	pub remarks: Remarks,
+	/// Name the workbook defines around the table, like `DailyData`, its
+	/// header rows first. The table is read from there rather than from the
+	/// data_start marker in workbooks defining it on the sheet read.
+	pub data_name: String,
+	/// Name of the excel table holding the report's rows, or `*` for the
+	/// first one on the sheet read. Its columns are named after the table's
+	/// headers and it's read before `data_name` or the data_start marker in
+	/// workbooks that have it.
+	pub data_table: String,
+	/// Cells the table's in, header rows first, like `A7:N45`, counted from
+	/// the sheet's top left corner like excel does. Every workbook is read
+	/// from there rather than from the data_start marker, unless it has the
+	/// `data_table` or `data_name`.
+	pub data_range: String,
+	/// Number of header rows, a header spanning several columns is only
+	/// filled in on the first one, except in the last row.
+	pub header_rows: usize,
+	/// Cell holding the report's date, in A1 notation counted from the
+	/// sheet's first used cell. Empty leaves the date column empty.
+	pub date_cell: String,
+	/// Regex matched against the workbook's file name, like
+	/// `DDR_(\d{4}-\d{2}-\d{2})`, whose first capture group is the report's
+	/// date. Empty only reads the date cell.
+	pub file_date: String,
+	/// Whether the file name's date is only used when the date cell is
+	/// empty, or over it.
+	pub file_date_mode: FileDate,
+	/// Cell holding the report's shift, like `Night Shift` next to the date,
+	/// added to every row in a `shift` column as `day` or `night` when it
+	/// says which. Empty leaves the column out.
+	pub shift_cell: String,
+	/// Cells holding details of the whole report, like the rig number or the
+	/// client, keyed by the column they're added to every row in.
+	pub metadata: BTreeMap<String, String>,
+	/// Renames columns, keyed by the formatted header name.
+	pub columns: BTreeMap<String, String>,
+	/// Types values are coerced to, keyed by the column name after renames.
+	/// Values that don't parse are kept as they are and warned about.
+	pub types: BTreeMap<String, ValueType>,
+	/// Columns split into several, applied in order after renames and before
+	/// types are coerced.
+	pub splits: Vec<ColumnSplit>,
+	/// Columns melted into key and value rows, after splits and before types
+	/// are coerced.
+	pub unpivot: Option<Unpivot>,
+	/// Blocks besides the table read into tables of their own, like the
+	/// consumables used.
+	pub sections: Vec<Section>,
+	/// What's written for cells that link somewhere.
+	pub hyperlinks: Hyperlinks,
+	/// Fill colour of a row's first cell marking it voided, like `FF0000`
+	/// for red. Empty doesn't read the cells' fills.
+	pub voided_fill: String,
+	pub voided_rows: VoidedRows,
+	/// Checks the numbers in the row that ends the table against the sums of
+	/// their columns, warning about the ones that don't add up.
+	pub check_subtotals: bool,
+}
+
+/// A template for the workbooks whose path matches `pattern`, so reports
+/// laid out differently can be read in the same export.
+#[derive(Clone)]
+pub struct TemplateRule {
+	/// `*` stands for any run of characters and `?` for any one. Matched
+	/// against the file name, or the path's last folders as well when it has
+	/// several `/` separated parts, like `acme/*.xlsx`. Case is ignored.
+	pub pattern: String,
+	pub template: ReportTemplate,
+}
+
+impl TemplateRule {
+	pub fn matches(&self, path: &Path) -> bool {
+		let parts: Vec<_> = self
+			.pattern
+			.trim_matches('/')
+			.split('/')
+			.map(|part| part.to_lowercase())
+			.collect();
+		let components: Vec<_> = path
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+			.collect();
+		if components.len() < parts.len() {
+			return false;
+		}
+
+		let tail = &components[components.len() - parts.len()..];
+		parts
+			.iter()
+			.zip(tail)
+			.all(|(part, component)| wildcard_match(part.as_bytes(), component.as_bytes()))
+	}
+}
+
+// matches `*` against any run of characters and `?` against any one
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+	// position of the last star and the text it was matched up to, to
+	// backtrack to when the rest doesn't match
+	let (mut p, mut t) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some(b'*') => {
+				star = Some((p, t));
+				p += 1;
+			}
+			Some(c) if *c == b'?' || *c == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match star {
+				Some((star_p, star_t)) => {
+					p = star_p + 1;
+					t = star_t + 1;
+					star = Some((star_p, star_t + 1));
+				}
+				None => return false,
+			},
+		}
+	}
+
+	pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// The sheets of a workbook reports are read from.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Sheets {
+	/// The sheet named after the workbook.
+	#[default]
+	Named,
+	/// Every sheet named after a day, like `17-05-2024`, `17.05` or `Mon`, in
+	/// the order they're in, for workbooks keeping a whole week with a tab
+	/// per day. Each is read with its own date cell.
+	Days,
+}
+
+impl Sheets {
+	// names of the sheets read out of a workbook called `file_name`
+	pub(crate) fn select(&self, file_name: &str, sheet_names: &[String]) -> Vec<String> {
+		match self {
+			Sheets::Named => vec![file_name.to_string()],
+			Sheets::Days => sheet_names
+				.iter()
+				.filter(|name| is_day_name(name))
+				.cloned()
+				.collect(),
+		}
+	}
+}
+
+const WEEKDAYS: &[&str] = &[
+	"monday",
+	"tuesday",
+	"wednesday",
+	"thursday",
+	"friday",
+	"saturday",
+	"sunday",
+];
+
+// whether a sheet's name has a weekday, like "Mon" or "Tuesday", or a day
+// and month in it, like "17-05", "17.05.2024" or "2024-05-17"
+fn is_day_name(name: &str) -> bool {
+	name
+		.split(|c: char| c.is_whitespace() || c == '_')
+		.filter(|word| word.is_empty().not())
+		.any(|word| {
+			let word = word.to_lowercase();
+			if word.chars().all(|c| c.is_ascii_alphabetic()) {
+				return word.len() >= 3 && WEEKDAYS.iter().any(|day| day.starts_with(&word));
+			}
+
+			let parts: Option<Vec<u32>> = word
+				.split(['-', '.', '/'])
+				.map(|part| part.parse().ok())
+				.collect();
+			let (day, month) = match parts.as_deref() {
+				Some([year, month, day]) if *year > 31 => (*day, *month),
+				Some([day, month]) | Some([day, month, _]) => (*day, *month),
+				_ => return false,
+			};
+			(1..=31).contains(&day) && (1..=12).contains(&month)
+		})
+}
+
+/// When a report's date is taken from its file name.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FileDate {
+	/// Only when the date cell is empty, a date cell saying otherwise is
+	/// warned about.
+	#[default]
+	Fallback,
+	/// Always, for reports whose date cell can't be trusted.
+	Override,
+}
+
+/// What happens to the rows from the remarks marker on.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Remarks {
+	/// Only the marker row is dropped, rows below it are still data.
+	#[default]
+	Skip,
+	/// The table ends at the marker.
+	End,
+	/// The marker row is read as data like any other row.
+	Keep,
+}
+
+impl Default for ReportTemplate {
+	fn default() -> Self {
+		ReportTemplate {
+			sheets: Sheets::Named,
+			sheet_include: Vec::new(),
+			sheet_exclude: Vec::new(),
+			data_start: DATA_START_ID.to_string(),
+			data_end: DATA_END_ID.to_string(),
+			remarks_start: REMARKS_START_ID.to_string(),
+			remarks: Remarks::Skip,
+			data_name: String::new(),
+			data_table: String::new(),
+			data_range: String::new(),
+			header_rows: 2,
+			date_cell: "A2".to_string(),
+			file_date: String::new(),
+			file_date_mode: FileDate::Fallback,
+			shift_cell: String::new(),
+			metadata: BTreeMap::new(),
+			columns: BTreeMap::new(),
+			types: BTreeMap::new(),
+			splits: Vec::new(),
+			unpivot: None,
+			sections: Vec::new(),
+			hyperlinks: Hyperlinks::Text,
+			voided_fill: String::new(),
+			voided_rows: VoidedRows::Skip,
+			check_subtotals: true,
+		}
+	}
+}
+
+impl ReportTemplate {
+	pub fn load(path: &Path) -> Result<ReportTemplate, OxideError> {
+		let invalid = |e: String| OxideError::io(path, io::Error::new(io::ErrorKind::InvalidData, e));
+		let contents = fs::read_to_string(path).map_err(|e| OxideError::io(path, e))?;
+		let template: ReportTemplate =
+			serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+		template.validate().map_err(invalid)?;
+		Ok(template)
+	}
+
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		fs::write(path, serde_json::to_string_pretty(self)?)
+	}
+
+	/// Checks the template describes a table that can be read.
+	pub fn validate(&self) -> Result<(), String> {
+		if self.data_start.is_empty() {
+			return Err("data_start can't be empty".to_string());
+		}
+
+		if self.header_rows == 0 {
+			return Err("header_rows must be at least 1".to_string());
+		}
+
+		if self.data_range.is_empty().not() && Region::parse(&self.data_range).is_none() {
+			return Err(format!(
+				"data_range {} isn't a range of cells like A7:N45",
+				self.data_range
+			));
+		}
+
+		if self.date_cell.is_empty().not() && cell_position(&self.date_cell).is_none() {
+			return Err(format!("date_cell {} isn't a cell like A2", self.date_cell));
+		}
+
+		if self.file_date.is_empty().not() {
+			let regex =
+				Regex::new(&self.file_date).map_err(|e| format!("invalid file_date pattern: {}", e))?;
+			if regex.captures_len() < 2 {
+				return Err("the file_date pattern needs a capture group for the date".to_string());
+			}
+		}
+
+		if self.shift_cell.is_empty().not() && cell_position(&self.shift_cell).is_none() {
+			return Err(format!(
+				"shift_cell {} isn't a cell like B2",
+				self.shift_cell
+			));
+		}
+
+		for (column, cell) in &self.metadata {
+			if cell_position(cell).is_none() {
+				return Err(format!(
+					"metadata cell {} for {} isn't a cell like B1",
+					cell, column
+				));
+			}
+		}
+
+		if self.voided_fill.is_empty().not() && fill::parse_color(&self.voided_fill).is_none() {
+			return Err(format!(
+				"voided_fill {} isn't a colour like FF0000",
+				self.voided_fill
+			));
+		}
+
+		for split in &self.splits {
+			split.validate()?;
+		}
+
+		if let Some(unpivot) = &self.unpivot {
+			unpivot.validate()?;
+		}
+
+		for (i, section) in self.sections.iter().enumerate() {
+			section.validate()?;
+			if self.sections[..i].iter().any(|s| s.name == section.name) {
+				return Err(format!(
+					"there's more than one section called {}",
+					section.name
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Renames the headers spelled like one of a column's aliases, like
+	/// `From (m)` or `Start Depth` for `depth_from`, to the column, keyed by
+	/// the column. Aliases are compared once they're formatted in `style`,
+	/// and the template's own `columns` are kept over them.
+	pub fn add_aliases(&mut self, aliases: &BTreeMap<String, Vec<String>>, style: &HeaderStyle) {
+		for (column, spellings) in aliases {
+			for spelling in spellings {
+				self
+					.columns
+					.entry(style.format(spelling))
+					.or_insert_with(|| column.clone());
+			}
+		}
+	}
+
+	// names of the sheets read out of a workbook called `file_name`
+	pub(crate) fn select_sheets(&self, file_name: &str, sheet_names: &[String]) -> Vec<String> {
+		let matches = |patterns: &[String], name: &str| {
+			let name = name.to_lowercase();
+			patterns
+				.iter()
+				.any(|pattern| wildcard_match(pattern.to_lowercase().as_bytes(), name.as_bytes()))
+		};
+
+		let sheets = match self.sheet_include.is_empty() {
+			true => self.sheets.select(file_name, sheet_names),
+			false => sheet_names
+				.iter()
+				.filter(|name| matches(&self.sheet_include, name))
+				.cloned()
+				.collect(),
+		};
+		sheets
+			.into_iter()
+			.filter(|name| matches(&self.sheet_exclude, name).not())
+			.collect()
+	}
+
+	// whether more than one sheet of a workbook can be read, so warnings
+	// have to say which one they're about
+	pub(crate) fn several_sheets(&self) -> bool {
+		self.sheets == Sheets::Days || self.sheet_include.is_empty().not()
+	}
+
+	// why there's nothing to read when no sheet was selected
+	pub(crate) fn no_sheets(&self) -> &'static str {
+		match (self.sheet_include.is_empty(), self.sheets) {
+			(false, _) => "there's no sheet matching sheet_include",
+			(true, Sheets::Days) => "there's no sheet named after a day",
+			(true, Sheets::Named) => "the sheet named after the workbook is excluded",
+		}
+	}
+
+	// whether voided rows are kept with a voided column
+	pub(crate) fn flags_voided(&self) -> bool {
+		self.voided_fill.is_empty().not() && self.voided_rows == VoidedRows::Flag
+	}
+
+	// row and column of the date cell, none when the template has none
+	pub(crate) fn date_position(&self) -> Option<(usize, usize)> {
+		cell_position(&self.date_cell)
+	}
+
+	// the date captured from a file's name, none without a pattern or when
+	// it doesn't match
+	pub(crate) fn file_date(&self, file_name: &str) -> Option<String> {
+		if self.file_date.is_empty() {
+			return None;
+		}
+
+		let regex = Regex::new(&self.file_date).ok()?;
+		let date = regex.captures(file_name)?.get(1)?.as_str().trim();
+		date.is_empty().not().then(|| date.to_string())
+	}
+
+	pub(crate) fn shift_position(&self) -> Option<(usize, usize)> {
+		cell_position(&self.shift_cell)
+	}
+
+	// columns of the metadata cells with their row and column, in the order
+	// they're added to the rows
+	pub(crate) fn metadata_positions(&self) -> impl Iterator<Item = (&String, (usize, usize))> {
+		self
+			.metadata
+			.iter()
+			.filter_map(|(column, cell)| Some((column, cell_position(cell)?)))
+	}
+}
+
+// zero-based row and column of an A1 style reference
+pub(crate) fn cell_position(cell: &str) -> Option<(usize, usize)> {
+	let split = cell.find(|c: char| c.is_ascii_digit())?;
+	let (letters, digits) = cell.split_at(split);
+	if letters.is_empty() || letters.chars().all(|c| c.is_ascii_alphabetic()).not() {
+		return None;
+	}
+
+	let col = letters.chars().try_fold(0usize, |col, c| {
+		let digit = (c.to_ascii_uppercase() as u8 - b'A') as usize + 1;
+		col.checked_mul(26)?.checked_add(digit)
+	})?;
+	let row: usize = digits.parse().ok()?;
+	Some((row.checked_sub(1)?, col - 1))
+}
@@ -0,0 +1,95 @@
+use std::ops::Not;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Melts a group of columns into key and value rows, like hourly readings
+/// laid out across the sheet into an `hour` and a `reading` column. Every
+/// row becomes one per column in the group.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Unpivot {
+	/// Columns in the group, named as they are after renames.
+	pub columns: Vec<String>,
+	/// Regex matching the names of further columns in the group.
+	pub pattern: Option<String>,
+	/// Column the name of each melted column goes to.
+	pub key: String,
+	/// Column its value goes to.
+	pub value: String,
+	/// Leaves out the rows of empty values.
+	pub skip_empty: bool,
+}
+
+impl Unpivot {
+	/// Checks the unpivot can be applied, compiling its pattern.
+	pub(crate) fn validate(&self) -> Result<Option<Regex>, String> {
+		if self.key.is_empty() || self.value.is_empty() {
+			return Err("unpivot needs a key and a value column".to_string());
+		}
+
+		if self.columns.is_empty() && self.pattern.is_none() {
+			return Err("unpivot needs columns or a pattern to pick them".to_string());
+		}
+
+		self
+			.pattern
+			.as_ref()
+			.map(|pattern| {
+				// anchored, so the pattern has to match the whole name
+				Regex::new(&format!("^(?:{})$", pattern))
+					.map_err(|e| format!("invalid unpivot pattern: {}", e))
+			})
+			.transpose()
+	}
+
+	/// Melts the group in the table, the key and value columns take the place
+	/// of its first column. Returns a warning when no column is in it.
+	pub(crate) fn apply(
+		&self,
+		headers: &mut Vec<String>,
+		rows: &mut Vec<Vec<String>>,
+	) -> Option<String> {
+		// templates are validated when they're loaded
+		let regex = self.validate().ok()?;
+		let in_group = |name: &String| {
+			self.columns.contains(name) || regex.as_ref().is_some_and(|r| r.is_match(name))
+		};
+		let group: Vec<_> = (0..headers.len())
+			.filter(|col| in_group(&headers[*col]))
+			.collect();
+		let Some(&first) = group.first() else {
+			return Some("unpivot: there are no columns to unpivot".to_string());
+		};
+
+		// values outside the group, with the key and value in the group's place
+		let melt = |values: &[String], key: &str, value: &str| -> Vec<String> {
+			let mut melted = Vec::with_capacity(values.len() + 2 - group.len());
+			for (col, v) in values.iter().enumerate() {
+				if col == first {
+					melted.extend([key.to_string(), value.to_string()]);
+				}
+
+				if group.contains(&col).not() {
+					melted.push(v.clone());
+				}
+			}
+
+			melted
+		};
+
+		let mut melted = Vec::with_capacity(rows.len() * group.len());
+		for row in rows.iter() {
+			for col in &group {
+				let value = row.get(*col).map_or("", |v| v.as_str());
+				if self.skip_empty.not() || value.trim().is_empty().not() {
+					melted.push(melt(row, &headers[*col], value));
+				}
+			}
+		}
+
+		*rows = melted;
+		*headers = melt(headers, &self.key, &self.value);
+		None
+	}
+}
@@ -0,0 +1,179 @@
+use std::ops::Not;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Checks on the values of a column, as listed in the settings. Every check
+/// but `required` passes on empty values.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ColumnRule {
+	pub column: String,
+	pub required: bool,
+	// values have to be numbers within the range
+	pub min: Option<f64>,
+	pub max: Option<f64>,
+	// regex the whole value has to match
+	pub pattern: Option<String>,
+	// the only values the column can hold, any value when empty
+	pub allowed: Vec<String>,
+}
+
+/// What happens to rows that break a rule, they end up in the violations
+/// report either way.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum InvalidRows {
+	/// Written like any other row.
+	#[default]
+	Keep,
+	/// Written with the rules they break in a `violations` column.
+	Flag,
+	/// Left out of the export, its totals and summaries.
+	Exclude,
+}
+
+impl InvalidRows {
+	pub const NAMES: &[&str] = &["keep", "flag", "exclude"];
+
+	pub fn from_name(name: &str) -> Option<InvalidRows> {
+		Some(match name {
+			"keep" => InvalidRows::Keep,
+			"flag" => InvalidRows::Flag,
+			"exclude" => InvalidRows::Exclude,
+			_ => return None,
+		})
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			InvalidRows::Keep => "keep",
+			InvalidRows::Flag => "flag",
+			InvalidRows::Exclude => "exclude",
+		}
+	}
+}
+
+/// Rules exported rows are checked against.
+#[derive(Clone)]
+pub struct Validation {
+	checks: Vec<(String, Check)>,
+	pub invalid_rows: InvalidRows,
+}
+
+#[derive(Clone)]
+enum Check {
+	Required,
+	Range(Option<f64>, Option<f64>),
+	Pattern(Regex),
+	Allowed(Vec<String>),
+}
+
+impl Validation {
+	/// Fails on a rule with an invalid pattern.
+	pub fn new(rules: &[ColumnRule], invalid_rows: InvalidRows) -> Result<Validation, String> {
+		let mut checks = Vec::new();
+		for rule in rules {
+			let mut add = |check| checks.push((rule.column.clone(), check));
+			if rule.required {
+				add(Check::Required);
+			}
+
+			if rule.min.is_some() || rule.max.is_some() {
+				add(Check::Range(rule.min, rule.max));
+			}
+
+			if let Some(pattern) = &rule.pattern {
+				// anchored, so the pattern has to match the whole value
+				let regex = Regex::new(&format!("^(?:{})$", pattern))
+					.map_err(|e| format!("invalid pattern for {}: {}", rule.column, e))?;
+				add(Check::Pattern(regex));
+			}
+
+			if rule.allowed.is_empty().not() {
+				add(Check::Allowed(rule.allowed.clone()));
+			}
+		}
+
+		Ok(Validation {
+			checks,
+			invalid_rows,
+		})
+	}
+}
+
+impl Check {
+	// name of the rule the value breaks, if any
+	fn broken_by(&self, value: &str) -> Option<&'static str> {
+		if value.is_empty() {
+			return matches!(self, Check::Required).then_some("required");
+		}
+
+		match self {
+			Check::Required => None,
+			Check::Range(min, max) => {
+				let Ok(number) = value.trim().parse::<f64>() else {
+					return Some("numeric");
+				};
+
+				if min.is_some_and(|min| number < min) {
+					Some("min")
+				} else if max.is_some_and(|max| number > max) {
+					Some("max")
+				} else {
+					None
+				}
+			}
+			Check::Pattern(regex) => regex.is_match(value).not().then_some("pattern"),
+			Check::Allowed(allowed) => allowed
+				.iter()
+				.any(|a| a == value)
+				.not()
+				.then_some("allowed"),
+		}
+	}
+}
+
+/// A rule a row breaks.
+pub(crate) struct Violation<'a, 'r> {
+	pub column: &'a str,
+	pub rule: &'static str,
+	pub value: &'r str,
+}
+
+/// Checks rows against a [`Validation`] as they're written.
+pub(crate) struct Validator<'a> {
+	checks: Vec<(usize, &'a str, &'a Check)>,
+}
+
+impl<'a> Validator<'a> {
+	/// Rules on columns that aren't in `header` are left out, and returned so
+	/// they can be warned about.
+	pub fn new(validation: &'a Validation, header: &[String]) -> (Validator<'a>, Vec<&'a str>) {
+		let mut checks = Vec::new();
+		let mut missing = Vec::new();
+		for (column, check) in &validation.checks {
+			match header.iter().position(|h| h == column) {
+				Some(col) => checks.push((col, column.as_str(), check)),
+				None if missing.contains(&column.as_str()) => {}
+				None => missing.push(column.as_str()),
+			}
+		}
+
+		(Validator { checks }, missing)
+	}
+
+	pub fn check<'r>(&self, row: &[&'r str]) -> Vec<Violation<'a, 'r>> {
+		self
+			.checks
+			.iter()
+			.filter_map(|(col, column, check)| {
+				let value = row.get(*col).copied().unwrap_or_default();
+				check.broken_by(value).map(|rule| Violation {
+					column,
+					rule,
+					value,
+				})
+			})
+			.collect()
+	}
+}
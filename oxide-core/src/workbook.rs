@@ -0,0 +1,86 @@
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use calamine::Reader;
+
+use crate::source::SpreadsheetSource;
+
+/// A workbook found in an input directory.
+pub struct WorkbookFile {
+	pub path: PathBuf,
+	pub modified: SystemTime,
+	// in bytes, none when the source can't tell
+	pub size: Option<u64>,
+}
+
+impl WorkbookFile {
+	/// Whether the workbook looks done being written: it isn't empty and
+	/// wasn't modified in the last `settle_time`. Excel and sync clients like
+	/// onedrive leave a file empty or cut short while they're writing it.
+	pub fn settled(&self, now: SystemTime, settle_time: Duration) -> bool {
+		let recent = now
+			.duration_since(self.modified)
+			.is_ok_and(|age| age < settle_time);
+		self.size != Some(0) && recent.not()
+	}
+}
+
+/// Cheap per-file details shown before an export is run.
+#[derive(Clone)]
+pub struct FileMetadata {
+	pub sheet_names: Vec<String>,
+	pub row_count: usize,
+}
+
+/// Lists every `.xlsx` file directly inside `dir`, sorted by path.
+pub fn list_workbooks(dir: &Path) -> io::Result<Vec<WorkbookFile>> {
+	let mut files = Vec::new();
+	for entry in std::fs::read_dir(dir)? {
+		let entry = entry?;
+		let path = entry.path();
+		if path.extension().and_then(|s| s.to_str()) != Some("xlsx") {
+			continue;
+		}
+
+		let metadata = entry.metadata();
+		let modified = metadata
+			.as_ref()
+			.ok()
+			.and_then(|m| m.modified().ok())
+			.unwrap_or(SystemTime::UNIX_EPOCH);
+		let size = metadata.ok().map(|m| m.len());
+		files.push(WorkbookFile {
+			path,
+			modified,
+			size,
+		});
+	}
+
+	files.sort_by(|a, b| a.path.cmp(&b.path));
+	Ok(files)
+}
+
+/// The sheet a workbook's report is read from, which is named after the file.
+pub fn worksheet_name(path: &Path) -> &str {
+	path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or_default()
+}
+
+/// Reads the sheet names and the row count of the report sheet.
+pub fn read_metadata(source: &dyn SpreadsheetSource, path: &Path) -> Result<FileMetadata, String> {
+	let mut workbook = source.open(path).map_err(|e| e.to_string())?;
+	let sheet_names = workbook.sheet_names();
+	let row_count = workbook
+		.worksheet_range(worksheet_name(path))
+		.map(|r| r.height())
+		.unwrap_or(0);
+
+	Ok(FileMetadata {
+		sheet_names,
+		row_count,
+	})
+}
@@ -0,0 +1,3234 @@
+//! Runs whole exports over fixture workbooks and compares the csv they
+//! produce byte for byte with the one in `tests/fixtures`. Workbooks are
+//! built in memory and the csv is written to a buffer, so nothing touches
+//! the filesystem.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Not;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use oxide_core::{
+	AggregateFn, Aggregation, Archive, Checkpoint, ColumnRule, ColumnSplit, CsvDialect, CsvSink,
+	CumulativeMeters, DateRange, DateTimes, DepthIntervals, DuplicateReports, ExportOptions,
+	ExportPreset, FileDate, HeaderMatch, HeaderStyle, HoleIds, Hyperlinks, IdenticalFile,
+	InvalidRows, LocalDir, Lookup, Measure, MemorySource, MergeCounts, NumberFormat, OpenRetries,
+	OutputSink, OxideError, PREVIEW_ROWS, PdfReport, Plugins, Preview, Provenance, QaCheck, QaOutput,
+	ReadSeek, Remarks, ReportTemplate, RowFilter, RowIds, RunSummary, SchemaSidecar, Section, Sheets,
+	SinkPlugin, SortKey, SpreadsheetSource, StaleExport, TemplateRule, Totals, Unpivot, Validation,
+	ValueType, VoidedRows, WorkbookFile, check_sample, clipboard_table, generate_output,
+	read_preview, remove_stale_spills, write_output,
+};
+use rust_xlsxwriter::{
+	Color, ExcelDateTime, Format, Table, TableColumn, Url, Workbook, Worksheet, XlsxError,
+};
+
+// a report as the site crews fill them in: title, date, the table with its
+// two header rows and the sub-totals and remarks below it
+fn daily_report(sheet: &str, date: &str, rows: &[[&str; 5]]) -> Vec<u8> {
+	workbook(sheet, |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, date)?;
+		write_header(ws, 3)?;
+		let mut row = 5;
+		for values in rows {
+			write_values(ws, row, values)?;
+			row += 1;
+		}
+
+		ws.write(row, 0, "Sub-Totals")?;
+		ws.write(row, 3, "sum")?;
+		ws.write(row + 2, 0, "Remarks")?;
+		ws.write(row + 3, 0, "rig down for maintenance")?;
+		Ok(())
+	})
+}
+
+// "Depth" spans the from and to columns, which are only named on the row below
+fn write_header(ws: &mut Worksheet, row: u32) -> Result<(), XlsxError> {
+	ws.write(row, 0, "Hole Number")?;
+	ws.merge_range(row, 1, row, 2, "Depth", &Format::new())?;
+	ws.write(row, 3, "Meters")?;
+	ws.write(row, 4, "Comment")?;
+	ws.write(row + 1, 1, "From")?;
+	ws.write(row + 1, 2, "To")?;
+	Ok(())
+}
+
+// numbers are written as numbers, like they would be typed into excel
+fn write_values(ws: &mut Worksheet, row: u32, values: &[&str]) -> Result<(), XlsxError> {
+	for (col, value) in values.iter().enumerate() {
+		match value.parse::<f64>() {
+			Ok(number) => ws.write(row, col as u16, number)?,
+			Err(_) if value.is_empty() => continue,
+			Err(_) => ws.write(row, col as u16, *value)?,
+		};
+	}
+
+	Ok(())
+}
+
+fn workbook(sheet: &str, build: impl FnOnce(&mut Worksheet) -> Result<(), XlsxError>) -> Vec<u8> {
+	let mut workbook = Workbook::new();
+	let worksheet = workbook.add_worksheet().set_name(sheet).unwrap();
+	build(worksheet).unwrap();
+	workbook.save_to_buffer().unwrap()
+}
+
+fn options() -> ExportOptions {
+	ExportOptions {
+		schema_cache: None,
+		checkpoint: false,
+		..Default::default()
+	}
+}
+
+fn export(source: &dyn SpreadsheetSource, options: &ExportOptions) -> (String, RunSummary) {
+	let mut sink = CsvSink::new(Vec::new());
+	let summary = write_output(source, &mut sink, "fixture.csv", options).unwrap();
+	(String::from_utf8(sink.into_inner()).unwrap(), summary)
+}
+
+fn two_days() -> MemorySource {
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-18.xlsx",
+		daily_report(
+			"DDR_2024-05-18",
+			"18/05/2024",
+			&[
+				["DH-01", "5", "10.5", "5.5", ""],
+				["DH-03", "0", "4", "4", "lost core"],
+			],
+		),
+	);
+	source.add(
+		"DDR_2024-05-17.xlsx",
+		daily_report(
+			"DDR_2024-05-17",
+			"17/05/2024",
+			&[
+				["DH-01", "0", "5", "5", "collar"],
+				["", "", "", "", "standby"],
+				["DH-02", "5", "9", "4", ""],
+			],
+		),
+	);
+	source
+}
+
+#[test]
+fn default_layout() {
+	let (csv, summary) = export(&two_days(), &options());
+	assert_eq!(csv, include_str!("fixtures/default_layout.csv"));
+	assert_eq!(summary.files.len(), 2);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn generate_output_writes_the_same_file() {
+	let output = env::temp_dir().join(format!("oxide-fixture-{}.csv", process::id()));
+	let output_file = output.display().to_string();
+	let result = generate_output(&two_days(), output_file, &options());
+	let csv = fs::read_to_string(&output);
+	let _ = fs::remove_file(&output);
+
+	result.unwrap();
+	assert_eq!(csv.unwrap(), include_str!("fixtures/default_layout.csv"));
+}
+
+#[test]
+fn clipboard() {
+	let output = env::temp_dir().join(format!("oxide-clipboard-{}.csv", process::id()));
+	let output_file = output.display().to_string();
+	let result = generate_output(&two_days(), output_file, &options());
+	let table = clipboard_table(&output, &CsvDialect::default());
+	let _ = fs::remove_file(&output);
+
+	result.unwrap();
+	let table = table.unwrap();
+	assert_eq!(table.rows, 4);
+	assert_eq!(table.text, include_str!("fixtures/default_layout.csv"));
+	assert!(
+		table.html.starts_with(
+			"<table>\n<thead>\n<tr><th>hole_number</th><th>depth_from</th><th>depth_to</th>"
+		)
+	);
+	assert!(table.html.contains(
+		"<tr><td>DH-01</td><td>0</td><td>5</td><td>5</td><td>collar</td><td>17/05/2024</td></tr>"
+	));
+	assert!(table.html.ends_with("</tbody>\n</table>\n"));
+}
+
+#[test]
+fn manifest() {
+	let output = env::temp_dir().join(format!("oxide-manifest-{}.csv", process::id()));
+	let checksum_path = output.with_file_name(format!("oxide-manifest-{}.csv.sha256", process::id()));
+	let manifest_path =
+		output.with_file_name(format!("oxide-manifest-{}.manifest.json", process::id()));
+	let options = ExportOptions {
+		manifest: true,
+		..options()
+	};
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let checksum = fs::read_to_string(&checksum_path);
+	let manifest = fs::read_to_string(&manifest_path);
+	for path in [&output, &checksum_path, &manifest_path] {
+		let _ = fs::remove_file(path);
+	}
+
+	let summary = result.unwrap();
+	let hash = summary.output_sha256.unwrap();
+	assert_eq!(hash.len(), 64);
+	assert_eq!(
+		checksum.unwrap(),
+		format!("{}  oxide-manifest-{}.csv\n", hash, process::id())
+	);
+
+	let manifest: serde_json::Value = serde_json::from_str(&manifest.unwrap()).unwrap();
+	assert_eq!(manifest["output"]["sha256"], hash.as_str());
+	assert_eq!(manifest["output"]["rows"], 4);
+	let sources = manifest["sources"].as_array().unwrap();
+	assert_eq!(sources.len(), 2);
+	assert_eq!(sources[0]["file"], "DDR_2024-05-17.xlsx");
+	assert_eq!(sources[1]["file"], "DDR_2024-05-18.xlsx");
+	assert_eq!(sources[0]["rows"], 2);
+	assert_ne!(sources[0]["sha256"], sources[1]["sha256"]);
+}
+
+#[test]
+fn archive() {
+	let inbox = env::temp_dir().join(format!("oxide-archive-{}", process::id()));
+	let output = env::temp_dir().join(format!("oxide-archive-{}.csv", process::id()));
+	let run = |archive| {
+		fs::create_dir_all(&inbox).unwrap();
+		let source = two_days();
+		for file in source.list().unwrap() {
+			let mut data = Vec::new();
+			source
+				.reader(&file.path)
+				.unwrap()
+				.read_to_end(&mut data)
+				.unwrap();
+			fs::write(inbox.join(&file.path), data).unwrap();
+		}
+		let options = ExportOptions {
+			archive: Some(archive),
+			// only just written
+			settle_time: Duration::ZERO,
+			..options()
+		};
+		// the second export only reads what the first left to read
+		let export = || {
+			generate_output(
+				&LocalDir::new(&inbox),
+				output.display().to_string(),
+				&options,
+			)
+		};
+		let result = export();
+		let again = export();
+		let listed = |dir: &Path| -> Vec<String> {
+			let mut names: Vec<_> = fs::read_dir(dir)
+				.map(|entries| {
+					entries
+						.map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+						.collect()
+				})
+				.unwrap_or_default();
+			names.sort();
+			names
+		};
+		let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+		let listing = (listed(&inbox), listed(&inbox.join("done").join(&today)));
+		let _ = fs::remove_dir_all(&inbox);
+		let _ = fs::remove_file(&output);
+		(result.unwrap(), again.unwrap().total_rows(), listing)
+	};
+
+	let (summary, again, (inbox_files, done_files)) = run(Archive::default());
+	assert_eq!(summary.total_rows(), 4);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+	assert_eq!(again, 0);
+	assert_eq!(inbox_files, ["done"]);
+	assert_eq!(done_files, ["DDR_2024-05-17.xlsx", "DDR_2024-05-18.xlsx"]);
+
+	let copy = Archive {
+		copy: true,
+		..Archive::default()
+	};
+	let (_, again, (inbox_files, done_files)) = run(copy);
+	assert_eq!(again, 4);
+	assert_eq!(
+		inbox_files,
+		["DDR_2024-05-17.xlsx", "DDR_2024-05-18.xlsx", "done"]
+	);
+	assert_eq!(done_files, ["DDR_2024-05-17.xlsx", "DDR_2024-05-18.xlsx"]);
+
+	// renamed where they are, and left out of the next export
+	let rename = Archive {
+		folder: "".into(),
+		rename: "DONE_{name}".to_string(),
+		..Archive::default()
+	};
+	let (summary, again, (inbox_files, _)) = run(rename);
+	assert_eq!(summary.total_rows(), 4);
+	assert_eq!(again, 0);
+	assert_eq!(
+		inbox_files,
+		[
+			".oxide-renamed",
+			"DONE_DDR_2024-05-17.xlsx",
+			"DONE_DDR_2024-05-18.xlsx"
+		]
+	);
+
+	// new workbooks named like renamed ones, DDR_2024-05-17 like
+	// {name}_{date}, are still read
+	let dated = Archive {
+		folder: "".into(),
+		rename: "{name}_{date}".to_string(),
+		..Archive::default()
+	};
+	let (summary, again, (inbox_files, _)) = run(dated);
+	assert_eq!(summary.total_rows(), 4);
+	assert_eq!(again, 0);
+	let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+	assert_eq!(
+		inbox_files,
+		[
+			".oxide-renamed".to_string(),
+			format!("DDR_2024-05-17_{}.xlsx", today),
+			format!("DDR_2024-05-18_{}.xlsx", today)
+		]
+	);
+	assert!(Archive::check_rename("{name}_{date}_{time}").is_ok());
+	assert!(Archive::check_rename("{name}").is_err());
+	assert!(Archive::check_rename("DONE").is_err());
+	assert!(Archive::check_rename("done/{name}").is_err());
+}
+
+#[test]
+fn quarantine() {
+	let inbox = env::temp_dir().join(format!("oxide-quarantine-{}", process::id()));
+	let output = env::temp_dir().join(format!("oxide-quarantine-{}.csv", process::id()));
+	fs::create_dir_all(&inbox).unwrap();
+	fs::write(inbox.join("DDR_broken.xlsx"), "not a workbook").unwrap();
+	let source = two_days();
+	for file in source.list().unwrap() {
+		let mut data = Vec::new();
+		source
+			.reader(&file.path)
+			.unwrap()
+			.read_to_end(&mut data)
+			.unwrap();
+		fs::write(inbox.join(&file.path), data).unwrap();
+	}
+	let options = ExportOptions {
+		quarantine: Some("quarantine".into()),
+		settle_time: Duration::ZERO,
+		..options()
+	};
+
+	let result = generate_output(
+		&LocalDir::new(&inbox),
+		output.display().to_string(),
+		&options,
+	);
+	let mut quarantined: Vec<_> = fs::read_dir(inbox.join("quarantine"))
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+		.collect();
+	quarantined.sort();
+	let reason = fs::read_to_string(inbox.join("quarantine").join("DDR_broken.error.txt"));
+	let _ = fs::remove_dir_all(&inbox);
+	let _ = fs::remove_file(&output);
+
+	let summary = result.unwrap();
+	assert_eq!(summary.total_rows(), 4);
+	assert_eq!(quarantined, ["DDR_broken.error.txt", "DDR_broken.xlsx"]);
+	let reason = reason.unwrap();
+	assert!(
+		reason.starts_with("DDR_broken.xlsx couldn't be exported on 20"),
+		"{}",
+		reason
+	);
+	assert!(reason.contains("failed to open workbook"), "{}", reason);
+}
+
+// writes a line and then fails, like a drive filling up halfway
+struct FailingSink(fs::File);
+
+impl OutputSink for FailingSink {
+	fn write_header(&mut self, _: &[String]) -> io::Result<()> {
+		writeln!(self.0, "half")
+	}
+
+	fn write_row(&mut self, _: &[&str]) -> io::Result<()> {
+		Err(io::Error::other("no space left"))
+	}
+
+	fn finish(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+struct FailingPlugin;
+
+impl SinkPlugin for FailingPlugin {
+	fn extension(&self) -> &str {
+		"fail"
+	}
+
+	fn create_sink(&self, path: &Path) -> io::Result<Box<dyn OutputSink>> {
+		Ok(Box::new(FailingSink(fs::File::create(path)?)))
+	}
+}
+
+#[test]
+fn partial_output() {
+	let output = env::temp_dir().join(format!("oxide-partial-{}.fail", process::id()));
+	let partial = output.with_file_name(format!("oxide-partial-{}.partial.fail", process::id()));
+	let checkpoint = Checkpoint::path_for(&output.display().to_string());
+	fs::write(&output, "last export\n").unwrap();
+	let mut plugins = Plugins::default();
+	plugins.add_sink(FailingPlugin);
+	let options = ExportOptions {
+		plugins,
+		checkpoint: false,
+		..options()
+	};
+
+	// the last output is only replaced by a complete one
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let kept = fs::read_to_string(&output);
+	let partial_left = partial.exists();
+	let nothing = StaleExport::find(&output.display().to_string());
+
+	// as an export that was killed leaves them
+	fs::write(&partial, "half\n").unwrap();
+	fs::write(&checkpoint, "").unwrap();
+	let stale = StaleExport::find(&output.display().to_string());
+	let kept_checkpoint = stale.as_ref().map(|stale| {
+		stale.remove_partial().unwrap();
+		(partial.exists(), checkpoint.exists())
+	});
+	let discarded = stale.as_ref().map(|stale| {
+		stale.discard().unwrap();
+		checkpoint.exists()
+	});
+	for path in [&output, &partial, &checkpoint] {
+		let _ = fs::remove_file(path);
+	}
+
+	assert!(result.is_err());
+	assert_eq!(kept.unwrap(), "last export\n");
+	assert!(partial_left.not());
+	assert_eq!(nothing, None);
+	assert_eq!(
+		stale,
+		Some(StaleExport {
+			partial: Some(partial.clone()),
+			checkpoint: Some(checkpoint.clone()),
+		})
+	);
+	assert_eq!(kept_checkpoint, Some((false, true)));
+	assert_eq!(discarded, Some(false));
+
+	// spills of an export that was killed, and one still being written
+	let spill = |name: &str| env::temp_dir().join(format!("{}-{}-0.tmp", name, process::id()));
+	let (stale_spill, fresh_spill) = (spill("oxide-stage"), spill("oxide-sort"));
+	fs::write(&stale_spill, "").unwrap();
+	fs::write(&fresh_spill, "").unwrap();
+	let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+	fs::File::options()
+		.write(true)
+		.open(&stale_spill)
+		.and_then(|file| file.set_modified(two_days_ago))
+		.unwrap();
+	let removed = remove_stale_spills(Duration::from_secs(24 * 60 * 60));
+	let left = (stale_spill.exists(), fresh_spill.exists());
+	for path in [&stale_spill, &fresh_spill] {
+		let _ = fs::remove_file(path);
+	}
+
+	assert!(removed >= 1);
+	assert_eq!(left, (false, true));
+}
+
+// a folder whose reports were all saved at `modified`, with the export
+// killed on the read after the first `killed_after`
+struct Killed {
+	source: MemorySource,
+	modified: SystemTime,
+	reads: Mutex<usize>,
+	killed_after: usize,
+}
+
+impl SpreadsheetSource for Killed {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		let mut files = self.source.list()?;
+		for file in &mut files {
+			file.modified = self.modified;
+		}
+
+		Ok(files)
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let mut reads = self.reads.lock().unwrap();
+		*reads += 1;
+		if *reads > self.killed_after {
+			panic!("killed while reading {}", path.display());
+		}
+
+		self.source.reader(path)
+	}
+}
+
+#[test]
+fn resume() {
+	let output = env::temp_dir().join(format!("oxide-resume-{}.csv", process::id()));
+	let output_file = output.display().to_string();
+	let checkpoint = Checkpoint::path_for(&output_file);
+	let three_days = |row: [&str; 5]| {
+		let mut source = two_days();
+		let report = daily_report("DDR_2024-05-19", "19/05/2024", &[row]);
+		source.add("DDR_2024-05-19.xlsx", report);
+		source
+	};
+	let row = ["DH-03", "4", "7", "3", ""];
+	let saved = SystemTime::UNIX_EPOCH + Duration::from_secs(1_716_000_000);
+	let killed = |source, modified, killed_after| Killed {
+		source,
+		modified,
+		reads: Mutex::new(0),
+		killed_after,
+	};
+	let checkpointed = ExportOptions {
+		checkpoint: true,
+		// one file at a time, so the kill always lands after the first one
+		max_concurrent_files: 1,
+		// every read is of a workbook itself, not for hashing it
+		skip_identical: false,
+		..options()
+	};
+	let run = |source: &Killed, resume| {
+		let mut sink = CsvSink::new(Vec::new());
+		let options = ExportOptions {
+			resume,
+			..checkpointed.clone()
+		};
+		let summary = write_output(source, &mut sink, &output_file, &options)?;
+		Ok::<_, OxideError>((String::from_utf8(sink.into_inner()).unwrap(), summary))
+	};
+	let (uninterrupted, _) = export(&three_days(row), &options());
+
+	// killed after the first file, which was already in the checkpoint
+	let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+		run(&killed(three_days(row), saved, 1), false)
+	}));
+	let left = checkpoint.exists();
+
+	// only the files after it are read again
+	let resumed_source = killed(three_days(row), saved, usize::MAX);
+	let resumed = run(&resumed_source, true);
+	let resumed_reads = *resumed_source.reads.lock().unwrap();
+	let finished = checkpoint.exists();
+
+	// a checkpoint of the same files saved at another time isn't theirs, so
+	// none of its rows are kept
+	let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+		run(&killed(three_days(row), saved, 1), false)
+	}));
+	let edited = ["DH-03", "4", "8", "4", "relogged"];
+	let (fresh, _) = export(&three_days(edited), &options());
+	let edited_source = killed(
+		three_days(edited),
+		saved + Duration::from_secs(60),
+		usize::MAX,
+	);
+	let other = run(&edited_source, true);
+	let other_reads = *edited_source.reads.lock().unwrap();
+	let _ = fs::remove_file(&checkpoint);
+
+	assert!(interrupted.is_err());
+	assert!(left);
+	let (resumed, summary) = resumed.unwrap();
+	assert_eq!(resumed, uninterrupted);
+	assert_eq!(summary.total_rows(), 5);
+	assert_eq!(resumed_reads, 2);
+	assert!(finished.not());
+
+	assert_eq!(other.unwrap().0, fresh);
+	assert_eq!(other_reads, 3);
+}
+
+#[test]
+fn provenance() {
+	let with = |provenance| ExportOptions {
+		provenance,
+		profile: "acme".to_string(),
+		..options()
+	};
+	let version = env!("CARGO_PKG_VERSION");
+
+	let (csv, _) = export(&two_days(), &with(Provenance::Preamble));
+	let lines: Vec<_> = csv.lines().collect();
+	assert_eq!(lines[0], format!("# oxide_version: {}", version));
+	assert_eq!(lines[1], "# profile: acme");
+	assert!(lines[2].starts_with("# exported_at: 20"), "{}", lines[2]);
+	assert_eq!(lines[3], "# source_files: 2");
+	assert_eq!(
+		lines[4],
+		"hole_number,depth_from,depth_to,meters,comment,date"
+	);
+
+	let (csv, _) = export(&two_days(), &with(Provenance::Column));
+	let lines: Vec<_> = csv.lines().collect();
+	assert_eq!(
+		lines[0],
+		"hole_number,depth_from,depth_to,meters,comment,date,oxide_version,profile,exported_at,source_files"
+	);
+	assert!(lines[1].starts_with(&format!(
+		"DH-01,0,5,5,collar,17/05/2024,{},acme,20",
+		version
+	)));
+	assert!(lines[1].ends_with(",2"));
+
+	// a preamble is left out of the rows merged into
+	let output = env::temp_dir().join(format!("oxide-provenance-{}.csv", process::id()));
+	let sidecar = output.with_file_name(format!(
+		"oxide-provenance-{}.provenance.json",
+		process::id()
+	));
+	let merged = ExportOptions {
+		merge_key: vec!["date".to_string(), "hole_number".to_string()],
+		..with(Provenance::Preamble)
+	};
+	let first = generate_output(&two_days(), output.display().to_string(), &merged);
+	let again = generate_output(&two_days(), output.display().to_string(), &merged);
+	let merged_csv = fs::read_to_string(&output);
+	let result = generate_output(
+		&two_days(),
+		output.display().to_string(),
+		&with(Provenance::Sidecar),
+	);
+	let json = fs::read_to_string(&sidecar);
+	for path in [&output, &sidecar, &output.with_extension("csv.sha256")] {
+		let _ = fs::remove_file(path);
+	}
+
+	first.unwrap();
+	assert_eq!(
+		again.unwrap().merged,
+		Some(MergeCounts {
+			updated: 0,
+			added: 0,
+			unchanged: 4
+		})
+	);
+	let merged_csv = merged_csv.unwrap();
+	assert_eq!(
+		merged_csv
+			.lines()
+			.filter(|line| line.starts_with('#'))
+			.count(),
+		4
+	);
+	assert_eq!(merged_csv.lines().count(), 9);
+
+	result.unwrap();
+	let json: serde_json::Value = serde_json::from_str(&json.unwrap()).unwrap();
+	assert_eq!(json["oxide_version"], version);
+	assert_eq!(json["profile"], "acme");
+	assert_eq!(json["source_files"], "2");
+}
+
+#[test]
+fn number_formats() {
+	let options = ExportOptions {
+		number_formats: [("depth_from", "000"), ("meters", "0.00")]
+			.into_iter()
+			.map(|(column, format)| (column.to_string(), NumberFormat::parse(format).unwrap()))
+			.collect(),
+		..options()
+	};
+	let (csv, _) = export(&two_days(), &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,000,5,5.00,collar,17/05/2024\n\
+		DH-02,005,9,4.00,,17/05/2024\n\
+		DH-01,005,10.5,5.50,,18/05/2024\n\
+		DH-03,000,4,4.00,lost core,18/05/2024\n"
+	);
+
+	let separated = NumberFormat::parse("#,##0.0#").unwrap();
+	assert_eq!(
+		separated.apply("1234567.891").as_deref(),
+		Some("1,234,567.89")
+	);
+	assert_eq!(separated.apply("-0.001").as_deref(), Some("0.0"));
+	assert_eq!(separated.apply("DH-01"), None);
+	let plain = NumberFormat::parse("0").unwrap();
+	assert_eq!(plain.apply("1,234.5").as_deref(), Some("1235"));
+	assert_eq!(plain.apply("1,23"), None);
+	assert!(NumberFormat::parse("0.#0").is_err());
+}
+
+#[test]
+fn pdf_report() {
+	let output = env::temp_dir().join(format!("oxide-report-{}.csv", process::id()));
+	let options = ExportOptions {
+		pdf_report: Some(PdfReport::default()),
+		..options()
+	};
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let _ = fs::remove_file(&output);
+
+	let report_path = result.unwrap().report.unwrap();
+	let pdf = fs::read(&report_path);
+	let _ = fs::remove_file(&report_path);
+
+	let pdf = String::from_utf8(pdf.unwrap()).unwrap();
+	assert!(pdf.starts_with("%PDF-1.4"));
+	assert!(pdf.ends_with("%%EOF\n"));
+	assert!(pdf.contains("(DDR_2024-05-17     2) Tj"), "{}", pdf);
+	assert!(pdf.contains("(DH-01    10.5     2) Tj"), "{}", pdf);
+	assert!(pdf.contains("(17/05/2024       9     2) Tj"), "{}", pdf);
+}
+
+#[test]
+fn merge_into_existing_output() {
+	let output = env::temp_dir().join(format!("oxide-merge-{}.csv", process::id()));
+	let output_file = output.display().to_string();
+	let mut corrected = MemorySource::new();
+	corrected.add(
+		"DDR_2024-05-17.xlsx",
+		daily_report(
+			"DDR_2024-05-17",
+			"17/05/2024",
+			&[
+				["DH-01", "0", "5", "5", "collar"],
+				["DH-02", "5", "9.5", "4.5", ""],
+			],
+		),
+	);
+	corrected.add(
+		"DDR_2024-05-19.xlsx",
+		daily_report(
+			"DDR_2024-05-19",
+			"19/05/2024",
+			&[["DH-04", "0", "3", "3", ""]],
+		),
+	);
+	let options = ExportOptions {
+		merge_key: vec!["date".to_string(), "hole_number".to_string()],
+		..options()
+	};
+
+	let first = generate_output(&two_days(), output_file.clone(), &options);
+	let merged = generate_output(&corrected, output_file, &options);
+	let csv = fs::read_to_string(&output);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(output.with_extension("csv.sha256"));
+
+	assert_eq!(first.unwrap().merged, None);
+	assert_eq!(
+		merged.unwrap().merged,
+		Some(MergeCounts {
+			updated: 1,
+			added: 1,
+			unchanged: 3
+		})
+	);
+	assert_eq!(
+		csv.unwrap(),
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,collar,17/05/2024\n\
+		DH-02,5,9.5,4.5,,17/05/2024\n\
+		DH-01,5,10.5,5.5,,18/05/2024\n\
+		DH-03,0,4,4,lost core,18/05/2024\n\
+		DH-04,0,3,3,,19/05/2024\n"
+	);
+}
+
+#[test]
+fn edited_output() {
+	let output = env::temp_dir().join(format!("oxide-edited-{}.csv", process::id()));
+	let output_file = output.display().to_string();
+	let edited_path = output.with_extension("csv.edited");
+	let options = ExportOptions {
+		merge_key: vec!["date".to_string(), "hole_number".to_string()],
+		..options()
+	};
+
+	let first = generate_output(&two_days(), output_file.clone(), &options);
+	// a comment added by hand, which merging the same rows overwrites
+	let csv = fs::read_to_string(&output).unwrap();
+	let hand_edited = csv.replace("lost core", "lost core, see photos");
+	fs::write(&output, &hand_edited).unwrap();
+	let merged = generate_output(&two_days(), output_file.clone(), &options);
+	let edited = fs::read_to_string(&edited_path);
+	let again = generate_output(&two_days(), output_file, &options);
+	for path in [&output, &edited_path, &output.with_extension("csv.sha256")] {
+		let _ = fs::remove_file(path);
+	}
+
+	assert!(first.unwrap().warnings.is_empty());
+	let warnings = merged.unwrap().warnings;
+	assert_eq!(warnings.len(), 1, "{:?}", warnings);
+	assert!(warnings[0].starts_with(&format!(
+		"oxide-edited-{}.csv was changed since the last export",
+		process::id()
+	)));
+	assert_eq!(edited.unwrap(), hand_edited);
+	assert!(again.unwrap().warnings.is_empty());
+}
+
+#[test]
+fn summaries() {
+	let measure = |column: &str, function| Measure {
+		column: column.to_string(),
+		function,
+	};
+	let options = ExportOptions {
+		aggregations: vec![
+			Aggregation {
+				name: "per_hole".to_string(),
+				group_by: vec!["hole_number".to_string()],
+				measures: vec![
+					measure("meters", AggregateFn::Sum),
+					measure("meters", AggregateFn::Mean),
+					measure("comment", AggregateFn::Count),
+				],
+			},
+			Aggregation {
+				name: "missing".to_string(),
+				group_by: vec!["rig".to_string()],
+				measures: vec![measure("meters", AggregateFn::Max)],
+			},
+		],
+		..options()
+	};
+
+	let output = env::temp_dir().join(format!("oxide-summaries-{}.csv", process::id()));
+	let per_hole = output.with_file_name(format!("oxide-summaries-{}.per_hole.csv", process::id()));
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let csv = fs::read_to_string(&output);
+	let summary_csv = fs::read_to_string(&per_hole);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&per_hole);
+
+	let summary = result.unwrap();
+	assert_eq!(csv.unwrap(), include_str!("fixtures/default_layout.csv"));
+	assert_eq!(summary_csv.unwrap(), include_str!("fixtures/summaries.csv"));
+	assert_eq!(
+		summary.warnings,
+		["summary missing: there's no rig column".to_string()]
+	);
+}
+
+#[test]
+fn hole_summary() {
+	let mut source = two_days();
+	// dates sort by day rather than as text
+	source.add(
+		"DDR_2024-06-01.xlsx",
+		daily_report(
+			"DDR_2024-06-01",
+			"01/06/2024",
+			&[["DH-02", "9", "12", "3", ""]],
+		),
+	);
+	let options = ExportOptions {
+		aggregations: vec![Aggregation::per_hole("hole_number", "meters", "date")],
+		..options()
+	};
+
+	let name = format!("oxide-holes-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let holes = env::temp_dir().join(format!("{}.holes.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let holes_csv = fs::read_to_string(&holes);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&holes);
+
+	result.unwrap();
+	assert_eq!(
+		holes_csv.unwrap(),
+		"hole_number,sum_meters,count_date,earliest_date,latest_date\n\
+		DH-01,10.5,2,17/05/2024,18/05/2024\n\
+		DH-02,7,2,17/05/2024,01/06/2024\n\
+		DH-03,4,1,18/05/2024,18/05/2024\n"
+	);
+}
+
+#[test]
+fn cumulative_meters() {
+	let mut source = two_days();
+	// read after the others, but drilled before them
+	source.add(
+		"late_upload.xlsx",
+		daily_report("late_upload", "16/05/2024", &[["DH-01", "0", "2", "2", ""]]),
+	);
+	let options = ExportOptions {
+		cumulative_meters: Some(CumulativeMeters::default()),
+		..options()
+	};
+
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date,cumulative_meters\n\
+		DH-01,0,5,5,collar,17/05/2024,7\n\
+		DH-02,5,9,4,,17/05/2024,4\n\
+		DH-01,5,10.5,5.5,,18/05/2024,12.5\n\
+		DH-03,0,4,4,lost core,18/05/2024,4\n\
+		DH-01,0,2,2,,16/05/2024,2\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn interval_import_preset() {
+	let options = ExportOptions {
+		preset: ExportPreset::IntervalImport,
+		source_columns: true,
+		totals: Totals::End,
+		total_columns: vec!["meters".to_string()],
+		..options()
+	};
+
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(
+		csv,
+		"HOLEID,FROM,TO,LENGTH,DRILL_DATE,COMMENTS\n\
+		DH-01,0,5,5,2024-05-17,collar\n\
+		DH-02,5,9,4,2024-05-17,\n\
+		DH-01,5,10.5,5.5,2024-05-18,\n\
+		DH-03,0,4,4,2024-05-18,lost core\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn totals() {
+	let options = ExportOptions {
+		totals: Totals::PerFile,
+		total_columns: vec!["meters".to_string(), "bit".to_string()],
+		..options()
+	};
+
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/totals.csv"));
+	assert_eq!(
+		summary.warnings,
+		["totals: there's no bit column".to_string()]
+	);
+
+	let options = ExportOptions {
+		totals: Totals::End,
+		..options
+	};
+	let (csv, _) = export(&two_days(), &options);
+	assert!(
+		csv.ends_with("\ndata,DH-03,0,4,4,lost core,18/05/2024\ntotal,,,,18.5,,\n"),
+		"{}",
+		csv
+	);
+}
+
+#[test]
+fn date_range() {
+	let mut source = two_days();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		daily_report("DDR_2024-05-19", "", &[["DH-04", "0", "3", "3", ""]]),
+	);
+	let options = ExportOptions {
+		date_range: DateRange::new("2024-05-18", "").unwrap(),
+		..options()
+	};
+
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,5,10.5,5.5,,18/05/2024\n\
+		DH-03,0,4,4,lost core,18/05/2024\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		["date range: left out 1 rows without a date".to_string()]
+	);
+
+	let options = ExportOptions {
+		date_range: DateRange::new("", "17/05/2024").unwrap(),
+		..options
+	};
+	let (csv, _) = export(&two_days(), &options);
+	assert_eq!(csv.lines().count(), 3);
+	assert!(DateRange::new("2024-05-18", "2024-05-17").is_err());
+	assert!(DateRange::new("last week", "").is_err());
+}
+
+#[test]
+fn filter() {
+	let filter = |expression: &str| {
+		let options = ExportOptions {
+			filter: Some(RowFilter::parse(expression).unwrap()),
+			..options()
+		};
+		export(&two_days(), &options)
+	};
+
+	let (csv, summary) = filter("meters > 4.5 && hole_number == \"DH-01\"");
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,collar,17/05/2024\n\
+		DH-01,5,10.5,5.5,,18/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+	let (csv, _) = filter("!(comment == 'collar' || depth_from >= 5) && comment");
+	assert_eq!(
+		csv.lines().skip(1).collect::<Vec<_>>(),
+		["DH-03,0,4,4,lost core,18/05/2024"]
+	);
+
+	let (csv, summary) = filter("rig == 'RC-04'");
+	assert_eq!(csv.lines().count(), 5);
+	assert_eq!(
+		summary.warnings,
+		["filter: there's no rig column".to_string()]
+	);
+
+	assert!(RowFilter::parse("meters >").is_err());
+	assert!(RowFilter::parse("(meters > 1").is_err());
+	assert!(RowFilter::parse("meters = 1").is_err());
+}
+
+#[test]
+fn sort_by() {
+	let sorted = |keys: &str, totals: Totals| {
+		let options = ExportOptions {
+			sort_by: SortKey::parse_list(keys).unwrap(),
+			row_ids: RowIds::PerFile,
+			totals,
+			total_columns: vec!["meters".to_string()],
+			..options()
+		};
+		export(&two_days(), &options)
+	};
+
+	// equal values keep the order the files were read in
+	let (csv, summary) = sorted("-meters, hole", Totals::None);
+	assert_eq!(
+		csv,
+		"row_id,hole_number,depth_from,depth_to,meters,comment,date\n\
+		1,DH-01,5,10.5,5.5,,18/05/2024\n\
+		1,DH-01,0,5,5,collar,17/05/2024\n\
+		2,DH-02,5,9,4,,17/05/2024\n\
+		2,DH-03,0,4,4,lost core,18/05/2024\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		["sort: there's no hole column".to_string()]
+	);
+
+	// dates are sorted by day rather than as text
+	let (csv, summary) = sorted("hole_number,-date", Totals::PerFile);
+	let rows: Vec<_> = csv.lines().skip(1).collect();
+	assert_eq!(
+		rows,
+		[
+			"1,data,DH-01,5,10.5,5.5,,18/05/2024",
+			"1,data,DH-01,0,5,5,collar,17/05/2024",
+			"2,data,DH-02,5,9,4,,17/05/2024",
+			"2,data,DH-03,0,4,4,lost core,18/05/2024",
+			",total,,,,18.5,,"
+		]
+	);
+	assert_eq!(
+		summary.warnings,
+		["totals: sorted rows only get totals at the end".to_string()]
+	);
+	assert!(SortKey::parse_list("date,-").is_err());
+}
+
+#[test]
+fn spilled_rows() {
+	// a limit of a byte puts every row in the spill file, and every sorted
+	// one in a run of its own
+	for sort_by in ["", "-meters, hole_number"] {
+		let options = ExportOptions {
+			sort_by: SortKey::parse_list(sort_by).unwrap(),
+			..options()
+		};
+		let spilled = ExportOptions {
+			memory_limit: 1,
+			..options.clone()
+		};
+		assert_eq!(
+			export(&two_days(), &spilled).0,
+			export(&two_days(), &options).0
+		);
+	}
+}
+
+#[test]
+fn column_order() {
+	let options = ExportOptions {
+		column_order: ["date", "hole_number", "meters", "rig"]
+			.map(String::from)
+			.to_vec(),
+		..options()
+	};
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(
+		csv,
+		"date,hole_number,meters,depth_from,depth_to,comment\n\
+		17/05/2024,DH-01,5,0,5,collar\n\
+		17/05/2024,DH-02,4,5,9,\n\
+		18/05/2024,DH-01,5.5,5,10.5,\n\
+		18/05/2024,DH-03,4,0,4,lost core\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		["column order: there's no rig column".to_string()]
+	);
+}
+
+#[test]
+fn duplicate_reports() {
+	let mut source = two_days();
+	source.add(
+		"DDR_2024-05-17_corrected.xlsx",
+		daily_report(
+			"DDR_2024-05-17_corrected",
+			"17/05/2024",
+			&[["DH-02", "5", "9.5", "4.5", ""]],
+		),
+	);
+	let duplicates = |duplicate_reports| {
+		let options = ExportOptions {
+			duplicate_reports,
+			..options()
+		};
+		export(&source, &options)
+	};
+
+	let (csv, summary) = duplicates(DuplicateReports::Warn);
+	assert_eq!(csv.lines().count(), 6);
+	assert_eq!(
+		summary.warnings,
+		[
+			"DDR_2024-05-17.xlsx and DDR_2024-05-17_corrected.xlsx are reports for 17/05/2024"
+				.to_string()
+		]
+	);
+
+	// files modified at the same time count as newer the later they're listed
+	let (csv, summary) = duplicates(DuplicateReports::KeepNewest);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-02,5,9.5,4.5,,17/05/2024\n\
+		DH-01,5,10.5,5.5,,18/05/2024\n\
+		DH-03,0,4,4,lost core,18/05/2024\n"
+	);
+	assert!(summary.warnings[0].ends_with(", only DDR_2024-05-17_corrected.xlsx was exported"));
+
+	let (csv, _) = duplicates(DuplicateReports::Revision);
+	let revisions: Vec<_> = csv.lines().map(|l| l.rsplit(',').next().unwrap()).collect();
+	assert_eq!(revisions, ["revision", "1", "1", "2", "1", "1"]);
+}
+
+#[test]
+fn row_ids() {
+	let options = ExportOptions {
+		row_ids: RowIds::PerFile,
+		totals: Totals::PerFile,
+		total_columns: vec!["meters".to_string()],
+		..options()
+	};
+
+	let (csv, _) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/row_ids.csv"));
+
+	let options = ExportOptions {
+		row_ids: RowIds::Sequential,
+		totals: Totals::None,
+		..options
+	};
+	let (csv, _) = export(&two_days(), &options);
+	let ids: Vec<_> = csv.lines().map(|l| l.split(',').next().unwrap()).collect();
+	assert_eq!(ids, ["row_id", "1", "2", "3", "4"]);
+}
+
+#[test]
+fn validation() {
+	let rules = [
+		ColumnRule {
+			column: "hole_number".to_string(),
+			required: true,
+			pattern: Some(r"DH-\d+".to_string()),
+			..Default::default()
+		},
+		ColumnRule {
+			column: "meters".to_string(),
+			min: Some(0.0),
+			max: Some(5.0),
+			..Default::default()
+		},
+		ColumnRule {
+			column: "comment".to_string(),
+			allowed: vec!["collar".to_string()],
+			..Default::default()
+		},
+		ColumnRule {
+			column: "bit".to_string(),
+			required: true,
+			..Default::default()
+		},
+	];
+
+	// writes the export and the violations report next to it, reading both
+	// back in
+	let export = |invalid_rows| {
+		let options = ExportOptions {
+			validation: Some(Validation::new(&rules, invalid_rows).unwrap()),
+			..options()
+		};
+		let name = format!("oxide-validation-{}", process::id());
+		let output = env::temp_dir().join(format!("{}.csv", name));
+		let violations = env::temp_dir().join(format!("{}.violations.csv", name));
+		let result = generate_output(&two_days(), output.display().to_string(), &options);
+		let csv = fs::read_to_string(&output);
+		let violations_csv = fs::read_to_string(&violations);
+		let _ = fs::remove_file(&output);
+		let _ = fs::remove_file(&violations);
+		(result.unwrap(), csv.unwrap(), violations_csv.unwrap())
+	};
+
+	let (summary, csv, violations) = export(InvalidRows::Flag);
+	assert_eq!(csv, include_str!("fixtures/validation.csv"));
+	assert_eq!(violations, include_str!("fixtures/violations.csv"));
+	assert_eq!(
+		summary.warnings,
+		[
+			"validation: there's no bit column".to_string(),
+			"2 values break validation rules, see the violations report".to_string()
+		]
+	);
+
+	let (_, csv, violations) = export(InvalidRows::Exclude);
+	let valid_rows: Vec<_> = include_str!("fixtures/default_layout.csv")
+		.lines()
+		.take(3)
+		.collect();
+	assert_eq!(csv.lines().collect::<Vec<_>>(), valid_rows);
+	assert_eq!(violations, include_str!("fixtures/violations.csv"));
+}
+
+#[test]
+fn qa_checks() {
+	let checks = vec![
+		QaCheck {
+			column: "meters".to_string(),
+			max: Some(5.0),
+			deviations: Some(1.0),
+			..Default::default()
+		},
+		QaCheck {
+			column: "depth_from".to_string(),
+			min: Some(1.0),
+			..Default::default()
+		},
+		QaCheck {
+			column: "bit".to_string(),
+			min: Some(0.0),
+			..Default::default()
+		},
+	];
+	let options = ExportOptions {
+		qa_checks: checks,
+		..options()
+	};
+
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/qa_flags.csv"));
+	assert_eq!(
+		summary.warnings,
+		[
+			"qa: there's no bit column".to_string(),
+			"4 values were flagged by qa checks, see the qa_flags column".to_string()
+		]
+	);
+
+	// the report leaves the export as it is
+	let options = ExportOptions {
+		qa_output: QaOutput::Report,
+		..options
+	};
+	let name = format!("oxide-qa-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let report = env::temp_dir().join(format!("{}.qa.csv", name));
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let (csv, report_csv) = (fs::read_to_string(&output), fs::read_to_string(&report));
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&report);
+
+	result.unwrap();
+	assert_eq!(csv.unwrap(), include_str!("fixtures/default_layout.csv"));
+	assert_eq!(report_csv.unwrap(), include_str!("fixtures/qa.csv"));
+}
+
+#[test]
+fn depth_intervals() {
+	let mut source = two_days();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		daily_report(
+			"DDR_2024-05-19",
+			"19/05/2024",
+			&[
+				["DH-01", "12", "15", "3", ""],
+				["DH-02", "8", "11", "3", ""],
+				["DH-03", "4", "7", "3", ""],
+			],
+		),
+	);
+	let options = ExportOptions {
+		depth_intervals: Some(DepthIntervals::default()),
+		..options()
+	};
+
+	let name = format!("oxide-intervals-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let report = env::temp_dir().join(format!("{}.intervals.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let report_csv = fs::read_to_string(&report);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&report);
+
+	let summary = result.unwrap();
+	assert_eq!(
+		report_csv.unwrap(),
+		"hole,issue,from,to,file,row\n\
+		DH-01,gap,10.5,12,DDR_2024-05-19,1\n\
+		DH-02,overlap,8,9,DDR_2024-05-19,2\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		["2 gaps and overlaps between depth intervals, see the intervals report".to_string()]
+	);
+}
+
+#[test]
+fn preview() {
+	let source = two_days();
+	let options = options();
+	let template = ReportTemplate::default();
+	let preview = |file: &str, max_rows| {
+		let path = Path::new(file);
+		read_preview(&source, path, max_rows, &template, &options.header_style).unwrap()
+	};
+	let (csv, _) = export(&source, &options);
+	let lines: Vec<_> = csv.lines().collect();
+	let joined = |preview: &Preview| -> Vec<String> {
+		let mut lines = vec![preview.headers.join(",")];
+		lines.extend(preview.rows.iter().map(|row| row.join(",")));
+		lines
+	};
+
+	// the same headers and rows the export reads, stopping at the sub-totals
+	let first = preview("DDR_2024-05-17.xlsx", PREVIEW_ROWS);
+	assert_eq!(joined(&first), lines[..3]);
+	let second = preview("DDR_2024-05-18.xlsx", PREVIEW_ROWS);
+	assert_eq!(joined(&second)[1..], lines[3..]);
+
+	// or once it has as many rows as asked for
+	let short = preview("DDR_2024-05-17.xlsx", 1);
+	assert_eq!(joined(&short), lines[..2]);
+}
+
+#[test]
+fn schema_cache() {
+	let cache_path = env::temp_dir().join(format!("oxide-schema-cache-{}.json", process::id()));
+	let options = ExportOptions {
+		schema_cache: Some(cache_path.clone()),
+		..options()
+	};
+	// the same report with its comment column renamed
+	let renamed = || {
+		let mut source = MemorySource::new();
+		let report = workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "19/05/2024")?;
+			write_header(ws, 3)?;
+			ws.write(3, 4, "Notes")?;
+			write_values(ws, 5, &["DH-03", "4", "7", "3", ""])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			Ok(())
+		});
+		source.add("DDR_2024-05-19.xlsx", report);
+		source
+	};
+
+	// the first run detects the header and caches it
+	let (detected, _) = export(&two_days(), &options);
+	let cached = fs::read_to_string(&cache_path).unwrap_or_default();
+
+	// an unchanged layout takes its headers from the cache as they are, which
+	// an edited cache gives away
+	fs::write(&cache_path, cached.replace("\"Comment\"", "\"Cached\"")).unwrap();
+	let (reused, reused_summary) = export(&two_days(), &options);
+
+	// once the header cells change the cached layout is thrown away and the
+	// header detected again, and that layout is cached in turn
+	let (drifted, drifted_summary) = export(&renamed(), &options);
+	let (redetected, redetected_summary) = export(&renamed(), &options);
+	let _ = fs::remove_file(&cache_path);
+
+	let header = |csv: &str| csv.lines().next().unwrap_or_default().to_string();
+	assert_eq!(detected, include_str!("fixtures/default_layout.csv"));
+	assert!(cached.contains("\"Comment\""), "{}", cached);
+	assert_eq!(
+		header(&reused),
+		"hole_number,depth_from,depth_to,meters,cached,date"
+	);
+	assert!(
+		reused_summary.warnings.is_empty(),
+		"{:?}",
+		reused_summary.warnings
+	);
+
+	assert_eq!(
+		header(&drifted),
+		"hole_number,depth_from,depth_to,meters,notes,date"
+	);
+	assert_eq!(
+		drifted_summary.warnings,
+		["DDR_2024-05-19: header layout at row 4 no longer matches the cached template"]
+	);
+	assert_eq!(redetected, drifted);
+	assert!(
+		redetected_summary.warnings.is_empty(),
+		"{:?}",
+		redetected_summary.warnings
+	);
+}
+
+#[test]
+fn schema_sidecars() {
+	let options = ExportOptions {
+		schema_sidecar: SchemaSidecar::Both,
+		..options()
+	};
+
+	let name = format!("oxide-schema-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let json = env::temp_dir().join(format!("{}.schema.json", name));
+	let sql = env::temp_dir().join(format!("{}.schema.sql", name));
+	let result = generate_output(&two_days(), output.display().to_string(), &options);
+	let (json_schema, sql_schema) = (fs::read_to_string(&json), fs::read_to_string(&sql));
+	for path in [&output, &json, &sql] {
+		let _ = fs::remove_file(path);
+	}
+
+	result.unwrap();
+	assert_eq!(json_schema.unwrap(), include_str!("fixtures/schema.json"));
+	assert_eq!(sql_schema.unwrap(), include_str!("fixtures/schema.sql"));
+}
+
+#[test]
+fn types() {
+	let mut source = two_days();
+	// a unit after the number and an excel date rather than text
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, 45431)?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-04", "0", "2", "2", ""])?;
+			write_values(ws, 6, &["DH-04", "2", "4.5", "2.5m", " "])?;
+			ws.write(7, 0, "Sub-Totals")?;
+			Ok(())
+		}),
+	);
+
+	let mut template = ReportTemplate::default();
+	for (column, value_type) in [
+		("depth_from", ValueType::Int),
+		("meters", ValueType::Float),
+		("comment", ValueType::String),
+		("date", ValueType::Date),
+	] {
+		template.types.insert(column.to_string(), value_type);
+	}
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(csv, include_str!("fixtures/types.csv"));
+	assert_eq!(
+		summary.warnings,
+		["DDR_2024-05-19: 1 values in meters aren't float, the first in row 2: 2.5m".to_string()]
+	);
+}
+
+#[test]
+fn null_value() {
+	let options = ExportOptions {
+		null_value: r"\N".to_string(),
+		..options()
+	};
+
+	let (csv, _) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/null_value.csv"));
+}
+
+#[test]
+fn set_options() {
+	let mut options = options();
+	options.set("csv-dialect", "excel-germany").unwrap();
+	options.set("merge-key", "date, hole_number").unwrap();
+	options.set("keep-identical", "true").unwrap();
+	options.set("row-limit", "20").unwrap();
+	options.set("max-concurrent-files", "2").unwrap();
+	assert_eq!(
+		options.csv_dialect,
+		CsvDialect::from_name("excel-germany").unwrap()
+	);
+	assert_eq!(options.merge_key, ["date", "hole_number"]);
+	assert!(options.skip_identical.not());
+	assert_eq!(options.row_limit, Some(20));
+	assert_eq!(options.max_concurrent_files, 2);
+
+	// empty is the default again
+	options.set("csv-dialect", "").unwrap();
+	options.set("row-limit", "").unwrap();
+	options.set("max-concurrent-files", "").unwrap();
+	assert_eq!(options.csv_dialect, CsvDialect::default());
+	assert_eq!(options.row_limit, None);
+	assert_eq!(
+		options.max_concurrent_files,
+		ExportOptions::default().max_concurrent_files
+	);
+	assert!(options.set("max-concurrent-files", "0").is_err());
+
+	assert_eq!(
+		options.set("csv-dialect", "excel-france").unwrap_err(),
+		format!(
+			"unknown csv dialect excel-france, expected one of: {}",
+			CsvDialect::NAMES.join(", ")
+		)
+	);
+	assert_eq!(
+		options.set("resume", "yes").unwrap_err(),
+		"expected true or false for resume, not yes"
+	);
+	assert!(options.set("colour", "red").is_err());
+	assert!(ExportOptions::KEYS.contains(&"row-limit"));
+}
+
+#[test]
+fn csv_dialect() {
+	let excel_germany = CsvDialect::from_name("excel-germany").unwrap();
+	let mut sink = CsvSink::with_dialect(Vec::new(), excel_germany);
+	write_output(&two_days(), &mut sink, "fixture.csv", &options()).unwrap();
+	let csv = String::from_utf8(sink.into_inner()).unwrap();
+	assert_eq!(
+		csv,
+		"\u{feff}hole_number;depth_from;depth_to;meters;comment;date\r\n\
+		DH-01;0;5;5;collar;17/05/2024\r\n\
+		DH-02;5;9;4;;17/05/2024\r\n\
+		DH-01;5;10,5;5,5;;18/05/2024\r\n\
+		DH-03;0;4;4;lost core;18/05/2024\r\n"
+	);
+
+	let mut sink = CsvSink::with_dialect(Vec::new(), CsvDialect::from_name("unix").unwrap());
+	sink
+		.write_header(&["a".to_string(), "b".to_string()])
+		.unwrap();
+	sink.write_row(&["1,5", "say \"hi\""]).unwrap();
+	assert_eq!(
+		String::from_utf8(sink.into_inner()).unwrap(),
+		"a,b\n\"1,5\",\"say \"\"hi\"\"\"\n"
+	);
+	assert!(CsvDialect::from_name("excel-france").is_none());
+
+	// numbers in a format have both separators swapped
+	let formats = ExportOptions {
+		number_formats: [("meters", "#,##0.00"), ("depth_to", "0.0#")]
+			.into_iter()
+			.map(|(column, format)| (column.to_string(), NumberFormat::parse(format).unwrap()))
+			.collect(),
+		..options()
+	};
+	let mut sink = CsvSink::with_dialect(Vec::new(), excel_germany);
+	write_output(&two_days(), &mut sink, "fixture.csv", &formats).unwrap();
+	assert_eq!(
+		String::from_utf8(sink.into_inner()).unwrap(),
+		"\u{feff}hole_number;depth_from;depth_to;meters;comment;date\r\n\
+		DH-01;0;5,0;5,00;collar;17/05/2024\r\n\
+		DH-02;5;9,0;4,00;;17/05/2024\r\n\
+		DH-01;5;10,5;5,50;;18/05/2024\r\n\
+		DH-03;0;4,0;4,00;lost core;18/05/2024\r\n"
+	);
+	let mut sink = CsvSink::with_dialect(Vec::new(), excel_germany);
+	let thousands = NumberFormat::parse("#,##0.00").unwrap();
+	assert!(sink.number_formats(&[Some(&thousands)]));
+	sink.write_header(&["meters".to_string()]).unwrap();
+	sink.write_row(&["1234.5"]).unwrap();
+	assert_eq!(
+		String::from_utf8(sink.into_inner()).unwrap(),
+		"\u{feff}meters\r\n1.234,50\r\n"
+	);
+
+	// a merge reads the rows back in the same dialect
+	let output = env::temp_dir().join(format!("oxide-csv-dialect-{}.csv", process::id()));
+	let merged = ExportOptions {
+		csv_dialect: excel_germany,
+		merge_key: vec!["date".to_string(), "hole_number".to_string()],
+		..options()
+	};
+	let first = generate_output(&two_days(), output.display().to_string(), &merged);
+	let again = generate_output(&two_days(), output.display().to_string(), &merged);
+	let merged_csv = fs::read_to_string(&output);
+	for path in [&output, &output.with_extension("csv.sha256")] {
+		let _ = fs::remove_file(path);
+	}
+
+	first.unwrap();
+	again.unwrap();
+	assert_eq!(merged_csv.unwrap(), csv);
+}
+
+#[test]
+fn lookup() {
+	// the first row for a key wins
+	let planned = "hole_number,planned_depth,easting\n\
+		DH-01,120,\n\
+		\"DH-02\",80,512300\n\
+		DH-01,999,1\n";
+	let by_rig = "rig,contractor\nR1,Boart\n";
+	let options = ExportOptions {
+		lookups: vec![
+			Lookup::from_reader(planned.as_bytes(), "hole_number").unwrap(),
+			Lookup::from_reader(by_rig.as_bytes(), "rig").unwrap(),
+		],
+		..options()
+	};
+
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/lookup.csv"));
+	assert_eq!(
+		summary.warnings,
+		[
+			"lookup on rig: there's no rig column".to_string(),
+			"lookup on hole_number: no row for DH-03".to_string()
+		]
+	);
+	assert!(Lookup::from_reader(planned.as_bytes(), "hole").is_err());
+}
+
+#[test]
+fn source_columns() {
+	let options = ExportOptions {
+		source_columns: true,
+		..options()
+	};
+
+	let (csv, _) = export(&two_days(), &options);
+	assert_eq!(csv, include_str!("fixtures/source_columns.csv"));
+}
+
+#[test]
+fn sheet_per_day() {
+	let mut week = Workbook::new();
+	let days = [
+		("Mon 13-05", "13/05/2024", ["DH-01", "0", "5", "5", ""]),
+		("Notes", "", ["DH-99", "0", "1", "1", "not a report"]),
+		("14.05", "14/05/2024", ["DH-01", "5", "9", "4", "lost core"]),
+	];
+	for (sheet, date, values) in days {
+		let ws = week.add_worksheet().set_name(sheet).unwrap();
+		ws.write(0, 0, "Daily Drilling Report").unwrap();
+		ws.write(1, 0, date).unwrap();
+		write_header(ws, 3).unwrap();
+		write_values(ws, 5, &values).unwrap();
+		ws.write(6, 0, "Sub-Totals").unwrap();
+	}
+
+	let mut source = MemorySource::new();
+	source.add("Week 20.xlsx", week.save_to_buffer().unwrap());
+	let options = ExportOptions {
+		template: ReportTemplate {
+			sheets: Sheets::Days,
+			..Default::default()
+		},
+		source_columns: true,
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date,source_file,source_sheet\n\
+		DH-01,0,5,5,,13/05/2024,Week 20.xlsx,Mon 13-05\n\
+		DH-01,5,9,4,lost core,14/05/2024,Week 20.xlsx,14.05\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn sheet_patterns() {
+	// the rates tab has the same table, with prices instead of meters
+	let mut crew = Workbook::new();
+	let sheets = [
+		("Template", "", ["DH-00", "0", "0", "0", ""]),
+		("Day 1", "13/05/2024", ["DH-01", "0", "5", "5", ""]),
+		("Rates", "", ["DH-01", "0", "100", "250", "per meter"]),
+		("day 2", "14/05/2024", ["DH-01", "5", "9", "4", "lost core"]),
+	];
+	for (sheet, date, values) in sheets {
+		let ws = crew.add_worksheet().set_name(sheet).unwrap();
+		ws.write(0, 0, "Daily Drilling Report").unwrap();
+		ws.write(1, 0, date).unwrap();
+		write_header(ws, 3).unwrap();
+		write_values(ws, 5, &values).unwrap();
+		ws.write(6, 0, "Sub-Totals").unwrap();
+	}
+
+	let mut source = MemorySource::new();
+	source.add("Crew 2.xlsx", crew.save_to_buffer().unwrap());
+	let options = ExportOptions {
+		template: ReportTemplate {
+			sheet_include: vec!["*".to_string()],
+			sheet_exclude: vec!["template".to_string(), "Rates*".to_string()],
+			..Default::default()
+		},
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,,13/05/2024\n\
+		DH-01,5,9,4,lost core,14/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+	// or only the ones named like a day's
+	let days = ExportOptions {
+		template: ReportTemplate {
+			sheet_include: vec!["Day ?".to_string()],
+			..Default::default()
+		},
+		..options
+	};
+	let (_, summary) = export(&source, &days);
+	assert_eq!(summary.total_rows(), 2);
+}
+
+#[test]
+fn merged_headers() {
+	// three header rows, "Depth" spans four columns and "Start" and "End"
+	// two each below it
+	let data = workbook("DDR_2024-06-01", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "01/06/2024")?;
+		ws.write(3, 0, "Hole Number")?;
+		ws.merge_range(3, 1, 3, 4, "Depth", &Format::new())?;
+		ws.write(3, 5, "Bit")?;
+		ws.merge_range(4, 1, 4, 2, "Start", &Format::new())?;
+		ws.merge_range(4, 3, 4, 4, "End", &Format::new())?;
+		ws.write_row(5, 1, ["From", "To", "From", "To"])?;
+		write_values(ws, 6, &["DH-07", "0", "1.5", "12", "14.5", "PQ"])?;
+		ws.write(7, 0, "Sub-Totals")?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-06-01.xlsx", data);
+	let options = ExportOptions {
+		template: ReportTemplate {
+			header_rows: 3,
+			..Default::default()
+		},
+		..options()
+	};
+
+	let (csv, _) = export(&source, &options);
+	assert_eq!(csv, include_str!("fixtures/merged_headers.csv"));
+}
+
+#[test]
+fn row_limit() {
+	let options = ExportOptions {
+		row_limit: Some(1),
+		..options()
+	};
+	let (csv, summary) = export(&two_days(), &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,collar,17/05/2024\n\
+		DH-01,5,10.5,5.5,,18/05/2024\n"
+	);
+	assert_eq!(summary.total_rows(), 2);
+}
+
+#[test]
+fn sample_check() {
+	let mut source = two_days();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			Ok(())
+		}),
+	);
+	// a report whose date was never filled in, with a column the rest don't
+	// have
+	source.add(
+		"DDR_2024-05-20.xlsx",
+		workbook("DDR_2024-05-20", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "dd/mm/yyyy")?;
+			write_header(ws, 3)?;
+			ws.write(3, 5, "Bit")?;
+			write_values(ws, 5, &["DH-04", "0", "3", "3", "", "HQ"])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			Ok(())
+		}),
+	);
+
+	let report = check_sample(&source, &options(), 2, 7).unwrap();
+	assert_eq!(report.files, 4);
+	assert_eq!(report.sampled.len(), 2);
+	assert_eq!(report.failed.len(), 1);
+	assert_eq!(report.failed[0].0, "DDR_2024-05-19");
+	assert_eq!(report.other_layouts, ["DDR_2024-05-20"]);
+	assert_eq!(report.bad_dates, ["DDR_2024-05-20"]);
+	assert_eq!(report.confidence(), 0.25);
+
+	// every file's rows are read when the sample is as big as the folder
+	let report = check_sample(&two_days(), &options(), 10, 7).unwrap();
+	assert_eq!(report.rows, 4);
+	assert_eq!(report.confidence(), 1.0);
+}
+
+// a folder whose workbooks are open in excel for the first few reads
+struct Locked {
+	source: MemorySource,
+	reads: Mutex<usize>,
+	locked_for: usize,
+}
+
+impl SpreadsheetSource for Locked {
+	fn list(&self) -> Result<Vec<WorkbookFile>, OxideError> {
+		self.source.list()
+	}
+
+	fn reader(&self, path: &Path) -> Result<Box<dyn ReadSeek>, OxideError> {
+		let mut reads = self.reads.lock().unwrap();
+		*reads += 1;
+		match *reads > self.locked_for {
+			true => self.source.reader(path),
+			false => Err(OxideError::Io {
+				path: path.to_path_buf(),
+				source: io::Error::from(io::ErrorKind::ResourceBusy),
+			}),
+		}
+	}
+}
+
+#[test]
+fn locked_files() {
+	let locked = |locked_for| Locked {
+		source: MemorySource::new(),
+		reads: Mutex::new(0),
+		locked_for,
+	};
+	let options = ExportOptions {
+		max_concurrent_files: 1,
+		open_retries: OpenRetries {
+			retries: 2,
+			delay: Duration::from_millis(1),
+		},
+		// the locked reads are counted for the workbooks themselves, not for
+		// hashing them
+		skip_identical: false,
+		..options()
+	};
+
+	// closed again before the retries run out
+	let mut source = locked(2);
+	source.source = two_days();
+	let (_, summary) = export(&source, &options);
+	assert_eq!(summary.total_rows(), 4);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+	// still open, so that workbook is skipped and the other one read
+	let mut source = locked(3);
+	source.source = two_days();
+	let (_, summary) = export(&source, &options);
+	assert_eq!(summary.total_rows(), 2);
+	assert!(
+		summary.warnings[0].ends_with("DDR_2024-05-17.xlsx is in use by another program, like excel"),
+		"{:?}",
+		summary.warnings
+	);
+}
+
+#[test]
+fn unsettled_files() {
+	// a workbook onedrive has only just started downloading
+	let mut source = two_days();
+	source.add("DDR_2024-05-19.xlsx", Vec::new());
+	let (_, summary) = export(&source, &options());
+	assert_eq!(summary.total_rows(), 4);
+	assert_eq!(
+		summary.warnings,
+		["DDR_2024-05-19: skipped, it's still being written or synced, it's left for the next export"]
+	);
+
+	let now = SystemTime::now();
+	let file = |modified, size| WorkbookFile {
+		path: "DDR_2024-05-19.xlsx".into(),
+		modified,
+		size,
+	};
+	let settle_time = Duration::from_secs(5);
+	assert!(file(now - Duration::from_secs(60), Some(9000)).settled(now, settle_time));
+	assert!(file(now - Duration::from_secs(60), None).settled(now, settle_time));
+	assert!(
+		file(now - Duration::from_secs(2), Some(9000))
+			.settled(now, settle_time)
+			.not()
+	);
+	assert!(
+		file(now - Duration::from_secs(60), Some(0))
+			.settled(now, settle_time)
+			.not()
+	);
+}
+
+#[test]
+fn identical_files() {
+	// the same report dropped in the weekly folder as well, copied rather
+	// than written again so they can't be saved a second apart
+	let mut source = two_days();
+	let mut copy = Vec::new();
+	source
+		.reader(Path::new("DDR_2024-05-18.xlsx"))
+		.unwrap()
+		.read_to_end(&mut copy)
+		.unwrap();
+	source.add("weekly/DDR_2024-05-18.xlsx", copy);
+	let (_, summary) = export(&source, &options());
+	assert_eq!(summary.total_rows(), 4);
+	assert_eq!(
+		summary.identical_files,
+		[IdenticalFile {
+			path: "weekly/DDR_2024-05-18.xlsx".into(),
+			same_as: "DDR_2024-05-18.xlsx".into(),
+		}]
+	);
+
+	let options = ExportOptions {
+		skip_identical: false,
+		..options()
+	};
+	let (_, summary) = export(&source, &options);
+	assert_eq!(summary.total_rows(), 6);
+	assert!(summary.identical_files.is_empty());
+}
+
+#[test]
+fn missing_markers() {
+	let mut source = two_days();
+	// no table at all, only the title
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "19/05/2024")?;
+			ws.write(3, 0, "no drilling, public holiday")?;
+			Ok(())
+		}),
+	);
+	// a table without sub-totals runs to the end of the sheet
+	source.add(
+		"DDR_2024-05-20.xlsx",
+		workbook("DDR_2024-05-20", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "20/05/2024")?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-04", "0", "3", "3", "no sub-totals"])?;
+			Ok(())
+		}),
+	);
+	// the sheet isn't named after the file, so there's no report to read
+	source.add(
+		"DDR_2024-05-21.xlsx",
+		daily_report("Sheet1", "21/05/2024", &[["DH-05", "0", "1", "1", ""]]),
+	);
+
+	let (csv, summary) = export(&source, &options());
+	assert_eq!(csv, include_str!("fixtures/missing_markers.csv"));
+	assert_eq!(summary.files.len(), 4);
+	assert_eq!(summary.warnings.len(), 2, "{:?}", summary.warnings);
+	assert!(summary.warnings[0].starts_with("DDR_2024-05-19: skipped, "));
+	assert!(summary.warnings[0].contains("Hole Number"));
+	assert_eq!(
+		summary.warnings[1],
+		"DDR_2024-05-21: worksheet \"DDR_2024-05-21\" not found in DDR_2024-05-21.xlsx"
+	);
+}
+
+#[test]
+fn weird_dates() {
+	let date_format = Format::new().set_num_format("dd/mm/yyyy");
+	let datetime_format = Format::new().set_num_format("dd/mm/yyyy hh:mm");
+	let mut source = MemorySource::new();
+	let mut add = |name: &str, build: &dyn Fn(&mut Worksheet) -> Result<(), XlsxError>| {
+		let data = workbook(name, |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			build(ws)?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-01", "0", "1", "1", ""])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			Ok(())
+		});
+		source.add(format!("{}.xlsx", name), data);
+	};
+
+	// a real date cell rather than text. only text is read as the report's
+	// date, date cells come out empty
+	add("DDR_a_date_cell", &|ws| {
+		let date = ExcelDateTime::from_ymd(2024, 5, 17)?;
+		ws.write_datetime_with_format(1, 0, &date, &date_format)?;
+		Ok(())
+	});
+	// a date with the time the shift started
+	add("DDR_b_datetime_cell", &|ws| {
+		let date = ExcelDateTime::from_ymd(2024, 5, 17)?.and_hms(6, 30, 0)?;
+		ws.write_datetime_with_format(1, 0, &date, &datetime_format)?;
+		Ok(())
+	});
+	// an unformatted serial number, written as is
+	add("DDR_c_serial", &|ws| {
+		ws.write(1, 0, 45429)?;
+		Ok(())
+	});
+	// american order, typed as text and written as typed
+	add("DDR_d_text", &|ws| {
+		ws.write(1, 0, "5/17/24")?;
+		Ok(())
+	});
+	// nobody filled it in
+	add("DDR_e_empty", &|_| Ok(()));
+
+	let (csv, summary) = export(&source, &options());
+	assert_eq!(csv, include_str!("fixtures/weird_dates.csv"));
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn time_cells() {
+	let time_format = Format::new().set_num_format("hh:mm");
+	let datetime_format = Format::new().set_num_format("dd/mm/yyyy hh:mm");
+	let duration_format = Format::new().set_num_format("[h]:mm");
+	let report = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["DH-01", "0", "1", "1", ""])?;
+		ws.write_datetime_with_format(5, 4, ExcelDateTime::from_hms(6, 30, 0)?, &time_format)?;
+		write_values(ws, 6, &["DH-02", "1", "2", "1", ""])?;
+		let shift_end = ExcelDateTime::from_ymd(2024, 5, 17)?.and_hms(4, 15, 0)?;
+		ws.write_datetime_with_format(6, 4, &shift_end, &datetime_format)?;
+		write_values(ws, 7, &["DH-03", "2", "3", "1", ""])?;
+		ws.write_number_with_format(7, 4, 1.25, &duration_format)?;
+		ws.write(8, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", report);
+
+	let (csv, _) = export(&source, &options());
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,1,1,06:30,17/05/2024\n\
+		DH-02,1,2,1,2024-05-17 04:15,17/05/2024\n\
+		DH-03,2,3,1,30:00,17/05/2024\n"
+	);
+
+	// the shift ended in perth, written in utc with the offset
+	let options = ExportOptions {
+		date_times: DateTimes::new("%Y-%m-%d", "%H:%M", "%Y-%m-%dT%H:%M%:z", "+08:00", "Z").unwrap(),
+		..options()
+	};
+	let (csv, _) = export(&source, &options);
+	assert!(csv.contains("DH-01,0,1,1,06:30,"), "{}", csv);
+	assert!(
+		csv.contains("DH-02,1,2,1,2024-05-16T20:15+00:00,"),
+		"{}",
+		csv
+	);
+	assert!(DateTimes::new("%Y-%m-%d", "%H:%M", "%Q", "", "").is_err());
+	assert!(DateTimes::new("%Y-%m-%d", "%H:%M", "%H:%M", "+08:00", "").is_err());
+	assert!(DateTimes::new("%Y-%m-%d", "%H:%M", "%H:%M", "perth", "Z").is_err());
+}
+
+#[test]
+fn date_cells() {
+	// a date typed into the comment column, shown the way the sheet's locale
+	// writes it but kept as excel's day count
+	let date_format = Format::new().set_num_format("dd/mm/yyyy");
+	let report = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["DH-01", "0", "1", "1", ""])?;
+		ws.write_datetime_with_format(5, 4, ExcelDateTime::from_ymd(2024, 5, 16)?, &date_format)?;
+		ws.write(6, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", report);
+
+	let (csv, _) = export(&source, &options());
+	assert!(csv.contains("DH-01,0,1,1,2024-05-16,"), "{}", csv);
+
+	let options = ExportOptions {
+		date_times: DateTimes::new("%d/%m/%Y", "%H:%M", "%Y-%m-%d %H:%M", "+08:00", "Z").unwrap(),
+		..options()
+	};
+	let (csv, _) = export(&source, &options);
+	assert!(csv.contains("DH-01,0,1,1,16/05/2024,"), "{}", csv);
+}
+
+// the workbook with its shared strings rewritten, for writing strings
+// rust_xlsxwriter wouldn't
+fn replace_strings(workbook: Vec<u8>, replacements: &[(&str, &str)]) -> Vec<u8> {
+	let mut archive = zip::ZipArchive::new(io::Cursor::new(workbook)).unwrap();
+	let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+	for i in 0..archive.len() {
+		let mut file = archive.by_index(i).unwrap();
+		let mut contents = String::new();
+		file.read_to_string(&mut contents).unwrap();
+		if file.name() == "xl/sharedStrings.xml" {
+			for (from, to) in replacements {
+				assert!(contents.contains(from), "{}", contents);
+				contents = contents.replace(from, to);
+			}
+		}
+
+		let options = zip::write::SimpleFileOptions::default();
+		writer.start_file(file.name(), options).unwrap();
+		writer.write_all(contents.as_bytes()).unwrap();
+	}
+
+	writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn rich_text() {
+	// a bold hole number in front of the rest of the comment, one piece
+	// without formatting of its own and another with, and a line break
+	// excel escapes
+	let report = daily_report(
+		"DDR_2024-05-17",
+		"17/05/2024",
+		&[
+			["DH-01", "0", "1", "1", "first comment"],
+			["DH-02", "1", "2", "1", "second comment"],
+		],
+	);
+	let report = replace_strings(
+		report,
+		&[
+			(
+				"<t>first comment</t>",
+				"<t>DH-01: </t><r><rPr><b/></rPr><t>lost circulation</t></r>",
+			),
+			(
+				"<t>second comment</t>",
+				"<r><rPr><b/></rPr><t>stuck_x000D_</t></r><r><t xml:space=\"preserve\">\n freed_x005F_x000D_</t></r>",
+			),
+		],
+	);
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", report);
+
+	let (csv, _) = export(&source, &options());
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,1,1,DH-01: lost circulation,17/05/2024\n\
+		DH-02,1,2,1,stuck\r\n freed_x000D_,17/05/2024\n"
+	);
+}
+
+#[test]
+fn hyperlinks() {
+	// hole numbers linked to their photos, one to another sheet
+	let report = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["", "0", "1", "1", ""])?;
+		ws.write(
+			5,
+			0,
+			Url::new("https://photos.example.com/DH-01").set_text("DH-01"),
+		)?;
+		write_values(ws, 6, &["", "1", "2", "1", ""])?;
+		ws.write(6, 0, Url::new("internal:Photos!A1").set_text("DH-02"))?;
+		write_values(ws, 7, &["DH-03", "2", "3", "1", ""])?;
+		ws.write(8, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", report);
+
+	let (csv, _) = export(&source, &options());
+	assert!(csv.contains("\nDH-01,0,1,1,,17/05/2024\n"), "{}", csv);
+
+	let options_with = |hyperlinks| ExportOptions {
+		template: ReportTemplate {
+			hyperlinks,
+			..Default::default()
+		},
+		..options()
+	};
+	let (csv, _) = export(&source, &options_with(Hyperlinks::Url));
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		https://photos.example.com/DH-01,0,1,1,,17/05/2024\n\
+		#Photos!A1,1,2,1,,17/05/2024\n\
+		DH-03,2,3,1,,17/05/2024\n"
+	);
+
+	let (csv, _) = export(&source, &options_with(Hyperlinks::Both));
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,hole_number_url,date\n\
+		DH-01,0,1,1,,https://photos.example.com/DH-01,17/05/2024\n\
+		DH-02,1,2,1,,#Photos!A1,17/05/2024\n\
+		DH-03,2,3,1,,,17/05/2024\n"
+	);
+}
+
+#[test]
+fn voided_rows() {
+	// the supervisor voided DH-02 by filling it in red, and highlighted a
+	// comment in yellow
+	let red = Format::new().set_background_color(Color::Red);
+	let yellow = Format::new().set_background_color(Color::Yellow);
+	let report = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["DH-01", "0", "1", "1", ""])?;
+		ws.write_with_format(5, 4, "check core", &yellow)?;
+		write_values(ws, 6, &["", "1", "2", "1", ""])?;
+		ws.write_with_format(6, 0, "DH-02", &red)?;
+		write_values(ws, 7, &["DH-03", "2", "3", "1", ""])?;
+		ws.write(8, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", report);
+
+	let options_with = |voided_fill: &str, voided_rows| ExportOptions {
+		template: ReportTemplate {
+			voided_fill: voided_fill.to_string(),
+			voided_rows,
+			..Default::default()
+		},
+		..options()
+	};
+	let (csv, _) = export(&source, &options_with("#ff0000", VoidedRows::Skip));
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,1,1,check core,17/05/2024\n\
+		DH-03,2,3,1,,17/05/2024\n"
+	);
+
+	let (csv, _) = export(&source, &options_with("FFFF0000", VoidedRows::Flag));
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,voided,date\n\
+		DH-01,0,1,1,check core,false,17/05/2024\n\
+		DH-02,1,2,1,,true,17/05/2024\n\
+		DH-03,2,3,1,,false,17/05/2024\n"
+	);
+
+	// only the first cell's fill counts
+	let (csv, _) = export(&source, &options_with("FFFF00", VoidedRows::Skip));
+	assert!(csv.contains("DH-01,0,1,1,check core,"), "{}", csv);
+	assert!(
+		ReportTemplate {
+			voided_fill: "red".to_string(),
+			..Default::default()
+		}
+		.validate()
+		.is_err()
+	);
+}
+
+#[test]
+fn named_table() {
+	// the table's first header isn't the marker, and notes are kept beside it
+	let mut workbook = Workbook::new();
+	let ws = workbook.add_worksheet().set_name("DDR_2024-05-17").unwrap();
+	ws.write(0, 0, "Daily Drilling Report").unwrap();
+	ws.write(1, 0, "17/05/2024").unwrap();
+	write_header(ws, 3).unwrap();
+	ws.write(3, 0, "Hole").unwrap();
+	ws.write(3, 6, "Notes").unwrap();
+	write_values(ws, 5, &["DH-01", "0", "1", "1", ""]).unwrap();
+	write_values(ws, 6, &["DH-02", "1", "2", "1", "collar"]).unwrap();
+	ws.write(6, 6, "call the geologist").unwrap();
+	ws.write(7, 0, "Sub-Totals").unwrap();
+	workbook
+		.define_name("DailyData", "='DDR_2024-05-17'!$A$4:$E$8")
+		.unwrap();
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", workbook.save_to_buffer().unwrap());
+
+	let options = ExportOptions {
+		template: ReportTemplate {
+			data_name: "dailydata".to_string(),
+			..Default::default()
+		},
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,1,1,,17/05/2024\n\
+		DH-02,1,2,1,collar,17/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+	// workbooks that don't define it still need the marker
+	let (_, summary) = export(&two_days(), &options);
+	assert_eq!(summary.total_rows(), 4);
+}
+
+#[test]
+fn excel_table() {
+	// the table's headers aren't the template's, and there's no marker
+	let mut workbook = Workbook::new();
+	let ws = workbook.add_worksheet().set_name("DDR_2024-05-17").unwrap();
+	ws.write(0, 0, "Daily Drilling Report").unwrap();
+	ws.write(1, 0, "17/05/2024").unwrap();
+	ws.write(2, 7, "checked by").unwrap();
+	let columns = ["Hole ID", "From", "To", "Meters", "Comment"]
+		.map(|header| TableColumn::new().set_header(header));
+	let table = Table::new().set_name("DailyData").set_columns(&columns);
+	ws.add_table(3, 0, 5, 4, &table).unwrap();
+	write_values(ws, 4, &["DH-01", "0", "1", "1", ""]).unwrap();
+	write_values(ws, 5, &["DH-02", "1", "2", "1", "collar"]).unwrap();
+	ws.write(6, 0, "Sub-Totals").unwrap();
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", workbook.save_to_buffer().unwrap());
+
+	for name in ["dailydata", "*"] {
+		let options = ExportOptions {
+			template: ReportTemplate {
+				data_table: name.to_string(),
+				..Default::default()
+			},
+			..options()
+		};
+		let (csv, summary) = export(&source, &options);
+		assert_eq!(
+			csv,
+			"hole_id,from,to,meters,comment,date\n\
+			DH-01,0,1,1,,17/05/2024\n\
+			DH-02,1,2,1,collar,17/05/2024\n"
+		);
+		assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+	}
+
+	// workbooks without the table still need the marker
+	let options = ExportOptions {
+		template: ReportTemplate {
+			data_table: "DailyData".to_string(),
+			..Default::default()
+		},
+		..options()
+	};
+	let (_, summary) = export(&two_days(), &options);
+	assert_eq!(summary.total_rows(), 4);
+}
+
+#[test]
+fn data_range() {
+	// no marker, and a row below the range that isn't the report's
+	let data = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		ws.write(3, 0, "Hole")?;
+		write_values(ws, 5, &["DH-01", "0", "1", "1", ""])?;
+		write_values(ws, 6, &["DH-02", "1", "2", "1", "collar"])?;
+		write_values(ws, 8, &["Checked", "", "", "", "JS"])?;
+		Ok(())
+	});
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", data);
+
+	let options = ExportOptions {
+		template: ReportTemplate {
+			data_range: "$A$4:$E$7".to_string(),
+			..Default::default()
+		},
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,1,1,,17/05/2024\n\
+		DH-02,1,2,1,collar,17/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+
+	let template = ReportTemplate {
+		data_range: "A7:N".to_string(),
+		..Default::default()
+	};
+	assert!(template.validate().is_err());
+}
+
+#[test]
+fn mixed_layouts() {
+	// a newer report format adds a bit column and drops the comment
+	let newer = workbook("DDR_2024-05-19", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "19/05/2024")?;
+		ws.write(3, 0, "Hole Number")?;
+		ws.merge_range(3, 1, 3, 2, "Depth", &Format::new())?;
+		ws.write(3, 3, "Meters")?;
+		ws.write(3, 4, "Bit")?;
+		ws.write_row(4, 1, ["From", "To"])?;
+		write_values(ws, 5, &["DH-03", "4", "8", "4", "HQ"])?;
+		ws.write(6, 0, "Sub-Totals")?;
+		Ok(())
+	});
+
+	let mut source = two_days();
+	source.add("DDR_2024-05-19.xlsx", newer);
+	let (csv, _) = export(&source, &options());
+	assert_eq!(csv, include_str!("fixtures/mixed_layouts.csv"));
+}
+
+#[test]
+fn template() {
+	// a single header row, the date in the top right corner and remarks
+	// ending the table instead of sub-totals
+	let data = workbook("Shift Report", |ws| {
+		ws.write(0, 3, "2024-05-17")?;
+		ws.write_row(2, 0, ["Hole", "From (m)", "To (m)", "Remark"])?;
+		write_values(ws, 3, &["DH-09", "0", "6", "start"])?;
+		write_values(ws, 4, &["DH-09", "6", "9", ""])?;
+		ws.write(5, 0, "Notes")?;
+		write_values(ws, 6, &["DH-09", "9", "9.5", "after notes"])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("Shift Report.xlsx", data);
+	let mut template = ReportTemplate {
+		data_start: "Hole".to_string(),
+		remarks_start: "Notes".to_string(),
+		remarks: Remarks::End,
+		header_rows: 1,
+		date_cell: "D1".to_string(),
+		..Default::default()
+	};
+	template
+		.columns
+		.insert("hole".to_string(), "hole_number".to_string());
+	template
+		.columns
+		.insert("from_(m)".to_string(), "depth_from".to_string());
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, _) = export(&source, &options);
+	assert_eq!(csv, include_str!("fixtures/template.csv"));
+}
+
+#[test]
+fn header_aliases() {
+	// another contractor's report calls the depths an interval
+	let other = workbook("DDR_2024-05-19", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "19/05/2024")?;
+		ws.write(3, 0, "Hole Number")?;
+		ws.merge_range(3, 1, 3, 2, "Interval", &Format::new())?;
+		ws.write(3, 3, "Meters")?;
+		ws.write(3, 4, "Comment")?;
+		ws.write_row(4, 1, ["Start", "End"])?;
+		write_values(ws, 5, &["DH-03", "4", "8", "4", ""])?;
+		ws.write(6, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = two_days();
+	source.add("DDR_2024-05-19.xlsx", other);
+
+	let aliases = BTreeMap::from([
+		("depth_from".to_string(), vec!["Interval Start".to_string()]),
+		("depth_to".to_string(), vec!["Interval-End".to_string()]),
+	]);
+	let mut template = ReportTemplate::default();
+	template.add_aliases(&aliases, &HeaderStyle::default());
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, _) = export(&source, &options);
+	assert!(
+		csv.starts_with("hole_number,depth_from,depth_to,meters,comment,date\n"),
+		"{}",
+		csv
+	);
+	assert!(csv.ends_with("DH-03,4,8,4,,19/05/2024\n"), "{}", csv);
+}
+
+#[test]
+fn header_matches() {
+	// a typo in one report's header, and a column that's new for real
+	let typo = workbook("DDR_2024-05-19", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "19/05/2024")?;
+		write_header(ws, 3)?;
+		ws.write(3, 3, "Metres")?;
+		ws.write(3, 5, "Bit")?;
+		write_values(ws, 5, &["DH-03", "4", "8", "4", "", "HQ"])?;
+		ws.write(6, 0, "Sub-Totals")?;
+		Ok(())
+	});
+	let mut source = two_days();
+	source.add("DDR_2024-05-19.xlsx", typo);
+
+	let (csv, summary) = export(&source, &options());
+	assert!(csv.starts_with("hole_number,depth_from,depth_to,meters,comment,metres,bit,date\n"));
+	assert_eq!(
+		summary.header_matches,
+		[HeaderMatch {
+			column: "metres".to_string(),
+			similar_to: "meters".to_string(),
+		}]
+	);
+}
+
+#[test]
+fn shift_cell() {
+	let report = |sheet: &str, date: &str, shift: &str, rows: &[[&str; 5]]| {
+		workbook(sheet, |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, date)?;
+			ws.write(1, 1, shift)?;
+			write_header(ws, 3)?;
+			for (row, values) in rows.iter().enumerate() {
+				write_values(ws, 5 + row as u32, values)?;
+			}
+
+			Ok(())
+		})
+	};
+
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-17.xlsx",
+		report(
+			"DDR_2024-05-17",
+			"17/05/2024",
+			"Shift: D",
+			&[["DH-01", "0", "5", "5", ""]],
+		),
+	);
+	source.add(
+		"DDR_2024-05-17N.xlsx",
+		report(
+			"DDR_2024-05-17N",
+			"17/05/2024",
+			"Night Shift",
+			&[["DH-01", "5", "9", "4", ""]],
+		),
+	);
+	source.add(
+		"DDR_2024-05-18.xlsx",
+		report(
+			"DDR_2024-05-18",
+			"18/05/2024",
+			"Swing",
+			&[["DH-02", "0", "3", "3", ""]],
+		),
+	);
+	let options = ExportOptions {
+		template: ReportTemplate {
+			shift_cell: "B2".to_string(),
+			..Default::default()
+		},
+		..options()
+	};
+
+	// day and night reports for the same date aren't duplicates
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,shift,date\n\
+		DH-01,0,5,5,,day,17/05/2024\n\
+		DH-01,5,9,4,,night,17/05/2024\n\
+		DH-02,0,3,3,,Swing,18/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn metadata_cells() {
+	let data = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(0, 2, "Rig")?;
+		ws.write(0, 3, "RC-07")?;
+		ws.write(1, 0, "17/05/2024")?;
+		ws.write(1, 2, "Client")?;
+		ws.write(1, 3, "Northern Gold")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["DH-01", "0", "5", "5", ""])?;
+		write_values(ws, 6, &["DH-02", "5", "9", "4", ""])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", data);
+	let mut template = ReportTemplate::default();
+	for (column, cell) in [("rig", "D1"), ("client", "D2"), ("supervisor", "D3")] {
+		template
+			.metadata
+			.insert(column.to_string(), cell.to_string());
+	}
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, _) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,client,rig,supervisor,date\n\
+		DH-01,0,5,5,,Northern Gold,RC-07,,17/05/2024\n\
+		DH-02,5,9,4,,Northern Gold,RC-07,,17/05/2024\n"
+	);
+}
+
+#[test]
+fn subtotals() {
+	let data = workbook("DDR_2024-05-17", |ws| {
+		ws.write(0, 0, "Daily Drilling Report")?;
+		ws.write(1, 0, "17/05/2024")?;
+		write_header(ws, 3)?;
+		write_values(ws, 5, &["DH-01", "0", "5", "5", ""])?;
+		write_values(ws, 6, &["DH-02", "5", "9", "4", ""])?;
+		// a row typed in below the sub-totals, after they were added up
+		write_values(ws, 7, &["Sub-Totals", "", "", "12.5", "2 holes"])?;
+		write_values(ws, 8, &["DH-03", "0", "3.5", "3.5", ""])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("DDR_2024-05-17.xlsx", data);
+	let (_, summary) = export(&source, &options());
+	assert_eq!(
+		summary.warnings,
+		[
+			"DDR_2024-05-17: sub-totals: meters adds up to 9, but the Sub-Totals row says 12.5"
+				.to_string()
+		]
+	);
+
+	let options = ExportOptions {
+		template: ReportTemplate {
+			check_subtotals: false,
+			..Default::default()
+		},
+		..options()
+	};
+	let (_, summary) = export(&source, &options);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn file_date() {
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		daily_report("DDR_2024-05-19", "", &[["DH-01", "0", "5", "5", ""]]),
+	);
+	// copied from the day before without updating the date
+	source.add(
+		"DDR_2024-05-20.xlsx",
+		daily_report(
+			"DDR_2024-05-20",
+			"19/05/2024",
+			&[["DH-01", "5", "9", "4", ""]],
+		),
+	);
+	let mut template = ReportTemplate {
+		file_date: r"DDR_(\d{4}-\d{2}-\d{2})".to_string(),
+		..Default::default()
+	};
+	template.types.insert("date".to_string(), ValueType::Date);
+	let options = ExportOptions {
+		template: template.clone(),
+		..options()
+	};
+
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,,2024-05-19\n\
+		DH-01,5,9,4,,2024-05-19\n"
+	);
+	assert_eq!(
+		summary.warnings[0],
+		"DDR_2024-05-20: the date cell says 19/05/2024, but the file name says 2024-05-20"
+	);
+
+	let options = ExportOptions {
+		template: ReportTemplate {
+			file_date_mode: FileDate::Override,
+			..template
+		},
+		..options
+	};
+	let (csv, _) = export(&source, &options);
+	assert!(csv.ends_with("DH-01,5,9,4,,2024-05-20\n"), "{}", csv);
+}
+
+#[test]
+fn hole_ids() {
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-17.xlsx",
+		daily_report(
+			"DDR_2024-05-17",
+			"17/05/2024",
+			&[
+				["DH 12", "0", "5", "5", ""],
+				["dh-012", "5", "9", "4", ""],
+				["DDH7", "0", "3", "3", ""],
+				["RC07A", "0", "2", "2", ""],
+			],
+		),
+	);
+	let mut hole_ids = HoleIds::default();
+	hole_ids
+		.prefixes
+		.insert("ddh".to_string(), "DH".to_string());
+	let options = ExportOptions {
+		hole_ids: Some(hole_ids),
+		..options()
+	};
+
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-012,0,5,5,,17/05/2024\n\
+		DH-012,5,9,4,,17/05/2024\n\
+		DH-007,0,3,3,,17/05/2024\n\
+		RC07A,0,2,2,,17/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn template_rules() {
+	// another contractor's reports, in a folder of their own
+	let data = workbook("ACME 17-05", |ws| {
+		ws.write(0, 0, "17/05/2024")?;
+		ws.write_row(2, 0, ["Hole", "From (m)", "To (m)", "Remark"])?;
+		write_values(ws, 3, &["DH-09", "0", "6", "start"])?;
+		Ok(())
+	});
+
+	let mut source = two_days();
+	source.add("acme/ACME 17-05.xlsx", data);
+	let mut acme = ReportTemplate {
+		data_start: "Hole".to_string(),
+		header_rows: 1,
+		date_cell: "A1".to_string(),
+		..Default::default()
+	};
+	for (header, renamed) in [
+		("hole", "hole_number"),
+		("from_(m)", "depth_from"),
+		("to_(m)", "depth_to"),
+		("remark", "comment"),
+	] {
+		acme.columns.insert(header.to_string(), renamed.to_string());
+	}
+
+	let options = ExportOptions {
+		template_rules: vec![
+			TemplateRule {
+				pattern: "DDR_*.xls".to_string(),
+				template: acme.clone(),
+			},
+			TemplateRule {
+				pattern: "ACME/*.xlsx".to_string(),
+				template: acme,
+			},
+		],
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	let mut expected = include_str!("fixtures/default_layout.csv").to_string();
+	expected.push_str("DH-09,0,6,,start,17/05/2024\n");
+	assert_eq!(csv, expected);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn coordinates() {
+	let data = workbook("Collars", |ws| {
+		ws.write(0, 0, "17/05/2024")?;
+		ws.write_row(
+			2,
+			0,
+			["Hole", "Latitude", "Longitude", "Easting", "Northing"],
+		)?;
+		ws.write_row(
+			3,
+			0,
+			[
+				"DH-01",
+				"23°26'21\"S",
+				"133 52 30 E",
+				"412,500 mE",
+				"7405210mN",
+			],
+		)?;
+		write_values(
+			ws,
+			4,
+			&["DH-02", "-23.4392", "133.875", "412517.5", "7405198"],
+		)?;
+		ws.write_row(5, 0, ["DH-03", "N 95", "W 12.5", "near the camp", ""])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("Collars.xlsx", data);
+	let mut template = ReportTemplate {
+		data_start: "Hole".to_string(),
+		header_rows: 1,
+		date_cell: "A1".to_string(),
+		..Default::default()
+	};
+	for (column, value_type) in [
+		("latitude", ValueType::Latitude),
+		("longitude", ValueType::Longitude),
+		("easting", ValueType::Grid),
+		("northing", ValueType::Grid),
+	] {
+		template.types.insert(column.to_string(), value_type);
+	}
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole,latitude,longitude,easting,northing,date\n\
+		DH-01,-23.439167,133.875,412500,7405210,17/05/2024\n\
+		DH-02,-23.4392,133.875,412517.5,7405198,17/05/2024\n\
+		DH-03,N 95,-12.5,near the camp,,17/05/2024\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		[
+			"Collars: 1 values in latitude aren't latitude, the first in row 3: N 95".to_string(),
+			"Collars: 1 values in easting aren't grid, the first in row 3: near the camp".to_string(),
+		]
+	);
+}
+
+#[test]
+fn column_splits() {
+	let data = workbook("Core Log", |ws| {
+		ws.write(0, 0, "17/05/2024")?;
+		ws.write_row(2, 0, ["Hole", "Interval", "Bit"])?;
+		write_values(ws, 3, &["DH-09", "12.5–15.0", "HQ3 (96mm)"])?;
+		write_values(ws, 4, &["DH-09", "15–18", "NQ"])?;
+		write_values(ws, 5, &["DH-10", "", "NQ2 (76mm)"])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("Core Log.xlsx", data);
+	let mut template = ReportTemplate {
+		data_start: "Hole".to_string(),
+		header_rows: 1,
+		date_cell: "A1".to_string(),
+		splits: vec![
+			ColumnSplit {
+				column: "interval".to_string(),
+				into: vec!["depth_from".to_string(), "depth_to".to_string()],
+				delimiter: Some("–".to_string()),
+				..Default::default()
+			},
+			ColumnSplit {
+				column: "bit".to_string(),
+				into: vec!["bit_size".to_string(), "bit_mm".to_string()],
+				pattern: Some(r"(\w+) \((\d+)mm\)".to_string()),
+				keep: true,
+				..Default::default()
+			},
+		],
+		..Default::default()
+	};
+	template
+		.types
+		.insert("depth_to".to_string(), ValueType::Float);
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"hole,depth_from,depth_to,bit,bit_size,bit_mm,date\n\
+		DH-09,12.5,15,HQ3 (96mm),HQ3,96,17/05/2024\n\
+		DH-09,15,18,NQ,NQ,,17/05/2024\n\
+		DH-10,,,NQ2 (76mm),NQ2,76,17/05/2024\n"
+	);
+	assert_eq!(
+		summary.warnings,
+		["Core Log: 1 values in bit don't match its split, the first in row 2: NQ".to_string()]
+	);
+
+	let no_groups = ColumnSplit {
+		column: "bit".to_string(),
+		into: vec!["bit_size".to_string()],
+		pattern: Some(r"\w+".to_string()),
+		..Default::default()
+	};
+	let template = ReportTemplate {
+		splits: vec![no_groups],
+		..Default::default()
+	};
+	assert!(template.validate().is_err());
+}
+
+#[test]
+fn unpivot() {
+	let data = workbook("Pump Log", |ws| {
+		ws.write(0, 0, "17/05/2024")?;
+		ws.write_row(2, 0, ["Pump", "Hour 06", "Hour 07", "Hour 08", "Note"])?;
+		write_values(ws, 3, &["P-1", "12", "", "14", "ok"])?;
+		write_values(ws, 4, &["P-2", "8", "9", "", ""])?;
+		Ok(())
+	});
+
+	let mut source = MemorySource::new();
+	source.add("Pump Log.xlsx", data);
+	let template = ReportTemplate {
+		data_start: "Pump".to_string(),
+		header_rows: 1,
+		date_cell: "A1".to_string(),
+		unpivot: Some(Unpivot {
+			columns: vec!["hour_06".to_string()],
+			pattern: Some(r"hour_0[78]".to_string()),
+			key: "hour".to_string(),
+			value: "flow".to_string(),
+			skip_empty: true,
+		}),
+		..Default::default()
+	};
+
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+	let (csv, summary) = export(&source, &options);
+	assert_eq!(
+		csv,
+		"pump,hour,flow,note,date\n\
+		P-1,hour_06,12,ok,17/05/2024\n\
+		P-1,hour_08,14,ok,17/05/2024\n\
+		P-2,hour_06,8,,17/05/2024\n\
+		P-2,hour_07,9,,17/05/2024\n"
+	);
+	assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+}
+
+#[test]
+fn remarks_output() {
+	let mut source = two_days();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "19/05/2024")?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-04", "0", "3", "3", ""])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			ws.write(8, 0, "Remarks")?;
+			ws.write(9, 0, "DH-04: bit changed at 3m")?;
+			ws.write(10, 0, "Safety")?;
+			ws.write(10, 2, "toolbox talk held")?;
+			ws.write(12, 0, "DH-09: not drilled yet")?;
+			Ok(())
+		}),
+	);
+	let options = ExportOptions {
+		remarks_output: true,
+		..options()
+	};
+
+	let name = format!("oxide-remarks-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let remarks = env::temp_dir().join(format!("{}.remarks.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let remarks_csv = fs::read_to_string(&remarks);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&remarks);
+
+	result.unwrap();
+	assert_eq!(
+		remarks_csv.unwrap(),
+		"date,hole,remark,file\n\
+		17/05/2024,,rig down for maintenance,DDR_2024-05-17\n\
+		18/05/2024,,rig down for maintenance,DDR_2024-05-18\n\
+		19/05/2024,DH-04,bit changed at 3m,DDR_2024-05-19\n\
+		19/05/2024,Safety,toolbox talk held,DDR_2024-05-19\n\
+		19/05/2024,,DH-09: not drilled yet,DDR_2024-05-19\n"
+	);
+}
+
+#[test]
+fn sections() {
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "19/05/2024")?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-04", "0", "3", "3", ""])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			ws.write(8, 0, "Remarks")?;
+			ws.write(9, 0, "DH-04: bit changed at 3m")?;
+			ws.write(11, 0, "Consumables")?;
+			ws.write_row(12, 0, ["Item", "", "Qty", "Unit"])?;
+			write_values(ws, 13, &["PCD bit", "", "1", "ea"])?;
+			write_values(ws, 14, &["Polymer", "", "2.5", "bags"])?;
+			ws.write(15, 0, "End Consumables")?;
+			ws.write(16, 0, "left out")?;
+			Ok(())
+		}),
+	);
+	let mut template = ReportTemplate::default();
+	template.sections.push(Section {
+		name: "consumables".to_string(),
+		start: "Consumables".to_string(),
+		end: "End Consumables".to_string(),
+		..Default::default()
+	});
+	let options = ExportOptions {
+		template,
+		remarks_output: true,
+		..options()
+	};
+
+	let name = format!("oxide-sections-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let remarks = env::temp_dir().join(format!("{}.remarks.csv", name));
+	let consumables = env::temp_dir().join(format!("{}.consumables.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let remarks_csv = fs::read_to_string(&remarks);
+	let consumables_csv = fs::read_to_string(&consumables);
+	for path in [&output, &remarks, &consumables] {
+		let _ = fs::remove_file(path);
+	}
+
+	result.unwrap();
+	assert_eq!(
+		consumables_csv.unwrap(),
+		"item,qty,unit,date,file\n\
+		PCD bit,1,ea,19/05/2024,DDR_2024-05-19\n\
+		Polymer,2.5,bags,19/05/2024,DDR_2024-05-19\n"
+	);
+	// the remarks end where the consumables start
+	assert_eq!(
+		remarks_csv.unwrap(),
+		"date,hole,remark,file\n\
+		19/05/2024,DH-04,bit changed at 3m,DDR_2024-05-19\n"
+	);
+}
+
+#[test]
+fn timesheet_section() {
+	let mut source = MemorySource::new();
+	for (day, crew) in [
+		(
+			"17",
+			[
+				["J. Smith", "Driller", "12", "day"],
+				["A. Lee", "Offsider", "11.5", ""],
+			],
+		),
+		(
+			"18",
+			[
+				["J. Smith", "Driller", "10", ""],
+				["K. Ng", "Offsider", "12", ""],
+			],
+		),
+	] {
+		let sheet = format!("DDR_2024-05-{}", day);
+		let data = workbook(&sheet, |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, format!("{}/05/2024", day))?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-01", "0", "5", "5", ""])?;
+			ws.write(6, 0, "Sub-Totals")?;
+			ws.write(8, 0, "Personnel")?;
+			ws.write_row(9, 0, ["Name", "Position", "Hours", "Notes"])?;
+			write_values(ws, 10, &crew[0])?;
+			write_values(ws, 11, &crew[1])?;
+			Ok(())
+		});
+		source.add(format!("{}.xlsx", sheet), data);
+	}
+
+	let mut template = ReportTemplate::default();
+	let mut timesheet = Section {
+		name: "timesheet".to_string(),
+		start: "Personnel".to_string(),
+		keep: ["date", "name", "role", "hours"].map(String::from).to_vec(),
+		..Default::default()
+	};
+	timesheet
+		.columns
+		.insert("position".to_string(), "role".to_string());
+	template.sections.push(timesheet);
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+
+	let name = format!("oxide-timesheet-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let timesheet = env::temp_dir().join(format!("{}.timesheet.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let timesheet_csv = fs::read_to_string(&timesheet);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&timesheet);
+
+	result.unwrap();
+	assert_eq!(
+		timesheet_csv.unwrap(),
+		"date,name,role,hours,file\n\
+		17/05/2024,J. Smith,Driller,12,DDR_2024-05-17\n\
+		17/05/2024,A. Lee,Offsider,11.5,DDR_2024-05-17\n\
+		18/05/2024,J. Smith,Driller,10,DDR_2024-05-18\n\
+		18/05/2024,K. Ng,Offsider,12,DDR_2024-05-18\n"
+	);
+}
+
+#[test]
+fn two_tables() {
+	// the surveys follow the drilled intervals without a sub-totals row
+	let mut source = MemorySource::new();
+	source.add(
+		"DDR_2024-05-19.xlsx",
+		workbook("DDR_2024-05-19", |ws| {
+			ws.write(0, 0, "Daily Drilling Report")?;
+			ws.write(1, 0, "19/05/2024")?;
+			write_header(ws, 3)?;
+			write_values(ws, 5, &["DH-04", "0", "30", "30", ""])?;
+			write_values(ws, 6, &["DH-04", "30", "61", "31", "broken ground"])?;
+			ws.write(8, 0, "Surveys")?;
+			ws.write_row(9, 0, ["Hole", "Depth", "Dip", "Azimuth"])?;
+			write_values(ws, 10, &["DH-04", "30", "-60", "45"])?;
+			write_values(ws, 11, &["DH-04", "60", "-59.5", "n/a"])?;
+			Ok(())
+		}),
+	);
+	let mut template = ReportTemplate::default();
+	template.sections.push(Section {
+		name: "surveys".to_string(),
+		start: "Surveys".to_string(),
+		types: [("azimuth".to_string(), ValueType::Float)].into(),
+		..Default::default()
+	});
+	let options = ExportOptions {
+		template,
+		..options()
+	};
+
+	let name = format!("oxide-two-tables-{}", process::id());
+	let output = env::temp_dir().join(format!("{}.csv", name));
+	let surveys = env::temp_dir().join(format!("{}.surveys.csv", name));
+	let result = generate_output(&source, output.display().to_string(), &options);
+	let csv = fs::read_to_string(&output);
+	let surveys_csv = fs::read_to_string(&surveys);
+	let _ = fs::remove_file(&output);
+	let _ = fs::remove_file(&surveys);
+
+	let summary = result.unwrap();
+	assert_eq!(
+		csv.unwrap(),
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-04,0,30,30,,19/05/2024\n\
+		DH-04,30,61,31,broken ground,19/05/2024\n"
+	);
+	assert_eq!(
+		surveys_csv.unwrap(),
+		"hole,depth,dip,azimuth,date,file\n\
+		DH-04,30,-60,45,19/05/2024,DDR_2024-05-19\n\
+		DH-04,60,-59.5,n/a,19/05/2024,DDR_2024-05-19\n"
+	);
+	assert_eq!(summary.warnings.len(), 1, "{:?}", summary.warnings);
+	assert!(
+		summary.warnings[0].contains("surveys: 1 values in azimuth aren't"),
+		"{:?}",
+		summary.warnings
+	);
+}
@@ -0,0 +1,8 @@
+CREATE TABLE "report" (
+	"hole_number" TEXT NOT NULL,
+	"depth_from" BIGINT NOT NULL,
+	"depth_to" DOUBLE PRECISION NOT NULL,
+	"meters" DOUBLE PRECISION NOT NULL,
+	"comment" TEXT,
+	"date" TEXT NOT NULL
+);
@@ -0,0 +1,75 @@
+/*
+ * C interface to oxide's aggregation pipeline, built as the oxide_ffi
+ * library by the oxide-ffi crate. Strings are utf-8 and nul terminated.
+ * Functions returning an int return OXIDE_OK or OXIDE_ERROR, after which
+ * oxide_last_error() says what went wrong.
+ */
+
+#ifndef OXIDE_H
+#define OXIDE_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define OXIDE_OK 0
+#define OXIDE_ERROR 1
+
+/* Settings of the exports run with them. */
+typedef struct OxideOptions OxideOptions;
+
+/*
+ * Called as each workbook is read, with its name, its index counting up
+ * from 0 and how many there are, on the thread oxide_run was called on.
+ */
+typedef void (*OxideProgress)(void *user_data, const char *file_name, size_t index, size_t total);
+
+/* The version of oxide, living as long as the library. */
+const char *oxide_version(void);
+
+/*
+ * Why the last call on this thread that failed did, or NULL. Only valid
+ * until the next call that fails.
+ */
+const char *oxide_last_error(void);
+
+/* Options with every setting at its default, freed with oxide_options_free. */
+OxideOptions *oxide_options_new(void);
+void oxide_options_free(OxideOptions *options);
+
+/*
+ * Sets one of the options by name: template (the path of a template file),
+ * header-style, preset, csv-dialect, provenance, profile, null-value,
+ * schema-sidecar, row-ids, duplicate-reports, qa-output, source-columns,
+ * remarks-output, depth-intervals, cumulative-meters, manifest, pdf-report,
+ * keep-identical, merge-key and column-order (comma separated), filter,
+ * sort-by, settle-time, max-concurrent-files, quarantine-folder, resume or
+ * row-limit, with the values `oxide config set` takes for them and an empty
+ * one for the default.
+ */
+int oxide_options_set(OxideOptions *options, const char *key, const char *value);
+
+/*
+ * Exports every workbook in input_dir to output_file, in the format matching
+ * its extension, returning once it's written. progress is called with
+ * user_data as each workbook is read, and can be NULL. The summary of the
+ * export is put in summary unless it's NULL, freed with oxide_string_free.
+ */
+int oxide_run(
+	const OxideOptions *options,
+	const char *input_dir,
+	const char *output_file,
+	OxideProgress progress,
+	void *user_data,
+	char **summary);
+
+/* Frees a string oxide handed out, doing nothing for NULL. */
+void oxide_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
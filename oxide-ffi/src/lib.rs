@@ -0,0 +1,217 @@
+//! C interface to the aggregation pipeline, for applications that embed
+//! oxide rather than running it, declared in `include/oxide.h`. Options are
+//! set one by one by name, like `oxide config set` does, and an export runs
+//! on the calling thread's behalf, reporting every workbook it reads.
+//!
+//! Functions returning an int return [`OXIDE_OK`] or [`OXIDE_ERROR`], after
+//! which [`oxide_last_error`] says what went wrong.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ops::Not;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::mpsc;
+use std::thread;
+
+use oxide_core::{ExportEvent, ExportOptions, LocalDir, generate_output};
+
+pub const OXIDE_OK: c_int = 0;
+pub const OXIDE_ERROR: c_int = 1;
+
+/// Called as each workbook is read, with its name, its index counting up
+/// from 0 and how many there are, on the thread [`oxide_run`] was called on.
+pub type OxideProgress =
+	extern "C" fn(user_data: *mut c_void, file_name: *const c_char, index: usize, total: usize);
+
+/// Settings of the exports run with them, created with
+/// [`oxide_options_new`].
+pub struct OxideOptions {
+	options: ExportOptions,
+}
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// keeps the error for oxide_last_error, returning OXIDE_ERROR to pass on
+fn fail(message: impl Into<String>) -> c_int {
+	// a message can't hold a nul, which ends it early instead
+	let message = message.into().replace('\0', " ");
+	let message = CString::new(message).unwrap_or_default();
+	LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+	OXIDE_ERROR
+}
+
+// the caller's string, failing on a null or one that isn't utf-8
+unsafe fn string<'s>(value: *const c_char, name: &str) -> Result<&'s str, String> {
+	if value.is_null() {
+		return Err(format!("{} is null", name));
+	}
+
+	// SAFETY: the caller passes a nul terminated string that outlives the call
+	let value = unsafe { CStr::from_ptr(value) };
+	value.to_str().map_err(|_| format!("{} isn't utf-8", name))
+}
+
+/// The version of oxide, as a string that lives as long as the library.
+#[unsafe(no_mangle)]
+pub extern "C" fn oxide_version() -> *const c_char {
+	concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Why the last call on this thread that failed did, or null. The string
+/// is only valid until the next call that fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn oxide_last_error() -> *const c_char {
+	LAST_ERROR.with(|last| {
+		last
+			.borrow()
+			.as_ref()
+			.map_or(ptr::null(), |message| message.as_ptr())
+	})
+}
+
+/// Options with every setting at its default, to be freed with
+/// [`oxide_options_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn oxide_options_new() -> *mut OxideOptions {
+	Box::into_raw(Box::new(OxideOptions {
+		options: ExportOptions::default(),
+	}))
+}
+
+/// Frees options created with [`oxide_options_new`], doing nothing for null.
+///
+/// # Safety
+///
+/// `options` must come from [`oxide_options_new`] and not be used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn oxide_options_free(options: *mut OxideOptions) {
+	if options.is_null().not() {
+		// SAFETY: the caller gives back what oxide_options_new handed out
+		drop(unsafe { Box::from_raw(options) });
+	}
+}
+
+/// Sets one of the options by name, like `template` (the path of a
+/// template file), `header-style`, `preset`, `csv-dialect`, `merge-key`
+/// (comma separated) or `row-limit`, with the values `oxide config set`
+/// takes for them. [`ExportOptions::KEYS`] lists them all.
+///
+/// # Safety
+///
+/// `options` must come from [`oxide_options_new`], `key` and `value` must be
+/// nul terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn oxide_options_set(
+	options: *mut OxideOptions,
+	key: *const c_char,
+	value: *const c_char,
+) -> c_int {
+	// SAFETY: the caller passes options from oxide_options_new, or null
+	let Some(options) = (unsafe { options.as_mut() }) else {
+		return fail("options is null");
+	};
+	// SAFETY: the caller passes nul terminated strings
+	let result = unsafe { string(key, "key").and_then(|key| Ok((key, string(value, "value")?))) };
+	let result = result.and_then(|(key, value)| options.options.set(key, value));
+	match result {
+		Ok(()) => OXIDE_OK,
+		Err(e) => fail(e),
+	}
+}
+
+/// Exports every workbook in `input_dir` to `output_file`, in the format
+/// matching its extension, returning once it's written. `progress` is
+/// called with `user_data` as each workbook is read, and can be null. The
+/// summary of the export is put in `summary` unless it's null, to be freed
+/// with [`oxide_string_free`].
+///
+/// # Safety
+///
+/// `options` must come from [`oxide_options_new`], `input_dir` and
+/// `output_file` must be nul terminated strings and `summary` null or
+/// writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn oxide_run(
+	options: *const OxideOptions,
+	input_dir: *const c_char,
+	output_file: *const c_char,
+	progress: Option<OxideProgress>,
+	user_data: *mut c_void,
+	summary: *mut *mut c_char,
+) -> c_int {
+	// SAFETY: the caller passes options from oxide_options_new, or null
+	let Some(options) = (unsafe { options.as_ref() }) else {
+		return fail("options is null");
+	};
+	// SAFETY: the caller passes nul terminated strings
+	let paths = unsafe {
+		string(input_dir, "input_dir")
+			.and_then(|input| Ok((input, string(output_file, "output_file")?)))
+	};
+	let (input_dir, output_file) = match paths {
+		Ok(paths) => paths,
+		Err(e) => return fail(e),
+	};
+
+	// a panic can't unwind into the caller, so it fails the export instead
+	let result = panic::catch_unwind(AssertUnwindSafe(|| {
+		let (events_tx, events) = mpsc::channel();
+		let options = ExportOptions {
+			events: Some(events_tx),
+			..options.options.clone()
+		};
+
+		thread::scope(|scope| {
+			let source = LocalDir::new(input_dir);
+			let export = scope.spawn(move || generate_output(&source, output_file.to_string(), &options));
+
+			// the channel closes once the export is done and its options dropped
+			for event in events {
+				if let (
+					Some(progress),
+					ExportEvent::FileStarted {
+						file_name,
+						index,
+						total,
+					},
+				) = (progress, event)
+				{
+					let file_name = CString::new(file_name.replace('\0', " ")).unwrap_or_default();
+					progress(user_data, file_name.as_ptr(), index, total);
+				}
+			}
+
+			export.join().unwrap_or_else(|e| panic::resume_unwind(e))
+		})
+	}));
+
+	let run_summary = match result {
+		Ok(Ok(run_summary)) => run_summary,
+		Ok(Err(e)) => return fail(e.to_string()),
+		Err(_) => return fail("the export panicked"),
+	};
+	if summary.is_null().not() {
+		let text = CString::new(run_summary.to_string().replace('\0', " ")).unwrap_or_default();
+		// SAFETY: the caller passes a pointer that can be written to
+		unsafe { *summary = text.into_raw() };
+	}
+
+	OXIDE_OK
+}
+
+/// Frees a string oxide handed out, doing nothing for null.
+///
+/// # Safety
+///
+/// `value` must come from oxide, like [`oxide_run`]'s summary, and not be
+/// used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn oxide_string_free(value: *mut c_char) {
+	if value.is_null().not() {
+		// SAFETY: the caller gives back a string made with CString::into_raw
+		drop(unsafe { CString::from_raw(value) });
+	}
+}
@@ -0,0 +1,197 @@
+use std::env;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::fs;
+use std::ops::Not;
+use std::path::PathBuf;
+use std::process;
+use std::ptr;
+
+use oxide_ffi::{
+	OXIDE_ERROR, OXIDE_OK, oxide_last_error, oxide_options_free, oxide_options_new,
+	oxide_options_set, oxide_run, oxide_string_free, oxide_version,
+};
+use rust_xlsxwriter::{Format, Workbook};
+
+// a folder holding one daily report, as the site crews fill them in
+fn fixture_folder(name: &str) -> PathBuf {
+	let dir = env::temp_dir().join(format!("oxide-ffi-{}-{}", name, process::id()));
+	fs::create_dir_all(&dir).unwrap();
+
+	let mut workbook = Workbook::new();
+	let ws = workbook.add_worksheet().set_name("DDR_2024-05-17").unwrap();
+	ws.write(0, 0, "Daily Drilling Report").unwrap();
+	ws.write(1, 0, "17/05/2024").unwrap();
+	ws.write(3, 0, "Hole Number").unwrap();
+	ws.merge_range(3, 1, 3, 2, "Depth", &Format::new()).unwrap();
+	ws.write(3, 3, "Meters").unwrap();
+	ws.write(3, 4, "Comment").unwrap();
+	ws.write(4, 1, "From").unwrap();
+	ws.write(4, 2, "To").unwrap();
+	for (row, values) in [[0.0, 5.0, 5.0], [5.0, 10.5, 5.5]].iter().enumerate() {
+		let row = 5 + row as u32;
+		ws.write(row, 0, "DH-01").unwrap();
+		for (col, value) in values.iter().enumerate() {
+			ws.write(row, 1 + col as u16, *value).unwrap();
+		}
+	}
+	ws.write(7, 0, "Sub-Totals").unwrap();
+	ws.write(9, 0, "Remarks").unwrap();
+	workbook.save(dir.join("DDR_2024-05-17.xlsx")).unwrap();
+	dir
+}
+
+fn c(value: &str) -> CString {
+	CString::new(value).unwrap()
+}
+
+fn last_error() -> String {
+	let error = oxide_last_error();
+	assert!(error.is_null().not());
+	// SAFETY: oxide_last_error hands out a nul terminated string
+	unsafe { CStr::from_ptr(error) }
+		.to_string_lossy()
+		.into_owned()
+}
+
+extern "C" fn progress(
+	user_data: *mut c_void,
+	file_name: *const c_char,
+	index: usize,
+	total: usize,
+) {
+	// SAFETY: the tests pass their Vec as user_data, and a file name
+	let files = unsafe { &mut *user_data.cast::<Vec<(String, usize, usize)>>() };
+	let file_name = unsafe { CStr::from_ptr(file_name) }.to_string_lossy();
+	files.push((file_name.into_owned(), index, total));
+}
+
+#[test]
+fn version() {
+	// SAFETY: the version is a nul terminated string living as long as the library
+	let version = unsafe { CStr::from_ptr(oxide_version()) };
+	assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn set_options() {
+	let options = oxide_options_new();
+	unsafe {
+		assert_eq!(
+			oxide_options_set(options, c("csv-dialect").as_ptr(), c("excel").as_ptr()),
+			OXIDE_OK
+		);
+
+		assert_eq!(
+			oxide_options_set(options, c("colour").as_ptr(), c("red").as_ptr()),
+			OXIDE_ERROR
+		);
+		assert!(last_error().starts_with("unknown option colour"));
+
+		assert_eq!(
+			oxide_options_set(options, c("resume").as_ptr(), c("yes").as_ptr()),
+			OXIDE_ERROR
+		);
+		assert_eq!(last_error(), "expected true or false for resume, not yes");
+
+		oxide_options_free(options);
+	}
+}
+
+#[test]
+fn null_pointers() {
+	let options = oxide_options_new();
+	let mut summary = ptr::null_mut();
+	unsafe {
+		assert_eq!(
+			oxide_options_set(ptr::null_mut(), c("resume").as_ptr(), c("true").as_ptr()),
+			OXIDE_ERROR
+		);
+		assert_eq!(last_error(), "options is null");
+
+		assert_eq!(
+			oxide_options_set(options, ptr::null(), c("true").as_ptr()),
+			OXIDE_ERROR
+		);
+		assert_eq!(last_error(), "key is null");
+
+		assert_eq!(
+			oxide_options_set(options, c("resume").as_ptr(), ptr::null()),
+			OXIDE_ERROR
+		);
+		assert_eq!(last_error(), "value is null");
+
+		let output = c("out.csv");
+		let run = |options, input: *const c_char, summary| {
+			oxide_run(
+				options,
+				input,
+				output.as_ptr(),
+				None,
+				ptr::null_mut(),
+				summary,
+			)
+		};
+		assert_eq!(
+			run(ptr::null(), c("in").as_ptr(), &mut summary),
+			OXIDE_ERROR
+		);
+		assert_eq!(last_error(), "options is null");
+		assert_eq!(run(options, ptr::null(), &mut summary), OXIDE_ERROR);
+		assert_eq!(last_error(), "input_dir is null");
+		assert!(summary.is_null());
+
+		// freeing nothing does nothing
+		oxide_string_free(ptr::null_mut());
+		oxide_options_free(ptr::null_mut());
+		oxide_options_free(options);
+	}
+}
+
+#[test]
+fn run() {
+	let input = fixture_folder("run");
+	let output = input.with_extension("csv");
+	let (input_dir, output_file) = (
+		c(&input.display().to_string()),
+		c(&output.display().to_string()),
+	);
+
+	let options = oxide_options_new();
+	let mut files: Vec<(String, usize, usize)> = Vec::new();
+	let mut summary = ptr::null_mut();
+	let result = unsafe {
+		// the workbook was only just written, which is fine here
+		oxide_options_set(options, c("settle-time").as_ptr(), c("0").as_ptr());
+		let result = oxide_run(
+			options,
+			input_dir.as_ptr(),
+			output_file.as_ptr(),
+			Some(progress),
+			(&raw mut files).cast(),
+			&mut summary,
+		);
+		oxide_options_free(options);
+		result
+	};
+
+	let csv = fs::read_to_string(&output);
+	let _ = fs::remove_dir_all(&input);
+	let _ = fs::remove_file(&output);
+
+	assert_eq!(result, OXIDE_OK, "{}", last_error());
+	assert_eq!(files, [("DDR_2024-05-17".to_string(), 0, 1)]);
+	assert_eq!(
+		csv.unwrap(),
+		"hole_number,depth_from,depth_to,meters,comment,date\n\
+		DH-01,0,5,5,,17/05/2024\n\
+		DH-01,5,10.5,5.5,,17/05/2024\n"
+	);
+
+	assert!(summary.is_null().not());
+	// SAFETY: oxide_run hands out a nul terminated summary
+	let text = unsafe { CStr::from_ptr(summary) }
+		.to_string_lossy()
+		.into_owned();
+	assert!(text.starts_with("2 rows from 1 files"), "{}", text);
+	unsafe { oxide_string_free(summary) };
+}
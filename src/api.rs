@@ -0,0 +1,375 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use oxide_core::{OxideError, RunSummary};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::cli;
+use crate::deliver::Delivery;
+use crate::export_task::ExportTask;
+
+// larger bodies aren't export requests
+const MAX_BODY: usize = 64 * 1024;
+// nor are requests with more or larger headers, which are turned away
+// before a client can keep the listener reading them
+const MAX_HEADER_BYTES: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+// how long a client has to send its whole request, however slowly it does
+const REQUEST_TIME: Duration = Duration::from_secs(10);
+
+// what POST /export can change about the export, the rest follows the
+// settings like `oxide export` does
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ExportRequest {
+	input: Option<String>,
+	output: Option<String>,
+	template: Option<String>,
+	resume: bool,
+}
+
+#[derive(Debug)]
+struct Request {
+	method: String,
+	path: String,
+	host: String,
+	content_type: String,
+	body: Vec<u8>,
+}
+
+// the export running or last run, answered with on GET /status
+#[derive(Default)]
+struct Api {
+	task: Option<ExportTask>,
+	input_dir: String,
+	// the output and result of the last export that finished
+	last: Option<(String, Result<RunSummary, OxideError>)>,
+}
+
+/// Listens on localhost:`port` for other tools to start exports with
+/// `POST /export` and follow them with `GET /status`, until the process is
+/// stopped. Exports run one at a time, like from the gui.
+pub fn serve(port: u16) -> io::Result<()> {
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+	println!("listening on http://localhost:{}", port);
+
+	let mut api = Api::default();
+	for stream in listener.incoming().flatten() {
+		let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+		let (status, body) = match read_request(&stream) {
+			Ok(request) => api.handle(request, port),
+			Err(e) => (400, json!({ "error": e.to_string() })),
+		};
+		let _ = respond(&stream, status, &body);
+	}
+
+	Ok(())
+}
+
+impl Api {
+	fn handle(&mut self, request: Request, port: u16) -> (u16, Value) {
+		// pages in a browser can only reach it through a name of their own
+		// pointed at the machine, which is turned away here
+		let host = request.host.as_str();
+		if host != format!("localhost:{}", port) && host != format!("127.0.0.1:{}", port) {
+			return (
+				403,
+				json!({ "error": "only requests to localhost are answered" }),
+			);
+		}
+
+		match (request.method.as_str(), request.path.as_str()) {
+			("POST", "/export") => self.start(&request),
+			("GET", "/status") => (200, self.status()),
+			(_, "/export" | "/status") => (405, json!({ "error": "method not allowed" })),
+			_ => (404, json!({ "error": "not found" })),
+		}
+	}
+
+	fn start(&mut self, request: &Request) -> (u16, Value) {
+		// nor can they post json without asking first, which is never allowed
+		if request.content_type != "application/json" {
+			return (415, json!({ "error": "expected a json body" }));
+		}
+
+		self.poll();
+		if self.task.is_some() {
+			return (409, json!({ "error": "an export is already running" }));
+		}
+
+		let export: ExportRequest = match request.body.is_empty() {
+			true => ExportRequest::default(),
+			false => match serde_json::from_slice(&request.body) {
+				Ok(export) => export,
+				Err(e) => return (400, json!({ "error": e.to_string() })),
+			},
+		};
+
+		// every export follows the settings as they are when it starts
+		let mut config = cli::load_config();
+		if let Some(template) = export.template {
+			config.template = template;
+		}
+		let input_dir = export.input.unwrap_or_else(|| config.input_dir.clone());
+		let output_file = export.output.unwrap_or_else(|| config.output_file.clone());
+		if input_dir.is_empty() || output_file.is_empty() {
+			return (
+				400,
+				json!({ "error": "no input or output, and none in the settings" }),
+			);
+		}
+
+		let mut options = match config.export_options() {
+			Ok(options) => options,
+			Err(e) => return (400, json!({ "error": e })),
+		};
+		options.resume = export.resume;
+		self.task = Some(ExportTask::start(
+			input_dir.clone(),
+			output_file,
+			config.template.clone(),
+			&options,
+			Delivery::new(&config),
+		));
+		self.input_dir = input_dir;
+		(202, self.status())
+	}
+
+	fn poll(&mut self) {
+		let Some(task) = &mut self.task else {
+			return;
+		};
+
+		if let Some(result) = task.poll() {
+			self.last = Some((task.output_file.clone(), result));
+			self.task = None;
+		}
+	}
+
+	fn status(&mut self) -> Value {
+		self.poll();
+		if let Some(task) = &self.task {
+			return json!({
+				"state": "running",
+				"input": self.input_dir,
+				"output": task.output_file,
+				"files": task.total,
+				"done": task.done,
+				"rows": task.rows,
+				"current": task.current,
+			});
+		}
+
+		match &self.last {
+			None => json!({ "state": "idle" }),
+			Some((output, Ok(summary))) => json!({
+				"state": "finished",
+				"input": self.input_dir,
+				"output": output,
+				"files": summary.files.len(),
+				"rows": summary.total_rows(),
+				"warnings": summary.warnings,
+				"summary": summary.to_string(),
+			}),
+			Some((output, Err(e))) => json!({
+				"state": "failed",
+				"input": self.input_dir,
+				"output": output,
+				"error": e.to_string(),
+			}),
+		}
+	}
+}
+
+// fails reading once the client has taken too long to send its request,
+// while the socket's timeout only limits each read
+struct Deadline<R> {
+	inner: R,
+	until: Instant,
+}
+
+impl<R: Read> Read for Deadline<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if Instant::now() > self.until {
+			return Err(io::Error::new(
+				io::ErrorKind::TimedOut,
+				"the request took too long",
+			));
+		}
+
+		self.inner.read(buf)
+	}
+}
+
+fn read_request(stream: impl Read) -> io::Result<Request> {
+	let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+	let mut reader = BufReader::new(Deadline {
+		inner: stream,
+		until: Instant::now() + REQUEST_TIME,
+	});
+	let mut head = (&mut reader).take(MAX_HEADER_BYTES);
+	let mut line = String::new();
+	head.read_line(&mut line)?;
+	let mut parts = line.split_whitespace();
+	let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+		return Err(invalid("not an http request"));
+	};
+	let mut request = Request {
+		method: method.to_string(),
+		// a query doesn't change what's asked for
+		path: path.split('?').next().unwrap_or_default().to_string(),
+		host: String::new(),
+		content_type: String::new(),
+		body: Vec::new(),
+	};
+
+	let mut length = 0;
+	let mut headers = 0;
+	loop {
+		line.clear();
+		if head.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+			break;
+		}
+		headers += 1;
+		if headers > MAX_HEADERS {
+			return Err(invalid("there are too many headers"));
+		}
+		let Some((name, value)) = line.split_once(':') else {
+			continue;
+		};
+
+		let value = value.trim();
+		match name.trim().to_ascii_lowercase().as_str() {
+			"host" => request.host = value.to_string(),
+			"content-type" => {
+				let media_type = value.split(';').next().unwrap_or_default();
+				request.content_type = media_type.trim().to_ascii_lowercase();
+			}
+			"content-length" => {
+				length = value
+					.parse()
+					.map_err(|_| invalid("content-length isn't a number"))?;
+			}
+			_ => {}
+		}
+	}
+
+	if head.limit() == 0 {
+		return Err(invalid("the headers are too large"));
+	}
+	if length > MAX_BODY {
+		return Err(invalid("the body is too large"));
+	}
+	if length > 0 {
+		request.body = vec![0; length];
+		reader.read_exact(&mut request.body)?;
+	}
+
+	Ok(request)
+}
+
+fn respond(mut stream: &TcpStream, status: u16, body: &Value) -> io::Result<()> {
+	let reason = match status {
+		200 => "OK",
+		202 => "Accepted",
+		400 => "Bad Request",
+		403 => "Forbidden",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		409 => "Conflict",
+		415 => "Unsupported Media Type",
+		_ => "",
+	};
+	let body = body.to_string();
+	write!(
+		stream,
+		"HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		status,
+		reason,
+		body.len(),
+		body
+	)?;
+	stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc;
+
+	use super::*;
+
+	fn request(text: &str) -> io::Result<Request> {
+		read_request(text.replace('\n', "\r\n").as_bytes())
+	}
+
+	fn post_export(host: &str, content_type: &str) -> Request {
+		request(&format!(
+			"POST /export HTTP/1.1\nHost: {}\nContent-Type: {}\nContent-Length: 2\n\n{{}}",
+			host, content_type
+		))
+		.unwrap()
+	}
+
+	#[test]
+	fn reads_request() {
+		let request = request(
+			"POST /export?from=script HTTP/1.1\n\
+			Host: localhost:7878\n\
+			content-type: Application/JSON; charset=utf-8\n\
+			Content-Length: 16\n\
+			\n\
+			{\"resume\": true}",
+		)
+		.unwrap();
+		assert_eq!(request.method, "POST");
+		assert_eq!(request.path, "/export");
+		assert_eq!(request.host, "localhost:7878");
+		assert_eq!(request.content_type, "application/json");
+		assert_eq!(request.body, b"{\"resume\": true}");
+
+		assert!(self::request("\n").is_err());
+		assert!(self::request("GET /status HTTP/1.1\nContent-Length: lots\n\n").is_err());
+		let body = format!("Content-Length: {}", MAX_BODY + 1);
+		assert!(self::request(&format!("POST /export HTTP/1.1\n{}\n\n", body)).is_err());
+	}
+
+	#[test]
+	fn limits_headers() {
+		let headers = |count: usize, value: &str| {
+			let header = format!("X-Padding: {}\n", value);
+			request(&format!("GET /status HTTP/1.1\n{}\n", header.repeat(count)))
+		};
+		assert!(headers(MAX_HEADERS, "x").is_ok());
+		assert!(headers(MAX_HEADERS + 1, "x").is_err());
+		assert!(headers(1, &"x".repeat(MAX_HEADER_BYTES as usize)).is_err());
+	}
+
+	#[test]
+	fn rejects_other_hosts_and_bodies() {
+		let mut api = Api::default();
+		let (status, _) = api.handle(
+			post_export("attacker.example:7878", "application/json"),
+			7878,
+		);
+		assert_eq!(status, 403);
+		let (status, _) = api.handle(post_export("localhost:7878", "text/plain"), 7878);
+		assert_eq!(status, 415);
+
+		let status = request("GET /status HTTP/1.1\nHost: 127.0.0.1:7878\n\n").unwrap();
+		assert_eq!(api.handle(status, 7878), (200, json!({ "state": "idle" })));
+	}
+
+	#[test]
+	fn one_export_at_a_time() {
+		let (finish, finished) = mpsc::channel();
+		let mut api = Api {
+			task: Some(ExportTask::running("out.csv", finished)),
+			..Api::default()
+		};
+		let (status, _) = api.handle(post_export("localhost:7878", "application/json"), 7878);
+		assert_eq!(status, 409);
+		drop(finish);
+	}
+}
@@ -0,0 +1,72 @@
+use std::path::Path;
+use std::time::Duration;
+
+use oxide_core::{
+	ExportOptions, LocalDir, OxideError, SchemaCache, SpreadsheetSource, StageTimings, parse_file,
+};
+
+// runs the parse pipeline over every workbook in the input directory without
+// writing any output, printing how long each stage took per file. each
+// workbook is read with the template and styles `options` pick for it.
+pub fn run(input_dir: &Path, options: &ExportOptions) -> Result<(), OxideError> {
+	let source = LocalDir::new(input_dir);
+	let paths: Vec<_> = source.list()?.into_iter().map(|f| f.path).collect();
+	println!(
+		"{:<40} {:>10} {:>10} {:>10} {:>10} {:>8}",
+		"file", "open", "decode", "scan", "serialize", "rows"
+	);
+
+	let mut totals = StageTimings::default();
+	let mut total_rows = 0;
+	for path in &paths {
+		let file_name = path
+			.file_name()
+			.and_then(|s| s.to_str())
+			.unwrap_or_default();
+		// always run the full header detection, so timings stay comparable
+		// no matter what earlier exports left in the schema cache
+		match parse_file(
+			&source,
+			path,
+			&SchemaCache::default(),
+			options.template_for(path),
+			&options.header_style,
+			&options.date_times,
+		) {
+			Ok(parsed) => {
+				print_row(file_name, &parsed.stages, parsed.rows.len());
+				totals.open += parsed.stages.open;
+				totals.decode += parsed.stages.decode;
+				totals.scan += parsed.stages.scan;
+				totals.serialize += parsed.stages.serialize;
+				total_rows += parsed.rows.len();
+			}
+			Err(e) => println!("{:<40} failed: {}", file_name, e),
+		}
+	}
+
+	print_row("total", &totals, total_rows);
+	let elapsed = totals.total().as_secs_f64();
+	if elapsed > 0.0 {
+		println!(
+			"\n{} files, {:.0} rows/s",
+			paths.len(),
+			total_rows as f64 / elapsed
+		);
+	}
+
+	Ok(())
+}
+
+fn print_row(name: &str, stages: &StageTimings, rows: usize) {
+	let ms = |d: Duration| format!("{:.1}ms", d.as_secs_f64() * 1000.0);
+	println!(
+		"{:<40} {:>10} {:>10} {:>10} {:>10} {:>8}",
+		name,
+		ms(stages.open),
+		ms(stages.decode),
+		ms(stages.scan),
+		ms(stages.serialize),
+		rows
+	);
+}
@@ -0,0 +1,136 @@
+use oxide_core::RunSummary;
+use serde::{Deserialize, Serialize};
+
+/// Chat services a finished export can be announced in.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatService {
+	#[default]
+	Slack,
+	Teams,
+}
+
+/// A Slack or Teams channel told about every finished export, through an
+/// incoming webhook of the channel's.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ChatNotification {
+	pub service: ChatService,
+	pub webhook_url: String,
+	// url of the folder the output ends up in, like its sharepoint or drive
+	// folder, which the output's file name is added to for a link. empty
+	// to give the output's path instead
+	pub link: String,
+}
+
+#[cfg(feature = "chat")]
+mod send {
+	use std::ops::Not;
+	use std::path::Path;
+	use std::time::Duration;
+
+	use serde_json::json;
+
+	use super::{ChatNotification, ChatService, RunSummary};
+
+	/// Posts the message about the export to the channel.
+	pub fn send(
+		chat: &ChatNotification,
+		output_file: &str,
+		summary: &RunSummary,
+	) -> Result<(), String> {
+		if chat.webhook_url.is_empty() {
+			return Err("there's no webhook url for the channel".to_string());
+		}
+
+		let text = message(chat, output_file, summary);
+		let body = match chat.service {
+			ChatService::Slack => json!({ "text": text }),
+			// teams workflows only take adaptive cards
+			ChatService::Teams => json!({
+				"type": "message",
+				"attachments": [{
+					"contentType": "application/vnd.microsoft.card.adaptive",
+					"content": {
+						"type": "AdaptiveCard",
+						"$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+						"version": "1.4",
+						"body": text.lines().map(|line| json!({
+							"type": "TextBlock",
+							"text": line,
+							"wrap": true,
+						})).collect::<Vec<_>>(),
+					},
+				}],
+			}),
+		};
+
+		ureq::post(&chat.webhook_url)
+			.config()
+			.timeout_global(Some(Duration::from_secs(60)))
+			.build()
+			.send_json(body)
+			.map_err(|e| e.to_string())?;
+		Ok(())
+	}
+
+	/// The message announcing the export, like `Export finished: 2,340 rows
+	/// from 31 files, 2 warnings` and where to find it.
+	fn message(chat: &ChatNotification, output_file: &str, summary: &RunSummary) -> String {
+		let plural = |count: usize, noun: &str| match count {
+			1 => format!("1 {}", noun),
+			_ => format!("{} {}s", thousands(count), noun),
+		};
+		let mut message = format!(
+			"Export finished: {} from {}",
+			plural(summary.total_rows(), "row"),
+			plural(summary.files.len(), "file")
+		);
+		if summary.warnings.is_empty().not() {
+			message.push_str(&format!(", {}", plural(summary.warnings.len(), "warning")));
+		}
+
+		let file_name = Path::new(output_file)
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.unwrap_or_else(|| output_file.to_string());
+		let url = format!(
+			"{}/{}",
+			chat.link.trim_end_matches('/'),
+			file_name.replace(' ', "%20")
+		);
+		let location = match (chat.link.is_empty(), chat.service) {
+			(true, _) => format!("`{}`", output_file),
+			(false, ChatService::Slack) => format!("<{}|{}>", url, file_name),
+			(false, ChatService::Teams) => format!("[{}]({})", file_name, url),
+		};
+		format!("{}\n{}", message, location)
+	}
+
+	// 2340 as 2,340
+	fn thousands(count: usize) -> String {
+		let digits = count.to_string();
+		let mut grouped = String::new();
+		for (i, digit) in digits.chars().enumerate() {
+			if i > 0 && (digits.len() - i).is_multiple_of(3) {
+				grouped.push(',');
+			}
+
+			grouped.push(digit);
+		}
+
+		grouped
+	}
+}
+
+#[cfg(feature = "chat")]
+pub use send::send;
+
+#[cfg(not(feature = "chat"))]
+pub fn send(
+	_chat: &ChatNotification,
+	_output_file: &str,
+	_summary: &RunSummary,
+) -> Result<(), String> {
+	Err("oxide was built without the chat feature".to_string())
+}
@@ -0,0 +1,408 @@
+use std::ops::Not;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use oxide_core::{LocalDir, ReportTemplate, StaleExport, check_sample};
+
+use crate::config::{self, Config};
+use crate::deliver::Delivery;
+use crate::drive;
+use crate::history::{self, Run};
+#[cfg(feature = "updater")]
+use crate::update;
+use crate::{api, bench, export, time};
+
+const USAGE: &str = "usage:
+  oxide [--input DIR]
+  oxide bench --input DIR
+  oxide check --input DIR [--sample FILES] [--template NAME]
+  oxide export --input DIR --output FILE [--resume] [--limit ROWS] [--script FILE] [--template NAME]
+  oxide config get [KEY]
+  oxide config set KEY VALUE
+  oxide drive login
+  oxide history
+  oxide serve [--port PORT]
+  oxide template list
+  oxide template new NAME
+  oxide update [--check]";
+
+// handles command line subcommands, returning the exit code when one was
+// run, or none if the gui should be launched instead
+pub fn run(args: &[String]) -> Option<i32> {
+	let (command, rest) = args.split_first()?;
+	attach_console();
+
+	let code = match command.as_str() {
+		"bench" => match flag_value(rest, "--input") {
+			Some(input_dir) => bench(input_dir),
+			None => usage(),
+		},
+		"check" => match flag_value(rest, "--input") {
+			Some(input_dir) => check(input_dir, rest),
+			None => usage(),
+		},
+		"export" => match (flag_value(rest, "--input"), flag_value(rest, "--output")) {
+			(Some(input_dir), Some(output_file)) => {
+				// exports from the command line follow the same settings as the gui
+				let mut config = load_config();
+				if let Some(script) = flag_value(rest, "--script") {
+					config.script = script.to_string();
+				}
+
+				if let Some(template) = flag_value(rest, "--template") {
+					config.template = template.to_string();
+				}
+
+				let mut options = match config.export_options() {
+					Ok(options) => options,
+					Err(e) => {
+						eprintln!("{}", e);
+						return Some(1);
+					}
+				};
+				options.resume = rest.iter().any(|a| a == "--resume");
+				if let Some(limit) = flag_value(rest, "--limit") {
+					match limit.parse() {
+						Ok(limit) => options.row_limit = Some(limit),
+						Err(_) => return Some(usage()),
+					}
+				}
+
+				oxide_core::remove_stale_spills(Duration::from_secs(24 * 60 * 60));
+				if let Some(stale) = StaleExport::find(output_file) {
+					let _ = stale.remove_partial();
+					if stale.checkpoint.is_some() && options.resume.not() {
+						eprintln!(
+							"the last export to {} did not finish, starting over rather than resuming it with --resume",
+							output_file
+						);
+					}
+				}
+
+				let result = export::run(input_dir, output_file, options);
+				let run = Run::new(&config.template, input_dir, output_file, &result);
+				if let Err(e) = history::record(&run) {
+					eprintln!("failed to add the export to the history: {}", e);
+				}
+
+				match result {
+					Ok(summary) => {
+						let errors = Delivery::new(&config).send(output_file, &summary);
+						for e in &errors {
+							eprintln!("{}", e);
+						}
+
+						if errors.is_empty() { 0 } else { 1 }
+					}
+					Err(e) => {
+						eprintln!("export failed: {}", e);
+						1
+					}
+				}
+			}
+			_ => usage(),
+		},
+		"config" => config(rest),
+		"drive" => drive(rest),
+		"history" => history(rest),
+		"serve" => serve(rest),
+		"template" => template(rest),
+		"update" => update(rest),
+		_ => usage(),
+	};
+
+	Some(code)
+}
+
+// times the parse pipeline with the templates the settings pick for each
+// workbook, like an export would read it
+fn bench(input_dir: &str) -> i32 {
+	let options = match load_config().export_options() {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{}", e);
+			return 1;
+		}
+	};
+
+	match bench::run(&PathBuf::from(input_dir), &options) {
+		Ok(_) => 0,
+		Err(e) => {
+			eprintln!("bench failed: {}", e);
+			1
+		}
+	}
+}
+
+// checks every workbook in the folder would be read, and the rows of a
+// random sample of them, without exporting anything
+fn check(input_dir: &str, args: &[String]) -> i32 {
+	let mut config = load_config();
+	if let Some(template) = flag_value(args, "--template") {
+		config.template = template.to_string();
+	}
+
+	let sample = match flag_value(args, "--sample").map(str::parse) {
+		Some(Ok(sample)) => sample,
+		Some(Err(_)) => return usage(),
+		None => 20,
+	};
+	let options = match config.export_options() {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{}", e);
+			return 1;
+		}
+	};
+
+	let seed = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |elapsed| elapsed.as_nanos() as u64);
+	match check_sample(&LocalDir::new(input_dir), &options, sample, seed) {
+		Ok(report) => {
+			print!("{}", report);
+			if report.confidence() < 1.0 { 1 } else { 0 }
+		}
+		Err(e) => {
+			eprintln!("check failed: {}", e);
+			1
+		}
+	}
+}
+
+// reads or changes the persisted settings, so deployment scripts can set
+// the tool up without opening the gui
+fn config(args: &[String]) -> i32 {
+	let mut config = load_config();
+	match args {
+		[command] if command == "get" => {
+			for key in Config::KEYS {
+				println!("{} = {}", key, config.get(key).unwrap_or_default());
+			}
+
+			0
+		}
+		[command, key] if command == "get" => match config.get(key) {
+			Some(value) => {
+				println!("{}", value);
+				0
+			}
+			None => unknown_key(key),
+		},
+		[command, key, value] if command == "set" => {
+			if let Err(e) = config.set(key, value.to_string()) {
+				eprintln!("{}", e);
+				return 2;
+			}
+
+			match config.save() {
+				Ok(warning) => {
+					if let Some(warning) = warning {
+						eprintln!("warning: {}", warning);
+					}
+
+					0
+				}
+				Err(e) => {
+					eprintln!("failed to save settings: {}", e);
+					1
+				}
+			}
+		}
+		_ => usage(),
+	}
+}
+
+// logs in to the cloud drive exports are saved to, keeping the login for
+// the exports that follow
+fn drive(args: &[String]) -> i32 {
+	match args {
+		[command] if command == "login" => {
+			let Some(cloud_drive) = load_config().drive else {
+				eprintln!("there's no drive in the settings to log in to");
+				return 1;
+			};
+
+			match drive::login(&cloud_drive) {
+				Ok(()) => {
+					println!("logged in, exports will be saved to the drive");
+					0
+				}
+				Err(e) => {
+					eprintln!("login failed: {}", e);
+					1
+				}
+			}
+		}
+		_ => usage(),
+	}
+}
+
+// answers exports asked for over http until it's stopped
+fn serve(args: &[String]) -> i32 {
+	let port = match flag_value(args, "--port").map(str::parse) {
+		Some(Ok(port)) => port,
+		Some(Err(_)) => return usage(),
+		None => load_config().api_port(),
+	};
+
+	match api::serve(port) {
+		Ok(()) => 0,
+		Err(e) => {
+			eprintln!("failed to listen on port {}: {}", port, e);
+			1
+		}
+	}
+}
+
+// prints every export run so far, newest first
+fn history(args: &[String]) -> i32 {
+	if args.is_empty().not() {
+		return usage();
+	}
+
+	for run in history::load() {
+		let result = match run.error.is_empty() {
+			true => format!(
+				"{} rows from {} files, {} warnings",
+				run.rows,
+				run.inputs.len(),
+				run.warnings
+			),
+			false => format!("failed: {}", run.error),
+		};
+		println!(
+			"{} UTC  {}  {} -> {}  {}",
+			time::utc_time(run.time),
+			run.user,
+			run.input_dir,
+			run.output_file,
+			result
+		);
+		if run.output_sha256.is_empty().not() {
+			println!("  sha256 {}", run.output_sha256);
+		}
+	}
+
+	0
+}
+
+// lists the shared templates, or starts a new one from the default layout
+fn template(args: &[String]) -> i32 {
+	match args {
+		[command] if command == "list" => {
+			for name in config::template_names() {
+				println!("{}", name);
+			}
+
+			0
+		}
+		[command, name] if command == "new" => {
+			let path = config::template_path(name);
+			if path.exists() {
+				eprintln!("{} already exists", path.display());
+				return 1;
+			}
+
+			match ReportTemplate::default().save(&path) {
+				Ok(_) => {
+					println!("{}", path.display());
+					0
+				}
+				Err(e) => {
+					eprintln!("failed to save template: {}", e);
+					1
+				}
+			}
+		}
+		_ => usage(),
+	}
+}
+
+// installs the latest release in place of this executable, or with --check
+// only says whether there is one
+#[cfg(feature = "updater")]
+fn update(args: &[String]) -> i32 {
+	let check_only = match args {
+		[] => false,
+		[flag] if flag == "--check" => true,
+		_ => return usage(),
+	};
+
+	let config = load_config();
+	let release = match update::check(&config) {
+		Ok(Some(release)) => release,
+		Ok(None) => {
+			println!("oxide {} is up to date", env!("CARGO_PKG_VERSION"));
+			return 0;
+		}
+		Err(e) => {
+			eprintln!("{}", e);
+			return 1;
+		}
+	};
+
+	println!(
+		"oxide {} is available, this is {}",
+		release.version,
+		env!("CARGO_PKG_VERSION")
+	);
+	if check_only {
+		return 0;
+	}
+
+	match update::install(&release, &config) {
+		Ok(()) => {
+			println!("updated to {}", release.version);
+			0
+		}
+		Err(e) => {
+			eprintln!("update failed: {}", e);
+			1
+		}
+	}
+}
+
+#[cfg(not(feature = "updater"))]
+fn update(_args: &[String]) -> i32 {
+	eprintln!("oxide was built without the updater feature");
+	1
+}
+
+fn unknown_key(key: &str) -> i32 {
+	eprintln!(
+		"unknown setting {}, expected one of: {}",
+		key,
+		Config::KEYS.join(", ")
+	);
+	2
+}
+
+// the settings, with what went wrong reading them told on stderr
+pub(crate) fn load_config() -> Config {
+	let config = Config::load();
+	for warning in config.load_warnings() {
+		eprintln!("warning: {}", warning);
+	}
+
+	config
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+	let idx = args.iter().position(|a| a == flag)?;
+	args.get(idx + 1).map(|s| s.as_str())
+}
+
+fn usage() -> i32 {
+	eprintln!("{}", USAGE);
+	2
+}
+
+// release builds use the windows subsystem, so there's no console to print
+// to unless we borrow the one we were launched from
+fn attach_console() {
+	#[cfg(windows)]
+	unsafe {
+		winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+	}
+}
@@ -0,0 +1,919 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use directories::ProjectDirs;
+use oxide_core::{
+	Aggregation, Archive, ColumnRule, DateRange, DateTimes, ExportOptions, HeaderReplacement,
+	HeaderStyle, HoleIds, InvalidRows, Lookup, NumberFormat, OpenRetries, QaCheck, ReportTemplate,
+	TemplateRule, Totals, Validation, check_name, parse_flag,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::chat::ChatNotification;
+use crate::drive::CloudDrive;
+use crate::email::EmailSettings;
+use crate::plugins;
+use crate::upload::{S3Upload, SftpUpload};
+use crate::webhook::Webhook;
+
+/// Version written into saved settings, bumped with a new entry in
+/// [`MIGRATIONS`] whenever a setting is renamed or changes meaning.
+pub const CONFIG_VERSION: u64 = 1;
+
+// upgrades settings saved by the version at the same index to the next one
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+	// 0 -> 1: files written before settings were versioned hold the same
+	// fields, they only gain the version
+	|_| {},
+];
+
+// settings exports take as they are, read into export options by
+// oxide-core like the c library's options are
+const EXPORT_KEYS: &[&str] = &[
+	"schema-sidecar",
+	"provenance",
+	"null-value",
+	"csv-dialect",
+	"source-columns",
+	"row-ids",
+	"filter",
+	"sort-by",
+	"duplicate-reports",
+	"merge-key",
+	"column-order",
+	"qa-output",
+	"remarks-output",
+	"depth-intervals",
+	"cumulative-meters",
+	"preset",
+	"manifest",
+	"pdf-report",
+	"settle-time",
+	"max-concurrent-files",
+	"keep-identical",
+	"quarantine-folder",
+];
+
+/// A csv file joined onto every exported row by its `key` column.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct LookupFile {
+	pub path: String,
+	pub key: String,
+}
+
+/// A template for the workbooks matching `pattern`, see [`TemplateRule`].
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct TemplateRuleFile {
+	pub pattern: String,
+	// name of a template in the templates folder or a path to one
+	pub template: String,
+}
+
+/// Settings kept between runs, in the user's config directory.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+	pub input_dir: String,
+	pub output_file: String,
+	// one of HeaderStyle::NAMES, empty for the default
+	pub header_style: String,
+	// pattern and replacement pairs for the replace style, only editable in
+	// the file itself
+	pub header_replacements: Vec<(String, String)>,
+	// other spellings of columns' headers keyed by the column, like
+	// "depth_from": ["From (m)", "Start Depth"], added to every template's
+	// renames. only editable in the file itself
+	pub header_aliases: BTreeMap<String, Vec<String>>,
+	// rhai script every row goes through, see RowScript
+	pub script: String,
+	// name of a template in the templates folder or a path to one, empty
+	// for the default layout
+	pub template: String,
+	// templates for the workbooks matching a pattern, the first match wins.
+	// only editable in the file itself
+	pub template_rules: Vec<TemplateRuleFile>,
+	// group-by summaries written alongside the export, only editable in the
+	// file itself
+	pub aggregations: Vec<Aggregation>,
+	// adds the built in per hole summary to the aggregations
+	pub hole_summary: bool,
+	// one of Totals::NAMES, empty for none
+	pub totals: String,
+	// columns the totals rows sum up, only editable in the file itself
+	pub total_columns: Vec<String>,
+	// checks on the exported columns, only editable in the file itself
+	pub validation_rules: Vec<ColumnRule>,
+	// one of InvalidRows::NAMES, empty to keep rows that break a rule
+	pub invalid_rows: String,
+	// one of SchemaSidecar::NAMES, empty for none
+	pub schema_sidecar: String,
+	// one of Provenance::NAMES, empty for none. the template's name is
+	// written as the profile
+	pub provenance: String,
+	// written in place of empty values, empty leaves them empty
+	pub null_value: String,
+	// one of CsvDialect::NAMES, empty for plain
+	pub csv_dialect: String,
+	// csv files joined onto the exported rows, only editable in the file
+	// itself
+	pub lookups: Vec<LookupFile>,
+	// adds the workbook and sheet every row came from as columns
+	pub source_columns: bool,
+	// one of RowIds::NAMES, empty for none
+	pub row_ids: String,
+	// report dates exported rows have to fall within, like 2024-05-17,
+	// empty leaves that end open
+	pub date_from: String,
+	pub date_to: String,
+	// expression exported rows have to match, see RowFilter, empty for all
+	// of them
+	pub filter: String,
+	// columns rows are sorted by, separated by commas and descending with a
+	// leading -, like date,hole_number
+	pub sort_by: String,
+	// one of DuplicateReports::NAMES, empty to warn about them
+	pub duplicate_reports: String,
+	// columns identifying a row, separated by commas, like date,hole_number.
+	// exports are merged into an existing csv output by them when set
+	pub merge_key: String,
+	// columns written first, separated by commas, like
+	// hole_number,date,depth_from. the rest follow in the order they're read
+	pub column_order: String,
+	// checks flagging numeric values that look off, only editable in the
+	// file itself
+	pub qa_checks: Vec<QaCheck>,
+	// one of QaOutput::NAMES, empty for a qa_flags column
+	pub qa_output: String,
+	// writes the reports' remarks blocks to a remarks table
+	pub remarks_output: bool,
+	// checks the hole_number, depth_from and depth_to intervals for gaps and
+	// overlaps
+	pub depth_intervals: bool,
+	// adds a running total of every hole's meters in date order
+	pub cumulative_meters: bool,
+	// one of ExportPreset::NAMES, empty to write the columns as they are
+	pub preset: String,
+	// how hole ids are rewritten to a single spelling, only editable in the
+	// file itself
+	pub hole_ids: Option<HoleIds>,
+	// writes the output's sha256 and a manifest of the workbooks read next
+	// to it
+	pub manifest: bool,
+	// writes a short pdf of the files read, totals per hole and per day
+	// and the warnings next to the output
+	pub pdf_report: bool,
+	// excel style formats of columns' numbers like "000" or "0.00", keyed by
+	// the column, only editable in the file itself
+	pub number_formats: BTreeMap<String, String>,
+	// strftime formats of date cells, time cells and date and time cells,
+	// empty for DateTimes::DATE_FORMAT, TIME_FORMAT and DATETIME_FORMAT
+	pub date_format: String,
+	pub time_format: String,
+	pub datetime_format: String,
+	// utc offsets like +08:00 of the workbooks' times and the one they're
+	// written in, both empty to write them as they are
+	pub timezone: String,
+	pub output_timezone: String,
+	// times a workbook open in excel is tried again before it's skipped, and
+	// milliseconds to wait before the first retry, doubled before every one
+	// after it. empty for OpenRetries' defaults
+	pub open_retries: String,
+	pub open_retry_delay: String,
+	// seconds since a workbook was last modified for it to be read, as it's
+	// still being written or synced before, empty for the default of 5
+	pub settle_time: String,
+	// workbooks read at once, empty for the default of 4. more than that
+	// tends to slow down reading from a smb share
+	pub max_concurrent_files: String,
+	// reads every copy of a workbook rather than only the first of the
+	// byte for byte identical ones
+	pub keep_identical: bool,
+	// folder exported workbooks are moved to, relative to the one they're
+	// read from, empty to leave them where they are
+	pub archive_folder: String,
+	// copies them to the archive folder rather than moving them
+	pub archive_copy: bool,
+	// what exported workbooks are renamed to, see Archive::rename. they're
+	// renamed where they are without an archive folder
+	pub archive_rename: String,
+	// folder workbooks that fail to parse are copied to with the error,
+	// relative to the one they're read from, empty for none
+	pub quarantine_folder: String,
+	// where finished exports are emailed, only editable in the file itself
+	pub email: Option<EmailSettings>,
+	// where finished exports are uploaded, only editable in the file itself
+	pub s3: Option<S3Upload>,
+	pub sftp: Option<SftpUpload>,
+	// cloud drive folder finished exports are saved to, only editable in the
+	// file itself
+	pub drive: Option<CloudDrive>,
+	// told about every finished export, only editable in the file itself
+	pub webhook: Option<Webhook>,
+	// slack or teams channel told about every finished export, only
+	// editable in the file itself
+	pub chat: Option<ChatNotification>,
+	// manifest of the latest release, empty for the one built in
+	pub update_url: String,
+	// where crash reports can be sent, empty to only keep them on disk
+	pub crash_report_url: String,
+	// port `oxide serve` listens on for exports, empty for 8321
+	pub api_port: String,
+	// set when the settings were saved by a newer oxide, which this one
+	// mustn't overwrite with whatever fields it happens to know about
+	#[serde(skip)]
+	newer_version: Option<u64>,
+	// what went wrong reading the settings, told by the gui or the command
+	// line as the gui has no stderr to print it to
+	#[serde(skip)]
+	load_warnings: Vec<String>,
+	// settings this oxide doesn't know, like ones a newer one added, kept
+	// as they are so saving doesn't drop them
+	#[serde(flatten)]
+	unknown: Map<String, Value>,
+}
+
+impl Config {
+	/// Names settings go by on the command line.
+	pub const KEYS: &[&str] = &[
+		"input-dir",
+		"output-file",
+		"header-style",
+		"script",
+		"template",
+		"hole-summary",
+		"totals",
+		"invalid-rows",
+		"schema-sidecar",
+		"provenance",
+		"null-value",
+		"csv-dialect",
+		"source-columns",
+		"row-ids",
+		"date-from",
+		"date-to",
+		"filter",
+		"sort-by",
+		"duplicate-reports",
+		"merge-key",
+		"column-order",
+		"qa-output",
+		"remarks-output",
+		"depth-intervals",
+		"cumulative-meters",
+		"preset",
+		"manifest",
+		"pdf-report",
+		"date-format",
+		"time-format",
+		"datetime-format",
+		"timezone",
+		"output-timezone",
+		"open-retries",
+		"open-retry-delay",
+		"settle-time",
+		"max-concurrent-files",
+		"keep-identical",
+		"archive-folder",
+		"archive-copy",
+		"archive-rename",
+		"quarantine-folder",
+		"update-url",
+		"crash-report-url",
+		"api-port",
+	];
+
+	pub fn load() -> Config {
+		let mut warnings = Vec::new();
+		// portable builds carry their settings inside the executable, a copy
+		// that was never configured starts from the user's config instead
+		#[cfg(feature = "portable")]
+		let config = crate::portable::load(&mut warnings);
+		#[cfg(not(feature = "portable"))]
+		let config = None;
+
+		let mut config = config
+			.or_else(|| config_path().and_then(|path| Config::read_from(&path, &mut warnings)))
+			.unwrap_or_default();
+		config.load_warnings = warnings;
+		config
+	}
+
+	/// What went wrong reading the settings, like a file that couldn't be
+	/// read and was moved aside, for the user to be told.
+	pub fn load_warnings(&self) -> &[String] {
+		&self.load_warnings
+	}
+
+	/// Saves the settings, returning a warning for the user if they had to
+	/// be saved somewhere other than usual.
+	pub fn save(&self) -> io::Result<Option<String>> {
+		if let Some(version) = self.newer_version {
+			return Ok(Some(format!(
+				"Settings were saved by a newer version of oxide (config version {}), they weren't overwritten.",
+				version
+			)));
+		}
+
+		#[cfg(feature = "portable")]
+		return crate::portable::save(self);
+
+		#[cfg(not(feature = "portable"))]
+		match config_path() {
+			Some(path) => self.write_to(&path).map(|_| None),
+			None => Ok(None),
+		}
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		match key {
+			"input-dir" => Some(&self.input_dir),
+			"output-file" => Some(&self.output_file),
+			"header-style" => Some(&self.header_style),
+			"script" => Some(&self.script),
+			"template" => Some(&self.template),
+			"hole-summary" => Some(if self.hole_summary { "true" } else { "false" }),
+			"totals" => Some(&self.totals),
+			"invalid-rows" => Some(&self.invalid_rows),
+			"schema-sidecar" => Some(&self.schema_sidecar),
+			"provenance" => Some(&self.provenance),
+			"null-value" => Some(&self.null_value),
+			"csv-dialect" => Some(&self.csv_dialect),
+			"source-columns" => Some(if self.source_columns { "true" } else { "false" }),
+			"row-ids" => Some(&self.row_ids),
+			"date-from" => Some(&self.date_from),
+			"date-to" => Some(&self.date_to),
+			"filter" => Some(&self.filter),
+			"sort-by" => Some(&self.sort_by),
+			"duplicate-reports" => Some(&self.duplicate_reports),
+			"merge-key" => Some(&self.merge_key),
+			"column-order" => Some(&self.column_order),
+			"qa-output" => Some(&self.qa_output),
+			"remarks-output" => Some(if self.remarks_output { "true" } else { "false" }),
+			"depth-intervals" => Some(if self.depth_intervals {
+				"true"
+			} else {
+				"false"
+			}),
+			"cumulative-meters" => Some(if self.cumulative_meters {
+				"true"
+			} else {
+				"false"
+			}),
+			"preset" => Some(&self.preset),
+			"manifest" => Some(if self.manifest { "true" } else { "false" }),
+			"pdf-report" => Some(if self.pdf_report { "true" } else { "false" }),
+			"date-format" => Some(&self.date_format),
+			"time-format" => Some(&self.time_format),
+			"datetime-format" => Some(&self.datetime_format),
+			"timezone" => Some(&self.timezone),
+			"output-timezone" => Some(&self.output_timezone),
+			"open-retries" => Some(&self.open_retries),
+			"open-retry-delay" => Some(&self.open_retry_delay),
+			"settle-time" => Some(&self.settle_time),
+			"max-concurrent-files" => Some(&self.max_concurrent_files),
+			"keep-identical" => Some(if self.keep_identical { "true" } else { "false" }),
+			"archive-folder" => Some(&self.archive_folder),
+			"archive-copy" => Some(if self.archive_copy { "true" } else { "false" }),
+			"archive-rename" => Some(&self.archive_rename),
+			"quarantine-folder" => Some(&self.quarantine_folder),
+			"update-url" => Some(&self.update_url),
+			"crash-report-url" => Some(&self.crash_report_url),
+			"api-port" => Some(&self.api_port),
+			_ => None,
+		}
+	}
+
+	/// Sets a setting by name, returning why if the value can't be used.
+	pub fn set(&mut self, key: &str, value: String) -> Result<(), String> {
+		// checked by reading them into export options, like exports do
+		if EXPORT_KEYS.contains(&key) {
+			ExportOptions::default().set(key, &value)?;
+		}
+
+		match key {
+			"input-dir" => self.input_dir = value,
+			"output-file" => self.output_file = value,
+			"header-style" => {
+				check_name("header style", HeaderStyle::NAMES, &value)?;
+				self.header_style = value;
+			}
+			"script" => self.script = value,
+			"template" => self.template = value,
+			"hole-summary" => self.hole_summary = parse_flag(key, &value)?,
+			"totals" => {
+				check_name("totals", Totals::NAMES, &value)?;
+				self.totals = value;
+			}
+			"invalid-rows" => {
+				check_name("invalid rows", InvalidRows::NAMES, &value)?;
+				self.invalid_rows = value;
+			}
+			"schema-sidecar" => self.schema_sidecar = value,
+			"provenance" => self.provenance = value,
+			"null-value" => self.null_value = value,
+			"csv-dialect" => self.csv_dialect = value,
+			"source-columns" => self.source_columns = parse_flag(key, &value)?,
+			"row-ids" => self.row_ids = value,
+			"date-from" => {
+				DateRange::new(&value, &self.date_to)?;
+				self.date_from = value;
+			}
+			"date-to" => {
+				DateRange::new(&self.date_from, &value)?;
+				self.date_to = value;
+			}
+			"filter" => self.filter = value,
+			"sort-by" => self.sort_by = value,
+			"duplicate-reports" => self.duplicate_reports = value,
+			"merge-key" => self.merge_key = value,
+			"column-order" => self.column_order = value,
+			"qa-output" => self.qa_output = value,
+			"remarks-output" => self.remarks_output = parse_flag(key, &value)?,
+			"depth-intervals" => self.depth_intervals = parse_flag(key, &value)?,
+			"cumulative-meters" => self.cumulative_meters = parse_flag(key, &value)?,
+			"preset" => self.preset = value,
+			"manifest" => self.manifest = parse_flag(key, &value)?,
+			"pdf-report" => self.pdf_report = parse_flag(key, &value)?,
+			"date-format" => self.date_format = value,
+			"time-format" => self.time_format = value,
+			"datetime-format" => self.datetime_format = value,
+			"timezone" => self.timezone = value,
+			"output-timezone" => self.output_timezone = value,
+			"open-retries" | "open-retry-delay" => {
+				if value.is_empty().not() && value.parse::<u32>().is_err() {
+					return Err(format!("expected a number for {}, not {}", key, value));
+				}
+
+				match key {
+					"open-retries" => self.open_retries = value,
+					_ => self.open_retry_delay = value,
+				}
+			}
+			"settle-time" => self.settle_time = value,
+			"max-concurrent-files" => self.max_concurrent_files = value,
+			"keep-identical" => self.keep_identical = parse_flag(key, &value)?,
+			"archive-folder" => self.archive_folder = value,
+			"archive-copy" => self.archive_copy = parse_flag(key, &value)?,
+			"archive-rename" => {
+				if value.is_empty().not() {
+					Archive::check_rename(&value)?;
+				}
+				self.archive_rename = value;
+			}
+			"quarantine-folder" => self.quarantine_folder = value,
+			"update-url" => self.update_url = value,
+			"crash-report-url" => self.crash_report_url = value,
+			"api-port" => {
+				if value.is_empty().not() && value.parse::<u16>().is_err() {
+					return Err(format!("expected a port for api-port, not {}", value));
+				}
+
+				self.api_port = value;
+			}
+			_ => {
+				return Err(format!(
+					"unknown setting {}, expected one of: {}",
+					key,
+					Config::KEYS.join(", ")
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Options for an export following these settings, failing on settings
+	/// that can't be used.
+	pub fn export_options(&self) -> Result<ExportOptions, String> {
+		let mut options = ExportOptions {
+			header_style: self.header_style()?,
+			template: self.report_template()?,
+			template_rules: self.template_rules()?,
+			plugins: plugins::registered(self)?,
+			aggregations: self.aggregations()?,
+			totals: self.totals()?,
+			total_columns: self.total_columns.clone(),
+			validation: self.validation()?,
+			profile: self.template.clone(),
+			lookups: self.lookups()?,
+			date_range: DateRange::new(&self.date_from, &self.date_to)?,
+			qa_checks: self.qa_checks.clone(),
+			hole_ids: self.hole_ids.clone(),
+			number_formats: self.number_formats()?,
+			date_times: self.date_times()?,
+			open_retries: self.open_retries()?,
+			archive: (self.archive_folder.is_empty() && self.archive_rename.is_empty())
+				.not()
+				.then(|| Archive {
+					folder: self.archive_folder.clone().into(),
+					copy: self.archive_copy,
+					rename: self.archive_rename.clone(),
+				}),
+			..Default::default()
+		};
+		for key in EXPORT_KEYS {
+			options.set(key, self.get(key).unwrap_or_default())?;
+		}
+
+		Ok(options)
+	}
+
+	/// The summaries exports write, the per hole one after the rest, failing
+	/// on one that can't be written.
+	pub fn aggregations(&self) -> Result<Vec<Aggregation>, String> {
+		let mut aggregations = self.aggregations.clone();
+		if self.hole_summary {
+			aggregations.push(Aggregation::per_hole("hole_number", "meters", "date"));
+		}
+
+		for (i, aggregation) in aggregations.iter().enumerate() {
+			aggregation.validate()?;
+			if aggregations[..i].iter().any(|a| a.name == aggregation.name) {
+				return Err(format!(
+					"there's more than one summary named {}",
+					aggregation.name
+				));
+			}
+		}
+
+		Ok(aggregations)
+	}
+
+	/// Where exports write totals rows, failing on unknown totals or totals
+	/// without any columns to sum up.
+	pub fn totals(&self) -> Result<Totals, String> {
+		if self.totals.is_empty() {
+			return Ok(Totals::None);
+		}
+
+		let totals =
+			Totals::from_name(&self.totals).ok_or_else(|| format!("unknown totals {}", self.totals))?;
+		if totals != Totals::None && self.total_columns.is_empty() {
+			return Err("totals need the columns to sum up listed in total_columns".to_string());
+		}
+
+		Ok(totals)
+	}
+
+	/// The rules exports check rows against, none without any rules. Fails
+	/// on an invalid pattern or unknown invalid rows.
+	pub fn validation(&self) -> Result<Option<Validation>, String> {
+		if self.validation_rules.is_empty() {
+			return Ok(None);
+		}
+
+		let invalid_rows = match self.invalid_rows.is_empty() {
+			true => InvalidRows::default(),
+			false => InvalidRows::from_name(&self.invalid_rows)
+				.ok_or_else(|| format!("unknown invalid rows {}", self.invalid_rows))?,
+		};
+
+		Validation::new(&self.validation_rules, invalid_rows).map(Some)
+	}
+
+	/// The port `oxide serve` listens on.
+	pub fn api_port(&self) -> u16 {
+		self.api_port.parse().unwrap_or(8321)
+	}
+
+	/// Reads the lookup files, failing on one that can't be read.
+	pub fn lookups(&self) -> Result<Vec<Lookup>, String> {
+		self
+			.lookups
+			.iter()
+			.map(|lookup| Lookup::load(Path::new(&lookup.path), &lookup.key).map_err(|e| e.to_string()))
+			.collect()
+	}
+
+	/// How often exports try a workbook open in excel again, failing on a
+	/// setting that isn't a number.
+	pub fn open_retries(&self) -> Result<OpenRetries, String> {
+		let mut retries = OpenRetries::default();
+		if self.open_retries.is_empty().not() {
+			retries.retries = self
+				.open_retries
+				.parse()
+				.map_err(|_| format!("open-retries {} isn't a number", self.open_retries))?;
+		}
+		if self.open_retry_delay.is_empty().not() {
+			let millis = self
+				.open_retry_delay
+				.parse()
+				.map_err(|_| format!("open-retry-delay {} isn't a number", self.open_retry_delay))?;
+			retries.delay = Duration::from_millis(millis);
+		}
+
+		Ok(retries)
+	}
+
+	/// The number formats of the columns that have one, failing on a format
+	/// that can't be read.
+	pub fn number_formats(&self) -> Result<BTreeMap<String, NumberFormat>, String> {
+		self
+			.number_formats
+			.iter()
+			.map(|(column, format)| {
+				NumberFormat::parse(format)
+					.map(|format| (column.clone(), format))
+					.map_err(|e| format!("{}: {}", column, e))
+			})
+			.collect()
+	}
+
+	/// How exports write date and time cells, failing on a format or
+	/// timezone that can't be used.
+	pub fn date_times(&self) -> Result<DateTimes, String> {
+		let date_format = match self.date_format.is_empty() {
+			true => DateTimes::DATE_FORMAT,
+			false => &self.date_format,
+		};
+		let time_format = match self.time_format.is_empty() {
+			true => DateTimes::TIME_FORMAT,
+			false => &self.time_format,
+		};
+		let datetime_format = match self.datetime_format.is_empty() {
+			true => DateTimes::DATETIME_FORMAT,
+			false => &self.datetime_format,
+		};
+
+		DateTimes::new(
+			date_format,
+			time_format,
+			datetime_format,
+			&self.timezone,
+			&self.output_timezone,
+		)
+	}
+
+	/// The template exports read workbooks with, renaming the header aliases.
+	pub fn report_template(&self) -> Result<ReportTemplate, String> {
+		let mut template = match self.template.is_empty() {
+			true => ReportTemplate::default(),
+			false => ReportTemplate::load(&template_path(&self.template)).map_err(|e| e.to_string())?,
+		};
+		template.add_aliases(&self.header_aliases, &self.header_style()?);
+		Ok(template)
+	}
+
+	/// The templates of the workbooks matching a pattern, failing on one
+	/// that can't be loaded.
+	pub fn template_rules(&self) -> Result<Vec<TemplateRule>, String> {
+		self
+			.template_rules
+			.iter()
+			.map(|rule| {
+				let mut template = ReportTemplate::load(&template_path(&rule.template))
+					.map_err(|e| format!("template for {}: {}", rule.pattern, e))?;
+				template.add_aliases(&self.header_aliases, &self.header_style()?);
+				Ok(TemplateRule {
+					pattern: rule.pattern.clone(),
+					template,
+				})
+			})
+			.collect()
+	}
+
+	/// The header style exports use, failing on an unknown style or an
+	/// invalid replacement pattern.
+	pub fn header_style(&self) -> Result<HeaderStyle, String> {
+		if self.header_style.is_empty() {
+			return Ok(HeaderStyle::default());
+		}
+
+		let replacements = self.header_replacements()?;
+		HeaderStyle::from_name(&self.header_style, replacements)
+			.ok_or_else(|| format!("unknown header style {}", self.header_style))
+	}
+
+	pub fn header_replacements(&self) -> Result<Vec<HeaderReplacement>, String> {
+		self
+			.header_replacements
+			.iter()
+			.map(|(pattern, replacement)| {
+				HeaderReplacement::new(pattern, replacement)
+					.map_err(|e| format!("invalid header replacement {}: {}", pattern, e))
+			})
+			.collect()
+	}
+
+	// settings that can't be read are moved aside rather than left to be
+	// overwritten by the defaults on the next save, unless a newer oxide
+	// saved them, which are left alone and never overwritten
+	pub(crate) fn read_from(path: &Path, warnings: &mut Vec<String>) -> Option<Config> {
+		let contents = fs::read_to_string(path).ok()?;
+		let error = match Config::parse(&contents) {
+			Ok(config) => return Some(config),
+			Err(e) => e,
+		};
+
+		match Config::unreadable_newer(&contents) {
+			Some(config) => {
+				warnings.push(format!(
+					"{} was saved by a newer version of oxide and can't be read ({}), the default settings are used and it isn't overwritten",
+					path.display(),
+					error
+				));
+				Some(config)
+			}
+			None => {
+				let backup = path.with_extension("json.bak");
+				warnings.push(format!(
+					"{} is unreadable ({}), it was moved to {} and the default settings are used",
+					path.display(),
+					error,
+					backup.display()
+				));
+				let _ = fs::rename(path, backup);
+				None
+			}
+		}
+	}
+
+	// the default settings, never saved, for settings that failed to parse
+	// when they're from a newer oxide. the version is read on its own, as
+	// the rest can have changed in any way
+	pub(crate) fn unreadable_newer(contents: &str) -> Option<Config> {
+		let fields: Value = serde_json::from_str(contents).ok()?;
+		let version = fields.get("version")?.as_u64()?;
+		(version > CONFIG_VERSION).then(|| Config {
+			newer_version: Some(version),
+			..Config::default()
+		})
+	}
+
+	pub(crate) fn parse(contents: &str) -> serde_json::Result<Config> {
+		let mut fields: Map<String, Value> = serde_json::from_str(contents)?;
+		// settings from before versioning have no version at all
+		let version = match fields.remove("version") {
+			Some(v) => serde_json::from_value(v)?,
+			None => 0,
+		};
+
+		for migrate in MIGRATIONS.iter().skip(version as usize) {
+			migrate(&mut fields);
+		}
+
+		let mut config: Config = serde_json::from_value(Value::Object(fields))?;
+		if version > CONFIG_VERSION {
+			config.newer_version = Some(version);
+		}
+
+		Ok(config)
+	}
+
+	pub(crate) fn write_to(&self, path: &Path) -> io::Result<()> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		fs::write(path, serde_json::to_string_pretty(&self.versioned()?)?)
+	}
+
+	// the settings as they're saved, for portable builds to carry them in
+	// the executable
+	#[cfg(feature = "portable")]
+	pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+		serde_json::to_string(&self.versioned()?)
+	}
+
+	fn versioned(&self) -> serde_json::Result<Map<String, Value>> {
+		let Value::Object(mut fields) = serde_json::to_value(self)? else {
+			unreachable!("config serializes to an object");
+		};
+		fields.insert("version".to_string(), CONFIG_VERSION.into());
+		Ok(fields)
+	}
+}
+
+fn config_path() -> Option<PathBuf> {
+	ProjectDirs::from("", "", "oxide").map(|dirs| dirs.config_dir().join("config.json"))
+}
+
+/// Where templates are shared from, each one a json file named after it.
+pub fn templates_dir() -> Option<PathBuf> {
+	ProjectDirs::from("", "", "oxide").map(|dirs| dirs.config_dir().join("templates"))
+}
+
+/// Names of the templates in the templates folder.
+pub fn template_names() -> Vec<String> {
+	let Some(entries) = templates_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+		return Vec::new();
+	};
+
+	let mut names: Vec<_> = entries
+		.filter_map(|entry| {
+			let path = entry.ok()?.path();
+			if path.extension().is_some_and(|e| e == "json").not() {
+				return None;
+			}
+
+			path.file_stem()?.to_str().map(|s| s.to_string())
+		})
+		.collect();
+	names.sort();
+	names
+}
+
+// a template is named after its file in the templates folder, unless it's
+// given as a path to a file anywhere else
+pub fn template_path(template: &str) -> PathBuf {
+	let path = Path::new(template);
+	if path.extension().is_some() || path.components().count() > 1 {
+		return path.to_path_buf();
+	}
+
+	templates_dir()
+		.unwrap_or_default()
+		.join(format!("{}.json", template))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use std::process;
+
+	use super::*;
+
+	// a settings file of its own for every test, removed once it's read
+	fn saved(name: &str, contents: &str) -> PathBuf {
+		let path = env::temp_dir().join(format!("oxide-config-{}-{}.json", name, process::id()));
+		fs::write(&path, contents).unwrap();
+		path
+	}
+
+	fn fields(path: &Path) -> Map<String, Value> {
+		serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn migrates_unversioned_settings() {
+		let path = saved(
+			"unversioned",
+			r#"{ "input_dir": "D:\\Reports", "hole_summary": true }"#,
+		);
+		let mut warnings = Vec::new();
+		let config = Config::read_from(&path, &mut warnings).unwrap();
+		assert!(warnings.is_empty(), "{:?}", warnings);
+		assert_eq!(config.input_dir, "D:\\Reports");
+		assert!(config.hole_summary);
+
+		config.write_to(&path).unwrap();
+		let fields = fields(&path);
+		let _ = fs::remove_file(&path);
+		assert_eq!(fields["version"], CONFIG_VERSION);
+		assert_eq!(fields["input_dir"], "D:\\Reports");
+	}
+
+	#[test]
+	fn leaves_newer_settings_alone() {
+		let newer = CONFIG_VERSION + 1;
+		// a setting that changed type can't be read at all
+		let contents = format!(
+			r#"{{ "version": {}, "input_dir": ["D:\\Reports"] }}"#,
+			newer
+		);
+		let path = saved("newer", &contents);
+		let mut warnings = Vec::new();
+		let config = Config::read_from(&path, &mut warnings).unwrap();
+		assert_eq!(warnings.len(), 1);
+		assert!(warnings[0].contains("newer version"), "{}", warnings[0]);
+		assert_eq!(config.input_dir, "");
+
+		let warning = config.save().unwrap().unwrap();
+		let kept = fs::read_to_string(&path).unwrap();
+		let _ = fs::remove_file(&path);
+		assert!(warning.contains(&format!("config version {}", newer)));
+		assert_eq!(kept, contents);
+
+		// one that can be read is still never saved over
+		let config = Config::parse(&format!(
+			r#"{{ "version": {}, "input_dir": "D:\\Reports" }}"#,
+			newer
+		))
+		.unwrap();
+		assert_eq!(config.input_dir, "D:\\Reports");
+		assert!(config.save().unwrap().is_some());
+	}
+
+	#[test]
+	fn keeps_unknown_settings() {
+		let path = saved(
+			"unknown",
+			r#"{ "version": 1, "input_dir": "D:\\Reports", "rig_colours": { "RD-4": "red" } }"#,
+		);
+		let mut config = Config::read_from(&path, &mut Vec::new()).unwrap();
+		config.output_file = "D:\\out.csv".to_string();
+		config.write_to(&path).unwrap();
+		let fields = fields(&path);
+		let _ = fs::remove_file(&path);
+		assert_eq!(fields["rig_colours"], serde_json::json!({ "RD-4": "red" }));
+		assert_eq!(fields["output_file"], "D:\\out.csv");
+	}
+}
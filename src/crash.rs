@@ -0,0 +1,177 @@
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::ops::Not;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+
+// lines kept for the next crash report
+const MAX_LOG_LINES: usize = 50;
+
+// oldest reports are removed once there are more than this
+const MAX_REPORTS: usize = 10;
+
+const REPORT_PREFIX: &str = "crash-";
+// reports that were already offered to the user
+const SEEN_EXTENSION: &str = "seen";
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Keeps a line for crash reports, which should only ever say what oxide was
+/// doing, never what was read from a workbook.
+pub fn log(line: String) {
+	let Ok(mut log) = LOG.lock() else {
+		return;
+	};
+
+	if log.len() == MAX_LOG_LINES {
+		log.pop_front();
+	}
+
+	log.push_back(line);
+}
+
+/// Writes a report for every panic before it's handled as usual, release
+/// builds have no console for the panic message to show up in.
+pub fn install_hook() {
+	let default_hook = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		match write_report(info) {
+			Ok(Some(path)) => eprintln!("crash report saved to {}", path.display()),
+			Ok(None) => {}
+			Err(e) => eprintln!("failed to save crash report: {}", e),
+		}
+
+		default_hook(info);
+	}));
+}
+
+/// The newest report that hasn't been offered to the user yet, marking every
+/// report as offered.
+pub fn take_pending() -> Option<PathBuf> {
+	let mut pending = None;
+	for report in reports().into_iter().filter(|path| is_seen(path).not()) {
+		let seen = report.with_extension(format!("{}.txt", SEEN_EXTENSION));
+		if fs::rename(&report, &seen).is_ok() {
+			pending = Some(seen);
+		}
+	}
+
+	pending
+}
+
+/// Opens the report, or a url, with whatever the system opens it with.
+pub fn open(path: impl AsRef<OsStr>) -> io::Result<()> {
+	#[cfg(windows)]
+	let mut command = Command::new("explorer");
+	#[cfg(target_os = "macos")]
+	let mut command = Command::new("open");
+	#[cfg(not(any(windows, target_os = "macos")))]
+	let mut command = Command::new("xdg-open");
+
+	command.arg(path.as_ref()).spawn().map(|_| ())
+}
+
+/// Sends the report's contents to `url`, only ever done when the user agrees
+/// to it.
+#[cfg(feature = "crash-reports")]
+pub fn submit(path: &Path, url: &str) -> Result<(), String> {
+	let report = fs::read_to_string(path).map_err(|e| e.to_string())?;
+	ureq::post(url)
+		.content_type("text/plain; charset=utf-8")
+		.send(&report)
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+fn write_report(info: &PanicHookInfo) -> io::Result<Option<PathBuf>> {
+	let Some(dir) = reports_dir() else {
+		return Ok(None);
+	};
+
+	let timestamp = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default();
+
+	let message = match info.payload().downcast_ref::<&str>() {
+		Some(message) => message.to_string(),
+		None => info
+			.payload()
+			.downcast_ref::<String>()
+			.cloned()
+			.unwrap_or_else(|| "unknown panic".to_string()),
+	};
+
+	let mut report = String::new();
+	let _ = writeln!(report, "oxide {}", env!("CARGO_PKG_VERSION"));
+	let _ = writeln!(report, "{} {}", env::consts::OS, env::consts::ARCH);
+	let _ = writeln!(report, "time: {}", timestamp);
+	let _ = writeln!(
+		report,
+		"thread: {}",
+		thread::current().name().unwrap_or("unnamed")
+	);
+	let _ = writeln!(report, "panic: {}", message);
+	if let Some(location) = info.location() {
+		let _ = writeln!(report, "at: {}", location);
+	}
+
+	let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+	let _ = writeln!(report, "\nlog:");
+	// the hook can run while the log is held, if logging itself panicked
+	if let Ok(log) = LOG.try_lock() {
+		for line in log.iter() {
+			let _ = writeln!(report, "{}", line);
+		}
+	}
+
+	fs::create_dir_all(&dir)?;
+	let path = dir.join(format!("{}{}.txt", REPORT_PREFIX, timestamp));
+	fs::write(&path, report)?;
+
+	let mut reports = reports();
+	while reports.len() > MAX_REPORTS {
+		let _ = fs::remove_file(reports.remove(0));
+	}
+
+	Ok(Some(path))
+}
+
+// reports oldest first
+fn reports() -> Vec<PathBuf> {
+	let Some(entries) = reports_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+		return Vec::new();
+	};
+
+	let mut reports: Vec<(SystemTime, PathBuf)> = entries
+		.filter_map(|e| e.ok())
+		.filter(|e| e.file_name().to_string_lossy().starts_with(REPORT_PREFIX))
+		.filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+		.collect();
+	reports.sort();
+	reports.into_iter().map(|(_, path)| path).collect()
+}
+
+fn is_seen(path: &Path) -> bool {
+	path
+		.file_stem()
+		.and_then(|s| Path::new(s).extension())
+		.is_some_and(|e| e == SEEN_EXTENSION)
+}
+
+fn reports_dir() -> Option<PathBuf> {
+	let dirs = ProjectDirs::from("", "", "oxide")?;
+	Some(dirs.data_local_dir().join("crashes"))
+}
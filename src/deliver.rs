@@ -0,0 +1,77 @@
+use oxide_core::RunSummary;
+
+use crate::chat::{self, ChatNotification};
+use crate::config::Config;
+use crate::drive::{self, CloudDrive};
+use crate::email::{self, EmailSettings};
+use crate::upload::{self, S3Upload, SftpUpload};
+use crate::webhook::{self, Webhook};
+
+/// Everywhere a finished export is sent, as set in the settings file.
+#[derive(Default, Clone)]
+pub struct Delivery {
+	pub email: Option<EmailSettings>,
+	pub s3: Option<S3Upload>,
+	pub sftp: Option<SftpUpload>,
+	pub drive: Option<CloudDrive>,
+	pub webhook: Option<Webhook>,
+	pub chat: Option<ChatNotification>,
+}
+
+impl Delivery {
+	pub fn new(config: &Config) -> Delivery {
+		Delivery {
+			email: config.email.clone(),
+			s3: config.s3.clone(),
+			sftp: config.sftp.clone(),
+			drive: config.drive.clone(),
+			webhook: config.webhook.clone(),
+			chat: config.chat.clone(),
+		}
+	}
+
+	/// Uploads the export, then posts to the webhook and chat and emails it,
+	/// returning what went wrong with each place it couldn't be sent to.
+	/// Uploads go first so the rest only announce an export that's already
+	/// where it's expected.
+	pub fn send(&self, output_file: &str, summary: &RunSummary) -> Vec<String> {
+		let mut errors = Vec::new();
+		if let Some(s3) = &self.s3
+			&& let Err(e) = upload::s3(s3, output_file)
+		{
+			errors.push(format!("failed to upload the export to s3: {}", e));
+		}
+
+		if let Some(sftp) = &self.sftp
+			&& let Err(e) = upload::sftp(sftp, output_file)
+		{
+			errors.push(format!("failed to upload the export over sftp: {}", e));
+		}
+
+		if let Some(cloud_drive) = &self.drive
+			&& let Err(e) = drive::upload(cloud_drive, output_file)
+		{
+			errors.push(format!("failed to save the export to the drive: {}", e));
+		}
+
+		if let Some(hook) = &self.webhook
+			&& let Err(e) = webhook::send(hook, output_file, summary)
+		{
+			errors.push(format!("failed to post to the webhook: {}", e));
+		}
+
+		if let Some(channel) = &self.chat
+			&& let Err(e) = chat::send(channel, output_file, summary)
+		{
+			errors.push(format!("failed to post to the chat: {}", e));
+		}
+
+		if let Some(email) = &self.email
+			&& let Err(e) = email::send(email, output_file, summary)
+		{
+			errors.push(format!("failed to email the export: {}", e));
+		}
+
+		errors
+	}
+}
@@ -0,0 +1,590 @@
+use serde::{Deserialize, Serialize};
+
+/// Cloud drives exports can be saved to.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DriveProvider {
+	#[default]
+	GoogleDrive,
+	Onedrive,
+}
+
+/// A cloud drive folder finished exports are saved to, through an app
+/// registered with the provider. The login itself is kept apart from the
+/// settings, see `oxide drive login`.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct CloudDrive {
+	pub provider: DriveProvider,
+	pub client_id: String,
+	// google's desktop apps have one, onedrive's don't
+	pub client_secret: String,
+	// id of the google drive folder, the last part of its url, or the path
+	// of the onedrive folder like Reports/Daily
+	pub folder: String,
+}
+
+#[cfg(feature = "drive")]
+mod send {
+	use std::collections::hash_map::RandomState;
+	use std::fs::{self, File};
+	use std::hash::{BuildHasher, Hasher};
+	use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+	use std::net::TcpListener;
+	use std::ops::Not;
+	use std::path::{Path, PathBuf};
+	use std::time::Duration;
+
+	use base64::Engine;
+	use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+	use directories::ProjectDirs;
+	use serde::{Deserialize, Serialize};
+	use serde_json::{Value, json};
+	use sha2::{Digest, Sha256};
+
+	use super::{CloudDrive, DriveProvider};
+	use crate::crash;
+
+	// the drive's own scopes, and a refresh token to keep using them
+	const GOOGLE_SCOPE: &str = "https://www.googleapis.com/auth/drive";
+	const ONEDRIVE_SCOPE: &str = "Files.ReadWrite offline_access";
+
+	// larger outputs go up in chunks through an upload session, which picks
+	// up where it got to when a chunk fails rather than starting over
+	const SESSION_SIZE: u64 = 4 * 1024 * 1024;
+	// a multiple of the 320 KiB onedrive and the 256 KiB google drive take
+	const CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+	// times a chunk is sent before the upload fails
+	const CHUNK_TRIES: usize = 3;
+
+	/// What's kept of a login, in the user's local data directory rather
+	/// than the settings so it never ends up in a shared or portable copy.
+	#[derive(Serialize, Deserialize)]
+	struct Login {
+		provider: DriveProvider,
+		refresh_token: String,
+	}
+
+	#[derive(Deserialize)]
+	struct Tokens {
+		access_token: String,
+		refresh_token: Option<String>,
+	}
+
+	impl DriveProvider {
+		fn auth_url(self) -> &'static str {
+			match self {
+				DriveProvider::GoogleDrive => "https://accounts.google.com/o/oauth2/v2/auth",
+				DriveProvider::Onedrive => "https://login.microsoftonline.com/common/oauth2/v2.0/authorize",
+			}
+		}
+
+		fn token_url(self) -> &'static str {
+			match self {
+				DriveProvider::GoogleDrive => "https://oauth2.googleapis.com/token",
+				DriveProvider::Onedrive => "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+			}
+		}
+
+		fn scope(self) -> &'static str {
+			match self {
+				DriveProvider::GoogleDrive => GOOGLE_SCOPE,
+				DriveProvider::Onedrive => ONEDRIVE_SCOPE,
+			}
+		}
+	}
+
+	/// Logs in to the drive in the browser, with the code sent back to a
+	/// port on this machine, and keeps the refresh token for exports.
+	pub fn login(drive: &CloudDrive) -> Result<(), String> {
+		if drive.client_id.is_empty() {
+			return Err("the drive settings need the client_id of an app registered with it".to_string());
+		}
+
+		let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+		let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+		let redirect_uri = format!("http://127.0.0.1:{}", port);
+		let state = random_string(2);
+		let verifier = random_string(4);
+		let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+		let mut url = format!(
+			"{}?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method=S256",
+			drive.provider.auth_url(),
+			encode(&drive.client_id),
+			encode(&redirect_uri),
+			encode(drive.provider.scope()),
+			state,
+			challenge
+		);
+		// google only hands out a refresh token when asked for offline access
+		if drive.provider == DriveProvider::GoogleDrive {
+			url.push_str("&access_type=offline&prompt=consent");
+		}
+
+		println!("log in at {}", url);
+		let _ = crash::open(&url);
+
+		let code = loop {
+			let (mut stream, _) = listener.accept().map_err(|e| e.to_string())?;
+			let mut request_line = String::new();
+			BufReader::new(&stream)
+				.read_line(&mut request_line)
+				.map_err(|e| e.to_string())?;
+			let query = request_line
+				.split_whitespace()
+				.nth(1)
+				.and_then(|target| target.split_once('?'))
+				.map(|(_, query)| query)
+				.unwrap_or_default();
+			let param = |name: &str| {
+				query
+					.split('&')
+					.filter_map(|pair| pair.split_once('='))
+					.find(|(key, _)| *key == name)
+					.map(|(_, value)| decode(value))
+			};
+
+			// the browser asks for things like the favicon too
+			let (code, error) = (param("code"), param("error"));
+			if code.is_none() && error.is_none() {
+				let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+				continue;
+			}
+
+			let page = match &error {
+				Some(_) => "Logging in to the drive failed, oxide says why.",
+				None => "Logged in, this window can be closed.",
+			};
+			let _ = write!(
+				stream,
+				"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+				page.len(),
+				page
+			);
+
+			// anything could have sent the error, only the provider knows the state
+			if param("state").as_deref() != Some(state.as_str()) {
+				return Err("the login came back for another request".to_string());
+			}
+
+			if let Some(error) = error {
+				return Err(format!("the login was refused: {}", error));
+			}
+
+			break code.unwrap_or_default();
+		};
+
+		let mut form = vec![
+			("grant_type", "authorization_code"),
+			("code", code.as_str()),
+			("redirect_uri", redirect_uri.as_str()),
+			("client_id", drive.client_id.as_str()),
+			("code_verifier", verifier.as_str()),
+		];
+		if drive.client_secret.is_empty().not() {
+			form.push(("client_secret", drive.client_secret.as_str()));
+		}
+
+		let tokens = request_tokens(drive.provider, &form)?;
+		let refresh_token = tokens
+			.refresh_token
+			.ok_or("the drive didn't hand out a refresh token")?;
+		save_login(&Login {
+			provider: drive.provider,
+			refresh_token,
+		})
+	}
+
+	/// Saves `output_file` into the drive's folder under its file name,
+	/// replacing a file already there.
+	pub fn upload(drive: &CloudDrive, output_file: &str) -> Result<(), String> {
+		let login = load_login()
+			.filter(|login| login.provider == drive.provider)
+			.ok_or("there's no login for the drive, run oxide drive login")?;
+		let mut form = vec![
+			("grant_type", "refresh_token"),
+			("refresh_token", login.refresh_token.as_str()),
+			("client_id", drive.client_id.as_str()),
+		];
+		if drive.client_secret.is_empty().not() {
+			form.push(("client_secret", drive.client_secret.as_str()));
+		}
+
+		if drive.provider == DriveProvider::Onedrive {
+			form.push(("scope", ONEDRIVE_SCOPE));
+		}
+
+		let tokens = request_tokens(drive.provider, &form)?;
+		// onedrive hands out a new refresh token with every use
+		if let Some(refresh_token) = tokens.refresh_token
+			&& refresh_token != login.refresh_token
+		{
+			save_login(&Login {
+				provider: drive.provider,
+				refresh_token,
+			})?;
+		}
+
+		let read_error = |e| format!("failed to read {}: {}", output_file, e);
+		let mut file = File::open(output_file).map_err(read_error)?;
+		let size = file.metadata().map_err(read_error)?.len();
+		let file_name = Path::new(output_file)
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.unwrap_or_else(|| output_file.to_string());
+		let authorization = format!("Bearer {}", tokens.access_token);
+
+		match drive.provider {
+			DriveProvider::GoogleDrive => {
+				// google drive tells files apart by id, so the one of the same
+				// name is looked up to be replaced
+				let query = format!(
+					"name = '{}' and '{}' in parents and trashed = false",
+					quote(&file_name),
+					quote(&drive.folder)
+				);
+				let found: Value = agent()
+					.get("https://www.googleapis.com/drive/v3/files")
+					.header("Authorization", &authorization)
+					.query("q", &query)
+					.query("fields", "files(id)")
+					.query("supportsAllDrives", "true")
+					.query("includeItemsFromAllDrives", "true")
+					.call()
+					.map_err(|e| e.to_string())?
+					.body_mut()
+					.read_json()
+					.map_err(|e| e.to_string())?;
+				let id = match found["files"][0]["id"].as_str() {
+					Some(id) => id.to_string(),
+					None => {
+						let created: Value = agent()
+							.post("https://www.googleapis.com/drive/v3/files")
+							.header("Authorization", &authorization)
+							.query("supportsAllDrives", "true")
+							.send_json(json!({ "name": file_name, "parents": [drive.folder] }))
+							.map_err(|e| e.to_string())?
+							.body_mut()
+							.read_json()
+							.map_err(|e| e.to_string())?;
+						created["id"]
+							.as_str()
+							.ok_or("google drive didn't say what the file's id is")?
+							.to_string()
+					}
+				};
+
+				let url = format!("https://www.googleapis.com/upload/drive/v3/files/{}", id);
+				if size <= SESSION_SIZE {
+					agent()
+						.patch(&url)
+						.header("Authorization", &authorization)
+						.query("uploadType", "media")
+						.query("supportsAllDrives", "true")
+						.send(&file)
+						.map_err(|e| e.to_string())?;
+					return Ok(());
+				}
+
+				let session = agent()
+					.patch(&url)
+					.header("Authorization", &authorization)
+					.header("X-Upload-Content-Length", &size.to_string())
+					.query("uploadType", "resumable")
+					.query("supportsAllDrives", "true")
+					.send_empty()
+					.map_err(|e| e.to_string())?;
+				let session_url = session
+					.headers()
+					.get("Location")
+					.and_then(|url| url.to_str().ok())
+					.ok_or("google drive didn't start an upload session")?
+					.to_string();
+				send_chunks(drive.provider, &session_url, &mut file, size, output_file)
+			}
+			DriveProvider::Onedrive => {
+				let path = format!("{}/{}", drive.folder.trim_matches('/'), file_name);
+				let url = format!(
+					"https://graph.microsoft.com/v1.0/me/drive/root:/{}:",
+					encode(path.trim_start_matches('/')).replace("%2F", "/")
+				);
+				if size <= SESSION_SIZE {
+					agent()
+						.put(&format!("{}/content", url))
+						.header("Authorization", &authorization)
+						.send(&file)
+						.map_err(|e| e.to_string())?;
+					return Ok(());
+				}
+
+				let session: Value = agent()
+					.post(&format!("{}/createUploadSession", url))
+					.header("Authorization", &authorization)
+					.send_json(json!({ "item": { "@microsoft.graph.conflictBehavior": "replace" } }))
+					.map_err(|e| e.to_string())?
+					.body_mut()
+					.read_json()
+					.map_err(|e| e.to_string())?;
+				let session_url = session["uploadUrl"]
+					.as_str()
+					.ok_or("onedrive didn't start an upload session")?;
+				send_chunks(drive.provider, session_url, &mut file, size, output_file)
+			}
+		}
+	}
+
+	// sends `file` to an upload session chunk by chunk, asking the session
+	// how much it has after a chunk fails. the session's url is all the
+	// authorization it takes.
+	fn send_chunks(
+		provider: DriveProvider,
+		session_url: &str,
+		file: &mut File,
+		size: u64,
+		output_file: &str,
+	) -> Result<(), String> {
+		let read_error = |e| format!("failed to read {}: {}", output_file, e);
+		let mut chunk = Vec::new();
+		let mut offset = 0;
+		let mut failures = 0;
+		while offset < size {
+			let end = (offset + CHUNK_SIZE).min(size);
+			file.seek(SeekFrom::Start(offset)).map_err(read_error)?;
+			chunk.clear();
+			Read::by_ref(file)
+				.take(end - offset)
+				.read_to_end(&mut chunk)
+				.map_err(read_error)?;
+
+			let sent = session_agent()
+				.put(session_url)
+				.header(
+					"Content-Range",
+					&format!("bytes {}-{}/{}", offset, end - 1, size),
+				)
+				.send(&chunk[..]);
+			let received = match sent {
+				Ok(response) => bytes_received(provider, response, size),
+				Err(e) => Err(e.to_string()),
+			};
+			// a session that keeps taking nothing fails like one that errors
+			let received = received.and_then(|received| match received > offset {
+				true => Ok(received),
+				false => Err("the drive didn't take the chunk".to_string()),
+			});
+			match received {
+				Ok(received) => {
+					offset = received;
+					failures = 0;
+				}
+				Err(e) => {
+					failures += 1;
+					if failures == CHUNK_TRIES {
+						return Err(format!("failed to upload {}: {}", output_file, e));
+					}
+
+					// the chunk may have gone through in part, or even in full
+					let status = match provider {
+						DriveProvider::GoogleDrive => session_agent()
+							.put(session_url)
+							.header("Content-Range", &format!("bytes */{}", size))
+							.send_empty(),
+						DriveProvider::Onedrive => session_agent().get(session_url).call(),
+					};
+					if let Ok(received) = status
+						.map_err(|e| e.to_string())
+						.and_then(|response| bytes_received(provider, response, size))
+					{
+						offset = received;
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	// how many bytes the upload session has, from its answer to a chunk or
+	// to being asked
+	fn bytes_received(
+		provider: DriveProvider,
+		mut response: ureq::http::Response<ureq::Body>,
+		size: u64,
+	) -> Result<u64, String> {
+		let status = response.status().as_u16();
+		match (provider, status) {
+			(_, 200 | 201) => Ok(size),
+			// google drive says which bytes it has, as bytes=0-last, or nothing
+			// when it has none
+			(DriveProvider::GoogleDrive, 308) => Ok(
+				response
+					.headers()
+					.get("Range")
+					.and_then(|range| range.to_str().ok())
+					.and_then(|range| range.strip_prefix("bytes=0-"))
+					.and_then(|last| last.parse::<u64>().ok())
+					.map_or(0, |last| last + 1),
+			),
+			// and onedrive which it still expects, as first- or first-last
+			(DriveProvider::Onedrive, 202) => {
+				let session: Value = response.body_mut().read_json().map_err(|e| e.to_string())?;
+				session["nextExpectedRanges"][0]
+					.as_str()
+					.and_then(|range| range.split('-').next())
+					.and_then(|first| first.parse().ok())
+					.ok_or_else(|| "onedrive didn't say what it expects next".to_string())
+			}
+			_ => Err(format!("the drive answered with status {}", status)),
+		}
+	}
+
+	// a value in a google drive query, between single quotes
+	fn quote(value: &str) -> String {
+		value.replace('\\', "\\\\").replace('\'', "\\'")
+	}
+
+	fn request_tokens(provider: DriveProvider, form: &[(&str, &str)]) -> Result<Tokens, String> {
+		agent()
+			.post(provider.token_url())
+			.send_form(form.iter().copied())
+			.map_err(|e| e.to_string())?
+			.body_mut()
+			.read_json()
+			.map_err(|e| e.to_string())
+	}
+
+	fn agent() -> ureq::Agent {
+		ureq::Agent::config_builder()
+			.timeout_global(Some(Duration::from_secs(300)))
+			.build()
+			.into()
+	}
+
+	// the statuses of an upload session, like google drive's 308 for a chunk
+	// it took, are told apart by send_chunks rather than being errors
+	fn session_agent() -> ureq::Agent {
+		ureq::Agent::config_builder()
+			.timeout_global(Some(Duration::from_secs(300)))
+			.http_status_as_error(false)
+			.max_redirects(0)
+			.build()
+			.into()
+	}
+
+	fn load_login() -> Option<Login> {
+		let contents = fs::read_to_string(login_path()?).ok()?;
+		serde_json::from_str(&contents).ok()
+	}
+
+	fn save_login(login: &Login) -> Result<(), String> {
+		let path = login_path().ok_or("there's no data directory to keep the login in")?;
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+		}
+
+		let contents = serde_json::to_string(login).map_err(|e| e.to_string())?;
+		fs::write(&path, contents).map_err(|e| format!("failed to save the login: {}", e))
+	}
+
+	fn login_path() -> Option<PathBuf> {
+		let dirs = ProjectDirs::from("", "", "oxide")?;
+		Some(dirs.data_local_dir().join("drive-login.json"))
+	}
+
+	// hex of `parts` randomly seeded hashes, plenty for a state and a pkce
+	// verifier without pulling in a random number generator
+	fn random_string(parts: usize) -> String {
+		(0..parts)
+			.map(|_| format!("{:016x}", RandomState::new().build_hasher().finish()))
+			.collect()
+	}
+
+	fn encode(value: &str) -> String {
+		value
+			.bytes()
+			.map(|b| match b {
+				b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+					(b as char).to_string()
+				}
+				_ => format!("%{:02X}", b),
+			})
+			.collect()
+	}
+
+	fn decode(value: &str) -> String {
+		let bytes = value.as_bytes();
+		let mut decoded = Vec::with_capacity(bytes.len());
+		let mut i = 0;
+		while i < bytes.len() {
+			let hex = bytes
+				.get(i + 1..i + 3)
+				.and_then(|hex| std::str::from_utf8(hex).ok())
+				.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+			match (bytes[i], hex) {
+				(b'%', Some(byte)) => {
+					decoded.push(byte);
+					i += 3;
+				}
+				(b'+', _) => {
+					decoded.push(b' ');
+					i += 1;
+				}
+				(byte, _) => {
+					decoded.push(byte);
+					i += 1;
+				}
+			}
+		}
+
+		String::from_utf8_lossy(&decoded).into_owned()
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn encodes_and_decodes() {
+			assert_eq!(
+				encode("http://127.0.0.1:8080"),
+				"http%3A%2F%2F127.0.0.1%3A8080"
+			);
+			assert_eq!(
+				encode("Files.ReadWrite offline_access"),
+				"Files.ReadWrite%20offline_access"
+			);
+			assert_eq!(encode("Daily Reports/é~"), "Daily%20Reports%2F%C3%A9~");
+			for value in ["http://127.0.0.1:8080", "4/0Ab_c-d~e.f", "Daily Reports/é"] {
+				assert_eq!(decode(&encode(value)), value);
+			}
+
+			// forms send spaces as +, and a stray % is kept as it is
+			assert_eq!(decode("access+denied"), "access denied");
+			assert_eq!(decode("100%+sure%2"), "100% sure%2");
+			assert_eq!(decode("%C3%A9%zz"), "é%zz");
+		}
+
+		#[test]
+		fn quotes_query_values() {
+			assert_eq!(quote("DDR's"), "DDR\\'s");
+			assert_eq!(
+				quote("a\\b' or 'x' in parents"),
+				"a\\\\b\\' or \\'x\\' in parents"
+			);
+		}
+	}
+}
+
+#[cfg(feature = "drive")]
+pub use send::{login, upload};
+
+#[cfg(not(feature = "drive"))]
+pub fn login(_drive: &CloudDrive) -> Result<(), String> {
+	Err("oxide was built without the drive feature".to_string())
+}
+
+#[cfg(not(feature = "drive"))]
+pub fn upload(_drive: &CloudDrive, _output_file: &str) -> Result<(), String> {
+	Err("oxide was built without the drive feature".to_string())
+}
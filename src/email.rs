@@ -0,0 +1,125 @@
+use oxide_core::RunSummary;
+use serde::{Deserialize, Serialize};
+
+/// Where a finished export is emailed to, along with its summary.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct EmailSettings {
+	pub server: String,
+	// 0 for the usual port, 587 or 465 with tls
+	pub port: u16,
+	// connects with tls from the start instead of upgrading with starttls
+	pub tls: bool,
+	pub username: String,
+	pub password: String,
+	// empty to send from the username
+	pub from: String,
+	pub to: Vec<String>,
+	// empty for "oxide export: " and the output's file name
+	pub subject: String,
+}
+
+/// Emails `output_file` as an attachment, with the summary as the message
+/// and the pdf report, when one was written, attached as well.
+#[cfg(feature = "email")]
+pub fn send(
+	settings: &EmailSettings,
+	output_file: &str,
+	summary: &RunSummary,
+) -> Result<(), String> {
+	use std::fs;
+	use std::ops::Not;
+	use std::path::Path;
+
+	use lettre::message::header::ContentType;
+	use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+	use lettre::transport::smtp::authentication::Credentials;
+	use lettre::{Message, SmtpTransport, Transport};
+	use oxide_core::OutputFormat;
+
+	if settings.server.is_empty() {
+		return Err("there's no email server set".to_string());
+	}
+
+	if settings.to.is_empty() {
+		return Err("there's no one to email the export to".to_string());
+	}
+
+	let path = Path::new(output_file);
+	let file_name = path
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_else(|| output_file.to_string());
+	let subject = match settings.subject.is_empty() {
+		true => format!("oxide export: {}", file_name),
+		false => settings.subject.clone(),
+	};
+
+	let from = match settings.from.is_empty() {
+		true => &settings.username,
+		false => &settings.from,
+	};
+	let from: Mailbox = from
+		.parse()
+		.map_err(|e| format!("invalid sender {}: {}", from, e))?;
+	let mut message = Message::builder().from(from).subject(subject);
+	for to in &settings.to {
+		let to: Mailbox = to
+			.parse()
+			.map_err(|e| format!("invalid recipient {}: {}", to, e))?;
+		message = message.to(to);
+	}
+
+	let content_type = match OutputFormat::from_path(path) == OutputFormat::Csv {
+		true => "text/csv",
+		false => "application/octet-stream",
+	};
+	let content_type = ContentType::parse(content_type).map_err(|e| e.to_string())?;
+	let contents = fs::read(path).map_err(|e| format!("failed to read {}: {}", output_file, e))?;
+	let mut parts = MultiPart::mixed()
+		.singlepart(SinglePart::plain(summary.to_string()))
+		.singlepart(Attachment::new(file_name).body(contents, content_type));
+	if let Some(report) = &summary.report {
+		let contents =
+			fs::read(report).map_err(|e| format!("failed to read {}: {}", report.display(), e))?;
+		let name = report
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.unwrap_or_default();
+		let content_type = ContentType::parse("application/pdf").map_err(|e| e.to_string())?;
+		parts = parts.singlepart(Attachment::new(name).body(contents, content_type));
+	}
+
+	let message = message.multipart(parts).map_err(|e| e.to_string())?;
+
+	let transport = match settings.tls {
+		true => SmtpTransport::relay(&settings.server),
+		false => SmtpTransport::starttls_relay(&settings.server),
+	};
+	let mut transport = transport.map_err(|e| e.to_string())?;
+	if settings.port != 0 {
+		transport = transport.port(settings.port);
+	}
+
+	if settings.username.is_empty().not() {
+		transport = transport.credentials(Credentials::new(
+			settings.username.clone(),
+			settings.password.clone(),
+		));
+	}
+
+	transport
+		.build()
+		.send(&message)
+		.map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+#[cfg(not(feature = "email"))]
+pub fn send(
+	_settings: &EmailSettings,
+	_output_file: &str,
+	_summary: &RunSummary,
+) -> Result<(), String> {
+	Err("oxide was built without the email feature".to_string())
+}
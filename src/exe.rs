@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// the running executable, if it's somewhere it can be replaced
+pub fn replaceable_exe() -> io::Result<PathBuf> {
+	if cfg!(not(any(unix, windows))) {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"executables can't be replaced on this platform",
+		));
+	}
+
+	let exe_path = env::current_exe()?;
+	// read-only installs (program files, network shares) fail further down
+	// anyway, but checking first avoids leaving a temp copy behind
+	if fs::metadata(&exe_path)?.permissions().readonly() {
+		return Err(io::Error::new(
+			io::ErrorKind::PermissionDenied,
+			"executable is read-only",
+		));
+	}
+
+	Ok(exe_path)
+}
+
+// moves `new` over the executable at `exe_path`, keeping its permissions
+pub fn replace(exe_path: &Path, new: &Path) -> io::Result<()> {
+	let perms = fs::metadata(exe_path)?.permissions();
+	fs::set_permissions(new, perms.clone())?;
+
+	#[cfg(unix)]
+	{
+		fs::rename(new, exe_path)?;
+	}
+
+	#[cfg(windows)]
+	{
+		let old = env::temp_dir().join(exe_path.with_extension("old"));
+		// can't just overwrite running exe on windows, so move/rename
+		// to temp and then rename back
+		fs::rename(exe_path, &old)?;
+		if let Err(e) = fs::rename(new, exe_path) {
+			// usually antivirus holding on to the new file, put the
+			// original back so there's still an executable to run
+			let _ = fs::rename(&old, exe_path);
+			return Err(e);
+		}
+	}
+
+	fs::set_permissions(exe_path, perms)
+}
+
+// removes the executable left behind by the last replace
+pub fn clean_up() {
+	let Ok(exe_path) = env::current_exe() else {
+		return;
+	};
+
+	let old_path = env::temp_dir().join(exe_path.with_extension("old"));
+	if old_path.exists() {
+		let _ = fs::remove_file(old_path);
+	}
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Output};
+
+// entries for right-clicking a folder and for right-clicking inside one,
+// the folder is %1 for the first and %V for the second
+const ENTRIES: [(&str, &str); 2] = [
+	(r"HKCU\Software\Classes\Directory\shell\oxide", "%1"),
+	(
+		r"HKCU\Software\Classes\Directory\Background\shell\oxide",
+		"%V",
+	),
+];
+
+// keeps reg.exe from flashing a console window
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Whether Explorer offers to aggregate a folder with oxide.
+pub fn is_registered() -> bool {
+	ENTRIES.iter().all(|(key, _)| {
+		reg(&["query", key])
+			.map(|output| output.status.success())
+			.unwrap_or(false)
+	})
+}
+
+/// Adds "Aggregate with oxide" to the menu of folders in Explorer, starting
+/// this executable with the folder as the input folder. It's only added
+/// for the current user, so it doesn't need an installer or an admin.
+pub fn register() -> io::Result<()> {
+	let exe = env::current_exe()?.display().to_string();
+	for (key, folder) in ENTRIES {
+		let command_key = format!(r"{}\command", key);
+		let command = format!("\"{}\" --input \"{}\"", exe, folder);
+		check(reg(&[
+			"add",
+			key,
+			"/ve",
+			"/d",
+			"Aggregate with oxide",
+			"/f",
+		])?)?;
+		check(reg(&["add", key, "/v", "Icon", "/d", exe.as_str(), "/f"])?)?;
+		check(reg(&["add", &command_key, "/ve", "/d", &command, "/f"])?)?;
+	}
+
+	Ok(())
+}
+
+/// Takes oxide back out of Explorer's menu.
+pub fn unregister() -> io::Result<()> {
+	for (key, _) in ENTRIES {
+		// deleting a key that isn't there fails the same as anything else
+		if reg(&["query", key])?.status.success() {
+			check(reg(&["delete", key, "/f"])?)?;
+		}
+	}
+
+	Ok(())
+}
+
+fn reg(args: &[&str]) -> io::Result<Output> {
+	Command::new("reg")
+		.args(args)
+		.creation_flags(CREATE_NO_WINDOW)
+		.output()
+}
+
+fn check(output: Output) -> io::Result<()> {
+	match output.status.success() {
+		true => Ok(()),
+		false => Err(io::Error::other(
+			String::from_utf8_lossy(&output.stderr).trim().to_string(),
+		)),
+	}
+}
@@ -0,0 +1,47 @@
+use std::sync::mpsc;
+use std::thread;
+
+use oxide_core::{ExportEvent, ExportOptions, LocalDir, OxideError, RunSummary, generate_output};
+
+// runs an export from the command line, printing progress as files are read
+pub fn run(
+	input_dir: &str,
+	output_file: &str,
+	options: ExportOptions,
+) -> Result<RunSummary, OxideError> {
+	let (events_tx, events) = mpsc::channel();
+	let options = ExportOptions {
+		events: Some(events_tx),
+		..options
+	};
+
+	let source = LocalDir::new(input_dir);
+	let output = output_file.to_string();
+	let handle = thread::spawn(move || generate_output(&source, output, &options));
+
+	// the channel closes once the export is done and its options dropped
+	for event in events {
+		match event {
+			ExportEvent::FileStarted {
+				file_name,
+				index,
+				total,
+			} => println!("[{}/{}] {}", index + 1, total, file_name),
+			ExportEvent::RowsWritten { .. } => {}
+			ExportEvent::Warning { file_name, message } => {
+				eprintln!("warning: {}: {}", file_name, message)
+			}
+			ExportEvent::FileSkipped { file_name, error } => {
+				eprintln!("skipped: {}: {}", file_name, error)
+			}
+			ExportEvent::Finished(summary) => {
+				println!("\n{}", summary);
+				println!("saved to {}", output_file);
+			}
+		}
+	}
+
+	handle
+		.join()
+		.unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
@@ -0,0 +1,130 @@
+use std::ops::Not;
+use std::panic;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+use oxide_core::{ExportEvent, ExportOptions, LocalDir, OxideError, RunSummary, generate_output};
+
+use crate::crash;
+use crate::deliver::Delivery;
+use crate::history::{self, Run};
+
+// an export running on a background thread, so the ui stays responsive and
+// can show progress while workbooks are read
+pub struct ExportTask {
+	events: Receiver<ExportEvent>,
+	handle: Option<JoinHandle<Result<RunSummary, OxideError>>>,
+	pub output_file: String,
+	pub total: usize,
+	pub done: usize,
+	pub rows: usize,
+	pub current: Option<String>,
+}
+
+impl ExportTask {
+	pub fn start(
+		input_dir: String,
+		output_file: String,
+		template: String,
+		options: &ExportOptions,
+		delivery: Delivery,
+	) -> ExportTask {
+		let (events_tx, events) = mpsc::channel();
+		let mut options = options.clone();
+		options.events = Some(events_tx);
+		let task_output_file = output_file.clone();
+		crash::log(format!(
+			"export of {} to {} started",
+			input_dir, output_file
+		));
+		let handle = thread::spawn(move || {
+			let result = generate_output(&LocalDir::new(&input_dir), output_file.clone(), &options);
+			let recorded = history::record(&Run::new(&template, &input_dir, &output_file, &result));
+			let mut summary = result?;
+			if let Err(e) = recorded {
+				summary
+					.warnings
+					.push(format!("failed to add the export to the history: {}", e));
+			}
+
+			// the export went through either way, so failing to send it only warns
+			let errors = delivery.send(&output_file, &summary);
+			summary.warnings.extend(errors);
+
+			Ok(summary)
+		});
+
+		ExportTask {
+			events,
+			handle: Some(handle),
+			output_file: task_output_file,
+			total: 0,
+			done: 0,
+			rows: 0,
+			current: None,
+		}
+	}
+
+	// drains progress events, returning the export's result once it's done
+	pub fn poll(&mut self) -> Option<Result<RunSummary, OxideError>> {
+		for event in self.events.try_iter() {
+			match event {
+				ExportEvent::FileStarted {
+					file_name, total, ..
+				} => {
+					crash::log(format!("reading {}", file_name));
+					self.total = total;
+					self.current = Some(file_name);
+				}
+				ExportEvent::RowsWritten { rows, .. } => {
+					self.done += 1;
+					self.rows += rows;
+				}
+				ExportEvent::FileSkipped { file_name, .. } => {
+					// the error can quote the workbook, so it's left out
+					crash::log(format!("skipped {}", file_name));
+					self.done += 1;
+				}
+				ExportEvent::Warning { .. } | ExportEvent::Finished(_) => {}
+			}
+		}
+
+		if self.handle.as_ref().is_some_and(|h| h.is_finished()).not() {
+			return None;
+		}
+
+		let handle = self.handle.take()?;
+		// a panic in the export thread shouldn't go unnoticed
+		Some(handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+	}
+
+	pub fn progress(&self) -> f32 {
+		if self.total == 0 {
+			return 0.0;
+		}
+
+		self.done as f32 / self.total as f32
+	}
+}
+
+#[cfg(test)]
+impl ExportTask {
+	// a task that keeps running until `finish` is sent on or dropped
+	pub fn running(output_file: &str, finish: Receiver<()>) -> ExportTask {
+		let (_, events) = mpsc::channel();
+		let handle = thread::spawn(move || {
+			let _ = finish.recv();
+			Ok(RunSummary::default())
+		});
+
+		ExportTask {
+			events,
+			handle: Some(handle),
+			output_file: output_file.to_string(),
+			total: 0,
+			done: 0,
+			rows: 0,
+			current: None,
+		}
+	}
+}
@@ -0,0 +1,117 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use oxide_core::{OxideError, RunSummary};
+use serde::{Deserialize, Serialize};
+
+/// An export as it's kept in the history, when it ran, who ran it and
+/// what it made out of what.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Run {
+	// seconds since 1970-01-01
+	pub time: u64,
+	pub user: String,
+	// template the workbooks were read with, empty for the default layout
+	pub template: String,
+	pub input_dir: String,
+	pub output_file: String,
+	// workbooks the rows were read from
+	pub inputs: Vec<String>,
+	pub rows: usize,
+	pub warnings: usize,
+	// sha256 of the output as it was written
+	pub output_sha256: String,
+	// why the export failed, empty when it went through
+	pub error: String,
+}
+
+impl Run {
+	pub fn new(
+		template: &str,
+		input_dir: &str,
+		output_file: &str,
+		result: &Result<RunSummary, OxideError>,
+	) -> Run {
+		let time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or_default();
+		let user = env::var("USERNAME")
+			.or_else(|_| env::var("USER"))
+			.unwrap_or_default();
+		let mut run = Run {
+			time,
+			user,
+			template: template.to_string(),
+			input_dir: input_dir.to_string(),
+			output_file: output_file.to_string(),
+			..Default::default()
+		};
+
+		match result {
+			Ok(summary) => {
+				let mut inputs: Vec<_> = summary
+					.files
+					.iter()
+					.map(|file| file.path.display().to_string())
+					.collect();
+				inputs.sort();
+				run.inputs = inputs;
+				run.rows = summary.total_rows();
+				run.warnings = summary.warnings.len();
+				// exports writing a manifest already hashed the output
+				run.output_sha256 = match &summary.output_sha256 {
+					Some(hash) => hash.clone(),
+					None => oxide_core::file_sha256(Path::new(output_file)).unwrap_or_default(),
+				};
+			}
+			Err(e) => run.error = e.to_string(),
+		}
+
+		run
+	}
+}
+
+/// Adds the run to the end of the history, which is only ever added to.
+pub fn record(run: &Run) -> io::Result<()> {
+	let Some(path) = history_path() else {
+		return Ok(());
+	};
+
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+
+	let mut line = serde_json::to_string(run)?;
+	line.push('\n');
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&path)?
+		.write_all(line.as_bytes())
+}
+
+/// Every run in the history, newest first. Lines that can't be read are
+/// left out rather than hiding the rest.
+pub fn load() -> Vec<Run> {
+	let Some(contents) = history_path().and_then(|path| fs::read_to_string(path).ok()) else {
+		return Vec::new();
+	};
+
+	let mut runs: Vec<Run> = contents
+		.lines()
+		.filter_map(|line| serde_json::from_str(line).ok())
+		.collect();
+	runs.reverse();
+	runs
+}
+
+fn history_path() -> Option<PathBuf> {
+	let dirs = ProjectDirs::from("", "", "oxide")?;
+	Some(dirs.data_local_dir().join("history.jsonl"))
+}
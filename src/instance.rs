@@ -0,0 +1,139 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use directories::ProjectDirs;
+use eframe::egui;
+
+// first line of every message, so a stale port that something else took
+// over isn't mistaken for a running oxide
+const GREETING: &str = "oxide";
+
+/// The app running for this user, holding on to the lock that keeps other
+/// launches from starting a second one. Both are missing when the data
+/// directory couldn't be used, which lets the app run without the check.
+#[derive(Default)]
+pub struct Instance {
+	lock: Option<File>,
+	listener: Option<TcpListener>,
+}
+
+impl Instance {
+	/// Takes over as the only running app, or hands the input folder it
+	/// was started with to the one already running and returns none. The
+	/// running app is brought to the front either way.
+	pub fn claim(input_dir: Option<&str>) -> Option<Instance> {
+		let Some(dirs) = ProjectDirs::from("", "", "oxide") else {
+			return Some(Instance::default());
+		};
+		let dir = dirs.data_local_dir();
+		let _ = fs::create_dir_all(dir);
+
+		let lock = OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(dir.join("instance.lock"));
+		let Ok(lock) = lock else {
+			return Some(Instance::default());
+		};
+
+		if lock.try_lock().is_err() {
+			forward(&dir.join("instance.port"), input_dir);
+			return None;
+		}
+
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok();
+		if let Some(addr) = listener.as_ref().and_then(|l| l.local_addr().ok()) {
+			let _ = fs::write(dir.join("instance.port"), addr.port().to_string());
+		}
+
+		Some(Instance {
+			lock: Some(lock),
+			listener,
+		})
+	}
+
+	/// Listens for later launches in the background, passing on the input
+	/// folders they were started with (empty when there wasn't one) and
+	/// bringing the window to the front.
+	pub fn listen(self, ctx: egui::Context) -> Receiver<String> {
+		let (sender, receiver) = mpsc::channel();
+		let Instance { lock, listener } = self;
+		let Some(listener) = listener else {
+			return receiver;
+		};
+
+		thread::spawn(move || {
+			// released when the app exits, along with the thread
+			let _lock = lock;
+			for stream in listener.incoming().flatten() {
+				let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+				let mut reader = BufReader::new(&stream);
+				let mut greeting = String::new();
+				let mut input_dir = String::new();
+				if reader.read_line(&mut greeting).is_err()
+					|| greeting.trim_end() != GREETING
+					|| reader.read_line(&mut input_dir).is_err()
+				{
+					continue;
+				}
+
+				let _ = (&stream).write_all(format!("{}\n", GREETING).as_bytes());
+				if sender.send(input_dir.trim_end().to_string()).is_err() {
+					return;
+				}
+
+				ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+				ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+				ctx.request_repaint();
+			}
+		});
+
+		receiver
+	}
+}
+
+// the running app may still be starting up and not listening yet, so this
+// tries for a few seconds before giving up, without starting another app
+fn forward(port_file: &Path, input_dir: Option<&str>) {
+	// windows only lets the app in the foreground hand it over
+	#[cfg(windows)]
+	unsafe {
+		winapi::um::winuser::AllowSetForegroundWindow(winapi::um::winuser::ASFW_ANY);
+	}
+
+	for _ in 0..10 {
+		if let Some(port) = fs::read_to_string(port_file)
+			.ok()
+			.and_then(|port| port.trim().parse::<u16>().ok())
+			&& send(port, input_dir.unwrap_or_default())
+		{
+			return;
+		}
+
+		thread::sleep(Duration::from_millis(300));
+	}
+}
+
+fn send(port: u16, input_dir: &str) -> bool {
+	let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+	let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_secs(1)) else {
+		return false;
+	};
+
+	let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+	if stream
+		.write_all(format!("{}\n{}\n", GREETING, input_dir).as_bytes())
+		.is_err()
+	{
+		return false;
+	}
+
+	let mut reply = String::new();
+	BufReader::new(&stream).read_line(&mut reply).is_ok() && reply.trim_end() == GREETING
+}
@@ -1,60 +1,180 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::fs::{self};
-use std::io::prelude::*;
+use std::collections::BTreeMap;
+use std::env;
 use std::ops::Not;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
-use std::{env, fs::OpenOptions};
+use std::time::Duration;
 
-use calamine::{DataType, Reader, ToCellDeserializer, Xlsx, open_workbook};
 use eframe::egui::{self, Layout};
 use native_dialog::DialogBuilder;
-use object::{Object, ObjectSection};
 use rfd::FileDialog;
 
-#[used]
-#[unsafe(link_section = "inptdir")]
-static mut INPUT_DIR_BYTES: [u8; 260] = [0; 260];
+use oxide_core::{
+	CsvDialect, DateRange, ExportOptions, ExportPreset, HeaderMatch, HeaderReplacement, HeaderStyle,
+	OutputFormat, OxideError, ReportTemplate, RowFilter, RunSummary, StaleExport,
+};
 
-#[used]
-#[unsafe(link_section = "outfil")]
-static mut OUTPUT_FILE_BYTES: [u8; 260] = [0; 260];
+use crate::config::Config;
+use crate::deliver::Delivery;
+use crate::export_task::ExportTask;
+use crate::history::Run;
+use crate::instance::Instance;
+use crate::metadata::{MetadataLoader, MetadataState, PreviewState};
 
-// todo: move to ui, we'll also then probably embed these into the binary
-// at which point, we might want to use a single section for all properties
-const DATA_START_ID: &str = "Hole Number";
-const DATA_END_ID: &str = "Sub-Totals";
-const REMARKS_START_ID: &str = "Remarks";
+mod api;
+mod bench;
+mod chat;
+mod cli;
+mod config;
+mod crash;
+mod deliver;
+mod drive;
+mod email;
+#[cfg(any(feature = "portable", feature = "updater"))]
+mod exe;
+#[cfg(windows)]
+mod explorer;
+mod export;
+mod export_task;
+mod history;
+mod instance;
+mod metadata;
+mod plugins;
+#[cfg(feature = "portable")]
+mod portable;
+mod time;
+#[cfg(feature = "updater")]
+mod update;
+mod upload;
+mod webhook;
 
 #[derive(Default)]
 struct App {
 	input_dir: String,
 	output_file: String,
 	shared_state: Arc<Mutex<AppState>>,
+	metadata: Option<MetadataLoader>,
+	export_options: ExportOptions,
+	// styles to pick from, replace is only offered once replacements are set up
+	header_styles: Vec<HeaderStyle>,
+	// name of the selected template, empty for the default layout
+	template: String,
+	template_names: Vec<String>,
+	// report dates as typed, only applied once they make a valid range
+	date_from: String,
+	date_to: String,
+	date_error: Option<String>,
+	// filter expression as typed, only applied once it parses
+	filter: String,
+	filter_error: Option<String>,
+	export: Option<ExportTask>,
+	// where finished exports are sent, if anywhere
+	delivery: Delivery,
+	// output of the last export that went through, offered for copying
+	// when it's a csv
+	last_output: Option<String>,
+	// kept for as long as the app runs, as on some platforms whatever was
+	// copied goes with it
+	clipboard: Option<arboard::Clipboard>,
+	// whether explorer's folder menu offers to aggregate with oxide
+	#[cfg(windows)]
+	in_explorer: bool,
+	// runs shown in the history window while it's open, newest first
+	history: Option<Vec<Run>>,
+	// input folders later launches handed over, empty when they had none
+	launches: Option<Receiver<String>>,
+	// release check started with the app, dropped once it has answered
+	#[cfg(feature = "updater")]
+	update_check: Option<Receiver<Result<Option<update::Release>, String>>>,
 }
 
 #[derive(Default, Clone)]
 struct AppState {
 	input_dir: String,
 	output_file: String,
+	header_style: String,
+	template: String,
+	preset: String,
+	source_columns: bool,
+	max_concurrent_files: String,
+	date_from: String,
+	date_to: String,
+	filter: String,
+	header_aliases: BTreeMap<String, Vec<String>>,
 }
 
 impl App {
-	fn new(input_dir: String, output_file: String) -> (App, Arc<Mutex<AppState>>) {
+	fn new(
+		config: &Config,
+		export_options: ExportOptions,
+		replacements: Vec<HeaderReplacement>,
+	) -> (App, Arc<Mutex<AppState>>) {
 		let shared_state = Arc::new(Mutex::new(AppState {
-			input_dir: input_dir.clone(),
-			output_file: output_file.clone(),
+			input_dir: config.input_dir.clone(),
+			output_file: config.output_file.clone(),
+			header_style: config.header_style.clone(),
+			template: config.template.clone(),
+			preset: config.preset.clone(),
+			source_columns: config.source_columns,
+			max_concurrent_files: config.max_concurrent_files.clone(),
+			date_from: config.date_from.clone(),
+			date_to: config.date_to.clone(),
+			filter: config.filter.clone(),
+			header_aliases: config.header_aliases.clone(),
 		}));
 
+		let mut header_styles = vec![
+			HeaderStyle::SnakeCase,
+			HeaderStyle::CamelCase,
+			HeaderStyle::Original,
+		];
+		if replacements.is_empty().not() {
+			header_styles.push(HeaderStyle::Replace(replacements));
+		}
+
 		let app = App {
-			input_dir,
-			output_file,
+			input_dir: config.input_dir.clone(),
+			output_file: config.output_file.clone(),
 			shared_state: shared_state.clone(),
+			metadata: None,
+			export_options,
+			header_styles,
+			template: config.template.clone(),
+			template_names: config::template_names(),
+			date_from: config.date_from.clone(),
+			date_to: config.date_to.clone(),
+			date_error: None,
+			filter: config.filter.clone(),
+			filter_error: None,
+			export: None,
+			delivery: Delivery::new(config),
+			last_output: None,
+			clipboard: None,
+			history: None,
+			#[cfg(windows)]
+			in_explorer: explorer::is_registered(),
+			launches: None,
+			#[cfg(feature = "updater")]
+			update_check: Some(update::check_in_background(config.clone())),
 		};
 
 		(app, shared_state)
 	}
 
+	fn start_export(&mut self) {
+		self.export = Some(ExportTask::start(
+			self.input_dir.to_string(),
+			self.output_file.to_string(),
+			self.template.clone(),
+			&self.export_options,
+			self.delivery.clone(),
+		));
+	}
+
 	fn update_input_dir(&mut self, new_dir: String) {
 		self.input_dir = new_dir.clone();
 		if let Ok(mut state) = self.shared_state.lock() {
@@ -62,24 +182,368 @@ impl App {
 		}
 	}
 
+	fn show_preview(&mut self, ctx: &egui::Context) {
+		let Some(metadata) = &mut self.metadata else {
+			return;
+		};
+
+		let Some((path, state)) = metadata.preview() else {
+			return;
+		};
+
+		let title = path
+			.file_name()
+			.and_then(|s| s.to_str())
+			.unwrap_or_default()
+			.to_string();
+		let mut open = true;
+		egui::Window::new(title)
+			.id(egui::Id::new("preview"))
+			.open(&mut open)
+			.default_size([300.0, 240.0])
+			.show(ctx, |ui| match state {
+				PreviewState::Loading => {
+					ui.label("Loading...");
+				}
+				PreviewState::Failed(e) => {
+					ui.label(format!("Failed to read preview: {}", e));
+				}
+				PreviewState::Loaded(preview) => {
+					egui::ScrollArea::both().show(ui, |ui| {
+						egui::Grid::new("preview_grid")
+							.striped(true)
+							.show(ui, |ui| {
+								for header in &preview.headers {
+									ui.strong(header);
+								}
+								ui.end_row();
+
+								for row in &preview.rows {
+									for value in row {
+										ui.label(value);
+									}
+									ui.end_row();
+								}
+							});
+					});
+				}
+			});
+
+		if open.not() {
+			metadata.close_preview();
+		}
+	}
+
+	fn show_history(&mut self, ctx: &egui::Context) {
+		let Some(runs) = &self.history else {
+			return;
+		};
+
+		let mut open = true;
+		egui::Window::new("History")
+			.open(&mut open)
+			.default_size([300.0, 240.0])
+			.show(ctx, |ui| {
+				if runs.is_empty() {
+					ui.label("No exports yet.");
+					return;
+				}
+
+				egui::ScrollArea::both().show(ui, |ui| {
+					egui::Grid::new("history_grid")
+						.striped(true)
+						.show(ui, |ui| {
+							for header in ["Time (UTC)", "User", "Template", "Output", "Result"] {
+								ui.strong(header);
+							}
+							ui.end_row();
+
+							for run in runs {
+								ui.label(time::utc_time(run.time));
+								ui.label(&run.user);
+								ui.label(match run.template.is_empty() {
+									true => "default",
+									false => &run.template,
+								});
+								let output = ui.label(ellipse_string(&run.output_file, 30));
+								// the full path, hash and inputs are too long for the table
+								let details = format!(
+									"{}\nfrom {}\nsha256 {}\n\n{}",
+									run.output_file,
+									run.input_dir,
+									run.output_sha256,
+									run.inputs.join("\n")
+								);
+								output.on_hover_text(details);
+								match run.error.is_empty() {
+									true => ui.label(format!(
+										"{} rows from {} files, {} warnings",
+										run.rows,
+										run.inputs.len(),
+										run.warnings
+									)),
+									false => ui.colored_label(ui.visuals().error_fg_color, &run.error),
+								};
+								ui.end_row();
+							}
+						});
+				});
+			});
+
+		if open.not() {
+			self.history = None;
+		}
+	}
+
+	// adds or removes "Aggregate with oxide" in explorer's folder menu
+	#[cfg(windows)]
+	fn explorer_button(&mut self, ui: &mut egui::Ui) {
+		let label = match self.in_explorer {
+			true => "Remove from Explorer",
+			false => "Add to Explorer",
+		};
+		if ui.button(label).clicked().not() {
+			return;
+		}
+
+		let result = match self.in_explorer {
+			true => explorer::unregister(),
+			false => explorer::register(),
+		};
+		self.in_explorer = explorer::is_registered();
+		if let Err(e) = result {
+			let _ = DialogBuilder::message()
+				.set_level(native_dialog::MessageLevel::Error)
+				.set_title("Explorer menu")
+				.set_text(format!("Failed to change Explorer's menu: {}", e))
+				.alert()
+				.show();
+		}
+	}
+
 	fn update_output_file(&mut self, new_file: String) {
 		self.output_file = new_file.clone();
 		if let Ok(mut state) = self.shared_state.lock() {
 			state.output_file = new_file;
 		}
 	}
+
+	fn update_header_style(&mut self, header_style: HeaderStyle) {
+		if let Ok(mut state) = self.shared_state.lock() {
+			state.header_style = header_style.name().to_string();
+		}
+
+		self.export_options.header_style = header_style;
+		self.refresh_preview();
+	}
+
+	fn update_template(&mut self, name: String) {
+		let template = match name.is_empty() {
+			true => Ok(ReportTemplate::default()),
+			false => ReportTemplate::load(&config::template_path(&name)),
+		};
+
+		match template {
+			Ok(mut template) => {
+				if let Ok(state) = self.shared_state.lock() {
+					template.add_aliases(&state.header_aliases, &self.export_options.header_style);
+				}
+				self.export_options.template = template;
+			}
+			Err(e) => {
+				let _ = DialogBuilder::message()
+					.set_level(native_dialog::MessageLevel::Error)
+					.set_title("Template")
+					.set_text(format!("Failed to load template: {}", e))
+					.alert()
+					.show();
+				return;
+			}
+		}
+
+		self.template = name.clone();
+		if let Ok(mut state) = self.shared_state.lock() {
+			state.template = name;
+		}
+
+		self.refresh_preview();
+	}
+
+	// offers to install a newer release once the check started with the app
+	// finds one, a failed check isn't worth interrupting anyone over
+	#[cfg(feature = "updater")]
+	fn poll_update_check(&mut self, ctx: &egui::Context) {
+		let Some(check) = &self.update_check else {
+			return;
+		};
+
+		let Ok(result) = check.try_recv() else {
+			// the check doesn't wake the ui when it's done
+			ctx.request_repaint_after(Duration::from_secs(1));
+			return;
+		};
+
+		self.update_check = None;
+		let release = match result {
+			Ok(Some(release)) => release,
+			Ok(None) => return,
+			Err(e) => {
+				eprintln!("{}", e);
+				crash::log(e);
+				return;
+			}
+		};
+
+		let confirmed = DialogBuilder::message()
+			.set_level(native_dialog::MessageLevel::Info)
+			.set_title("Update")
+			.set_text(format!(
+				"oxide {} is available, this is {}. Update now?",
+				release.version,
+				env!("CARGO_PKG_VERSION")
+			))
+			.confirm()
+			.show()
+			.unwrap_or(false);
+		if confirmed.not() {
+			return;
+		}
+
+		// the new executable starts out with the settings as they are now
+		let mut config = Config::load();
+		if let Ok(state) = self.shared_state.lock() {
+			config.input_dir = state.input_dir.clone();
+			config.output_file = state.output_file.clone();
+		}
+
+		let (level, message) = match update::install(&release, &config) {
+			Ok(()) => (
+				native_dialog::MessageLevel::Info,
+				format!("Updated to {}, restart oxide to use it.", release.version),
+			),
+			Err(e) => (
+				native_dialog::MessageLevel::Error,
+				format!("Update failed: {}", e),
+			),
+		};
+
+		let _ = DialogBuilder::message()
+			.set_level(level)
+			.set_title("Update")
+			.set_text(message)
+			.alert()
+			.show();
+	}
+
+	// asks about every column that looks like a typo of another one whether
+	// it should be written as that one from now on, which adds it to the
+	// header aliases
+	fn offer_header_aliases(&mut self, header_matches: Vec<HeaderMatch>) {
+		let mut aliased = false;
+		for header_match in header_matches {
+			let confirmed = DialogBuilder::message()
+				.set_level(native_dialog::MessageLevel::Info)
+				.set_title("Columns")
+				.set_text(format!(
+					"The {} column looks like {}. Write it as {} from now on?",
+					header_match.column, header_match.similar_to, header_match.similar_to
+				))
+				.confirm()
+				.show()
+				.unwrap_or(false);
+			if confirmed.not() {
+				continue;
+			}
+
+			let aliases = BTreeMap::from([(header_match.similar_to, vec![header_match.column])]);
+			let style = &self.export_options.header_style;
+			self.export_options.template.add_aliases(&aliases, style);
+			for rule in &mut self.export_options.template_rules {
+				rule.template.add_aliases(&aliases, style);
+			}
+			if let Ok(mut state) = self.shared_state.lock() {
+				for (column, spellings) in aliases {
+					state
+						.header_aliases
+						.entry(column)
+						.or_default()
+						.extend(spellings);
+				}
+			}
+			aliased = true;
+		}
+
+		if aliased {
+			self.refresh_preview();
+		}
+	}
+
+	// an open preview would otherwise keep showing the table as it was read
+	// with the previous settings
+	fn update_date_range(&mut self) {
+		match DateRange::new(&self.date_from, &self.date_to) {
+			Ok(range) => {
+				self.export_options.date_range = range;
+				self.date_error = None;
+				if let Ok(mut state) = self.shared_state.lock() {
+					state.date_from = self.date_from.clone();
+					state.date_to = self.date_to.clone();
+				}
+			}
+			Err(e) => self.date_error = Some(e),
+		}
+	}
+
+	fn update_filter(&mut self) {
+		let filter = match self.filter.trim().is_empty() {
+			true => Ok(None),
+			false => RowFilter::parse(&self.filter).map(Some),
+		};
+
+		match filter {
+			Ok(filter) => {
+				self.export_options.filter = filter;
+				self.filter_error = None;
+				if let Ok(mut state) = self.shared_state.lock() {
+					state.filter = self.filter.clone();
+				}
+			}
+			Err(e) => self.filter_error = Some(e),
+		}
+	}
+
+	fn refresh_preview(&mut self) {
+		if let Some(metadata) = &mut self.metadata
+			&& let Some((path, _)) = metadata.preview()
+		{
+			let path = path.clone();
+			let template = self.export_options.template_for(&path);
+			metadata.request_preview(path, template, &self.export_options.header_style);
+		}
+	}
 }
 
 impl eframe::App for App {
 	fn update(&mut self, ctx: &eframe::egui::Context, _: &mut eframe::Frame) {
+		if let Some(launches) = &self.launches
+			&& let Ok(input_dir) = launches.try_recv()
+			&& input_dir.is_empty().not()
+		{
+			self.update_input_dir(input_dir);
+		}
+
+		#[cfg(feature = "updater")]
+		self.poll_update_check(ctx);
+		self.show_preview(ctx);
+		self.show_history(ctx);
 		egui::CentralPanel::default().show(ctx, |ui| {
 			ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
 				ui.add_space(10.0);
 				ui.horizontal(|ui| {
-					if ui.button("Input Folder").clicked() {
-						if let Some(folder) = FileDialog::new().pick_folder() {
-							self.update_input_dir(folder.display().to_string());
-						}
+					if ui.button("Input Folder").clicked()
+						&& let Some(folder) = FileDialog::new().pick_folder()
+					{
+						self.update_input_dir(folder.display().to_string());
 					}
 
 					if self.input_dir.is_empty().not() {
@@ -89,68 +553,261 @@ impl eframe::App for App {
 
 				ui.add_space(10.0);
 				ui.horizontal(|ui| {
-					if ui.button("Output File").clicked() {
-						if let Some(file) = FileDialog::new().set_file_name("output.csv").save_file() {
-							self.update_output_file(file.display().to_string());
-						}
+					if ui.button("Output File").clicked()
+						&& let Some(file) = OutputFormat::ALL
+							.iter()
+							.map(|format| format.extension())
+							.chain(self.export_options.plugins.sink_extensions())
+							.fold(
+								FileDialog::new().set_file_name("output.csv"),
+								|dialog, extension| dialog.add_filter(extension, &[extension]),
+							)
+							.save_file()
+					{
+						self.update_output_file(file.display().to_string());
 					}
 
 					if self.output_file.is_empty().not() {
-						ui.label(&ellipse_string(&self.output_file, 30));
+						ui.label(ellipse_string(&self.output_file, 30));
+					}
+				});
+
+				ui.add_space(10.0);
+				ui.horizontal(|ui| {
+					ui.label("Max concurrent files");
+					let max_concurrent_files = &mut self.export_options.max_concurrent_files;
+					if ui
+						.add(egui::DragValue::new(max_concurrent_files).range(1..=16))
+						.changed()
+						&& let Ok(mut state) = self.shared_state.lock()
+					{
+						state.max_concurrent_files = max_concurrent_files.to_string();
+					}
+				});
+
+				ui.add_space(10.0);
+				let mut new_header_style = None;
+				ui.horizontal(|ui| {
+					ui.label("Header style");
+					let selected = self.export_options.header_style.name();
+					egui::ComboBox::from_id_salt("header_style")
+						.selected_text(selected)
+						.show_ui(ui, |ui| {
+							for style in &self.header_styles {
+								if ui
+									.selectable_label(style.name() == selected, style.name())
+									.clicked()
+								{
+									new_header_style = Some(style.clone());
+								}
+							}
+						});
+				});
+
+				if let Some(header_style) = new_header_style {
+					self.update_header_style(header_style);
+				}
+
+				ui.add_space(10.0);
+				let mut new_template = None;
+				ui.horizontal(|ui| {
+					ui.label("Template");
+					let selected = match self.template.is_empty() {
+						true => "default",
+						false => self.template.as_str(),
+					};
+					egui::ComboBox::from_id_salt("template")
+						.selected_text(selected)
+						.show_ui(ui, |ui| {
+							if ui
+								.selectable_label(self.template.is_empty(), "default")
+								.clicked()
+							{
+								new_template = Some(String::new());
+							}
+
+							for name in &self.template_names {
+								if ui.selectable_label(*name == self.template, name).clicked() {
+									new_template = Some(name.clone());
+								}
+							}
+						});
+				});
+
+				if let Some(name) = new_template {
+					self.update_template(name);
+				}
+
+				ui.add_space(10.0);
+				let mut new_preset = None;
+				ui.horizontal(|ui| {
+					ui.label("Preset");
+					let selected = self.export_options.preset;
+					egui::ComboBox::from_id_salt("preset")
+						.selected_text(selected.name())
+						.show_ui(ui, |ui| {
+							for name in ExportPreset::NAMES {
+								let preset = ExportPreset::from_name(name).unwrap_or_default();
+								if ui.selectable_label(preset == selected, *name).clicked() {
+									new_preset = Some(preset);
+								}
+							}
+						});
+				});
+
+				if let Some(preset) = new_preset {
+					self.export_options.preset = preset;
+					if let Ok(mut state) = self.shared_state.lock() {
+						state.preset = preset.name().to_string();
 					}
+				}
+
+				ui.add_space(10.0);
+				let source_columns = &mut self.export_options.source_columns;
+				if ui
+					.checkbox(source_columns, "Add source file and sheet columns")
+					.changed()
+					&& let Ok(mut state) = self.shared_state.lock()
+				{
+					state.source_columns = *source_columns;
+				}
+
+				ui.add_space(10.0);
+				let mut dates_changed = false;
+				ui.horizontal(|ui| {
+					ui.label("Report dates");
+					for (date, hint) in [(&mut self.date_from, "from"), (&mut self.date_to, "to")] {
+						let input = egui::TextEdit::singleline(date)
+							.hint_text(hint)
+							.desired_width(80.0);
+						dates_changed |= ui.add(input).changed();
+					}
+				});
+
+				if dates_changed {
+					self.update_date_range();
+				}
+
+				if let Some(error) = &self.date_error {
+					ui.colored_label(ui.visuals().error_fg_color, error);
+				}
+
+				ui.add_space(10.0);
+				let mut filter_changed = false;
+				ui.horizontal(|ui| {
+					ui.label("Filter");
+					let input = egui::TextEdit::singleline(&mut self.filter)
+						.hint_text("meters > 0 && rig == \"RC-04\"");
+					filter_changed = ui.add(input).changed();
 				});
 
+				if filter_changed {
+					self.update_filter();
+				}
+
+				if let Some(error) = &self.filter_error {
+					ui.colored_label(ui.visuals().error_fg_color, error);
+				}
+
 				ui.add_space(15.0);
 				ui.separator();
 				ui.add_space(15.0);
 
 				ui.label("Selected Worksheets");
 				ui.add_space(20.0);
-				if &self.input_dir.len() > &0 {
+				let metadata = self
+					.metadata
+					.get_or_insert_with(|| MetadataLoader::new(ctx.clone()));
+				metadata.poll(&self.input_dir);
+				if self.input_dir.is_empty().not() {
 					let scroll_area = egui::ScrollArea::vertical().max_height(120.0);
-					scroll_area.show(ui, |ui| {
-						if let Ok(entries) = std::fs::read_dir(&*self.input_dir) {
-							for entry in entries.flatten() {
-								let path = entry.path();
-								if path.extension().and_then(|s| s.to_str()) == Some("xlsx") {
-									let file_name = path
-										.file_name()
-										.and_then(|s| s.to_str())
-										.unwrap_or_default();
-									ui.label(file_name);
+					scroll_area.show(ui, |ui| match metadata.files() {
+						Some(files) if files.is_empty().not() => {
+							for file in files {
+								let details = match file.state {
+									MetadataState::Loading => "loading...".to_string(),
+									MetadataState::Loaded(m) => {
+										format!("{} sheets, {} rows", m.sheet_names.len(), m.row_count)
+									}
+									MetadataState::Failed(_) => "unreadable".to_string(),
+								};
+
+								let selected = metadata
+									.preview()
+									.is_some_and(|(path, _)| *path == file.path);
+								let label = format!("{} ({})", file.file_name, details);
+								if ui.selectable_label(selected, label).clicked() {
+									let template = self.export_options.template_for(&file.path);
+									metadata.request_preview(file.path, template, &self.export_options.header_style);
 								}
 							}
-						} else {
+						}
+						None if metadata.is_listed().not() => {
+							ui.label("Loading...");
+						}
+						_ => {
 							ui.label("No spreadsheets found in input directory.");
 						}
 					});
 				}
 
-				ui.add_space(ui.max_rect().height() - ui.cursor().top() - 20.0);
+				let copyable = self.export.is_none()
+					&& self
+						.last_output
+						.as_ref()
+						.is_some_and(|output| OutputFormat::from_path(Path::new(output)) == OutputFormat::Csv);
+				ui.add_space(ui.max_rect().height() - ui.cursor().top() - 44.0);
+				ui.horizontal(|ui| {
+					if ui.button("History").clicked() {
+						self.history = Some(history::load());
+					}
+
+					#[cfg(windows)]
+					self.explorer_button(ui);
+
+					if copyable
+						&& ui.button("Copy to clipboard").clicked()
+						&& let Some(output) = &self.last_output
+					{
+						copy_output(
+							&mut self.clipboard,
+							output,
+							&self.export_options.csv_dialect,
+						);
+					}
+				});
+
 				let generate_button = egui::Button::new("Export");
 
-				if self.output_file.is_empty().not() && self.input_dir.is_empty().not() {
-					if ui.button("Generate").clicked() {
-						match generate_output(self.input_dir.to_string(), self.output_file.to_string()) {
-							Ok(_) => {
-								DialogBuilder::message()
-									.set_level(native_dialog::MessageLevel::Info)
-									.set_title("Success")
-									.set_text(format!("Aggregate data saved to: {}", self.output_file).as_str())
-									.alert()
-									.show()
-									.unwrap();
-							}
-							Err(e) => {
-								DialogBuilder::message()
-									.set_level(native_dialog::MessageLevel::Error)
-									.set_title("Error")
-									.set_text(&format!("Failed to generate output: {}", e))
-									.alert()
-									.show()
-									.unwrap();
-							}
+				if let Some(task) = &mut self.export {
+					match task.poll() {
+						Some(result) => {
+							self.last_output = result.is_ok().then(|| task.output_file.clone());
+							let header_matches = result
+								.as_ref()
+								.map(|summary| summary.header_matches.clone())
+								.unwrap_or_default();
+							show_result(&task.output_file, result);
+							self.export = None;
+							self.offer_header_aliases(header_matches);
 						}
+						None => {
+							let text = match &task.current {
+								Some(file_name) => format!(
+									"{}/{} files, {} rows ({})",
+									task.done, task.total, task.rows, file_name
+								),
+								None => "Listing workbooks...".to_string(),
+							};
+							ui.add(egui::ProgressBar::new(task.progress()).text(text));
+							// events don't wake the ui, so keep polling while the export runs
+							ctx.request_repaint_after(Duration::from_millis(100));
+						}
+					}
+				} else if self.output_file.is_empty().not() && self.input_dir.is_empty().not() {
+					if ui.button("Generate").clicked() {
+						self.export_options.resume = offer_resume(&self.output_file);
+						self.start_export();
 					}
 				} else {
 					ui.add_enabled(false, generate_button);
@@ -160,192 +817,144 @@ impl eframe::App for App {
 	}
 }
 
-fn generate_output(
-	input_dir: String,
-	output_file: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-	if std::fs::exists(&output_file)? {
-		std::fs::remove_file(&output_file)?;
+fn show_result(output_file: &str, result: Result<RunSummary, OxideError>) {
+	crash::log(format!(
+		"export to {} done, ok: {}",
+		output_file,
+		result.is_ok()
+	));
+	match result {
+		Ok(summary) => {
+			DialogBuilder::message()
+				.set_level(native_dialog::MessageLevel::Info)
+				.set_title("Success")
+				.set_text(format!("Aggregate data saved to: {}\n\n{}", output_file, summary).as_str())
+				.alert()
+				.show()
+				.unwrap();
+		}
+		Err(e) => {
+			DialogBuilder::message()
+				.set_level(native_dialog::MessageLevel::Error)
+				.set_title("Error")
+				.set_text(format!("Failed to generate output: {}", e))
+				.alert()
+				.show()
+				.unwrap();
+		}
 	}
+}
 
-	let output_file = OpenOptions::new()
-		.create(true)
-		.append(true)
-		.open(&output_file)?;
-
-	let mut headers_set = false;
-	for entry in std::fs::read_dir(input_dir)? {
-		let path = entry?.path();
-		if path.extension().and_then(|s| s.to_str()) != Some("xlsx") {
-			continue;
+// puts a finished csv export on the clipboard, as the csv text and as an
+// html table that mail clients and spreadsheets paste as a table
+fn copy_output(
+	clipboard: &mut Option<arboard::Clipboard>,
+	output_file: &str,
+	dialect: &CsvDialect,
+) {
+	let result = oxide_core::clipboard_table(Path::new(output_file), dialect).and_then(|table| {
+		if clipboard.is_none() {
+			*clipboard = Some(arboard::Clipboard::new().map_err(|e| e.to_string())?);
 		}
 
-		let worksheet_name = path
-			.file_stem()
-			.and_then(|s| s.to_str())
-			.unwrap_or_default();
-		let mut workbook: Xlsx<_> = open_workbook(&path)?;
-
-		if let Ok(r) = workbook.worksheet_range(worksheet_name) {
-			let mut table_header_row: i32 = -1;
-			let mut table_end_row: i32 = -1;
-			let mut remarks_start_row: i32 = -1;
-
-			let report_date = r
-				.get((1, 0))
-				.and_then(|data| data.as_string())
-				.unwrap_or_default();
-			for (row_idx, row) in r.rows().enumerate() {
-				// might want to search more than just the first cell
-				let first_cell = row.first().unwrap_or(&calamine::Data::Empty);
-				if first_cell.as_string() == Some(DATA_START_ID.to_string()) {
-					let sub_header_row = row_idx as i32 + 1;
-					table_header_row = sub_header_row;
-
-					if headers_set {
-						continue;
-					}
-
-					let sub_headers = r.rows().nth(sub_header_row as usize).unwrap_or(&[]);
-					let mut prev_main_header = String::new();
-					let mut headers: Vec<_> = row
-						.iter()
-						.enumerate()
-						.map(|(i, c)| {
-							let main_header = c.as_string().unwrap_or_default();
-							let main_header = if main_header.is_empty() {
-								prev_main_header.as_str()
-							} else {
-								prev_main_header = main_header.to_string();
-								&main_header
-							};
-
-							let sub_header = sub_headers
-								.get(i)
-								.and_then(|sc| sc.as_string())
-								.unwrap_or_default();
-							if sub_header.is_empty().not() {
-								return format_header(format!("{}_{}", main_header, sub_header));
-							}
-
-							format_header(main_header.to_string())
-						})
-						.collect();
-
-					headers.push("date".to_string());
-					writeln!(&output_file, "{}", headers.join(","))?;
-					headers_set = true;
-					continue;
-				}
-
-				if first_cell.as_string() == Some(DATA_END_ID.to_string()) {
-					table_end_row = row_idx as i32;
-					continue;
-				}
-
-				if row_idx as i32 <= table_header_row {
-					continue;
-				}
+		if let Some(clipboard) = clipboard {
+			clipboard
+				.set_html(table.html, Some(table.text))
+				.map_err(|e| e.to_string())?;
+		}
 
-				if remarks_start_row < 0 && first_cell.as_string() == Some(REMARKS_START_ID.to_string()) {
-					remarks_start_row = row_idx as i32;
-					continue;
-				}
+		Ok(())
+	});
 
-				if table_header_row > 0 && table_end_row < 0 {
-					if ToCellDeserializer::is_empty(first_cell) {
-						continue;
-					}
+	if let Err(e) = result {
+		let _ = DialogBuilder::message()
+			.set_level(native_dialog::MessageLevel::Error)
+			.set_title("Copy to clipboard")
+			.set_text(format!("Failed to copy the export: {}", e))
+			.alert()
+			.show();
+	}
+}
 
-					let row_data: Vec<_> = row.iter().map(|c| c.to_string()).collect();
-					write!(&output_file, "{}", row_data.join(","))?;
-					writeln!(&output_file, ",{}", report_date)?;
-				}
-			}
+// a crash closes the window without a word, so the report it left behind is
+// brought up on the next start. it's only ever sent if the user says so
+fn offer_crash_report(config: &Config, report: &Path) {
+	let open = DialogBuilder::message()
+		.set_level(native_dialog::MessageLevel::Warning)
+		.set_title("Crash report")
+		.set_text(format!(
+			"oxide closed unexpectedly last time. A crash report was saved to {}, it doesn't hold any spreadsheet data.\n\nOpen it?",
+			report.display()
+		))
+		.confirm()
+		.show()
+		.unwrap_or(false);
+	if open && let Err(e) = crash::open(report) {
+		eprintln!("failed to open crash report: {}", e);
+	}
 
-			if table_header_row == -1 {
-				return Err(
-					format!(
-						"Failed to find table header in worksheet: {}",
-						worksheet_name
-					)
-					.into(),
-				);
-			}
+	#[cfg(feature = "crash-reports")]
+	if config.crash_report_url.is_empty().not() {
+		let send = DialogBuilder::message()
+			.set_level(native_dialog::MessageLevel::Info)
+			.set_title("Crash report")
+			.set_text(format!(
+				"Send the crash report to {}?",
+				config.crash_report_url
+			))
+			.confirm()
+			.show()
+			.unwrap_or(false);
+		if send && let Err(e) = crash::submit(report, &config.crash_report_url) {
+			let _ = DialogBuilder::message()
+				.set_level(native_dialog::MessageLevel::Error)
+				.set_title("Crash report")
+				.set_text(format!("Failed to send the crash report: {}", e))
+				.alert()
+				.show();
 		}
 	}
 
-	Ok(())
+	#[cfg(not(feature = "crash-reports"))]
+	let _ = config;
 }
 
-fn format_header(header: String) -> String {
-	header
-		.trim()
-		.to_lowercase()
-		.replace(" ", "_")
-		.replace("-", "_")
-		.replace("\n", "_")
+// an export that was killed, with the app or the computer, leaves its
+// checkpoint and the output it was writing behind. the output can't be
+// picked up again and is removed when the app starts, the checkpoint is
+// kept for the next export to offer resuming from
+fn clean_up_stale_export(config: &Config) {
+	oxide_core::remove_stale_spills(Duration::from_secs(24 * 60 * 60));
+	if let Some(stale) = StaleExport::find(&config.output_file) {
+		let _ = stale.remove_partial();
+	}
 }
 
-fn update_binary(sections: &[(&str, &str)]) {
-	let exe_path = env::current_exe().unwrap();
-	let tmp = exe_path.with_extension("tmp");
-	fs::copy(&exe_path, &tmp).unwrap();
-
-	let file = OpenOptions::new()
-		.read(true)
-		.write(true)
-		.open(&tmp)
-		.unwrap();
-	let mut buf = unsafe { memmap2::MmapOptions::new().map_mut(&file) }.unwrap();
-	let mut section_updates = Vec::new();
-	{
-		let parsed_file = object::File::parse(&*buf).unwrap();
-		for (section_name, data) in sections {
-			if data.is_empty() {
-				continue;
-			}
-
-			if let Some((offset, size)) = get_section(&parsed_file, section_name) {
-				let section_data = &buf[offset as usize..(offset + size) as usize];
-				if data.as_bytes() == section_data {
-					continue;
-				}
-
-				section_updates.push((offset as usize, size as usize, *data));
-			}
-		}
+// asks whether to resume the export to `output_file` that didn't finish,
+// discarding what it left behind otherwise. false when there's none
+fn offer_resume(output_file: &str) -> bool {
+	let Some(stale) = StaleExport::find(output_file) else {
+		return false;
+	};
+	if stale.checkpoint.is_none() {
+		return false;
 	}
 
-	if section_updates.is_empty().not() {
-		for (offset, size, data) in &section_updates {
-			buf[*offset..(*offset + *size)].fill(0);
-			buf[*offset..*offset + data.len()].copy_from_slice(data.as_bytes());
-		}
-
-		#[cfg(unix)]
-		{
-			let perms = fs::metadata(&exe_path).unwrap().permissions();
-			fs::set_permissions(&tmp, perms.clone()).unwrap();
-			fs::rename(&tmp, &exe_path).unwrap();
-			fs::set_permissions(&exe_path, perms).unwrap();
-		}
-
-		#[cfg(windows)]
-		{
-			let perms = fs::metadata(&exe_path).unwrap().permissions();
-			let old = env::temp_dir().join(exe_path.with_extension("old"));
-
-			fs::set_permissions(&tmp, perms.clone()).unwrap();
-			// can't just overwrite running exe on windows, so move/rename
-			// to temp and then rename back
-			fs::rename(&exe_path, &old).unwrap();
-			fs::rename(&tmp, &exe_path).unwrap();
-			fs::set_permissions(&exe_path, perms).unwrap();
-		}
-	} else {
-		fs::remove_file(&tmp).unwrap();
+	let resume = DialogBuilder::message()
+		.set_level(native_dialog::MessageLevel::Info)
+		.set_title("Resume export")
+		.set_text(format!(
+			"The last export to {} did not finish. Resume it from the last completed file?\n\nOtherwise it's discarded and this export starts over.",
+			output_file
+		))
+		.confirm()
+		.show()
+		.unwrap_or(false);
+	if resume.not() {
+		let _ = stale.discard();
 	}
+
+	resume
 }
 
 fn ellipse_string(s: &str, max_len: usize) -> String {
@@ -356,53 +965,102 @@ fn ellipse_string(s: &str, max_len: usize) -> String {
 	}
 }
 
-fn get_section(file: &object::File, name: &str) -> Option<(u64, u64)> {
-	for section in file.sections() {
-		match section.name() {
-			Ok(n) if n == name => {
-				return section.file_range();
-			}
-			_ => {}
-		}
-	}
-	None
-}
-
 fn main() -> eframe::Result {
-	// clean up old temp file
-	let old_path = env::current_exe().unwrap().with_extension("old");
-	let old_path = env::temp_dir().join(old_path);
-	if old_path.exists() {
-		let _ = fs::remove_file(old_path);
+	crash::install_hook();
+	let args: Vec<String> = env::args().skip(1).collect();
+	// explorer's menu starts the app with the folder that was clicked on,
+	// and a folder dropped on the executable is passed on its own
+	let input_dir = match args.as_slice() {
+		[flag, dir] if flag == "--input" => Some(dir.clone()),
+		[dir] if Path::new(dir).is_dir() => Some(dir.clone()),
+		_ => None,
+	};
+	if input_dir.is_none()
+		&& let Some(code) = cli::run(&args)
+	{
+		process::exit(code);
 	}
 
-	// read configured paths from binary sections
-	// blame: https://blog.dend.ro/self-modifying-rust/
-	let input_dir_bytes = unsafe { INPUT_DIR_BYTES };
-	let output_file_bytes = unsafe { OUTPUT_FILE_BYTES };
+	// launching again brings back the app that's already running, rather
+	// than having two of them updating the same executable
+	let Some(instance) = Instance::claim(input_dir.as_deref()) else {
+		return Ok(());
+	};
+
+	#[cfg(any(feature = "portable", feature = "updater"))]
+	exe::clean_up();
 
-	let input_dir_string = String::from_utf8_lossy(&input_dir_bytes);
-	let input_dir = input_dir_string.trim_end_matches(char::from(0)).to_owned();
-	let output_file_string = String::from_utf8_lossy(&output_file_bytes);
-	let output_file = output_file_string
-		.trim_end_matches(char::from(0))
-		.to_owned();
+	let mut config = Config::load();
+	if config.load_warnings().is_empty().not() {
+		let _ = DialogBuilder::message()
+			.set_level(native_dialog::MessageLevel::Warning)
+			.set_title("Settings")
+			.set_text(config.load_warnings().join("\n\n"))
+			.alert()
+			.show();
+	}
+	let export_options = config.export_options().unwrap_or_else(|e| {
+		let _ = DialogBuilder::message()
+			.set_level(native_dialog::MessageLevel::Warning)
+			.set_title("Settings")
+			.set_text(format!("{}, exports use the default settings instead.", e))
+			.alert()
+			.show();
+		ExportOptions::default()
+	});
+	let replacements = config.header_replacements().unwrap_or_default();
+	if let Some(input_dir) = input_dir {
+		config.input_dir = input_dir;
+	}
+
+	if let Some(report) = crash::take_pending() {
+		offer_crash_report(&config, &report);
+	}
+	clean_up_stale_export(&config);
 
 	let options = eframe::NativeOptions {
 		viewport: egui::ViewportBuilder::default()
-			.with_inner_size([320.0, 480.0])
-			.with_min_inner_size([320.0, 480.0]),
+			.with_inner_size([320.0, 600.0])
+			.with_min_inner_size([320.0, 600.0]),
 		..Default::default()
 	};
 
-	let (app, shared_state) = App::new(input_dir, output_file);
-	let native_result = eframe::run_native("oxide", options, Box::new(|_cc| Ok(Box::new(app))));
+	let (mut app, shared_state) = App::new(&config, export_options, replacements);
+	let native_result = eframe::run_native(
+		"oxide",
+		options,
+		Box::new(|cc| {
+			app.launches = Some(instance.listen(cc.egui_ctx.clone()));
+			Ok(Box::new(app))
+		}),
+	);
 	let final_state = shared_state.lock().unwrap().clone();
-	let sections = [
-		("inptdir", &final_state.input_dir as &str),
-		("outfil", &final_state.output_file as &str),
-	];
+	config.input_dir = final_state.input_dir;
+	config.output_file = final_state.output_file;
+	config.header_style = final_state.header_style;
+	config.template = final_state.template;
+	config.preset = final_state.preset;
+	config.source_columns = final_state.source_columns;
+	config.max_concurrent_files = final_state.max_concurrent_files;
+	config.date_from = final_state.date_from;
+	config.date_to = final_state.date_to;
+	config.filter = final_state.filter;
+	config.header_aliases = final_state.header_aliases;
+	let (level, message) = match config.save() {
+		Ok(None) => return native_result,
+		Ok(Some(warning)) => (native_dialog::MessageLevel::Warning, warning),
+		Err(e) => (
+			native_dialog::MessageLevel::Error,
+			format!("Failed to save settings: {}", e),
+		),
+	};
+
+	let _ = DialogBuilder::message()
+		.set_level(level)
+		.set_title("Settings")
+		.set_text(message)
+		.alert()
+		.show();
 
-	update_binary(&sections);
 	native_result
 }
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use eframe::egui;
+use oxide_core::{
+	FileMetadata, HeaderStyle, LocalDir, PREVIEW_ROWS, Preview, ReportTemplate, SpreadsheetSource,
+	read_metadata, read_preview,
+};
+
+// how often the input directory is re-listed while the ui is open
+const LIST_INTERVAL: Duration = Duration::from_secs(2);
+
+pub enum MetadataState {
+	Loading,
+	Loaded(FileMetadata),
+	Failed(String),
+}
+
+pub enum PreviewState {
+	Loading,
+	Loaded(Preview),
+	Failed(String),
+}
+
+pub struct FileEntry {
+	pub path: PathBuf,
+	pub file_name: String,
+	pub state: MetadataState,
+}
+
+enum Request {
+	List(PathBuf),
+	Load(PathBuf, SystemTime),
+	// boxed, templates are much bigger than the other requests
+	Preview(PathBuf, Box<ReportTemplate>, HeaderStyle),
+}
+
+enum Response {
+	Listing(PathBuf, Option<Vec<(PathBuf, SystemTime)>>),
+	Metadata(PathBuf, SystemTime, Result<FileMetadata, String>),
+	Preview(PathBuf, Result<Preview, String>),
+}
+
+// lists the input directory and reads per-file metadata on a background
+// thread, so the ui never touches the filesystem while drawing a frame
+pub struct MetadataLoader {
+	requests: Sender<Request>,
+	responses: Receiver<Response>,
+	cache: HashMap<PathBuf, (SystemTime, MetadataState)>,
+	files: Option<Vec<(PathBuf, SystemTime)>>,
+	listed_dir: PathBuf,
+	last_listed: Option<Instant>,
+	listing_pending: bool,
+	preview: Option<(PathBuf, PreviewState)>,
+}
+
+impl MetadataLoader {
+	pub fn new(ctx: egui::Context) -> MetadataLoader {
+		let (request_tx, request_rx) = mpsc::channel();
+		let (response_tx, response_rx) = mpsc::channel();
+		thread::spawn(move || worker(request_rx, response_tx, ctx));
+
+		MetadataLoader {
+			requests: request_tx,
+			responses: response_rx,
+			cache: HashMap::new(),
+			files: None,
+			listed_dir: PathBuf::new(),
+			last_listed: None,
+			listing_pending: false,
+			preview: None,
+		}
+	}
+
+	// drains finished work and schedules a new listing when the directory
+	// changed or the refresh interval elapsed, should be called every frame
+	pub fn poll(&mut self, input_dir: &str) {
+		let input_dir = Path::new(input_dir);
+		if input_dir != self.listed_dir {
+			self.listed_dir = input_dir.to_path_buf();
+			self.files = None;
+			self.last_listed = None;
+		}
+
+		while let Ok(response) = self.responses.try_recv() {
+			match response {
+				Response::Listing(dir, files) => {
+					if dir != self.listed_dir {
+						continue;
+					}
+
+					self.listing_pending = false;
+					self.last_listed = Some(Instant::now());
+					if let Some(files) = &files {
+						for (path, modified) in files {
+							let stale = self
+								.cache
+								.get(path)
+								.is_none_or(|(cached_modified, _)| cached_modified != modified);
+							if stale {
+								self
+									.cache
+									.insert(path.clone(), (*modified, MetadataState::Loading));
+								let _ = self.requests.send(Request::Load(path.clone(), *modified));
+							}
+						}
+
+						// files gone from the folder, or the folder left behind
+						// for another, are forgotten rather than kept for good
+						let listed: HashSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+						self.cache.retain(|path, _| listed.contains(path));
+					}
+
+					self.files = files;
+				}
+				Response::Preview(path, result) => {
+					if let Some((preview_path, state)) = &mut self.preview
+						&& *preview_path == path
+					{
+						*state = match result {
+							Ok(preview) => PreviewState::Loaded(preview),
+							Err(e) => PreviewState::Failed(e),
+						};
+					}
+				}
+				Response::Metadata(path, modified, result) => {
+					// a newer version of the file may have been queued in the meantime
+					if let Some((cached_modified, state)) = self.cache.get_mut(&path)
+						&& *cached_modified == modified
+					{
+						*state = match result {
+							Ok(metadata) => MetadataState::Loaded(metadata),
+							Err(e) => MetadataState::Failed(e),
+						};
+					}
+				}
+			}
+		}
+
+		let due = self
+			.last_listed
+			.is_none_or(|listed| listed.elapsed() >= LIST_INTERVAL);
+		if self.listing_pending || due.not() || self.listed_dir.as_os_str().is_empty() {
+			return;
+		}
+
+		self.listing_pending = true;
+		let _ = self.requests.send(Request::List(self.listed_dir.clone()));
+	}
+
+	// none until the first listing of the current directory has completed,
+	// or if the directory could not be read
+	pub fn files(&self) -> Option<Vec<FileEntry>> {
+		let files = self.files.as_ref()?;
+		let entries = files
+			.iter()
+			.map(|(path, _)| {
+				let state = match self.cache.get(path) {
+					Some((_, MetadataState::Loaded(metadata))) => MetadataState::Loaded(metadata.clone()),
+					Some((_, MetadataState::Failed(e))) => MetadataState::Failed(e.clone()),
+					_ => MetadataState::Loading,
+				};
+
+				FileEntry {
+					file_name: path
+						.file_name()
+						.and_then(|s| s.to_str())
+						.unwrap_or_default()
+						.to_string(),
+					path: path.clone(),
+					state,
+				}
+			})
+			.collect();
+
+		Some(entries)
+	}
+
+	pub fn is_listed(&self) -> bool {
+		self.last_listed.is_some()
+	}
+
+	// previews show the table the way the export would read and write it
+	pub fn request_preview(
+		&mut self,
+		path: PathBuf,
+		template: &ReportTemplate,
+		header_style: &HeaderStyle,
+	) {
+		self.preview = Some((path.clone(), PreviewState::Loading));
+		let _ = self.requests.send(Request::Preview(
+			path,
+			Box::new(template.clone()),
+			header_style.clone(),
+		));
+	}
+
+	pub fn preview(&self) -> Option<&(PathBuf, PreviewState)> {
+		self.preview.as_ref()
+	}
+
+	pub fn close_preview(&mut self) {
+		self.preview = None;
+	}
+}
+
+fn worker(requests: Receiver<Request>, responses: Sender<Response>, ctx: egui::Context) {
+	// metadata and previews are only requested for files from the last listing
+	let mut source = LocalDir::new(PathBuf::new());
+	for request in requests {
+		let response = match request {
+			Request::List(dir) => {
+				source = LocalDir::new(dir.clone());
+				let files = source
+					.list()
+					.ok()
+					.map(|files| files.into_iter().map(|f| (f.path, f.modified)).collect());
+				Response::Listing(dir, files)
+			}
+			Request::Load(path, modified) => {
+				let result = read_metadata(&source, &path);
+				Response::Metadata(path, modified, result)
+			}
+			Request::Preview(path, template, header_style) => {
+				let result = read_preview(&source, &path, PREVIEW_ROWS, &template, &header_style);
+				Response::Preview(path, result)
+			}
+		};
+
+		if responses.send(response).is_err() {
+			break;
+		}
+
+		ctx.request_repaint();
+	}
+}
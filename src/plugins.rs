@@ -0,0 +1,45 @@
+#[cfg(feature = "scripting")]
+use std::path::Path;
+
+use oxide_core::Plugins;
+#[cfg(feature = "scripting")]
+use oxide_core::RowScript;
+
+use crate::config::Config;
+
+// sinks and row transforms compiled into this build. a plugin is a crate
+// implementing oxide_core's SinkPlugin or RowTransform, added as an optional
+// dependency behind a cargo feature of its own and registered here, e.g.
+//
+//     #[cfg(feature = "acme-sink")]
+//     plugins.add_sink(acme_sink::AcmeSink);
+pub fn registered(config: &Config) -> Result<Plugins, String> {
+	let mut plugins = Plugins::default();
+	add_script(&mut plugins, &config.script)?;
+	Ok(plugins)
+}
+
+#[cfg(feature = "scripting")]
+fn add_script(plugins: &mut Plugins, script: &str) -> Result<(), String> {
+	if script.is_empty() {
+		return Ok(());
+	}
+
+	let script = RowScript::load(Path::new(script)).map_err(|e| e.to_string())?;
+	plugins.add_transform(script);
+	Ok(())
+}
+
+// running the export without the script would quietly write rows the user
+// meant to have changed
+#[cfg(not(feature = "scripting"))]
+fn add_script(_plugins: &mut Plugins, script: &str) -> Result<(), String> {
+	if script.is_empty() {
+		return Ok(());
+	}
+
+	Err(format!(
+		"{} can't be run, oxide was built without the scripting feature",
+		script
+	))
+}
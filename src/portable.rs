@@ -0,0 +1,383 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+use object::{BinaryFormat, Object, ObjectSection};
+
+use crate::config::Config;
+use crate::exe;
+
+// mach-o sections live inside a segment and are named with a leading
+// double underscore, everything else takes the name as is
+#[cfg(target_os = "macos")]
+const CONFIG_SECTION: &str = "__oxcfg";
+#[cfg(not(target_os = "macos"))]
+const CONFIG_SECTION: &str = "oxcfg";
+#[cfg(target_os = "macos")]
+const INPUT_DIR_SECTION: &str = "__inptdir";
+#[cfg(not(target_os = "macos"))]
+const INPUT_DIR_SECTION: &str = "inptdir";
+#[cfg(target_os = "macos")]
+const OUTPUT_FILE_SECTION: &str = "__outfil";
+#[cfg(not(target_os = "macos"))]
+const OUTPUT_FILE_SECTION: &str = "outfil";
+
+// every setting as the json the settings file holds, the length ahead of
+// it has to fit in 16 bits
+#[used]
+#[cfg_attr(target_os = "macos", unsafe(link_section = "__DATA,__oxcfg"))]
+#[cfg_attr(not(target_os = "macos"), unsafe(link_section = "oxcfg"))]
+static mut CONFIG_BYTES: [u8; 65535] = [0; 65535];
+
+// only the folders were kept before all the settings were, which an older
+// oxide still writes when it updates to this one
+#[used]
+#[cfg_attr(target_os = "macos", unsafe(link_section = "__DATA,__inptdir"))]
+#[cfg_attr(not(target_os = "macos"), unsafe(link_section = "inptdir"))]
+static mut INPUT_DIR_BYTES: [u8; 260] = [0; 260];
+
+#[used]
+#[cfg_attr(target_os = "macos", unsafe(link_section = "__DATA,__outfil"))]
+#[cfg_attr(not(target_os = "macos"), unsafe(link_section = "outfil"))]
+static mut OUTPUT_FILE_BYTES: [u8; 260] = [0; 260];
+
+// object format of the executables we build
+#[cfg(windows)]
+const NATIVE_FORMAT: BinaryFormat = BinaryFormat::Pe;
+#[cfg(target_os = "macos")]
+const NATIVE_FORMAT: BinaryFormat = BinaryFormat::MachO;
+#[cfg(not(any(windows, target_os = "macos")))]
+const NATIVE_FORMAT: BinaryFormat = BinaryFormat::Elf;
+
+// length and checksum written ahead of each section's value
+const SECTION_HEADER_LEN: usize = 6;
+
+// settings are written here when the executable can't be patched
+const FALLBACK_FILE_NAME: &str = "oxide.json";
+
+// reads the settings patched into the executable, none if it was never
+// configured. what went wrong reading them goes in `warnings`
+pub fn load(warnings: &mut Vec<String>) -> Option<Config> {
+	// a fallback file only exists when the last save couldn't patch the
+	// executable, so it's newer than whatever the sections hold
+	let fallback = fallback_paths()
+		.iter()
+		.find_map(|path| Config::read_from(path, warnings));
+	if fallback.is_some() {
+		return fallback;
+	}
+
+	// blame: https://blog.dend.ro/self-modifying-rust/
+	let config_bytes: &[u8] = unsafe { &*std::ptr::addr_of!(CONFIG_BYTES) };
+	let input_dir_bytes = unsafe { INPUT_DIR_BYTES };
+	let output_file_bytes = unsafe { OUTPUT_FILE_BYTES };
+
+	// an interrupted patch can leave a section half written, which is reset
+	// rather than handed out as a garbage path
+	let mut read = |name: &str, bytes: &[u8]| {
+		decode_section(bytes).unwrap_or_else(|| {
+			warnings.push(format!("the {} section is corrupt, it was reset", name));
+			String::new()
+		})
+	};
+	let json = read(CONFIG_SECTION, config_bytes);
+	let input_dir = read(INPUT_DIR_SECTION, &input_dir_bytes);
+	let output_file = read(OUTPUT_FILE_SECTION, &output_file_bytes);
+	if json.is_empty().not() {
+		match Config::parse(&json) {
+			Ok(config) => return Some(config),
+			Err(e) => match Config::unreadable_newer(&json) {
+				Some(config) => {
+					warnings.push(format!(
+						"the {} section was saved by a newer version of oxide and can't be read ({}), the default settings are used and it isn't overwritten",
+						CONFIG_SECTION, e
+					));
+					return Some(config);
+				}
+				None => warnings.push(format!(
+					"the {} section is unreadable ({}), it was reset",
+					CONFIG_SECTION, e
+				)),
+			},
+		}
+	}
+
+	if input_dir.is_empty() && output_file.is_empty() {
+		return None;
+	}
+
+	let mut config = Config::default();
+	config.input_dir = input_dir;
+	config.output_file = output_file;
+	Some(config)
+}
+
+// saves into the executable, or into a config file next to it (or in temp)
+// when the executable can't be patched, returning a warning saying so
+pub fn save(config: &Config) -> io::Result<Option<String>> {
+	let patch_error = match sections(config).and_then(|sections| update_binary(&sections)) {
+		Ok(()) => {
+			// the executable is current again, so stop preferring the fallback
+			for path in fallback_paths() {
+				let _ = fs::remove_file(path);
+			}
+
+			return Ok(None);
+		}
+		Err(e) => e,
+	};
+
+	let mut last_error = patch_error;
+	for path in fallback_paths() {
+		match config.write_to(&path) {
+			Ok(()) => {
+				return Ok(Some(format!(
+					"Couldn't update the executable ({}), settings were saved to {} instead.",
+					last_error,
+					path.display()
+				)));
+			}
+			Err(e) => last_error = e,
+		}
+	}
+
+	Err(last_error)
+}
+
+// carries the settings over into a new executable before it replaces this
+// one, which would otherwise start out unconfigured
+#[cfg(feature = "updater")]
+pub fn patch_into(exe_path: &Path, config: &Config) -> io::Result<()> {
+	if patch_copy(exe_path, &sections(config)?)? {
+		sign(exe_path)?;
+	}
+
+	Ok(())
+}
+
+// every setting goes into the one section, one that doesn't fit is saved
+// to the fallback file instead
+fn sections(config: &Config) -> io::Result<[(&'static str, String); 1]> {
+	Ok([(CONFIG_SECTION, config.to_json()?)])
+}
+
+// settings that couldn't be patched into the executable, most recent first
+fn fallback_paths() -> Vec<PathBuf> {
+	let mut paths = Vec::new();
+	if let Ok(exe_path) = env::current_exe() {
+		paths.push(exe_path.with_file_name(FALLBACK_FILE_NAME));
+	}
+
+	paths.push(env::temp_dir().join(FALLBACK_FILE_NAME));
+	paths
+}
+
+fn update_binary(sections: &[(&str, String)]) -> io::Result<()> {
+	let exe_path = exe::replaceable_exe()?;
+	let tmp = exe_path.with_extension("tmp");
+	fs::copy(&exe_path, &tmp)?;
+	let result = patch_copy(&tmp, sections).and_then(|changed| match changed {
+		true => sign(&tmp).and_then(|_| exe::replace(&exe_path, &tmp)),
+		false => fs::remove_file(&tmp),
+	});
+
+	if result.is_err() {
+		let _ = fs::remove_file(&tmp);
+	}
+
+	result
+}
+
+// writes the sections into the copy of the executable, returning whether
+// anything changed
+fn patch_copy(tmp: &Path, sections: &[(&str, String)]) -> io::Result<bool> {
+	let file = OpenOptions::new().read(true).write(true).open(tmp)?;
+	let mut buf = unsafe { memmap2::MmapOptions::new().map_mut(&file) }?;
+	let mut section_updates = Vec::new();
+	{
+		// universal mach-o binaries and anything else object can't read as
+		// a single executable end up here
+		let parsed_file = object::File::parse(&*buf).map_err(io::Error::other)?;
+		if parsed_file.format() != NATIVE_FORMAT {
+			return Err(io::Error::new(
+				io::ErrorKind::Unsupported,
+				format!("{:?} executables can't be patched", parsed_file.format()),
+			));
+		}
+
+		for (section_name, data) in sections {
+			// stripped or packed executables lose the sections, writing
+			// nothing would silently forget the settings
+			let Some((offset, size)) = get_section(&parsed_file, section_name) else {
+				return Err(io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("executable has no {} section", section_name),
+				));
+			};
+
+			let encoded = encode_section(section_name, data, size as usize)?;
+			let section_data = &buf[offset as usize..(offset + size) as usize];
+			if encoded == section_data {
+				continue;
+			}
+
+			section_updates.push((offset as usize, encoded));
+		}
+	}
+
+	for (offset, encoded) in &section_updates {
+		buf[*offset..*offset + encoded.len()].copy_from_slice(encoded);
+	}
+
+	buf.flush()?;
+	Ok(section_updates.is_empty().not())
+}
+
+// patching invalidates the code signature, which macos refuses to run, so
+// the copy gets an ad-hoc signature in its place. a developer id signature
+// is lost either way, such builds shouldn't use portable mode
+#[cfg(target_os = "macos")]
+fn sign(tmp: &Path) -> io::Result<()> {
+	let status = std::process::Command::new("codesign")
+		.args(["--force", "--sign", "-"])
+		.arg(tmp)
+		.status()?;
+	if status.success().not() {
+		return Err(io::Error::other(format!("codesign exited with {}", status)));
+	}
+
+	Ok(())
+}
+
+// windows and linux run modified executables as is, authenticode signatures
+// are invalidated but nothing enforces them
+#[cfg(not(target_os = "macos"))]
+fn sign(_tmp: &Path) -> io::Result<()> {
+	Ok(())
+}
+
+// sections hold the value's length and checksum ahead of the value itself,
+// with the rest zeroed. a section that's all zeroes was never written
+fn encode_section(name: &str, value: &str, size: usize) -> io::Result<Vec<u8>> {
+	if value.len() > size - SECTION_HEADER_LEN {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"{} setting is longer than {} bytes",
+				name,
+				size - SECTION_HEADER_LEN
+			),
+		));
+	}
+
+	let mut encoded = vec![0; size];
+	encoded[..2].copy_from_slice(&(value.len() as u16).to_le_bytes());
+	encoded[2..SECTION_HEADER_LEN].copy_from_slice(&checksum(value.as_bytes()).to_le_bytes());
+	encoded[SECTION_HEADER_LEN..SECTION_HEADER_LEN + value.len()].copy_from_slice(value.as_bytes());
+	Ok(encoded)
+}
+
+fn decode_section(bytes: &[u8]) -> Option<String> {
+	if bytes.iter().all(|b| *b == 0) {
+		return Some(String::new());
+	}
+
+	let len = u16::from_le_bytes(bytes.get(..2)?.try_into().ok()?) as usize;
+	let expected = u32::from_le_bytes(bytes.get(2..SECTION_HEADER_LEN)?.try_into().ok()?);
+	let value = bytes.get(SECTION_HEADER_LEN..SECTION_HEADER_LEN + len)?;
+	let padding = &bytes[SECTION_HEADER_LEN + len..];
+	if checksum(value) != expected || padding.iter().any(|b| *b != 0) {
+		return None;
+	}
+
+	String::from_utf8(value.to_vec()).ok()
+}
+
+// 32-bit fnv-1a, only meant to catch torn writes
+fn checksum(bytes: &[u8]) -> u32 {
+	bytes.iter().fold(0x811c_9dc5, |hash, b| {
+		(hash ^ *b as u32).wrapping_mul(0x0100_0193)
+	})
+}
+
+fn get_section(file: &object::File, name: &str) -> Option<(u64, u64)> {
+	for section in file.sections() {
+		match section.name() {
+			Ok(n) if n == name => {
+				return section.file_range();
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trips_sections() {
+		let encoded = encode_section(INPUT_DIR_SECTION, "D:\\Reports\\Daily", 260).unwrap();
+		assert_eq!(encoded.len(), 260);
+		assert_eq!(encoded[..2], 16u16.to_le_bytes());
+		assert_eq!(decode_section(&encoded).unwrap(), "D:\\Reports\\Daily");
+
+		let empty = encode_section(OUTPUT_FILE_SECTION, "", 260).unwrap();
+		assert_eq!(decode_section(&empty).unwrap(), "");
+		// the reference values of fnv-1a
+		assert_eq!(checksum(b""), 0x811c_9dc5);
+		assert_eq!(checksum(b"a"), 0xe40c_292c);
+	}
+
+	#[test]
+	fn rejects_truncated_sections() {
+		let encoded = encode_section(INPUT_DIR_SECTION, "D:\\Reports\\Daily", 260).unwrap();
+		// a section cut short of the length it claims
+		assert_eq!(decode_section(&encoded[..SECTION_HEADER_LEN + 8]), None);
+		assert_eq!(decode_section(&encoded[..1]), None);
+
+		// or a patch that stopped halfway through the value
+		let mut torn = encoded.clone();
+		torn[SECTION_HEADER_LEN + 8..].fill(0);
+		assert_eq!(decode_section(&torn), None);
+	}
+
+	#[test]
+	fn rejects_flipped_bytes() {
+		let encoded = encode_section(CONFIG_SECTION, r#"{"input_dir":"D:\\Reports"}"#, 260).unwrap();
+		for idx in [0, 3, SECTION_HEADER_LEN, SECTION_HEADER_LEN + 10, 259] {
+			let mut flipped = encoded.clone();
+			flipped[idx] ^= 0x04;
+			assert_eq!(decode_section(&flipped), None, "byte {}", idx);
+		}
+	}
+
+	#[test]
+	fn rejects_values_too_long() {
+		let longest = "x".repeat(260 - SECTION_HEADER_LEN);
+		assert!(encode_section(INPUT_DIR_SECTION, &longest, 260).is_ok());
+		let error = encode_section(INPUT_DIR_SECTION, &format!("{}x", longest), 260).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn unpatched_reads_fallback() {
+		// the test executable was never patched, so every section is zeroed
+		assert_eq!(decode_section(&[0; 260]).unwrap(), "");
+
+		let path = fallback_paths().remove(0);
+		assert!(path.exists().not());
+		let mut config = Config::default();
+		config.input_dir = "D:\\Reports\\Daily".to_string();
+		config.write_to(&path).unwrap();
+		let mut warnings = Vec::new();
+		let loaded = load(&mut warnings);
+		let _ = fs::remove_file(&path);
+
+		assert_eq!(loaded.unwrap().input_dir, "D:\\Reports\\Daily");
+		assert!(warnings.is_empty(), "{:?}", warnings);
+	}
+}
@@ -0,0 +1,28 @@
+/// Year, month and day of the days since 1970-01-01.
+pub fn civil_date(days: u64) -> (u64, u64, u64) {
+	let z = days + 719468;
+	let era = z / 146097;
+	let doe = z % 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + u64::from(month <= 2);
+	(year, month, day)
+}
+
+/// Seconds since 1970-01-01 as a UTC date and time, like
+/// `2024-05-17 06:30:00`.
+pub fn utc_time(secs: u64) -> String {
+	let (year, month, day) = civil_date(secs / 86400);
+	format!(
+		"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+		year,
+		month,
+		day,
+		secs % 86400 / 3600,
+		secs % 3600 / 60,
+		secs % 60
+	)
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Not;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::exe;
+
+// minisign public key release executables are signed with, a build without
+// one can check for updates but won't install them
+const PUBLIC_KEY: Option<&str> = option_env!("OXIDE_UPDATE_KEY");
+
+// manifest of the latest release, the update-url setting takes precedence
+const DEFAULT_URL: Option<&str> = option_env!("OXIDE_UPDATE_URL");
+
+// anything bigger isn't one of our executables
+const MAX_DOWNLOAD_LEN: u64 = 256 * 1024 * 1024;
+
+/// Describes the latest release, served from the update url.
+#[derive(Deserialize)]
+struct Manifest {
+	version: String,
+	// keyed by os and architecture as named in std::env::consts, like
+	// windows-x86_64
+	files: HashMap<String, ReleaseFile>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseFile {
+	url: String,
+	sha256: String,
+	// minisign signature of the executable
+	signature: String,
+}
+
+/// A release newer than the running executable, with a build for this
+/// platform.
+pub struct Release {
+	pub version: String,
+	file: ReleaseFile,
+}
+
+/// Looks for a newer release, none if this is the latest one.
+pub fn check(config: &Config) -> Result<Option<Release>, String> {
+	let url = match config.update_url.is_empty() {
+		true => DEFAULT_URL.ok_or("no update url is set")?,
+		false => config.update_url.as_str(),
+	};
+
+	let mut manifest: Manifest = agent()
+		.get(url)
+		.call()
+		.map_err(|e| format!("failed to check for updates: {}", e))?
+		.body_mut()
+		.read_json()
+		.map_err(|e| format!("failed to read the release manifest: {}", e))?;
+
+	if is_newer(&manifest.version, env!("CARGO_PKG_VERSION"))?.not() {
+		return Ok(None);
+	}
+
+	let target = format!("{}-{}", env::consts::OS, env::consts::ARCH);
+	let Some(file) = manifest.files.remove(&target) else {
+		return Err(format!(
+			"release {} has no build for {}",
+			manifest.version, target
+		));
+	};
+
+	Ok(Some(Release {
+		version: manifest.version,
+		file,
+	}))
+}
+
+/// Checks for a newer release without holding up the caller.
+pub fn check_in_background(config: Config) -> Receiver<Result<Option<Release>, String>> {
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || {
+		let _ = tx.send(check(&config));
+	});
+
+	rx
+}
+
+/// Downloads the release and swaps it in for the running executable, which
+/// keeps running until it's restarted. Nothing is replaced unless the
+/// download matches both its hash and its signature.
+pub fn install(release: &Release, config: &Config) -> Result<(), String> {
+	let public_key = PUBLIC_KEY.ok_or("this build has no key to verify updates with")?;
+	let public_key =
+		PublicKey::from_base64(public_key).map_err(|e| format!("update key is invalid: {}", e))?;
+	let signature = Signature::decode(&release.file.signature)
+		.map_err(|e| format!("release signature is invalid: {}", e))?;
+	let exe_path = exe::replaceable_exe().map_err(|e| e.to_string())?;
+
+	let bytes = download(&release.file.url)?;
+	let hash: String = Sha256::digest(&bytes)
+		.iter()
+		.map(|b| format!("{:02x}", b))
+		.collect();
+	if hash.eq_ignore_ascii_case(&release.file.sha256).not() {
+		return Err(format!(
+			"downloaded file has hash {}, expected {}",
+			hash, release.file.sha256
+		));
+	}
+
+	public_key
+		.verify(&bytes, &signature, false)
+		.map_err(|_| "downloaded file isn't signed with the update key".to_string())?;
+	// the signed comment names the version, so an older signed build can't
+	// be served as a newer one
+	let signed = signed_version(&signature).unwrap_or("no version");
+	if signed.trim_start_matches('v') != release.version.trim_start_matches('v') {
+		return Err(format!(
+			"downloaded file is signed as {}, not {}",
+			signed, release.version
+		));
+	}
+
+	let new = exe_path.with_extension("update");
+	let result = write_new(&new, &bytes, config).and_then(|_| exe::replace(&exe_path, &new));
+	if result.is_err() {
+		let _ = fs::remove_file(&new);
+	}
+
+	result.map_err(|e| format!("failed to replace the executable: {}", e))
+}
+
+fn write_new(path: &Path, bytes: &[u8], config: &Config) -> io::Result<()> {
+	fs::write(path, bytes)?;
+
+	// portable builds carry their settings inside the executable, so the new
+	// one needs them too. other builds keep them in the config directory,
+	// which an update doesn't touch
+	#[cfg(feature = "portable")]
+	crate::portable::patch_into(path, config)?;
+	#[cfg(not(feature = "portable"))]
+	let _ = config;
+
+	Ok(())
+}
+
+fn download(url: &str) -> Result<Vec<u8>, String> {
+	let download_error = |e: String| format!("failed to download {}: {}", url, e);
+	let response = agent()
+		.get(url)
+		.call()
+		.map_err(|e| download_error(e.to_string()))?;
+
+	let mut bytes = Vec::new();
+	response
+		.into_body()
+		.into_reader()
+		.take(MAX_DOWNLOAD_LEN + 1)
+		.read_to_end(&mut bytes)
+		.map_err(|e| download_error(e.to_string()))?;
+	if bytes.len() as u64 > MAX_DOWNLOAD_LEN {
+		return Err(download_error(format!(
+			"larger than {} bytes",
+			MAX_DOWNLOAD_LEN
+		)));
+	}
+
+	Ok(bytes)
+}
+
+fn agent() -> ureq::Agent {
+	ureq::Agent::config_builder()
+		.timeout_connect(Some(Duration::from_secs(10)))
+		.timeout_recv_body(Some(Duration::from_secs(30)))
+		.build()
+		.into()
+}
+
+// the version in the signature's trusted comment, signed like
+// `minisign -S -t "version:1.4.0"`
+fn signed_version(signature: &Signature) -> Option<&str> {
+	signature
+		.trusted_comment()
+		.split_whitespace()
+		.find_map(|field| field.strip_prefix("version:"))
+}
+
+// compares dotted versions number by number, a leading v is ignored
+fn is_newer(version: &str, current: &str) -> Result<bool, String> {
+	let parse = |v: &str| {
+		v.trim_start_matches('v')
+			.split('.')
+			.map(|n| n.parse::<u64>())
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|_| format!("{} isn't a version number", v))
+	};
+
+	Ok(parse(version)? > parse(current)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_signed_version() {
+		let signature = |trusted_comment: &str| {
+			Signature::decode(&format!(
+				"untrusted comment: signature from minisign secret key\n\
+				RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=\n\
+				trusted comment: {}\n\
+				QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==",
+				trusted_comment
+			))
+			.unwrap()
+		};
+
+		let versioned = signature("timestamp:1555779966\tfile:oxide.exe\tversion:1.4.0");
+		assert_eq!(signed_version(&versioned), Some("1.4.0"));
+		let unversioned = signature("timestamp:1555779966\tfile:oxide.exe");
+		assert_eq!(signed_version(&unversioned), None);
+	}
+}
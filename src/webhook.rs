@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use oxide_core::RunSummary;
+use serde::{Deserialize, Serialize};
+
+/// A url told about every finished export with a json summary of it.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Webhook {
+	pub url: String,
+	// sent with the request, like an Authorization token
+	pub headers: BTreeMap<String, String>,
+	// adds the output itself, base64 encoded
+	pub include_file: bool,
+}
+
+/// Posts the summary of the export to `output_file` to the webhook's url.
+#[cfg(feature = "webhook")]
+pub fn send(webhook: &Webhook, output_file: &str, summary: &RunSummary) -> Result<(), String> {
+	use std::fs;
+	use std::path::Path;
+	use std::time::Duration;
+
+	use base64::Engine;
+	use base64::engine::general_purpose::STANDARD;
+	use serde_json::json;
+
+	if webhook.url.is_empty() {
+		return Err("there's no webhook url set".to_string());
+	}
+
+	let file_name = Path::new(output_file)
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_else(|| output_file.to_string());
+	let mut body = json!({
+		"output_file": output_file,
+		"file_name": file_name,
+		"rows": summary.total_rows(),
+		"files": summary.files.len(),
+		"elapsed_secs": summary.elapsed.as_secs_f64(),
+		"warnings": summary.warnings,
+		"merged": summary.merged.as_ref().map(|merged| json!({
+			"updated": merged.updated,
+			"added": merged.added,
+			"unchanged": merged.unchanged,
+		})),
+	});
+	if webhook.include_file {
+		let contents =
+			fs::read(output_file).map_err(|e| format!("failed to read {}: {}", output_file, e))?;
+		body["file"] = STANDARD.encode(contents).into();
+	}
+
+	let mut request = ureq::post(&webhook.url)
+		.config()
+		.timeout_global(Some(Duration::from_secs(60)))
+		.build();
+	for (name, value) in &webhook.headers {
+		request = request.header(name, value);
+	}
+
+	request.send_json(body).map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+#[cfg(not(feature = "webhook"))]
+pub fn send(_webhook: &Webhook, _output_file: &str, _summary: &RunSummary) -> Result<(), String> {
+	Err("oxide was built without the webhook feature".to_string())
+}